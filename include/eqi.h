@@ -0,0 +1,49 @@
+/* electron-quit-and-install C 接口
+ * 链接 cargo build --release 生成的 electron_quit_and_install.dll / libelectron_quit_and_install.so / .dylib
+ * 字符串参数均为 UTF-8 编码、以 NUL 结尾
+ */
+#ifndef EQI_H
+#define EQI_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* 返回值 */
+#define EQI_OK 0                  /* 成功（包括未命中灰度、已交给商店/包管理器更新） */
+#define EQI_ERROR 1               /* 失败，详情见日志 */
+#define EQI_INVALID_ARGUMENT 2    /* 参数为空或不是合法的 UTF-8 */
+#define EQI_SANDBOXED 3           /* 应用为沙箱安装，拒绝更新 */
+#define EQI_NOTHING_TO_ROLL_BACK 4 /* 没有可回滚的备份 */
+
+/* 进度回调中的阶段编号 */
+#define EQI_PHASE_DOWNLOAD 0      /* done/total 单位为字节 */
+#define EQI_PHASE_WAIT_FOR_EXIT 1 /* 等待进程退出的检查次数 */
+#define EQI_PHASE_COPY_EXISTING 2 /* 文件数 */
+#define EQI_PHASE_COPY_UPDATE 3   /* 文件数 */
+#define EQI_PHASE_SWAP 4
+#define EQI_PHASE_CLEANUP 5
+#define EQI_PHASE_RESTART 6
+
+/* total 未知时为 0；在执行更新的线程上同步调用 */
+typedef void (*eqi_progress_cb)(void *user_data, int32_t phase, uint64_t done, uint64_t total);
+
+/* 注册进度回调，cb 为 NULL 时取消注册 */
+int32_t eqi_set_progress_cb(eqi_progress_cb cb, void *user_data);
+
+/* 执行一次更新：output 为 NULL 时由 app 的位置推断 resources 目录；
+ * processes 为以 ',' 分隔的进程名，可为 NULL；
+ * log_path 为 NULL 时写入宿主程序同级目录下的 updater.log */
+int32_t eqi_apply(const char *input, const char *output, const char *app,
+                  const char *processes, const char *log_path);
+
+/* 回滚 output 上未完成的替换（存在 <output>_old 时恢复） */
+int32_t eqi_rollback(const char *output, const char *log_path);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* EQI_H */
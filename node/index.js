@@ -0,0 +1,16 @@
+// 加载当前平台的扩展：napi build --platform 生成 eqi.<platform>-<arch>[-<abi>].node
+const { existsSync } = require('fs')
+const { join } = require('path')
+
+function abi() {
+  if (process.platform === 'win32') return '-msvc'
+  if (process.platform !== 'linux') return ''
+  const report = process.report && process.report.getReport()
+  return report && report.header && report.header.glibcVersionRuntime ? '-gnu' : '-musl'
+}
+
+const file = join(__dirname, `eqi.${process.platform}-${process.arch}${abi()}.node`)
+if (!existsSync(file)) {
+  throw new Error(`electron-quit-and-install: no prebuilt addon for ${process.platform}-${process.arch} (${file})`)
+}
+module.exports = require(file)
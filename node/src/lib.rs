@@ -0,0 +1,242 @@
+//! Node.js 扩展（napi-rs）：Electron 主进程在应用运行期间直接准备更新并订阅进度，
+//! 只有最后「替换并重启」一步交给独立的更新程序进程完成（应用退出后才能替换文件）
+
+use electron_quit_and_install::download::{self, DownloadOptions, Format};
+use electron_quit_and_install::source::{self, SourceContext};
+use electron_quit_and_install::{Logger, Phase, ProgressObserver};
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, Status, Task, bindgen_prelude::AsyncTask};
+use napi_derive::napi;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// 进度回调，不需要 JS 侧返回值，也不传递错误参数
+type ProgressCallback = ThreadsafeFunction<ProgressEvent, (), ProgressEvent, Status, false>;
+
+#[napi(object)]
+pub struct StageOptions {
+    /// 同命令行 --input：更新源、更新包文件或 URL、本地目录
+    pub input: String,
+    /// 日志文件
+    pub log: Option<String>,
+    /// 更新包的 SHA-256（十六进制）
+    pub sha256: Option<String>,
+    /// 更新渠道
+    pub channel: Option<String>,
+}
+
+#[napi(object)]
+pub struct StagedUpdate {
+    /// 准备好的更新目录，作为 apply 的 input
+    pub path: String,
+    /// 更新源提供的版本号
+    pub version: Option<String>,
+    /// 更新源提供的更新说明
+    pub release_notes: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct ProgressEvent {
+    /// 阶段名，与 IPC 模式的 phase 事件一致，如 "Download"
+    pub phase: String,
+    /// 当前阶段已完成的量
+    pub done: f64,
+    /// 当前阶段的总量，未知时为 undefined
+    pub total: Option<f64>,
+}
+
+#[napi(object)]
+pub struct ApplyOptions {
+    /// 更新程序可执行文件
+    pub updater: String,
+    /// 同命令行 --input，一般为 stage 返回的 path
+    pub input: String,
+    /// 同命令行 --app
+    pub app: String,
+    /// 同命令行 --ps：需要等待退出的进程名
+    pub ps: Vec<String>,
+    /// 同命令行 --output
+    pub output: Option<String>,
+    /// 同命令行 --log
+    pub log: Option<String>,
+    /// 同命令行 --app-version
+    pub version: Option<String>,
+    /// 其他命令行参数，原样传递
+    pub args: Option<Vec<String>>,
+}
+
+/// 把进度事件转发给 JS 回调，回调收到的是当前阶段的累计进度
+struct JsProgress {
+    callback: ProgressCallback,
+    /// 当前阶段及其已完成量、总量
+    state: Mutex<(Phase, u64, Option<u64>)>,
+}
+
+impl JsProgress {
+    fn notify(&self, phase: Phase, done: u64, total: Option<u64>) {
+        let event = ProgressEvent {
+            phase: format!("{:?}", phase),
+            done: done as f64,
+            total: total.map(|total| total as f64),
+        };
+        self.callback
+            .call(event, ThreadsafeFunctionCallMode::NonBlocking);
+    }
+}
+
+impl ProgressObserver for JsProgress {
+    fn phase_started(&self, phase: Phase, total: Option<u64>) {
+        *self.state.lock().unwrap() = (phase, 0, total);
+        self.notify(phase, 0, total);
+    }
+
+    fn advanced(&self, delta: u64) {
+        let (phase, done, total) = {
+            let mut state = self.state.lock().unwrap();
+            state.1 = state.1.saturating_add(delta);
+            *state
+        };
+        self.notify(phase, done, total);
+    }
+}
+
+fn to_napi(e: io::Error) -> napi::Error {
+    napi::Error::from_reason(e.to_string())
+}
+
+pub struct Stage {
+    options: StageOptions,
+    on_progress: Option<ProgressCallback>,
+}
+
+impl Task for Stage {
+    type Output = Option<StagedUpdate>;
+    type JsValue = Option<StagedUpdate>;
+
+    fn compute(&mut self) -> napi::Result<Self::Output> {
+        let options = &self.options;
+        let logger = Logger::new(options.log.as_deref()).map_err(to_napi)?;
+        if let Some(callback) = self.on_progress.take() {
+            logger.set_observer(Arc::new(JsProgress {
+                callback,
+                state: Mutex::new((Phase::Download, 0, None)),
+            }));
+        }
+        let download = DownloadOptions::default();
+        let ctx = SourceContext {
+            format: Format::Zip,
+            extract: true,
+            channel: options.channel.as_deref(),
+            rollout: None,
+            download: &download,
+            logger: &logger,
+        };
+        let prepared = source::from_input(&options.input, options.sha256.as_deref())
+            .prepare(&ctx)
+            .map_err(to_napi)?;
+        let Some(prepared) = prepared else {
+            return Ok(None);
+        };
+        // 缓存中的解包目录会被下一次准备覆盖，移到独占目录后再交给 apply
+        let path = download::isolate(prepared.path, "staged", 0).map_err(to_napi)?;
+        Ok(Some(StagedUpdate {
+            path: path.to_string_lossy().into_owned(),
+            version: prepared.version,
+            release_notes: prepared.release_notes,
+        }))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+/// 下载并解包更新（在后台线程中进行），不修改已安装的应用；
+/// onProgress 收到下载等阶段的进度。未命中灰度时结果为 null
+#[napi(ts_return_type = "Promise<StagedUpdate | null>")]
+pub fn stage(
+    options: StageOptions,
+    #[napi(ts_arg_type = "(event: ProgressEvent) => void")] on_progress: Option<ProgressCallback>,
+) -> AsyncTask<Stage> {
+    AsyncTask::new(Stage {
+        options,
+        on_progress,
+    })
+}
+
+/// 启动独立的更新程序进程完成替换与重启，返回其进程 ID；
+/// 调用后应用应尽快退出，更新程序等待 ps 中的进程退出后才开始替换
+#[napi]
+pub fn apply(options: ApplyOptions) -> napi::Result<u32> {
+    let mut command = Command::new(&options.updater);
+    command
+        .arg(format!("--input={}", options.input))
+        .arg(format!("--app={}", options.app));
+    for ps in &options.ps {
+        command.arg(format!("--ps={}", ps));
+    }
+    if let Some(output) = &options.output {
+        command.arg(format!("--output={}", output));
+    }
+    if let Some(log) = &options.log {
+        command.arg(format!("--log={}", log));
+    }
+    if let Some(version) = &options.version {
+        command.arg(format!("--app-version={}", version));
+    }
+    command
+        .args(options.args.iter().flatten())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    detach(&mut command);
+    let child = command.spawn().map_err(to_napi)?;
+    Ok(child.id())
+}
+
+/// 更新程序不能随应用退出：放到新的进程组，Windows 上同时脱离控制台
+#[cfg(unix)]
+fn detach(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+}
+
+#[cfg(windows)]
+fn detach(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
+
+pub struct Rollback {
+    output: PathBuf,
+    log: Option<String>,
+}
+
+impl Task for Rollback {
+    type Output = bool;
+    type JsValue = bool;
+
+    fn compute(&mut self) -> napi::Result<Self::Output> {
+        let logger = Logger::new(self.log.as_deref()).map_err(to_napi)?;
+        electron_quit_and_install::rollback(Path::new(&self.output), &logger).map_err(to_napi)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+/// 用保留的备份恢复 output（同命令行 --rollback），没有可回滚的备份时结果为 false；
+/// 应用需已退出
+#[napi]
+pub fn rollback(output: String, log: Option<String>) -> AsyncTask<Rollback> {
+    AsyncTask::new(Rollback {
+        output: PathBuf::from(output),
+        log,
+    })
+}
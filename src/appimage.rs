@@ -0,0 +1,49 @@
+use crate::Logger;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+/// 用新的 AppImage 原子替换 output：先复制到同目录的临时文件并设为可执行，再改名覆盖。
+/// 正在运行的旧版本仍从其挂载点继续运行，不受影响；暂存完成后调用 on_staged
+pub fn apply(
+    output: &Path,
+    image: &Path,
+    on_staged: &dyn Fn(&Path) -> io::Result<()>,
+    logger: &Logger,
+) -> io::Result<()> {
+    let mut name = OsString::from(".");
+    name.push(output.file_name().unwrap_or_default());
+    name.push(".new");
+    let staging = output.with_file_name(name);
+    logger.log(&format!(
+        "Staging {} -> {}",
+        image.display(),
+        staging.display()
+    ));
+    let staged = fs::copy(image, &staging)
+        .and_then(|_| set_executable(&staging))
+        .and_then(|_| File::open(&staging)?.sync_all())
+        .and_then(|_| on_staged(&staging))
+        .and_then(|_| logger.check_cancelled());
+    if let Err(e) = staged {
+        let _ = fs::remove_file(&staging);
+        return Err(e);
+    }
+    fs::rename(&staging, output)?;
+    logger.log(&format!("Replaced {}", output.display()));
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut perms = fs::metadata(path)?.permissions();
+    perms.set_mode(perms.mode() | 0o755);
+    fs::set_permissions(path, perms)
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
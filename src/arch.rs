@@ -0,0 +1,143 @@
+//! 识别可执行文件（PE、ELF、Mach-O）的 CPU 架构，避免把 x64 的更新装到 arm64 的安装上（或反之）
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// CPU 架构
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arch {
+    X86,
+    X64,
+    Arm,
+    Arm64,
+    /// macOS 通用二进制，包含多个架构
+    Universal,
+}
+
+impl Arch {
+    /// 两个架构的文件能否互相替换
+    pub fn compatible(self, other: Arch) -> bool {
+        self == other || self == Arch::Universal || other == Arch::Universal
+    }
+}
+
+impl fmt::Display for Arch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Arch::X86 => "x86",
+            Arch::X64 => "x64",
+            Arch::Arm => "arm",
+            Arch::Arm64 => "arm64",
+            Arch::Universal => "universal",
+        })
+    }
+}
+
+/// 读取可执行文件的架构；不是可识别的可执行文件时返回 None
+pub fn detect(path: &Path) -> io::Result<Option<Arch>> {
+    let mut file = File::open(path)?;
+    let mut head = [0u8; 64];
+    let len = read_up_to(&mut file, &mut head)?;
+    let head = &head[..len];
+    let u16_le = |i: usize| head.get(i..i + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let u32_le = |i: usize| {
+        head.get(i..i + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    };
+
+    // ELF：e_machine 位于偏移 18
+    if head.starts_with(b"\x7fELF") {
+        return Ok(match u16_le(18) {
+            Some(0x03) => Some(Arch::X86),
+            Some(0x3e) => Some(Arch::X64),
+            Some(0x28) => Some(Arch::Arm),
+            Some(0xb7) => Some(Arch::Arm64),
+            _ => None,
+        });
+    }
+    // PE：偏移 0x3c 处为 PE 头位置，PE 头签名之后是 Machine
+    if head.starts_with(b"MZ") {
+        let Some(pe) = u32_le(0x3c) else {
+            return Ok(None);
+        };
+        let mut signature = [0u8; 6];
+        file.seek(SeekFrom::Start(pe as u64))?;
+        if read_up_to(&mut file, &mut signature)? < 6 || &signature[..4] != b"PE\0\0" {
+            return Ok(None);
+        }
+        return Ok(match u16::from_le_bytes([signature[4], signature[5]]) {
+            0x014c => Some(Arch::X86),
+            0x8664 => Some(Arch::X64),
+            0x01c4 => Some(Arch::Arm),
+            0xaa64 => Some(Arch::Arm64),
+            _ => None,
+        });
+    }
+    // Mach-O：通用二进制以大端 0xcafebabe 开头（与 Java class 文件相同，需检查架构数量）
+    if head.starts_with(&[0xca, 0xfe, 0xba, 0xbe]) {
+        let count = head
+            .get(4..8)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()));
+        return Ok(count.filter(|&n| n > 0 && n < 20).map(|_| Arch::Universal));
+    }
+    if matches!(u32_le(0), Some(0xfeedface | 0xfeedfacf)) {
+        return Ok(match u32_le(4) {
+            Some(0x0000_0007) => Some(Arch::X86),
+            Some(0x0100_0007) => Some(Arch::X64),
+            Some(0x0000_000c) => Some(Arch::Arm),
+            Some(0x0100_000c) => Some(Arch::Arm64),
+            _ => None,
+        });
+    }
+    Ok(None)
+}
+
+/// 应用主程序的架构：app 为 macOS 的 .app 包时读取 Contents/MacOS 下的可执行文件
+pub fn of_app(app: &Path) -> io::Result<Option<Arch>> {
+    if !app.is_dir() {
+        return detect(app);
+    }
+    for entry in fs::read_dir(app.join("Contents").join("MacOS"))? {
+        let path = entry?.path();
+        if path.is_file()
+            && let Some(arch) = detect(&path)?
+        {
+            return Ok(Some(arch));
+        }
+    }
+    Ok(None)
+}
+
+/// 在更新文件目录中查找能代表其架构的文件：优先与主程序同名的文件，其次为原生模块（*.node）
+pub fn find_binary(dir: &Path, app_name: &OsStr) -> io::Result<Option<PathBuf>> {
+    let mut native_module = None;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.file_name().is_some_and(|n| n == app_name) {
+                return Ok(Some(path));
+            } else if native_module.is_none() && path.extension().is_some_and(|e| e == "node") {
+                native_module = Some(path);
+            }
+        }
+    }
+    Ok(native_module)
+}
+
+/// 尽量读满 buf，返回实际读取的字节数
+fn read_up_to(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
@@ -0,0 +1,232 @@
+//! 审计日志：每条日志末尾附带 `[chain <sha256>]`，为上一条记录的摘要与本条内容的 SHA-256，
+//! 构成哈希链；每次更新结束后把最新的摘要写入 `<日志>.digest`。事后修改、删除或插入记录都会使链断开，
+//! 截断日志则与摘要文件对不上，可通过 [`verify`] 检查
+
+use crate::download::hex;
+use crate::sink::{LogSink, Record};
+use chrono::Local;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 链中第一条记录之前的摘要
+const SEED: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// 以哈希链格式追加写入日志文件；每次写入前从文件末尾读取上一条摘要，
+/// 同一文件先后由多个进程写入（如提权、转移后的更新器）时链仍然连续
+pub struct AuditSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditSink {
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl LogSink for AuditSink {
+    fn write(&self, record: &Record) {
+        let mut file = self.file.lock().unwrap();
+        // 多行消息转义为一行，保证一条记录对应一行
+        let text = record.line().trim_end().replace('\n', "\\n");
+        let previous = last_digest(&self.path).ok().flatten();
+        let digest = chain(previous.as_deref().unwrap_or(SEED), &text);
+        let _ = writeln!(file, "{} [chain {}]", text, digest);
+    }
+}
+
+/// 本条记录的摘要
+fn chain(previous: &str, text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(previous.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(text.as_bytes());
+    hex(&hasher.finalize())
+}
+
+/// 拆分一行审计记录为（内容，摘要）；不是审计记录时返回 None
+fn split(line: &str) -> Option<(&str, &str)> {
+    let (text, rest) = line.rsplit_once(" [chain ")?;
+    let digest = rest.strip_suffix(']')?;
+    (digest.len() == 64).then_some((text, digest))
+}
+
+/// 日志最后一条审计记录的摘要；文件不存在或最后一行不是审计记录时返回 None
+pub fn last_digest(log: &Path) -> io::Result<Option<String>> {
+    let mut file = match File::open(log) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let len = file.metadata()?.len();
+    // 从末尾读取，直到包含完整的最后一行
+    let mut chunk = 4096u64;
+    loop {
+        let start = len.saturating_sub(chunk);
+        file.seek(SeekFrom::Start(start))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        // 起点可能落在多字节字符中间，只影响不完整的第一行
+        let tail = String::from_utf8_lossy(&bytes);
+        let trimmed = tail.trim_end_matches('\n');
+        match trimmed.rfind('\n') {
+            Some(i) => return Ok(split(&trimmed[i + 1..]).map(|(_, d)| d.to_string())),
+            None if start == 0 && !tail.is_empty() => {
+                return Ok(split(trimmed).map(|(_, d)| d.to_string()));
+            }
+            None if start == 0 => return Ok(None),
+            None => chunk *= 2,
+        }
+    }
+}
+
+/// 摘要文件路径：`<日志>.digest`
+pub fn digest_path(log: &Path) -> PathBuf {
+    let mut path = log.as_os_str().to_owned();
+    path.push(".digest");
+    PathBuf::from(path)
+}
+
+/// 把日志最新的摘要写入摘要文件，返回摘要
+pub fn write_digest(log: &Path) -> io::Result<String> {
+    let digest = last_digest(log)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} has no audit records", log.display()),
+        )
+    })?;
+    let content = serde_json::json!({
+        "time": Local::now().to_rfc3339(),
+        "log": log,
+        "digest": digest,
+    });
+    fs::write(digest_path(log), format!("{:#}\n", content))?;
+    Ok(digest)
+}
+
+/// 检查结果
+#[derive(Debug)]
+pub struct Verification {
+    /// 审计记录条数
+    pub records: u64,
+    /// 摘要文件中的摘要对应的记录序号（从 1 开始）；没有摘要文件时为 None
+    pub digest_record: Option<u64>,
+    /// 第一处问题：行号（从 1 开始）与说明
+    pub error: Option<(u64, String)>,
+}
+
+impl Verification {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// 检查日志的哈希链，并核对摘要文件。第一条审计记录之前的普通日志（开启审计之前写入）不参与检查，
+/// 之后的每一行都必须是审计记录
+pub fn verify(log: &Path) -> io::Result<Verification> {
+    let text = fs::read_to_string(log)?;
+    let expected = match fs::read_to_string(digest_path(log)) {
+        Ok(content) => serde_json::from_str::<serde_json::Value>(&content)
+            .ok()
+            .and_then(|v| v["digest"].as_str().map(str::to_string))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid digest file {}", digest_path(log).display()),
+                )
+            })
+            .map(Some)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    let mut result = Verification {
+        records: 0,
+        digest_record: None,
+        error: None,
+    };
+    let mut previous: Option<String> = None;
+    for (index, line) in text.lines().enumerate() {
+        let number = index as u64 + 1;
+        let Some((content, digest)) = split(line) else {
+            if previous.is_some() {
+                result.error = Some((number, "record is not part of the chain".to_string()));
+                return Ok(result);
+            }
+            continue;
+        };
+        let actual = chain(previous.as_deref().unwrap_or(SEED), content);
+        if actual != digest {
+            result.error = Some((
+                number,
+                "record was modified, inserted or removed".to_string(),
+            ));
+            return Ok(result);
+        }
+        result.records += 1;
+        if expected.as_deref() == Some(digest) {
+            result.digest_record = Some(result.records);
+        }
+        previous = Some(actual);
+    }
+    if expected.is_some() && result.digest_record.is_none() {
+        result.error = Some((
+            text.lines().count() as u64,
+            "log does not contain the recorded digest (truncated or replaced)".to_string(),
+        ));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 写入 lines 构成的审计日志（前面有一行开启审计之前的普通日志），返回日志路径
+    fn write_log(dir: &Path, lines: &[&str]) -> PathBuf {
+        fs::create_dir_all(dir).unwrap();
+        let log = dir.join("updater.log");
+        let mut text = "plain line before auditing\n".to_string();
+        let mut previous = SEED.to_string();
+        for line in lines {
+            previous = chain(&previous, line);
+            text.push_str(&format!("{} [chain {}]\n", line, previous));
+        }
+        fs::write(&log, text).unwrap();
+        log
+    }
+
+    #[test]
+    fn verify_detects_tampering() {
+        let dir = std::env::temp_dir().join(format!("eqi-audit-{}", std::process::id()));
+        let log = write_log(&dir, &["one", "two", "three"]);
+        let digest = write_digest(&log).unwrap();
+        assert_eq!(last_digest(&log).unwrap(), Some(digest));
+        let intact = verify(&log).unwrap();
+
+        let text = fs::read_to_string(&log).unwrap();
+        fs::write(&log, text.replacen("two", "2wo", 1)).unwrap();
+        let modified = verify(&log).unwrap();
+
+        let lines: Vec<_> = text.lines().collect();
+        fs::write(&log, lines[..3].join("\n") + "\n").unwrap();
+        let truncated = verify(&log).unwrap();
+
+        fs::write(&log, text.clone() + "appended without chain\n").unwrap();
+        let appended = verify(&log).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(intact.is_ok());
+        assert_eq!((intact.records, intact.digest_record), (3, Some(3)));
+        assert_eq!(modified.error.unwrap().0, 3);
+        assert!(truncated.error.unwrap().1.contains("truncated"));
+        assert_eq!(appended.error.unwrap().0, 5);
+    }
+}
@@ -0,0 +1,345 @@
+//! 批量更新：一次更新多个必须保持版本一致的目标（如主应用与配套工具）。
+//! 先准备并暂存全部目标，全部成功后才依次替换；任一步失败时已替换的目标全部回滚
+
+use crate::download::{self, DownloadOptions, Format};
+use crate::history::{self, Entry};
+use crate::policy;
+use crate::source::{self, SourceContext};
+use crate::stamp;
+use crate::updater::write_stamp;
+use crate::vfs::RealFs;
+use crate::{
+    DEFAULT_RENAME_RETRY, Logger, Phase, UpdateError, kill_processes_by_names, may_remove,
+    normalize_ignore, remove_path, resolve_output, resolve_path, restart_app, rollback_in,
+    stage_output, swap, version,
+};
+use serde::Deserialize;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 批量任务文件（YAML 或 JSON）：`jobs` 中每一项对应一次 resources 布局的更新
+#[derive(Debug, Deserialize)]
+pub struct BatchFile {
+    pub jobs: Vec<BatchJob>,
+}
+
+/// 单个更新目标，字段含义与同名命令行参数一致
+#[derive(Debug, Deserialize)]
+pub struct BatchJob {
+    #[serde(default)]
+    pub ps: Vec<String>,
+    pub input: String,
+    pub output: PathBuf,
+    pub app: PathBuf,
+    #[serde(default)]
+    pub ignore: Vec<PathBuf>,
+    #[serde(default)]
+    pub ignore_existing: Vec<PathBuf>,
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub confirm_delete: bool,
+    #[serde(default)]
+    pub keep_input: bool,
+}
+
+/// 读取批量任务文件
+pub fn load(path: &Path) -> io::Result<Vec<BatchJob>> {
+    let text = fs::read_to_string(path)?;
+    let file: BatchFile =
+        serde_yaml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if file.jobs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} does not list any jobs", path.display()),
+        ));
+    }
+    let mut jobs = file.jobs;
+    for job in &mut jobs {
+        job.output = resolve_output(&job.output)?;
+        job.app = resolve_path(&job.app)?;
+        if !download::is_url(&job.input) {
+            let input = resolve_path(Path::new(&job.input))?;
+            if let Ok(input) = input.into_os_string().into_string() {
+                job.input = input;
+            }
+        }
+        for ignores in [&mut job.ignore, &mut job.ignore_existing] {
+            *ignores = ignores
+                .iter()
+                .filter_map(|i| normalize_ignore(i.as_os_str()))
+                .collect();
+        }
+    }
+    for (i, a) in jobs.iter().enumerate() {
+        if let Some(b) = jobs[i + 1..]
+            .iter()
+            .find(|b| policy::overlaps(&a.output, &b.output))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Outputs {} and {} overlap",
+                    a.output.display(),
+                    b.output.display()
+                ),
+            ));
+        }
+    }
+    Ok(jobs)
+}
+
+/// 暂存完成、等待替换的目标
+struct Staged<'a> {
+    job: &'a BatchJob,
+    input: PathBuf,
+    output_new: PathBuf,
+    output_old: PathBuf,
+}
+
+/// 执行批量更新：全部准备 → 退出所有进程 → 全部暂存 → 依次替换 → 清理并重新启动所有应用
+pub fn run(
+    jobs: &[BatchJob],
+    download: &DownloadOptions,
+    logger: &Logger,
+) -> Result<(), UpdateError> {
+    logger.log(&format!("Batch update of {} target(s) started", jobs.len()));
+    let classify = |e: io::Error, kind: fn(io::Error) -> UpdateError| {
+        if e.kind() == io::ErrorKind::Interrupted && logger.is_cancelled() {
+            UpdateError::Cancelled
+        } else {
+            kind(e)
+        }
+    };
+
+    let from_versions: Vec<Option<String>> = jobs
+        .iter()
+        .map(|job| version::package_version(&job.output).ok().flatten())
+        .collect();
+
+    // 应用退出前下载并解包所有更新文件，任一失败时不做任何改动
+    let mut inputs = Vec::new();
+    for (i, job) in jobs.iter().enumerate() {
+        logger.log(&format!(
+            "Preparing {} from {}",
+            job.output.display(),
+            job.input
+        ));
+        let ctx = SourceContext {
+            format: Format::Zip,
+            extract: true,
+            channel: None,
+            rollout: None,
+            download,
+            logger,
+        };
+        let prepared = source::from_input(&job.input, job.sha256.as_deref())
+            .prepare(&ctx)
+            .map_err(|e| classify(e, UpdateError::Prepare))?;
+        // 更新源自身的 stagingPercentage 未选中本机：整批跳过，所有目标保持同一版本
+        let Some(prepared) = prepared else {
+            logger.log(&format!(
+                "{} is not rolled out to this machine, skipping the batch update",
+                job.input
+            ));
+            return Ok(());
+        };
+        let input = download::isolate(prepared.path, "batch", i)
+            .map_err(|e| classify(e, UpdateError::Prepare))?;
+        // 其他目标的替换同样不能涉及这份更新文件
+        for (k, other) in jobs.iter().enumerate() {
+            policy::check_overlap(&input, &other.output, &job.app, k != i)
+                .map_err(UpdateError::InvalidConfig)?;
+        }
+        inputs.push(input);
+    }
+
+    let processes: Vec<OsString> = jobs
+        .iter()
+        .flat_map(|j| j.ps.clone())
+        .map(OsString::from)
+        .collect();
+    kill_processes_by_names(&processes, logger).map_err(|e| classify(e, UpdateError::Apply))?;
+
+    let result = stage_all(jobs, &inputs, logger).and_then(|staged| {
+        swap_all(&staged, logger)?;
+        Ok(staged)
+    });
+    let staged = match result {
+        Ok(staged) => staged,
+        Err(e) => {
+            let error = classify(e, UpdateError::Apply);
+            for (job, from_version) in jobs.iter().zip(&from_versions) {
+                let entry = Entry {
+                    error: Some(error.to_string()),
+                    ..Entry::new("apply", "failed")
+                };
+                record(job, from_version, entry, logger);
+            }
+            restart_all(jobs, logger);
+            return Err(error);
+        }
+    };
+
+    logger.log("All targets updated successfully");
+    logger.progress_start(Phase::Cleanup, None);
+    for (target, from_version) in staged.iter().zip(&from_versions) {
+        let version = version::package_version(&target.job.output).ok().flatten();
+        if target.job.output.is_dir() {
+            let sha256 = stamp::tree_sha256(&target.input, 0);
+            write_stamp(&target.job.output, sha256, version, logger);
+        }
+        for (path, backup) in [(&target.input, false), (&target.output_old, true)] {
+            if !backup && target.job.keep_input {
+                logger.log(&format!("Keeping input: {}", path.display()));
+                continue;
+            }
+            let vfs = RealFs::default();
+            if path.exists()
+                && may_remove(&vfs, path, backup, target.job.confirm_delete, logger)
+                && let Err(e) = remove_path(&vfs, path)
+            {
+                logger.warn(&format!("Failed to remove {}: {}", path.display(), e));
+            }
+        }
+        record(
+            target.job,
+            from_version,
+            Entry::new("apply", "applied"),
+            logger,
+        );
+    }
+    logger.progress_finish();
+
+    restart_all(jobs, logger);
+    logger.log("Batch update finished");
+    Ok(())
+}
+
+/// 暂存所有目标，任一失败时删除已暂存的 <output>_new
+fn stage_all<'a>(
+    jobs: &'a [BatchJob],
+    inputs: &[PathBuf],
+    logger: &Logger,
+) -> io::Result<Vec<Staged<'a>>> {
+    let mut staged: Vec<Staged> = Vec::new();
+    for (job, input) in jobs.iter().zip(inputs) {
+        logger.log(&format!("Staging {}", job.output.display()));
+        match stage_output(
+            &RealFs::default(),
+            &job.output,
+            input,
+            &[],
+            &job.ignore,
+            &job.ignore_existing,
+            false,
+            &|_| Ok(()),
+            logger,
+        ) {
+            Ok((output_new, output_old)) => staged.push(Staged {
+                job,
+                input: input.clone(),
+                output_new,
+                output_old,
+            }),
+            Err(e) => {
+                for target in &staged {
+                    let _ = remove_path(&RealFs::default(), &target.output_new);
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(staged)
+}
+
+/// 依次替换所有目标；某个目标替换失败时（它自身已恢复）回滚之前已替换的目标，并删除剩余的 <output>_new
+fn swap_all(staged: &[Staged], logger: &Logger) -> io::Result<()> {
+    logger.progress_start(Phase::Swap, None);
+    let mut result = Ok(());
+    for (i, target) in staged.iter().enumerate() {
+        if let Err(e) = logger.check_cancelled().and_then(|()| {
+            swap(
+                &RealFs::default(),
+                &target.job.output,
+                &target.output_new,
+                &target.output_old,
+                DEFAULT_RENAME_RETRY,
+                logger,
+            )
+        }) {
+            logger.warn("Swap failed, rolling back all targets");
+            for done in &staged[..i] {
+                if let Err(e) = rollback_in(&RealFs::default(), &done.job.output, logger) {
+                    logger.error(&format!(
+                        "Failed to roll back {}: {}",
+                        done.job.output.display(),
+                        e
+                    ));
+                }
+            }
+            for pending in &staged[i..] {
+                let _ = remove_path(&RealFs::default(), &pending.output_new);
+            }
+            result = Err(e);
+            break;
+        }
+    }
+    logger.progress_finish();
+    result
+}
+
+/// 重新启动所有应用（同一应用只启动一次）
+fn restart_all(jobs: &[BatchJob], logger: &Logger) {
+    let mut started: Vec<&Path> = Vec::new();
+    for job in jobs {
+        if !started.contains(&job.app.as_path()) {
+            restart_app(&job.app, logger);
+            started.push(&job.app);
+        }
+    }
+}
+
+/// 写入目标的更新历史
+fn record(job: &BatchJob, from_version: &Option<String>, mut entry: Entry, logger: &Logger) {
+    entry.from_version = from_version.clone();
+    entry.to_version = version::package_version(&job.output).ok().flatten();
+    if let Err(e) = history::append(&job.output, &entry) {
+        logger.warn(&format!("Failed to write update history: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feed_outside_staging_skips_the_batch() {
+        let root = std::env::temp_dir().join(format!("eqi-batch-staging-{}", std::process::id()));
+        let output = root.join("app/resources");
+        fs::create_dir_all(&output).unwrap();
+        fs::write(output.join("app.asar"), "1.0").unwrap();
+        let feed = root.join("latest.yml");
+        fs::write(
+            &feed,
+            "version: 2.0.0\npath: app-2.0.0.zip\nsha512: AAAA\nstagingPercentage: 0\n",
+        )
+        .unwrap();
+        let job = BatchJob {
+            ps: Vec::new(),
+            input: feed.to_string_lossy().into_owned(),
+            output: output.clone(),
+            app: root.join("app/app"),
+            ignore: Vec::new(),
+            ignore_existing: Vec::new(),
+            sha256: None,
+            confirm_delete: false,
+            keep_input: false,
+        };
+        let logger = Logger::new(None).unwrap();
+        run(&[job], &DownloadOptions::default(), &logger).unwrap();
+        assert_eq!(fs::read_to_string(output.join("app.asar")).unwrap(), "1.0");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
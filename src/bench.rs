@@ -0,0 +1,285 @@
+//! 基准测试（`bench` 子命令）：在目标卷上生成测试文件，测量各复制方式（含多线程复制）、不同线程数的摘要计算与
+//! zip 解压的吞吐量，据此给出适合这台设备的 `--copy-engine`、`--copy-jobs`、`--hash-jobs` 与 `--stream-extract` 设置。
+//! Linux 上每次测量前丢弃测试文件的页缓存，其他平台的读取可能命中缓存，结果偏高
+
+use crate::download;
+use crate::manifest;
+use crate::vfs::{CopyEngine, FileSystem, Granularity, RealFs};
+use crate::walk::{self, Walk};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// 测试数据的规模
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// 测试目录所在位置，应与 output 位于同一个卷
+    pub dir: PathBuf,
+    /// 小文件（32 KB）数量
+    pub small_files: usize,
+    /// 大文件（64 MB）数量
+    pub large_files: usize,
+}
+
+/// 小文件大小
+const SMALL_SIZE: usize = 32 * 1024;
+
+/// 大文件大小
+const LARGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// 一次测量
+#[derive(Clone, Debug, Serialize)]
+pub struct Measurement {
+    /// 复制方式、线程数等
+    pub name: String,
+    pub bytes: u64,
+    pub seconds: f64,
+    /// MB/s
+    pub throughput: f64,
+}
+
+impl Measurement {
+    fn new(name: String, bytes: u64, started: Instant) -> Self {
+        let seconds = started.elapsed().as_secs_f64().max(1e-6);
+        Self {
+            name,
+            bytes,
+            seconds,
+            throughput: bytes as f64 / 1e6 / seconds,
+        }
+    }
+}
+
+/// 测量结果与推荐设置
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    pub files: usize,
+    pub bytes: u64,
+    pub copy: Vec<Measurement>,
+    pub hash: Vec<Measurement>,
+    pub extract: Measurement,
+    pub copy_engine: String,
+    pub copy_jobs: usize,
+    pub copy_granularity: String,
+    pub hash_jobs: usize,
+    pub stream_extract: bool,
+}
+
+/// 在 opts.dir 下的临时目录中执行全部测量，结束后删除该目录
+pub fn run(opts: &Options) -> io::Result<Report> {
+    let scratch = opts.dir.join(format!("eqi-bench-{}", std::process::id()));
+    fs::create_dir_all(&scratch)?;
+    let result = measure(opts, &scratch);
+    fs::remove_dir_all(&scratch)?;
+    result
+}
+
+fn measure(opts: &Options, scratch: &Path) -> io::Result<Report> {
+    let source = scratch.join("source");
+    let (files, bytes) = generate(&source, opts)?;
+
+    let mut copy = Vec::new();
+    let mut configs = Vec::new();
+    for (i, (engine, jobs, granularity)) in copy_configs().into_iter().enumerate() {
+        let dest = scratch.join(format!("copy-{}", i));
+        let pairs = copy_pairs(&source, &dest)?;
+        drop_cache(&source)?;
+        let started = Instant::now();
+        RealFs::with_copy_engine(engine)
+            .with_copy_jobs(jobs, granularity)
+            .copy_many(&pairs, &|_, _, _| {}, &mut |_, _| Ok(()))?;
+        let mut name = engine_name(engine).to_string();
+        if jobs > 1 {
+            name = format!("{} jobs={} {}", name, jobs, granularity_name(granularity));
+        }
+        copy.push(Measurement::new(name, bytes, started));
+        configs.push((engine, jobs, granularity));
+        drop_cache(&dest)?;
+        fs::remove_dir_all(&dest)?;
+    }
+
+    let mut hash = Vec::new();
+    for jobs in job_counts() {
+        drop_cache(&source)?;
+        let started = Instant::now();
+        manifest::digest_of(&source, jobs)?;
+        hash.push(Measurement::new(jobs.to_string(), bytes, started));
+    }
+
+    let archive = scratch.join("bench.zip");
+    write_zip(&source, &archive)?;
+    drop_cache(scratch)?;
+    let unpacked = scratch.join("unpacked");
+    let started = Instant::now();
+    download::extract_zip(&archive, &unpacked)?;
+    let extract = Measurement::new("zip".to_string(), bytes, started);
+    drop_cache(&unpacked)?;
+
+    let best = (0..copy.len())
+        .max_by(|a, b| copy[*a].throughput.total_cmp(&copy[*b].throughput))
+        .expect("std is always measured");
+    let best_copy = &copy[best];
+    // 与逐个复制相差不到 5% 时保持默认
+    let (engine, copy_jobs, granularity) = if best_copy.throughput > copy[0].throughput * 1.05 {
+        configs[best]
+    } else {
+        configs[0]
+    };
+    // 达到最快结果 90% 的最少线程数，给应用留出核心
+    let best_hash = hash.iter().map(|m| m.throughput).fold(0.0, f64::max);
+    let hash_jobs = hash
+        .iter()
+        .find(|m| m.throughput >= best_hash * 0.9)
+        .and_then(|m| m.name.parse().ok())
+        .unwrap_or(0);
+    // 直接解压省去一次复制：复制耗时达到解压的 25% 时值得开启
+    let stream_extract = best_copy.throughput < extract.throughput * 4.0;
+
+    Ok(Report {
+        files,
+        bytes,
+        copy,
+        hash,
+        extract,
+        copy_engine: engine_name(engine).to_string(),
+        copy_jobs,
+        copy_granularity: granularity_name(granularity).to_string(),
+        hash_jobs,
+        stream_extract,
+    })
+}
+
+/// 要测量的复制设置（复制方式，线程数，分配方式），第一项为默认的 std 依次复制；
+/// 多核设备上另外测量以全部核心按文件与按目录并行复制
+fn copy_configs() -> Vec<(CopyEngine, usize, Granularity)> {
+    let mut configs: Vec<_> = engines()
+        .into_iter()
+        .map(|engine| (engine, 1, Granularity::File))
+        .collect();
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if cores > 1 {
+        for granularity in [Granularity::File, Granularity::Dir] {
+            configs.push((CopyEngine::Std, cores, granularity));
+        }
+    }
+    configs
+}
+
+/// 当前平台可用的复制方式
+fn engines() -> Vec<CopyEngine> {
+    let supported = |engine: &CopyEngine| match engine {
+        CopyEngine::Std => true,
+        #[cfg(target_os = "linux")]
+        CopyEngine::Uring => crate::uring::available().is_ok(),
+        _ => cfg!(target_os = "linux"),
+    };
+    [CopyEngine::Std, CopyEngine::Readahead, CopyEngine::Uring]
+        .into_iter()
+        .filter(supported)
+        .collect()
+}
+
+fn engine_name(engine: CopyEngine) -> &'static str {
+    match engine {
+        CopyEngine::Std => "std",
+        CopyEngine::Readahead => "readahead",
+        CopyEngine::Uring => "uring",
+    }
+}
+
+fn granularity_name(granularity: Granularity) -> &'static str {
+    match granularity {
+        Granularity::File => "file",
+        Granularity::Dir => "dir",
+    }
+}
+
+/// 1、2、4……直到全部核心
+fn job_counts() -> Vec<usize> {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut counts: Vec<usize> = std::iter::successors(Some(1), |n| Some(n * 2))
+        .take_while(|n| *n < cores)
+        .collect();
+    counts.push(cores);
+    counts
+}
+
+/// 生成测试文件：小文件每 100 个一个目录，内容为伪随机数据（不可压缩，接近真实的二进制资源）
+fn generate(dir: &Path, opts: &Options) -> io::Result<(usize, u64)> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut fill = |buf: &mut [u8]| {
+        for chunk in buf.chunks_mut(8) {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            chunk.copy_from_slice(&state.to_le_bytes()[..chunk.len()]);
+        }
+    };
+    let mut buf = vec![0u8; SMALL_SIZE];
+    for i in 0..opts.small_files {
+        let sub = dir.join(format!("small-{:03}", i / 100));
+        fs::create_dir_all(&sub)?;
+        fill(&mut buf);
+        fs::write(sub.join(format!("{}.bin", i)), &buf)?;
+    }
+    fs::create_dir_all(dir)?;
+    for i in 0..opts.large_files {
+        let mut file = File::create(dir.join(format!("large-{}.bin", i)))?;
+        for _ in 0..LARGE_SIZE / SMALL_SIZE {
+            fill(&mut buf);
+            file.write_all(&buf)?;
+        }
+    }
+    let files = opts.small_files + opts.large_files;
+    let bytes = (opts.small_files * SMALL_SIZE + opts.large_files * LARGE_SIZE) as u64;
+    Ok((files, bytes))
+}
+
+/// source 中每个文件与其在 dest 中的位置，并创建所需的目录
+fn copy_pairs(source: &Path, dest: &Path) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut pairs = Vec::new();
+    for relative in Walk::new(source)? {
+        let relative = relative?;
+        let to = dest.join(&relative);
+        fs::create_dir_all(to.parent().unwrap_or(dest))?;
+        pairs.push((source.join(&relative), to));
+    }
+    Ok(pairs)
+}
+
+/// 把 source 打包为 zip（deflate，与 electron-builder 的更新包相同）
+fn write_zip(source: &Path, archive: &Path) -> io::Result<()> {
+    let mut zip = zip::ZipWriter::new(File::create(archive)?);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+    for relative in Walk::new(source)? {
+        let relative = relative?;
+        zip.start_file(walk::slash_path(&relative), options)
+            .map_err(io::Error::other)?;
+        io::copy(&mut File::open(source.join(&relative))?, &mut zip)?;
+    }
+    zip.finish().map_err(io::Error::other)?;
+    Ok(())
+}
+
+/// 把 dir 中的文件写回磁盘并丢弃其页缓存，下一次读取来自磁盘
+#[cfg(target_os = "linux")]
+fn drop_cache(dir: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    for relative in Walk::new(dir)? {
+        let file = File::open(dir.join(relative?))?;
+        file.sync_all()?;
+        // 只是建议，失败不影响测量
+        // SAFETY: fd 属于仍打开的 file，调用期间有效
+        unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn drop_cache(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use crate::Logger;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// 通过 Windows BITS 下载文件到 dest。
+/// 同一目标文件复用同一个 BITS 作业（按显示名识别），因此更新器被中断、注销或重启后
+/// 再次运行会接着之前的作业继续传输；在超时时间内没有进展时作业保留在后台，返回错误
+#[cfg(windows)]
+pub fn download(url: &str, dest: &Path, timeout: Duration, logger: &Logger) -> io::Result<()> {
+    use crate::powershell::{quote, run as powershell};
+    use sha2::{Digest, Sha256};
+    use std::time::Instant;
+
+    let dest = std::path::absolute(dest)?;
+    let hash = Sha256::digest(dest.to_string_lossy().as_bytes());
+    let name = format!(
+        "electron-quit-and-install-{}",
+        hash[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    );
+
+    let existing = powershell(&format!(
+        "(Get-BitsTransfer -Name {} -ErrorAction SilentlyContinue | Select-Object -First 1).JobState",
+        quote(&name)
+    ))?;
+    if existing.is_empty() {
+        logger.log(&format!("Creating BITS job {}", name));
+        powershell(&format!(
+            "Start-BitsTransfer -Source {} -Destination {} -DisplayName {} -Asynchronous -Priority Normal | Out-Null",
+            quote(url),
+            quote(&dest.to_string_lossy()),
+            quote(&name)
+        ))?;
+    } else {
+        logger.log(&format!("Resuming BITS job {} ({})", name, existing));
+    }
+
+    let job = format!(
+        "Get-BitsTransfer -Name {} | Select-Object -First 1",
+        quote(&name)
+    );
+    let mut last_progress = Instant::now();
+    let mut last_bytes = 0u64;
+    loop {
+        // 取消时作业留在后台，下次运行继续
+        logger.check_cancelled()?;
+        let status = powershell(&format!(
+            "$j = {}; \"$($j.JobState)|$($j.BytesTransferred)|$($j.BytesTotal)|$($j.ErrorDescription)\"",
+            job
+        ))?;
+        let mut fields = status.splitn(4, '|');
+        let state = fields.next().unwrap_or("");
+        let bytes = fields.next().and_then(|s| s.parse().ok()).unwrap_or(0u64);
+        let error = fields.nth(1).unwrap_or("").trim();
+
+        match state {
+            "Transferred" => {
+                powershell(&format!("{} | Complete-BitsTransfer", job))?;
+                return Ok(());
+            }
+            "Error" | "Cancelled" => {
+                let _ = powershell(&format!("{} | Remove-BitsTransfer", job));
+                return Err(io::Error::other(format!(
+                    "BITS job {} failed: {}",
+                    name,
+                    if error.is_empty() { state } else { error }
+                )));
+            }
+            "Suspended" => {
+                powershell(&format!(
+                    "{} | Resume-BitsTransfer -Asynchronous | Out-Null",
+                    job
+                ))?;
+            }
+            _ => {}
+        }
+
+        if bytes > last_bytes {
+            last_bytes = bytes;
+            last_progress = Instant::now();
+        } else if last_progress.elapsed() > timeout {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "BITS job {} made no progress in {}s ({}); it keeps running in the background and will be resumed on the next run",
+                    name,
+                    timeout.as_secs(),
+                    state
+                ),
+            ));
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+#[cfg(not(windows))]
+pub fn download(_url: &str, _dest: &Path, _timeout: Duration, _logger: &Logger) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "BITS backend is only available on Windows",
+    ))
+}
@@ -0,0 +1,48 @@
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 取消标记：嵌入程序或 Ctrl-C 处理函数调用 [`CancelToken::cancel`] 后，
+/// 更新在下一个安全点（等待进程退出、逐文件复制、下载分块、替换目录前）中止，
+/// 并清理暂存目录，已有文件保持不变
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+    /// 父标记被取消时本标记也视为已取消
+    parent: Option<Box<CancelToken>>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 子标记：随本标记一起取消，也可单独取消而不影响本标记（如只取消常驻进程中的当前这次更新）
+    pub fn child(&self) -> Self {
+        Self {
+            flag: Arc::default(),
+            parent: Some(Box::new(self.clone())),
+        }
+    }
+
+    /// 请求取消，可在任意线程（包括信号处理函数）中调用
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst) || self.parent.as_ref().is_some_and(|p| p.is_cancelled())
+    }
+
+    /// 已取消时返回 `Interrupted` 错误
+    pub(crate) fn check(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "Update cancelled",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
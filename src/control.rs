@@ -0,0 +1,400 @@
+//! 监视模式的控制接口：通过 Unix 套接字（Windows 上为命名管道）接收命令，
+//! 让应用或管理工具无需重新启动更新器即可控制更新时机。
+//!
+//! 每行一条命令，每条命令回复一行 JSON：
+//! - `status`：当前状态（watching、deferred、updating）、推迟到的时间、维护窗口、上次更新结果
+//! - `apply`：立即更新（不等待标记文件与维护窗口，并取消推迟）
+//! - `cancel`：取消正在进行的更新，监视继续
+//! - `defer <时长>`：推迟更新，时长如 `30m`、`1h`、`2d`
+
+use crate::schedule::Schedule;
+use crate::{CancelToken, Logger};
+use chrono::{DateTime, Local};
+use serde_json::{Value, json};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// 处理一行命令并返回回复，由各连接的线程共享
+type Handler = Arc<dyn Fn(&str) -> Value + Send + Sync>;
+
+#[derive(Default)]
+struct State {
+    /// 正在进行的更新的取消标记
+    current: Option<CancelToken>,
+    deferred_until: Option<DateTime<Local>>,
+    apply_requested: bool,
+    last_result: Option<String>,
+    /// 维护窗口，为空时随时可以更新
+    schedule: Option<Schedule>,
+}
+
+/// 监视模式与控制命令共享的状态
+#[derive(Default)]
+pub struct Control {
+    state: Mutex<State>,
+}
+
+impl Control {
+    pub fn new() -> Arc<Self> {
+        Arc::default()
+    }
+
+    /// 在后台线程中监听 path（Unix 套接字路径或 Windows 命名管道名，如 `\\.\pipe\eqi`），
+    /// 每个连接由单独的线程处理，一个客户端保持连接不影响其他客户端；之后监听出错时记录到 logger
+    pub fn serve(self: &Arc<Self>, path: &Path, logger: Arc<Logger>) -> io::Result<()> {
+        let control = self.clone();
+        let listener = listen(path)?;
+        thread::spawn(move || {
+            listener.run(Arc::new(move |line: &str| control.handle(line)), &logger)
+        });
+        Ok(())
+    }
+
+    /// 设置维护窗口：标记文件就绪后等到窗口内才更新
+    pub fn set_schedule(&self, schedule: Schedule) {
+        self.state.lock().unwrap().schedule = Some(schedule);
+    }
+
+    /// 是否应开始更新：收到 apply 命令，或 ready 为 true、未被推迟且位于维护窗口内
+    pub fn should_apply(&self, ready: bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.apply_requested {
+            state.apply_requested = false;
+            state.deferred_until = None;
+            return true;
+        }
+        if state
+            .deferred_until
+            .is_some_and(|until| until > Local::now())
+        {
+            return false;
+        }
+        state.deferred_until = None;
+        ready
+            && state
+                .schedule
+                .as_ref()
+                .is_none_or(|s| s.allows(&Local::now()))
+    }
+
+    /// 开始一次更新，返回其取消标记（parent 的子标记）
+    pub fn begin_update(&self, parent: &CancelToken) -> CancelToken {
+        let token = parent.child();
+        self.state.lock().unwrap().current = Some(token.clone());
+        token
+    }
+
+    /// 一次更新结束，记录结果
+    pub fn end_update(&self, result: impl Into<String>) {
+        let mut state = self.state.lock().unwrap();
+        state.current = None;
+        state.last_result = Some(result.into());
+    }
+
+    /// 执行一条命令，返回回复
+    fn handle(&self, line: &str) -> Value {
+        let mut parts = line.split_whitespace();
+        let command = parts.next().unwrap_or("");
+        let mut state = self.state.lock().unwrap();
+        match command {
+            "status" => {
+                let name = if state.current.is_some() {
+                    "updating"
+                } else if state.deferred_until.is_some_and(|u| u > Local::now()) {
+                    "deferred"
+                } else {
+                    "watching"
+                };
+                json!({
+                    "ok": true,
+                    "state": name,
+                    "deferred_until": state.deferred_until.map(|u| u.to_rfc3339()),
+                    "apply_at": state.schedule.as_ref().map(|s| s.to_string()),
+                    "last_result": state.last_result,
+                })
+            }
+            "apply" => {
+                state.apply_requested = true;
+                json!({"ok": true})
+            }
+            "cancel" => match &state.current {
+                Some(token) => {
+                    token.cancel();
+                    json!({"ok": true})
+                }
+                None => json!({"ok": false, "error": "No update in progress"}),
+            },
+            "defer" => match parts.next().map(|s| parse_duration(s).and_then(deadline)) {
+                Some(Ok(until)) => {
+                    state.deferred_until = Some(until);
+                    json!({"ok": true, "deferred_until": until.to_rfc3339()})
+                }
+                Some(Err(e)) => json!({"ok": false, "error": e}),
+                None => json!({"ok": false, "error": "Usage: defer <duration>"}),
+            },
+            _ => json!({"ok": false, "error": format!("Unknown command: {}", command)}),
+        }
+    }
+}
+
+/// 解析时长：数字加 s/m/h/d 后缀，无后缀时按秒
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let value: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", s))?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(format!("Invalid duration unit: {}", unit)),
+    };
+    value
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Duration is too long: {}", s))
+}
+
+/// 从现在起经过 duration 的时间；超出可表示的范围时返回错误
+fn deadline(duration: Duration) -> Result<DateTime<Local>, String> {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|d| Local::now().checked_add_signed(d))
+        .ok_or_else(|| format!("Duration is too long: {}s", duration.as_secs()))
+}
+
+/// 处理一个连接：读取每行命令并回复，直到客户端断开
+fn serve_connection(stream: impl io::Read + Write, handle: &dyn Fn(&str) -> Value) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while matches!(reader.read_line(&mut line), Ok(n) if n > 0) {
+        if !line.trim().is_empty() {
+            let reply = handle(line.trim());
+            let stream = reader.get_mut();
+            if writeln!(stream, "{}", reply)
+                .and_then(|()| stream.flush())
+                .is_err()
+            {
+                return;
+            }
+        }
+        line.clear();
+    }
+}
+
+#[cfg(unix)]
+struct Listener(std::os::unix::net::UnixListener);
+
+#[cfg(unix)]
+fn listen(path: &Path) -> io::Result<Listener> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    // 只删除上次异常退出留下的套接字：仍有进程在监听，或不是套接字的文件都不能删除
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        match UnixStream::connect(path) {
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("Another process is listening on {}", path.display()),
+                ));
+            }
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                std::fs::remove_file(path)?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Listener(UnixListener::bind(path)?))
+}
+
+#[cfg(unix)]
+impl Listener {
+    fn run(self, handle: Handler, logger: &Logger) {
+        for stream in self.0.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let handle = handle.clone();
+                    thread::spawn(move || serve_connection(stream, &*handle));
+                }
+                Err(e) => logger.warn(&format!("Control interface: failed to accept: {}", e)),
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+struct Listener {
+    name: Vec<u16>,
+    /// listen 创建的第一个实例，run 先在它上面等待连接
+    first: Option<std::fs::File>,
+}
+
+/// 只接受本机客户端；首个实例独占管道名，已被其他进程创建（抢占）时返回错误
+#[cfg(windows)]
+fn listen(path: &Path) -> io::Result<Listener> {
+    use std::os::windows::ffi::OsStrExt;
+    let name: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let first = create_pipe(&name, true).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("the pipe already exists or cannot be created: {}", e),
+        )
+    })?;
+    Ok(Listener {
+        name,
+        first: Some(first),
+    })
+}
+
+/// 创建管道的一个实例，first 为 true 时要求管道名尚不存在
+#[cfg(windows)]
+fn create_pipe(name: &[u16], first: bool) -> io::Result<std::fs::File> {
+    use std::os::windows::io::FromRawHandle;
+    use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX,
+    };
+    use windows_sys::Win32::System::Pipes::{
+        CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+        PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+
+    let open_mode = if first {
+        PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE
+    } else {
+        PIPE_ACCESS_DUPLEX
+    };
+    // SAFETY: 名称以 NUL 结尾，在调用期间有效；安全描述符为 null 时使用默认值
+    let pipe = unsafe {
+        CreateNamedPipeW(
+            name.as_ptr(),
+            open_mode,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            4096,
+            4096,
+            0,
+            std::ptr::null(),
+        )
+    };
+    if pipe == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: 句柄由此处独占，交给 File 负责关闭
+    Ok(unsafe { std::fs::File::from_raw_handle(pipe) })
+}
+
+#[cfg(windows)]
+impl Listener {
+    fn run(mut self, handle: Handler, logger: &Logger) {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::Foundation::{ERROR_PIPE_CONNECTED, GetLastError};
+        use windows_sys::Win32::System::Pipes::ConnectNamedPipe;
+
+        loop {
+            let stream = match self.first.take() {
+                Some(stream) => stream,
+                None => match create_pipe(&self.name, false) {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        logger.error(&format!(
+                            "Control interface stopped: failed to create pipe instance: {}",
+                            e
+                        ));
+                        return;
+                    }
+                },
+            };
+            // SAFETY: 句柄在 stream 存活期间有效；客户端在创建与等待之间连接时返回 ERROR_PIPE_CONNECTED
+            let connected = unsafe {
+                ConnectNamedPipe(stream.as_raw_handle(), std::ptr::null_mut()) != 0
+                    || GetLastError() == ERROR_PIPE_CONNECTED
+            };
+            if connected {
+                let handle = handle.clone();
+                thread::spawn(move || serve_connection(stream, &*handle));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        for (text, seconds) in [
+            ("45", 45),
+            ("0s", 0),
+            ("5m", 300),
+            ("2h", 7200),
+            ("1d", 86400),
+        ] {
+            assert_eq!(
+                parse_duration(text),
+                Ok(Duration::from_secs(seconds)),
+                "{}",
+                text
+            );
+        }
+        for text in ["", "m", "5x", "1.5h", "-1m", "5 m", "18446744073709551615d"] {
+            assert!(parse_duration(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn defer_rejects_out_of_range_durations() {
+        let control = Control::new();
+        for line in ["defer 213503982334601d", "defer 18446744073709551615"] {
+            let reply = control.handle(line);
+            assert_eq!(reply["ok"], false, "{}: {}", line, reply);
+        }
+        // 出错时没有持有锁崩溃，状态仍然可用
+        assert_eq!(control.handle("defer 1h")["ok"], true);
+        assert_eq!(control.handle("status")["state"], "deferred");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn listen_keeps_live_sockets_and_other_files() {
+        use std::os::unix::net::UnixStream;
+        let dir = std::env::temp_dir().join(format!("eqi-control-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (socket, file) = (dir.join("control.sock"), dir.join("file"));
+        std::fs::write(&file, "keep").unwrap();
+        let not_socket = listen(&file).err().map(|e| e.kind());
+
+        let control = Control::new();
+        control
+            .serve(&socket, Arc::new(Logger::new(None).unwrap()))
+            .unwrap();
+        let in_use = listen(&socket).err().map(|e| e.kind());
+        // 一个客户端保持连接时，其他客户端仍能得到回复
+        let _idle = UnixStream::connect(&socket).unwrap();
+        let mut client = UnixStream::connect(&socket).unwrap();
+        writeln!(client, "status").unwrap();
+        let mut reply = String::new();
+        BufReader::new(client).read_line(&mut reply).unwrap();
+
+        let kept = std::fs::read_to_string(&file).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(not_socket, Some(io::ErrorKind::AlreadyExists));
+        assert_eq!(in_use, Some(io::ErrorKind::AddrInUse));
+        assert!(reply.contains("\"state\":\"watching\""), "{}", reply);
+        assert_eq!(kept, "keep");
+    }
+}
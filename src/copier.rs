@@ -0,0 +1,165 @@
+//! 通过系统接口复制单个文件：Windows 上为 CopyFileExW（保留属性与备用数据流），
+//! macOS 上为 copyfile（保留扩展属性、ACL 与权限），均在复制过程中回调已复制的字节数；其他平台使用 std::fs::copy
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// 不小于此大小的文件才报告复制进度，避免大量小文件产生过多事件
+pub const PROGRESS_MIN: u64 = 1024 * 1024;
+
+/// 复制 from 到 to（覆盖已有文件），返回字节数；复制过程中以（已复制，总大小）调用 progress
+pub fn copy(from: &Path, to: &Path, progress: &mut dyn FnMut(u64, u64)) -> io::Result<u64> {
+    let total = fs::metadata(from)?.len();
+    let mut quiet = |_, _| {};
+    let progress: &mut dyn FnMut(u64, u64) = if total < PROGRESS_MIN {
+        &mut quiet
+    } else {
+        progress
+    };
+    native(from, to, total, progress)?;
+    Ok(total)
+}
+
+#[cfg(windows)]
+fn native(
+    from: &Path,
+    to: &Path,
+    _total: u64,
+    progress: &mut dyn FnMut(u64, u64),
+) -> io::Result<()> {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::HANDLE;
+    use windows_sys::Win32::Storage::FileSystem::{
+        COPYPROGRESSROUTINE_PROGRESS, CopyFileExW, LPPROGRESS_ROUTINE_CALLBACK_REASON,
+        PROGRESS_CONTINUE,
+    };
+
+    #[allow(clippy::too_many_arguments)]
+    unsafe extern "system" fn routine(
+        total: i64,
+        transferred: i64,
+        _stream_size: i64,
+        _stream_transferred: i64,
+        _stream_number: u32,
+        _reason: LPPROGRESS_ROUTINE_CALLBACK_REASON,
+        _source: HANDLE,
+        _dest: HANDLE,
+        data: *const c_void,
+    ) -> COPYPROGRESSROUTINE_PROGRESS {
+        // SAFETY: data 为下面传入的 &mut &mut dyn FnMut，复制期间有效
+        let progress = unsafe { &mut *(data as *mut &mut dyn FnMut(u64, u64)) };
+        progress(transferred as u64, total as u64);
+        PROGRESS_CONTINUE
+    }
+
+    let wide = |p: &Path| {
+        p.as_os_str()
+            .encode_wide()
+            .chain(Some(0))
+            .collect::<Vec<u16>>()
+    };
+    let (from, to) = (wide(from), wide(to));
+    let mut progress = progress;
+    // SAFETY: 路径以 NUL 结尾；回调数据在调用返回前有效
+    let copied = unsafe {
+        CopyFileExW(
+            from.as_ptr(),
+            to.as_ptr(),
+            Some(routine),
+            &mut progress as *mut &mut dyn FnMut(u64, u64) as *const c_void,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if copied == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn native(
+    from: &Path,
+    to: &Path,
+    total: u64,
+    progress: &mut dyn FnMut(u64, u64),
+) -> io::Result<()> {
+    use std::ffi::{CString, c_char, c_int, c_void};
+    use std::os::unix::ffi::OsStrExt;
+
+    /// 复制数据、权限、ACL 与扩展属性（copyfile.h 中的 COPYFILE_ALL）
+    const COPYFILE_ALL: libc::copyfile_flags_t = libc::COPYFILE_METADATA | libc::COPYFILE_DATA;
+
+    /// 回调所需的上下文
+    struct Context<'a> {
+        progress: &'a mut dyn FnMut(u64, u64),
+        total: u64,
+    }
+
+    extern "C" fn callback(
+        what: c_int,
+        stage: c_int,
+        state: libc::copyfile_state_t,
+        _source: *const c_char,
+        _dest: *const c_char,
+        ctx: *mut c_void,
+    ) -> c_int {
+        if what == libc::COPYFILE_COPY_DATA && stage == libc::COPYFILE_PROGRESS {
+            let mut copied: libc::off_t = 0;
+            // SAFETY: state 为当前复制的状态，ctx 为下面传入的 Context，复制期间有效
+            unsafe {
+                libc::copyfile_state_get(
+                    state,
+                    libc::COPYFILE_STATE_COPIED as u32,
+                    &mut copied as *mut libc::off_t as *mut c_void,
+                );
+                let ctx = &mut *(ctx as *mut Context);
+                (ctx.progress)(copied as u64, ctx.total);
+            }
+        }
+        libc::COPYFILE_CONTINUE
+    }
+
+    let path = |p: &Path| {
+        CString::new(p.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    };
+    let (from, to) = (path(from)?, path(to)?);
+    let mut ctx = Context { progress, total };
+    // SAFETY: state 在函数结束前释放；回调与上下文在 copyfile 返回前有效
+    let result = unsafe {
+        let state = libc::copyfile_state_alloc();
+        libc::copyfile_state_set(
+            state,
+            libc::COPYFILE_STATE_STATUS_CB as u32,
+            callback as *const c_void,
+        );
+        libc::copyfile_state_set(
+            state,
+            libc::COPYFILE_STATE_STATUS_CTX as u32,
+            &mut ctx as *mut Context as *const c_void,
+        );
+        let result = libc::copyfile(from.as_ptr(), to.as_ptr(), state, COPYFILE_ALL);
+        libc::copyfile_state_free(state);
+        result
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// 没有带进度的系统接口：复制完成后报告一次
+#[cfg(not(any(windows, target_os = "macos")))]
+fn native(
+    from: &Path,
+    to: &Path,
+    total: u64,
+    progress: &mut dyn FnMut(u64, u64),
+) -> io::Result<()> {
+    fs::copy(from, to)?;
+    progress(total, total);
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! 更新计划（`diff` 子命令）：比较更新文件与已安装的 output，列出暂存时会新增、替换、删除与忽略的文件，
+//! 不做任何改动。规则与暂存一致：旧 output 中的文件除 ignore_existing 外都会保留，
+//! 各层更新文件（后面的层覆盖前面的）除 ignores 外覆盖同名文件
+
+use crate::Logger;
+use crate::download::{self, DownloadOptions, Format};
+use crate::manifest;
+use crate::source::{self, SourceContext};
+use crate::vfs::RealFs;
+use crate::walk::{self, Walk};
+use crate::{is_ignored, normalize_ignore, single_file_input};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 暂存时会发生的变化，路径相对于 output、以 / 分隔
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Plan {
+    /// 新增的文件
+    pub added: Vec<String>,
+    /// 内容不同、将被替换的文件
+    pub replaced: Vec<String>,
+    /// 旧 output 中因 ignore_existing 不再带入新版本的文件
+    pub deleted: Vec<String>,
+    /// 更新文件中因 ignores 跳过的文件
+    pub ignored: Vec<String>,
+    /// 内容相同的文件数
+    pub unchanged: usize,
+}
+
+/// 准备每个 input（本地目录、更新包或 URL，同 `--input`），返回解包后的目录或文件；
+/// 缓存中的结果各自移动到独立的目录，避免多个更新包解包到同一位置
+pub fn prepare_inputs(
+    inputs: &[String],
+    options: &DownloadOptions,
+    logger: &Logger,
+) -> io::Result<Vec<PathBuf>> {
+    let ctx = SourceContext {
+        format: Format::Zip,
+        extract: true,
+        channel: None,
+        rollout: None,
+        download: options,
+        logger,
+    };
+    let mut paths = Vec::new();
+    for (i, input) in inputs.iter().enumerate() {
+        let source = source::from_input(input, None);
+        let prepared = source.prepare(&ctx)?.ok_or_else(|| {
+            io::Error::other(format!(
+                "{} is not in the current rollout",
+                source.describe()
+            ))
+        })?;
+        paths.push(download::isolate(prepared.path, "diff", i)?);
+    }
+    Ok(paths)
+}
+
+/// 计算以 inputs（第一项为更新文件，其余依次叠加）更新 output 的计划；jobs 为比较文件内容的线程数，0 表示全部核心
+pub fn plan(
+    inputs: &[PathBuf],
+    output: &Path,
+    ignores: &[OsString],
+    ignore_existing: &[OsString],
+    jobs: usize,
+) -> io::Result<Plan> {
+    let normalize = |list: &[OsString]| -> Vec<PathBuf> {
+        list.iter().filter_map(|s| normalize_ignore(s)).collect()
+    };
+    let (ignores, ignore_existing) = (&normalize(ignores)[..], &normalize(ignore_existing)[..]);
+    let [input, layers @ ..] = inputs else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No input given",
+        ));
+    };
+    // 单文件目标：只比较这一个文件
+    if let Some(file) = single_file_input(&RealFs::default(), output, input)? {
+        if !layers.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Layered inputs are not supported for single-file outputs",
+            ));
+        }
+        let name = walk::slash_path(Path::new(output.file_name().unwrap_or_default()));
+        let mut plan = Plan::default();
+        if !output.exists() {
+            plan.added.push(name);
+        } else if same_content(&file, output)? {
+            plan.unchanged = 1;
+        } else {
+            plan.replaced.push(name);
+        }
+        return Ok(plan);
+    }
+
+    // 各层的文件，后面的层覆盖前面的
+    let mut plan = Plan::default();
+    let mut sources = BTreeMap::new();
+    for root in inputs {
+        for relative in Walk::new(root)? {
+            let relative = relative?;
+            if is_ignored(&relative, ignores) {
+                plan.ignored.push(walk::slash_path(&relative));
+                continue;
+            }
+            let source = root.join(&relative);
+            sources.insert(relative, source);
+        }
+    }
+    let existing: BTreeSet<PathBuf> = if output.is_dir() {
+        Walk::new(output)?.collect::<io::Result<_>>()?
+    } else {
+        BTreeSet::new()
+    };
+
+    let mut common = Vec::new();
+    for (relative, source) in &sources {
+        if existing.contains(relative) {
+            common.push((relative, source));
+        } else {
+            plan.added.push(walk::slash_path(relative));
+        }
+    }
+    plan.deleted = existing
+        .iter()
+        .filter(|relative| {
+            is_ignored(relative, ignore_existing) && !sources.contains_key(*relative)
+        })
+        .map(|relative| walk::slash_path(relative))
+        .collect();
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(io::Error::other)?;
+    let same = pool.install(|| {
+        common
+            .par_iter()
+            .map(|(relative, source)| same_content(source, &output.join(relative)))
+            .collect::<io::Result<Vec<_>>>()
+    })?;
+    for ((relative, _), same) in common.iter().zip(same) {
+        if same {
+            plan.unchanged += 1;
+        } else {
+            plan.replaced.push(walk::slash_path(relative));
+        }
+    }
+    plan.added.sort();
+    plan.ignored.sort();
+    plan.ignored.dedup();
+    Ok(plan)
+}
+
+/// 两个文件内容是否相同：大小不同时不读取内容
+fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+    Ok(manifest::hash_file(a)?.1 == manifest::hash_file(b)?.1)
+}
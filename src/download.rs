@@ -0,0 +1,780 @@
+use crate::Logger;
+use crate::bits;
+use crate::pin;
+use crate::progress::Phase;
+use crate::s3::{self, S3Config};
+use crate::vfs::{FileSystem, RealFs};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use sha2::{Digest, Sha256, Sha512};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 下载参数
+#[derive(Clone, Debug)]
+pub struct DownloadOptions {
+    /// 失败后的重试次数
+    pub retries: u32,
+    /// 单次请求超时
+    pub timeout: Duration,
+    /// s3:// 地址的访问配置
+    pub s3: S3Config,
+    /// 显式指定的代理（http/https/socks5），为空时读取 HTTP(S)_PROXY / ALL_PROXY 环境变量
+    pub proxy: Option<String>,
+    /// 不走代理的主机列表，"*" 表示完全不使用代理
+    pub no_proxy: Vec<String>,
+    /// https 请求的证书固定值（SPKI 或证书的 sha256），为空时不做固定
+    pub pins: Vec<[u8; 32]>,
+    /// 下载限速（字节/秒），为空时不限速
+    pub max_rate: Option<u64>,
+    /// 下载方式
+    pub backend: Backend,
+}
+
+/// 下载方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// 内置 HTTP 客户端
+    Http,
+    /// Windows 后台智能传输服务（BITS），重启或注销后可继续传输
+    Bits,
+}
+
+impl Default for DownloadOptions {
+    /// 与命令行默认值一致：重试 3 次、超时 300 秒，使用环境变量中的代理
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            timeout: Duration::from_secs(300),
+            s3: S3Config::default(),
+            proxy: None,
+            no_proxy: Vec::new(),
+            pins: Vec::new(),
+            max_rate: None,
+            backend: Backend::Http,
+        }
+    }
+}
+
+impl DownloadOptions {
+    /// 为指定地址创建请求客户端（按主机决定是否使用代理）
+    fn agent(&self, url: &str) -> io::Result<ureq::Agent> {
+        let proxy = if self.bypass_proxy(url) {
+            None
+        } else if let Some(proxy) = &self.proxy {
+            Some(ureq::Proxy::new(proxy).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid proxy {}: {}", proxy, e),
+                )
+            })?)
+        } else {
+            ureq::Proxy::try_from_env()
+        };
+        let config = ureq::Agent::config_builder()
+            .timeout_global(Some(self.timeout))
+            .proxy(proxy)
+            .build();
+        if self.pins.is_empty() {
+            Ok(config.into())
+        } else {
+            pin::pinned_agent(config, &self.pins)
+        }
+    }
+
+    /// 主机是否命中 --no-proxy（支持 "*"、"example.com"、".example.com"）
+    fn bypass_proxy(&self, url: &str) -> bool {
+        let host = url
+            .split_once("://")
+            .map_or(url, |(_, rest)| rest)
+            .split(['/', '?', '#'])
+            .next()
+            .unwrap_or("");
+        let host = host.rsplit_once('@').map_or(host, |(_, h)| h);
+        let host = host.split(':').next().unwrap_or("").to_ascii_lowercase();
+        self.no_proxy.iter().any(|entry| {
+            let entry = entry.trim().to_ascii_lowercase();
+            let suffix = entry.trim_start_matches('*').trim_start_matches('.');
+            entry == "*" || host == suffix || host.ends_with(&format!(".{}", suffix))
+        })
+    }
+
+    /// 将 s3:// 地址转换为可直接请求的 https 地址
+    fn request_url(&self, url: &str) -> io::Result<String> {
+        if s3::is_s3_url(url) {
+            self.s3.presign(url)
+        } else {
+            Ok(url.to_string())
+        }
+    }
+
+    /// 按重试次数执行，失败间隔指数退避（1s, 2s, 4s ...）；只重试 [`is_transient`] 的错误
+    fn with_retries<T>(
+        &self,
+        logger: &Logger,
+        mut f: impl FnMut() -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Ok(v) => return Ok(v),
+                Err(e) if attempt < self.retries && is_transient(&e) && !logger.is_cancelled() => {
+                    let delay = Duration::from_secs(1 << attempt.min(5));
+                    attempt += 1;
+                    logger.warn(&format!(
+                        "Request failed ({}), retry {}/{} in {}s",
+                        e,
+                        attempt,
+                        self.retries,
+                        delay.as_secs()
+                    ));
+                    thread::sleep(delay);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// 错误是否可能在重试后消失：网络/IO 错误、408、429 与 5xx。
+/// 其余状态码（401/403/404/410 等）、TLS 校验与 --pin-sha256 不匹配重试也不会成功
+fn is_transient(e: &io::Error) -> bool {
+    let Some(err) = e
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<ureq::Error>())
+    else {
+        // 非 HTTP 错误（本地文件、BITS、416 后的重新下载）按原样重试
+        return true;
+    };
+    match err {
+        ureq::Error::StatusCode(code) => matches!(code, 408 | 429 | 500..=599),
+        // 握手失败（含证书固定不匹配）以 rustls::Error 包在 IO 错误中
+        ureq::Error::Io(e) => !e.get_ref().is_some_and(|inner| inner.is::<rustls::Error>()),
+        ureq::Error::Timeout(_)
+        | ureq::Error::HostNotFound
+        | ureq::Error::ConnectionFailed
+        | ureq::Error::ConnectProxyFailed(_)
+        | ureq::Error::Protocol(_)
+        | ureq::Error::BodyStalled => true,
+        _ => false,
+    }
+}
+
+/// 准备好的更新文件
+#[derive(Debug)]
+pub struct Prepared {
+    /// 可作为 input 使用的目录（AppImage 格式时为新的 AppImage 文件）
+    pub path: PathBuf,
+    /// 更新源或 Release 声明的版本号，直接给出的包/目录为空
+    pub version: Option<String>,
+    /// 更新源的 releaseNotes 或 Release 的说明
+    pub release_notes: Option<String>,
+}
+
+/// 更新包格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// zip 压缩包，解压后作为 input 目录
+    Zip,
+    /// 单文件 AppImage，直接替换
+    AppImage,
+    /// Debian 软件包，交给 dpkg 安装
+    Deb,
+    /// RPM 软件包，交给 rpm 安装
+    Rpm,
+}
+
+impl Format {
+    /// 文件名（或 URL）是否为该格式的包
+    pub fn matches(self, name: &str) -> bool {
+        let lower = url_path(name).to_ascii_lowercase();
+        match self {
+            Format::Zip => lower.ends_with(".zip"),
+            Format::AppImage => lower.ends_with(".appimage"),
+            Format::Deb => lower.ends_with(".deb"),
+            Format::Rpm => lower.ends_with(".rpm"),
+        }
+    }
+
+    /// 确定单文件格式的包：input 为文件时直接使用，为目录时取其中唯一的同格式文件
+    pub fn find(self, input: &Path) -> io::Result<PathBuf> {
+        if input.is_file() {
+            return Ok(input.to_path_buf());
+        }
+        let files: Vec<PathBuf> = fs::read_dir(input)?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && self.matches(&p.to_string_lossy()))
+            .collect();
+        match files.as_slice() {
+            [file] => Ok(file.clone()),
+            [] => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No {:?} package found in {}", self, input.display()),
+            )),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Multiple {:?} packages found in {}", self, input.display()),
+            )),
+        }
+    }
+}
+
+/// 判断是否为远程地址（http(s):// 或 s3://）
+pub fn is_url(s: &str) -> bool {
+    let lower = s.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://") || s3::is_s3_url(s)
+}
+
+/// 去掉 URL 的查询串和片段，便于判断扩展名、取文件名
+pub fn url_path(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+/// 更新器缓存目录（下载的包与解压结果），只有当前用户可以访问。
+/// 位于当前用户的缓存目录下（Windows 为 %LOCALAPPDATA%，macOS 为 ~/Library/Caches，
+/// 其他平台为 $XDG_CACHE_HOME 或 ~/.cache），而不是所有用户都可写入的临时目录
+pub fn cache_dir() -> io::Result<PathBuf> {
+    let env = |name: &str| {
+        std::env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    let base = if cfg!(windows) {
+        env("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|home| home.join("Library").join("Caches"))
+    } else {
+        env("XDG_CACHE_HOME").or_else(|| env("HOME").map(|home| home.join(".cache")))
+    };
+    let dir = base
+        .unwrap_or_else(std::env::temp_dir)
+        .join("electron-quit-and-install");
+    crate::secure::create_private_dir(&dir)?;
+    Ok(dir)
+}
+
+/// 检查来自更新源的 value（版本号、文件名）可以作为单个路径组成部分：不能为空，
+/// 不能含路径分隔符、`..`、盘符或 NUL，否则可能写入或删除缓存、安装目录之外的位置
+pub fn path_component<'a>(value: &'a str, what: &str) -> io::Result<&'a str> {
+    let mut components = Path::new(value).components();
+    let single = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    );
+    if !single || value.contains(['/', '\\', ':', '\0']) || value.contains("..") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Refusing to use {} {:?} as a file name", what, value),
+        ));
+    }
+    Ok(value)
+}
+
+/// 下载地址中的文件名，地址以 / 结尾时为 package.zip
+pub fn url_file_name(url: &str) -> io::Result<&str> {
+    let name = url_path(url).rsplit('/').next().unwrap_or_default();
+    if name.is_empty() {
+        return Ok("package.zip");
+    }
+    path_component(name, "package file name")
+}
+
+/// 同一格式的更新包总是解包到缓存中的同一目录，后准备的更新文件会覆盖先准备的；
+/// 准备结果位于缓存中时移动到 `<cache>/<group>/<index>`，由调用方独占
+pub fn isolate(path: PathBuf, group: &str, index: usize) -> io::Result<PathBuf> {
+    let cache = cache_dir()?;
+    if !path.starts_with(&cache) {
+        return Ok(path);
+    }
+    let dest = cache.join(group).join(index.to_string());
+    if dest.exists() {
+        crate::remove_path(&RealFs::default(), &dest)?;
+    }
+    fs::create_dir_all(cache.join(group))?;
+    fs::rename(&path, &dest)?;
+    Ok(dest)
+}
+
+/// 读取远程文本内容（更新源 yml、提供方 API）
+pub fn fetch_text(
+    url: &str,
+    headers: &[(&str, String)],
+    opts: &DownloadOptions,
+    logger: &Logger,
+) -> io::Result<String> {
+    opts.with_retries(logger, || {
+        let url = opts.request_url(url)?;
+        let mut req = opts.agent(&url)?.get(&url);
+        for (name, value) in headers {
+            req = req.header(*name, value);
+        }
+        let mut resp = req.call().map_err(io::Error::other)?;
+        resp.body_mut().read_to_string().map_err(io::Error::other)
+    })
+}
+
+/// 下载远程文件到指定路径，支持断点续传（先写入 .part 文件，完成后再改名）
+pub fn download_file(
+    url: &str,
+    dest: &Path,
+    opts: &DownloadOptions,
+    logger: &Logger,
+) -> io::Result<()> {
+    logger.log(&format!("Downloading {} -> {}", url, dest.display()));
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let part = dest.with_file_name(format!(
+        "{}.part",
+        dest.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    opts.with_retries(logger, || {
+        let url = opts.request_url(url)?;
+        match opts.backend {
+            Backend::Http => download_part(&opts.agent(&url)?, &url, &part, opts.max_rate, logger),
+            // BITS 自行处理代理与续传，由系统统一调度带宽
+            Backend::Bits => bits::download(&url, &part, opts.timeout, logger),
+        }
+    })?;
+    fs::rename(&part, dest)?;
+
+    logger.log(&format!(
+        "Downloaded {} bytes",
+        fs::metadata(dest).map(|m| m.len()).unwrap_or(0)
+    ));
+    Ok(())
+}
+
+/// 单次下载尝试：已有部分内容时带 Range 请求续传
+fn download_part(
+    agent: &ureq::Agent,
+    url: &str,
+    part: &Path,
+    max_rate: Option<u64>,
+    logger: &Logger,
+) -> io::Result<()> {
+    let existing = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+    let mut req = agent.get(url);
+    if existing > 0 {
+        req = req.header("Range", &format!("bytes={}-", existing));
+    }
+
+    let resp = match req.call() {
+        Ok(resp) => resp,
+        // 已下载部分超出服务器文件大小（文件已变更），丢弃后从头下载
+        Err(ureq::Error::StatusCode(416)) => {
+            fs::remove_file(part)?;
+            return Err(io::Error::other("Range not satisfiable, restarting"));
+        }
+        Err(e) => return Err(io::Error::other(e)),
+    };
+
+    let resumed = resp.status() == 206;
+    let mut file = if resumed {
+        logger.log(&format!("Resuming download at {} bytes", existing));
+        OpenOptions::new().append(true).open(part)?
+    } else {
+        File::create(part)?
+    };
+    let offset = if resumed { existing } else { 0 };
+    let total = resp.body().content_length().map(|len| len + offset);
+    let mut reader = resp.into_body().into_reader();
+
+    logger.progress_start(Phase::Download, total);
+    logger.progress_inc(offset);
+    let result = copy_with_rate(&mut reader, &mut file, max_rate, logger);
+    logger.progress_finish();
+    result
+}
+
+/// 逐块写入文件并上报字节数；指定限速时按已写入字节数计算应耗时，超前时休眠。
+/// 网络读取与磁盘写入在两个线程中并行进行，每块都直接落盘，中断后可续传
+fn copy_with_rate(
+    reader: &mut impl Read,
+    file: &mut File,
+    max_rate: Option<u64>,
+    logger: &Logger,
+) -> io::Result<()> {
+    let rate = max_rate.filter(|r| *r > 0);
+    let started = Instant::now();
+    let mut written: u64 = 0;
+    let mut buf = vec![0u8; rate.map_or(64 * 1024, |r| (r as usize).clamp(1024, 64 * 1024))];
+    let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(8);
+    thread::scope(|scope| {
+        let writer = scope.spawn(move || -> io::Result<()> {
+            for chunk in rx {
+                file.write_all(&chunk)?;
+            }
+            Ok(())
+        });
+
+        let read = loop {
+            if let Err(e) = logger.check_cancelled() {
+                break Err(e);
+            }
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break Ok(()),
+                Ok(n) => n,
+                Err(e) => break Err(e),
+            };
+            // 写入线程出错时停止读取，错误由 join 返回
+            if tx.send(buf[..n].to_vec()).is_err() {
+                break Ok(());
+            }
+            written += n as u64;
+            logger.progress_inc(n as u64);
+            if let Some(rate) = rate {
+                let expected = Duration::from_secs_f64(written as f64 / rate as f64);
+                if let Some(ahead) = expected.checked_sub(started.elapsed()) {
+                    thread::sleep(ahead);
+                }
+            }
+        };
+        drop(tx);
+        let write = writer
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("Download writer thread panicked")));
+        write.and(read)
+    })
+}
+
+/// 解析限速参数，例如 "500K"、"2M"、"1.5MB/s"、"1048576"（字节/秒）
+pub fn parse_rate(s: &str) -> Result<u64, String> {
+    let lower = s.trim().to_ascii_lowercase();
+    let lower = lower.trim_end_matches("/s").trim_end_matches('b');
+    let (num, unit) = match lower.chars().last() {
+        Some('k') => (&lower[..lower.len() - 1], 1024.0),
+        Some('m') => (&lower[..lower.len() - 1], 1024.0 * 1024.0),
+        Some('g') => (&lower[..lower.len() - 1], 1024.0 * 1024.0 * 1024.0),
+        _ => (lower, 1.0),
+    };
+    match num.trim().parse::<f64>() {
+        Ok(v) if v > 0.0 => Ok((v * unit) as u64),
+        _ => Err(format!("invalid rate {} (expected e.g. 500K, 2M)", s)),
+    }
+}
+
+/// 更新包的期望校验值
+#[derive(Clone, Debug)]
+pub enum Checksum {
+    /// 十六进制 sha256
+    Sha256(String),
+    /// base64 sha512（electron-builder 更新源的格式）
+    Sha512(String),
+}
+
+impl Checksum {
+    /// 校验文件内容，不匹配时返回 InvalidData 错误
+    pub fn verify(&self, path: &Path) -> io::Result<()> {
+        let (name, expected, actual) = match self {
+            Checksum::Sha256(expected) => ("sha256", expected, hash_file::<Sha256>(path)?),
+            Checksum::Sha512(expected) => ("sha512", expected, hash_file::<Sha512>(path)?),
+        };
+        let matches = match self {
+            Checksum::Sha256(_) => hex(&actual).eq_ignore_ascii_case(expected.trim()),
+            Checksum::Sha512(_) => STANDARD
+                .decode(expected.trim())
+                .is_ok_and(|bytes| bytes == actual),
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} mismatch for {}: expected {}, got {}",
+                    name,
+                    path.display(),
+                    expected,
+                    match self {
+                        Checksum::Sha256(_) => hex(&actual),
+                        Checksum::Sha512(_) => STANDARD.encode(&actual),
+                    }
+                ),
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for Checksum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Checksum::Sha256(v) => write!(f, "sha256 {}", v),
+            Checksum::Sha512(v) => write!(f, "sha512 {}", v),
+        }
+    }
+}
+
+/// 下载并校验更新包到 dest：
+/// 已缓存且校验通过时直接复用；新下载的内容先放在临时位置，校验通过后才替换缓存，
+/// 校验失败时丢弃新内容、保留之前缓存的包
+pub fn download_verified(
+    url: &str,
+    dest: &Path,
+    checksum: Option<&Checksum>,
+    opts: &DownloadOptions,
+    logger: &Logger,
+) -> io::Result<()> {
+    if let Some(checksum) = checksum
+        && dest.exists()
+        && checksum.verify(dest).is_ok()
+    {
+        logger.log(&format!("Using cached package {}", dest.display()));
+        return Ok(());
+    }
+
+    let incoming = dest.with_file_name(format!(
+        "{}.incoming",
+        dest.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    download_file(url, &incoming, opts, logger)?;
+    match checksum {
+        Some(checksum) => {
+            if let Err(e) = checksum.verify(&incoming) {
+                // 校验失败的文件不可续传，直接删除
+                let _ = fs::remove_file(&incoming);
+                return Err(e);
+            }
+            logger.log(&format!("Checksum verified: {}", checksum));
+        }
+        None => logger.warn(&format!(
+            "No checksum available for {}, skipping verification",
+            url
+        )),
+    }
+    fs::rename(&incoming, dest)
+}
+
+/// 下载 URL 指向的更新包到缓存目录，校验并解包（extract 为 false 时不解压 zip，见 [`unpack`]），
+/// 返回可作为 input 使用的路径
+pub fn prepare_package(
+    url: &str,
+    sha256: Option<&str>,
+    format: Format,
+    extract: bool,
+    opts: &DownloadOptions,
+    logger: &Logger,
+) -> io::Result<PathBuf> {
+    let cache = cache_dir()?.join("download");
+    let archive = cache.join(url_file_name(url)?);
+    let checksum = sha256.map(|s| Checksum::Sha256(s.to_string()));
+    download_verified(url, &archive, checksum.as_ref(), opts, logger)?;
+    unpack(&archive, format, extract, logger)
+}
+
+/// 解包已下载的更新包：zip 解压到同级的 unpacked 目录，单文件格式原样返回；
+/// extract 为 false 时 zip 也原样返回，由暂存直接解压（见 [`extract_zip_into`]）
+pub fn unpack(
+    archive: &Path,
+    format: Format,
+    extract: bool,
+    logger: &Logger,
+) -> io::Result<PathBuf> {
+    match format {
+        Format::Zip if extract => {
+            let unpacked = archive.with_file_name("unpacked");
+            extract_zip(archive, &unpacked)?;
+            logger.log(&format!("Extracted package to {}", unpacked.display()));
+            Ok(unpacked)
+        }
+        Format::Zip | Format::AppImage | Format::Deb | Format::Rpm => Ok(archive.to_path_buf()),
+    }
+}
+
+/// 解压 zip 包到目标目录（目标目录已存在时先清空）
+pub fn extract_zip(archive: &Path, dest: &Path) -> io::Result<()> {
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+    let mut zip = zip::ZipArchive::new(File::open(archive)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    zip.extract(dest)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// 把 zip 包中的文件直接解压到已有的目录 dest（暂存目录），不经过临时目录：
+/// 相对路径为 ignores 中某项本身或位于其下的条目跳过（见 [`crate::is_ignored`]），同名文件先删除再写入（可能是旧版本的硬链接）。
+/// 条目路径与符号链接目标不能位于 dest 之外
+pub fn extract_zip_into(
+    archive: &Path,
+    dest: &Path,
+    ignores: &[PathBuf],
+    logger: &Logger,
+) -> io::Result<()> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+    let mut zip = zip::ZipArchive::new(File::open(archive)?).map_err(|e| invalid(e.to_string()))?;
+    let ignored = |name: &Path| crate::is_ignored(name, ignores);
+    let total = zip
+        .file_names()
+        .filter(|name| {
+            name.as_ref()
+                .is_ok_and(|name| !name.ends_with('/') && !ignored(Path::new(&**name)))
+        })
+        .count();
+    logger.progress_start(Phase::CopyUpdate, Some(total as u64));
+    // 符号链接在所有文件解压后创建，此时才能确定目标是否为目录
+    let mut links = Vec::new();
+    for i in 0..zip.len() {
+        logger.check_cancelled()?;
+        let mut entry = zip.by_index(i).map_err(|e| invalid(e.to_string()))?;
+        let Some(relative) = entry.enclosed_name() else {
+            let name = entry.name().map(|n| n.into_owned()).unwrap_or_default();
+            return Err(invalid(format!(
+                "Refusing to extract {}: unsafe path",
+                name
+            )));
+        };
+        if ignored(&relative) {
+            logger.log(&format!("Ignored: {}", relative.display()));
+            continue;
+        }
+        let path = dest.join(&relative);
+        if entry.is_dir() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if path.is_symlink() || path.is_file() {
+            fs::remove_file(&path)?;
+        }
+        if entry.is_symlink() {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+            links.push((path, PathBuf::from(target)));
+            continue;
+        }
+        let mut file = File::create(&path)?;
+        let bytes = io::copy(&mut entry, &mut file)?;
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(mode & 0o7777))?;
+        }
+        logger.log(&format!("Extracted file: {}", path.display()));
+        logger.file_copied(&path, bytes);
+        logger.progress_inc(1);
+    }
+    for (link, target) in links {
+        let resolved = crate::normalize(&link.parent().unwrap_or(dest).join(&target));
+        if !resolved.starts_with(crate::normalize(dest)) {
+            return Err(invalid(format!(
+                "Refusing to extract symlink {} -> {}: it points outside {}",
+                link.display(),
+                target.display(),
+                dest.display()
+            )));
+        }
+        RealFs::default().symlink(&target, &link, resolved.is_dir())?;
+        logger.log(&format!(
+            "Extracted symlink: {} -> {}",
+            link.display(),
+            target.display()
+        ));
+        logger.progress_inc(1);
+    }
+    Ok(())
+}
+
+/// 计算文件摘要
+fn hash_file<D: Digest>(path: &Path) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut hasher = D::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_component_rejects_traversal() {
+        for value in ["1.2.3", "v2.0.0-beta.1", "MyApp-2.0.0-win.zip"] {
+            assert_eq!(path_component(value, "version").unwrap(), value);
+        }
+        for value in [
+            "", ".", "..", "../../x", "a/b", r"a\b", "/etc", r"C:\x", "C:", "1.0..2", "a\0b",
+        ] {
+            assert!(path_component(value, "version").is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn url_file_name_of_package() {
+        assert_eq!(
+            url_file_name("https://example.com/a/MyApp.zip?x=1").unwrap(),
+            "MyApp.zip"
+        );
+        assert_eq!(
+            url_file_name("https://example.com/a/").unwrap(),
+            "package.zip"
+        );
+        assert!(url_file_name("https://example.com/a/..").is_err());
+        assert!(url_file_name(r"https://example.com/..\..\x.zip").is_err());
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        let http = |e: ureq::Error| io::Error::other(e);
+        for code in [408, 429, 500, 503] {
+            assert!(
+                is_transient(&http(ureq::Error::StatusCode(code))),
+                "{}",
+                code
+            );
+        }
+        for code in [400, 401, 403, 404, 410] {
+            assert!(
+                !is_transient(&http(ureq::Error::StatusCode(code))),
+                "{}",
+                code
+            );
+        }
+        assert!(is_transient(&http(ureq::Error::ConnectionFailed)));
+        assert!(is_transient(&http(ureq::Error::Io(
+            io::ErrorKind::ConnectionReset.into()
+        ))));
+        assert!(is_transient(&io::Error::other(
+            "Range not satisfiable, restarting"
+        )));
+
+        let pin_mismatch = io::Error::new(
+            io::ErrorKind::InvalidData,
+            rustls::Error::General("server certificate does not match --pin-sha256".into()),
+        );
+        assert!(!is_transient(&http(ureq::Error::Io(pin_mismatch))));
+        assert!(!is_transient(&http(ureq::Error::Tls("invalid dns name"))));
+
+        let logger = Logger::new(None).unwrap();
+        let opts = DownloadOptions {
+            retries: 3,
+            ..DownloadOptions::default()
+        };
+        let mut calls = 0;
+        let result: io::Result<()> = opts.with_retries(&logger, || {
+            calls += 1;
+            Err(http(ureq::Error::StatusCode(404)))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,92 @@
+//! 权限提升：按机器安装（如 Program Files 下）的应用需要管理员权限才能替换，
+//! 预检发现没有写入权限时以管理员身份重新启动更新器（Windows 上弹出 UAC 确认）
+
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+
+/// 是否可以替换 output：需要在 output 所在目录中创建 <output>_new，
+/// output 为目录时还需要在其中写入（squirrel 布局在其中安装新版本）。只有拒绝访问才视为不可写
+pub fn can_replace(output: &Path) -> bool {
+    let parent = output
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut dirs = vec![parent];
+    if output.is_dir() {
+        dirs.push(output);
+    }
+    dirs.into_iter().all(|dir| {
+        let probe = dir.join(format!(".eqi-write-test-{}", std::process::id()));
+        match OpenOptions::new().write(true).create_new(true).open(&probe) {
+            Ok(_) => {
+                let _ = fs::remove_file(&probe);
+                true
+            }
+            Err(e) => e.kind() != io::ErrorKind::PermissionDenied,
+        }
+    })
+}
+
+/// 以管理员身份运行当前 exe（参数为 args），等待其结束并返回退出码；
+/// 用户在 UAC 中拒绝时返回错误
+#[cfg(windows)]
+pub fn run_elevated(args: &[OsString]) -> io::Result<i32> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, INFINITE, WaitForSingleObject,
+    };
+    use windows_sys::Win32::UI::Shell::{
+        SEE_MASK_NOASYNC, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW, ShellExecuteExW,
+    };
+    const SW_HIDE: i32 = 0;
+
+    let wide = |s: &str| s.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    let exe = std::env::current_exe()?;
+    let file = wide(&exe.to_string_lossy());
+    let parameters = wide(
+        &args
+            .iter()
+            .map(|arg| crate::service::quote(&arg.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    let verb = wide("runas");
+
+    // SAFETY: SHELLEXECUTEINFOW 为纯数据结构，全零是合法的初始值
+    let mut info: SHELLEXECUTEINFOW = unsafe { std::mem::zeroed() };
+    info.cbSize = std::mem::size_of::<SHELLEXECUTEINFOW>() as u32;
+    info.fMask = SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NOASYNC;
+    info.lpVerb = verb.as_ptr();
+    info.lpFile = file.as_ptr();
+    info.lpParameters = parameters.as_ptr();
+    info.nShow = SW_HIDE;
+    // SAFETY: 字符串均以 NUL 结尾，在调用期间有效
+    if unsafe { ShellExecuteExW(&mut info) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if info.hProcess.is_null() {
+        return Err(io::Error::other("Elevated process handle is not available"));
+    }
+    let mut code = 0u32;
+    // SAFETY: hProcess 由 ShellExecuteExW 返回（SEE_MASK_NOCLOSEPROCESS），使用后关闭
+    let ok = unsafe {
+        WaitForSingleObject(info.hProcess, INFINITE);
+        let ok = GetExitCodeProcess(info.hProcess, &mut code);
+        CloseHandle(info.hProcess);
+        ok
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(code as i32)
+}
+
+#[cfg(not(windows))]
+pub fn run_elevated(_args: &[OsString]) -> io::Result<i32> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Automatic elevation is only available on Windows",
+    ))
+}
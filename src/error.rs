@@ -0,0 +1,105 @@
+use crate::Arch;
+use crate::hooks::HookFailed;
+use std::io;
+
+/// 更新失败的原因；命令行据此决定退出码
+#[derive(Debug, thiserror::Error)]
+pub enum UpdateError {
+    /// 参数缺失或无效
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    /// 日志文件等基础设施不可用
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// 下载、校验或解包失败，应用未被退出
+    #[error("Failed to prepare update files: {0}")]
+    Prepare(#[source] io::Error),
+    /// 交给商店/包管理器更新失败
+    #[error("Update hand-off failed: {0}")]
+    HandOff(#[source] io::Error),
+    /// macOS 上应用被转移运行或位于只读位置，且未允许移动到 /Applications
+    #[error("Move the app to /Applications or rerun with --move-to-applications")]
+    NotRelocatable,
+    /// 移动到 /Applications 失败
+    #[error("Failed to move app to /Applications: {0}")]
+    Relocate(#[source] io::Error),
+    /// 退出应用或替换文件失败；暂存目录已清理，原有文件保持不变或已恢复
+    #[error("{0}")]
+    Apply(#[source] io::Error),
+    /// 生命周期钩子失败，更新已中止并回滚
+    #[error(transparent)]
+    Hook(HookFailed),
+    /// 已安装版本低于更新要求的最低版本（或无法确定），应用未被退出
+    #[error(
+        "Update requires installed version {required} or later, found {}",
+        installed.as_deref().unwrap_or("unknown")
+    )]
+    InstalledTooOld {
+        installed: Option<String>,
+        required: String,
+    },
+    /// 更新文件与已安装应用的 CPU 架构不一致，应用未被退出
+    #[error(
+        "Update is built for {update} but the installed app is {installed}, rerun with --force to install anyway"
+    )]
+    ArchMismatch { installed: Arch, update: Arch },
+    /// 更新文件中主程序没有与已安装主程序相同签名者的有效签名，应用未被退出
+    #[error(
+        "Update {} but the installed app is signed by {installed}, rerun with --allow-publisher-change to install anyway",
+        update.as_ref().map_or("has no valid signature".to_string(), |u| format!("is signed by {}", u))
+    )]
+    PublisherChanged {
+        installed: String,
+        update: Option<String>,
+    },
+    /// 安装后的版本与 --expected-version 不一致，已回滚并重新启动当前版本
+    #[error(
+        "Installed version {} does not match expected {expected}, update rolled back",
+        found.as_deref().unwrap_or("unknown")
+    )]
+    UnexpectedVersion {
+        expected: String,
+        found: Option<String>,
+    },
+    /// 被取消，已回滚并重新启动当前版本
+    #[error("Update cancelled")]
+    Cancelled,
+}
+
+impl UpdateError {
+    /// 失败所在的阶段，传给失败通知命令（`EQI_FAILED_PHASE`）
+    pub fn phase(&self) -> &'static str {
+        match self {
+            UpdateError::Io(_) => "io",
+            UpdateError::InvalidConfig(_) => "config",
+            UpdateError::Prepare(_)
+            | UpdateError::InstalledTooOld { .. }
+            | UpdateError::ArchMismatch { .. }
+            | UpdateError::PublisherChanged { .. } => "prepare",
+            UpdateError::Apply(_) => "apply",
+            UpdateError::HandOff(_) => "hand-off",
+            UpdateError::NotRelocatable | UpdateError::Relocate(_) => "relocate",
+            UpdateError::Hook(_) => "hook",
+            UpdateError::UnexpectedVersion { .. } => "verify",
+            UpdateError::Cancelled => "cancelled",
+        }
+    }
+
+    /// 命令行退出码
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            UpdateError::Io(_) => 1,
+            UpdateError::InvalidConfig(_) => 2,
+            UpdateError::Prepare(_) => 4,
+            UpdateError::Apply(_) => 5,
+            UpdateError::HandOff(_) => 6,
+            UpdateError::NotRelocatable | UpdateError::Relocate(_) => 7,
+            UpdateError::Hook(_) => 8,
+            UpdateError::InstalledTooOld { .. } => 9,
+            UpdateError::ArchMismatch { .. } => 10,
+            UpdateError::UnexpectedVersion { .. } => 11,
+            UpdateError::PublisherChanged { .. } => 13,
+            UpdateError::Cancelled => 130,
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use crate::Logger;
+use crate::download::{self, Checksum, DownloadOptions, Format, Prepared};
+use crate::{rollout, version};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// electron-builder 生成的 latest.yml / latest-mac.yml / latest-linux.yml
+#[derive(Debug, Deserialize)]
+pub struct Feed {
+    pub version: String,
+    #[serde(default)]
+    pub files: Vec<FeedFile>,
+    pub path: Option<String>,
+    pub sha512: Option<String>,
+    /// 旧版 electron-builder 的十六进制 sha256
+    #[serde(alias = "sha2")]
+    pub sha256: Option<String>,
+    /// 灰度发布百分比（electron-builder 的 stagingPercentage）
+    #[serde(rename = "stagingPercentage")]
+    pub staging_percentage: Option<f64>,
+    /// 更新要求的最低系统版本（Windows 为 NT 版本号如 10.0.17763，macOS 如 12.0）
+    #[serde(rename = "minimumSystemVersion")]
+    pub minimum_system_version: Option<String>,
+    /// 更新说明：字符串，或 electron-builder 的 [{version, note}] 列表
+    #[serde(rename = "releaseNotes")]
+    pub release_notes: Option<serde_yaml::Value>,
+    /// 按平台区分的设置，键为 Node.js 的平台名（win32、darwin、linux），可带架构后缀（如 darwin-arm64）；
+    /// 运行时选出与本机匹配的一节覆盖顶层设置
+    #[serde(default)]
+    pub platforms: BTreeMap<String, PlatformSection>,
+}
+
+/// 更新源中某个平台的设置，未填写的字段沿用顶层设置
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlatformSection {
+    #[serde(default)]
+    pub files: Vec<FeedFile>,
+    pub path: Option<String>,
+    pub sha512: Option<String>,
+    #[serde(alias = "sha2")]
+    pub sha256: Option<String>,
+    #[serde(rename = "minimumSystemVersion")]
+    pub minimum_system_version: Option<String>,
+}
+
+/// 更新源中的单个文件条目
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeedFile {
+    pub url: String,
+    pub sha512: Option<String>,
+    #[serde(alias = "sha2")]
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+}
+
+impl FeedFile {
+    /// 更新源声明的校验值，优先 sha512
+    pub fn checksum(&self) -> Option<Checksum> {
+        self.sha512
+            .clone()
+            .map(Checksum::Sha512)
+            .or_else(|| self.sha256.clone().map(Checksum::Sha256))
+    }
+}
+
+impl Feed {
+    /// 选出与本机匹配的平台设置并覆盖顶层设置：先找 <平台>-<架构>，再找 <平台>；返回选中的键
+    pub fn select_platform(&mut self) -> Option<String> {
+        let platform = node_platform();
+        let key = [
+            format!("{}-{}", platform, node_arch()),
+            platform.to_string(),
+        ]
+        .into_iter()
+        .find(|k| self.platforms.contains_key(k))?;
+        let section = self.platforms[&key].clone();
+        if !section.files.is_empty() {
+            self.files = section.files;
+        }
+        if section.path.is_some() {
+            self.path = section.path;
+            self.sha512 = section.sha512;
+            self.sha256 = section.sha256;
+        }
+        if section.minimum_system_version.is_some() {
+            self.minimum_system_version = section.minimum_system_version;
+        }
+        Some(key)
+    }
+
+    /// 更新说明文本；列表形式时按顺序拼接各版本的说明
+    pub fn release_notes(&self) -> Option<String> {
+        match self.release_notes.as_ref()? {
+            serde_yaml::Value::String(text) => Some(text.clone()),
+            serde_yaml::Value::Sequence(items) => {
+                let notes: Vec<String> = items
+                    .iter()
+                    .filter_map(|item| {
+                        let note = item.get("note")?.as_str()?;
+                        Some(match item.get("version").and_then(|v| v.as_str()) {
+                            Some(version) => format!("{}\n{}", version, note),
+                            None => note.to_string(),
+                        })
+                    })
+                    .collect();
+                (!notes.is_empty()).then(|| notes.join("\n\n"))
+            }
+            _ => None,
+        }
+    }
+
+    /// 选出可用于更新的包：优先 files 中指定格式的包，其次顶层 path
+    pub fn package(&self, format: Format) -> Option<FeedFile> {
+        if let Some(f) = self.files.iter().find(|f| format.matches(&f.url)) {
+            return Some(f.clone());
+        }
+        self.path
+            .as_ref()
+            .filter(|p| format.matches(p))
+            .map(|p| FeedFile {
+                url: p.clone(),
+                sha512: self.sha512.clone(),
+                sha256: self.sha256.clone(),
+                size: None,
+            })
+    }
+}
+
+/// 本机平台的 Node.js 名称（process.platform）
+fn node_platform() -> &'static str {
+    match std::env::consts::OS {
+        "windows" => "win32",
+        "macos" => "darwin",
+        other => other,
+    }
+}
+
+/// 本机架构的 Node.js 名称（process.arch）
+fn node_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "aarch64" => "arm64",
+        "x86" => "ia32",
+        other => other,
+    }
+}
+
+/// 判断 --input 是否指向更新源（.yml 文件，或以 / 结尾的更新源目录 URL）
+pub fn is_feed_input(input: &str) -> bool {
+    let path = download::url_path(input);
+    let lower = path.to_ascii_lowercase();
+    lower.ends_with(".yml")
+        || lower.ends_with(".yaml")
+        || (download::is_url(input) && path.ends_with('/'))
+}
+
+/// 当前平台、指定渠道对应的 electron-builder 更新源文件名，
+/// 例如 stable → latest.yml / latest-mac.yml，beta → beta.yml / beta-linux.yml
+pub fn channel_file(channel: &str) -> String {
+    let name = match channel {
+        "" | "stable" | "latest" => "latest",
+        other => other,
+    };
+    let suffix = match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("macos", _) => "-mac",
+        ("linux", "aarch64") => "-linux-arm64",
+        ("linux", _) => "-linux",
+        _ => "",
+    };
+    format!("{}{}.yml", name, suffix)
+}
+
+/// 按渠道改写更新源地址：目录 URL 直接拼接渠道文件名，
+/// 指定了渠道时将 yml 文件名替换为对应渠道的文件名
+fn channel_location(input: &str, channel: Option<&str>) -> String {
+    let path = download::url_path(input);
+    if download::is_url(input) && path.ends_with('/') {
+        return format!("{}{}", path, channel_file(channel.unwrap_or("stable")));
+    }
+    let Some(channel) = channel else {
+        return input.to_string();
+    };
+    if download::is_url(input) {
+        let query = &input[path.len()..];
+        let base = path.rsplit_once('/').map_or(path, |(b, _)| b);
+        format!("{}/{}{}", base, channel_file(channel), query)
+    } else {
+        Path::new(input)
+            .with_file_name(channel_file(channel))
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// 读取更新源，下载并解包目标包，返回可作为 input 使用的路径；
+/// 本机未命中灰度（--rollout 或更新源中的 stagingPercentage）时返回 None
+pub fn prepare_input(
+    input: &str,
+    channel: Option<&str>,
+    rollout: Option<f64>,
+    format: Format,
+    extract: bool,
+    opts: &DownloadOptions,
+    logger: &Logger,
+) -> io::Result<Option<Prepared>> {
+    let location = channel_location(input, channel);
+    let input = location.as_str();
+    logger.log(&format!("Reading update feed: {}", input));
+    let text = if download::is_url(input) {
+        download::fetch_text(input, &[], opts, logger)?
+    } else {
+        fs::read_to_string(input)?
+    };
+    let mut feed: Feed =
+        serde_yaml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if let Some(key) = feed.select_platform() {
+        logger.log(&format!("Using platform section {} of the feed", key));
+    } else if !feed.platforms.is_empty() {
+        logger.log(&format!(
+            "No platform section matches {}-{}, using top-level settings",
+            node_platform(),
+            node_arch()
+        ));
+    }
+
+    if let Some(percentage) = rollout.or(feed.staging_percentage)
+        && !rollout::is_selected(percentage, &feed.version, logger)
+    {
+        return Ok(None);
+    }
+
+    // 在下载前检查，避免安装无法在本机启动的 Electron 版本
+    if let Some(minimum) = &feed.minimum_system_version
+        && let Some(running) = version::check_system(minimum)?
+    {
+        logger.log(&format!(
+            "System version {} meets minimum {}",
+            running, minimum
+        ));
+    }
+
+    let package = feed.package(format).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Update feed does not list a {:?} package", format),
+        )
+    })?;
+    logger.log(&format!(
+        "Feed version {}, package {} (size: {}, checksum: {})",
+        feed.version,
+        package.url,
+        package
+            .size
+            .map_or_else(|| "unknown".to_string(), |s| s.to_string()),
+        package
+            .checksum()
+            .map_or_else(|| "none".to_string(), |c| c.to_string())
+    ));
+
+    let cache = download::cache_dir()?.join(download::path_component(&feed.version, "version")?);
+    let archive = cache.join(download::url_file_name(&package.url)?);
+    let package_location = resolve(input, &package.url);
+    let checksum = package.checksum();
+    if download::is_url(&package_location) {
+        download::download_verified(&package_location, &archive, checksum.as_ref(), opts, logger)?;
+    } else {
+        let source = Path::new(&package_location);
+        match &checksum {
+            Some(checksum) => {
+                checksum.verify(source)?;
+                logger.log(&format!("Checksum verified: {}", checksum));
+            }
+            None => logger.warn(&format!(
+                "No checksum available for {}, skipping verification",
+                package_location
+            )),
+        }
+        fs::create_dir_all(&cache)?;
+        fs::copy(source, &archive)?;
+    }
+
+    Ok(Some(Prepared {
+        path: download::unpack(&archive, format, extract, logger)?,
+        release_notes: feed.release_notes(),
+        version: Some(feed.version),
+    }))
+}
+
+/// 将包地址解析为相对于更新源的位置
+fn resolve(feed: &str, url: &str) -> String {
+    if download::is_url(url) || Path::new(url).is_absolute() {
+        return url.to_string();
+    }
+    if download::is_url(feed) {
+        let path = download::url_path(feed);
+        let base = path.rsplit_once('/').map_or(path, |(b, _)| b);
+        format!("{}/{}", base, url)
+    } else {
+        Path::new(feed)
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(url)
+            .to_string_lossy()
+            .to_string()
+    }
+}
@@ -0,0 +1,195 @@
+//! C ABI 导出，供 C++/C# 等非 Rust 安装程序直接链接（声明见 include/eqi.h）。
+//! 所有函数都是同步的，字符串参数为 UTF-8 编码、以 NUL 结尾，可选参数可传 NULL
+
+use crate::{Logger, Outcome, Phase, ProgressObserver, UpdateError, Updater};
+use std::ffi::{CStr, c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// 成功（包括已是最新版本、未命中灰度、已交给商店/包管理器更新）
+pub const EQI_OK: i32 = 0;
+/// 更新或回滚失败，详情见日志
+pub const EQI_ERROR: i32 = 1;
+/// 参数为空或不是合法的 UTF-8
+pub const EQI_INVALID_ARGUMENT: i32 = 2;
+/// 应用为沙箱安装，拒绝更新（与命令行退出码一致）
+pub const EQI_SANDBOXED: i32 = 3;
+/// 没有可回滚的备份
+pub const EQI_NOTHING_TO_ROLL_BACK: i32 = 4;
+
+/// 进度回调：phase 为阶段编号（见 include/eqi.h），total 未知时为 0
+pub type ProgressCallback =
+    extern "C" fn(user_data: *mut c_void, phase: i32, done: u64, total: u64);
+
+/// 已注册的回调及调用方数据
+#[derive(Clone, Copy)]
+struct Callback {
+    func: ProgressCallback,
+    user_data: *mut c_void,
+}
+
+// SAFETY: user_data 只原样传回给调用方，由调用方保证其可跨线程使用
+unsafe impl Send for Callback {}
+unsafe impl Sync for Callback {}
+
+static PROGRESS: Mutex<Option<Callback>> = Mutex::new(None);
+
+/// 把进度事件转发给 C 回调，回调收到的是当前阶段的累计进度
+struct FfiProgress {
+    callback: Callback,
+    /// 当前阶段及其已完成量、总量
+    state: Mutex<(Phase, u64, u64)>,
+}
+
+impl FfiProgress {
+    fn notify(&self, phase: Phase, done: u64, total: u64) {
+        (self.callback.func)(self.callback.user_data, phase_code(phase), done, total);
+    }
+}
+
+impl ProgressObserver for FfiProgress {
+    fn phase_started(&self, phase: Phase, total: Option<u64>) {
+        let total = total.unwrap_or(0);
+        *self.state.lock().unwrap() = (phase, 0, total);
+        self.notify(phase, 0, total);
+    }
+
+    fn advanced(&self, delta: u64) {
+        let (phase, done, total) = {
+            let mut state = self.state.lock().unwrap();
+            state.1 += delta;
+            *state
+        };
+        self.notify(phase, done, total);
+    }
+}
+
+/// 阶段编号，与 include/eqi.h 中的 EQI_PHASE_* 一致
+fn phase_code(phase: Phase) -> i32 {
+    match phase {
+        Phase::Download => 0,
+        Phase::WaitForExit => 1,
+        Phase::CopyExisting => 2,
+        Phase::CopyUpdate => 3,
+        Phase::Swap => 4,
+        Phase::Cleanup => 5,
+        Phase::Restart => 6,
+    }
+}
+
+/// 读取可为 NULL 的字符串参数
+///
+/// # Safety
+/// ptr 为 NULL 或指向以 NUL 结尾的字符串
+unsafe fn optional_str<'a>(ptr: *const c_char) -> Result<Option<&'a str>, i32> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    // SAFETY: 由调用方保证
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map(Some)
+        .map_err(|_| EQI_INVALID_ARGUMENT)
+}
+
+/// 读取必填的字符串参数
+///
+/// # Safety
+/// 同 [`optional_str`]
+unsafe fn required_str<'a>(ptr: *const c_char) -> Result<&'a str, i32> {
+    unsafe { optional_str(ptr) }?.ok_or(EQI_INVALID_ARGUMENT)
+}
+
+/// 捕获 panic，避免跨 C ABI 展开
+fn guard(f: impl FnOnce() -> Result<i32, i32>) -> i32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(code) | Err(code)) => code,
+        Err(_) => EQI_ERROR,
+    }
+}
+
+/// 注册进度回调，之后的 eqi_apply 调用都会使用它；func 为 NULL 时取消注册。
+/// 回调在执行更新的线程上同步调用
+///
+/// # Safety
+/// user_data 在回调注册期间必须保持有效
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eqi_set_progress_cb(
+    func: Option<ProgressCallback>,
+    user_data: *mut c_void,
+) -> i32 {
+    *PROGRESS.lock().unwrap() = func.map(|func| Callback { func, user_data });
+    EQI_OK
+}
+
+/// 执行一次更新，等同于命令行的 --input/--output/--app/--ps/--log：
+/// output 为 NULL 时由 app 的位置推断 resources 目录；processes 为以 `,` 分隔的进程名，可为 NULL；log_path 为 NULL 时写入宿主程序同级目录下的 updater.log
+///
+/// # Safety
+/// 字符串参数为 NULL 或指向以 NUL 结尾的字符串
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eqi_apply(
+    input: *const c_char,
+    output: *const c_char,
+    app: *const c_char,
+    processes: *const c_char,
+    log_path: *const c_char,
+) -> i32 {
+    guard(|| {
+        let input = unsafe { required_str(input) }?;
+        let output = unsafe { optional_str(output) }?;
+        let app = unsafe { required_str(app) }?;
+        let processes = unsafe { optional_str(processes) }?.unwrap_or("");
+        let log_path = unsafe { optional_str(log_path) }?;
+
+        let logger = Logger::new(log_path).map_err(|_| EQI_ERROR)?;
+        let mut builder = Updater::builder()
+            .input(input)
+            .app(app)
+            .processes(
+                processes
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty()),
+            )
+            .logger(logger);
+        if let Some(output) = output {
+            builder = builder.output(output);
+        }
+        if let Some(callback) = *PROGRESS.lock().unwrap() {
+            builder = builder.observer(Arc::new(FfiProgress {
+                callback,
+                state: Mutex::new((Phase::Download, 0, 0)),
+            }));
+        }
+        match builder.run() {
+            Ok(report) if report.outcome == Outcome::SandboxRefused => Ok(EQI_SANDBOXED),
+            Ok(_) => Ok(EQI_OK),
+            Err(UpdateError::InvalidConfig(_)) => Err(EQI_INVALID_ARGUMENT),
+            Err(_) => Err(EQI_ERROR),
+        }
+    })
+}
+
+/// 回滚 output 上未完成的替换（见 [`crate::rollback`]）
+///
+/// # Safety
+/// 字符串参数为 NULL 或指向以 NUL 结尾的字符串
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eqi_rollback(output: *const c_char, log_path: *const c_char) -> i32 {
+    guard(|| {
+        let output = unsafe { required_str(output) }?;
+        let log_path = unsafe { optional_str(log_path) }?;
+
+        let logger = Logger::new(log_path).map_err(|_| EQI_ERROR)?;
+        match crate::rollback(Path::new(output), &logger) {
+            Ok(true) => Ok(EQI_OK),
+            Ok(false) => Ok(EQI_NOTHING_TO_ROLL_BACK),
+            Err(e) => {
+                logger.error(&format!("Rollback failed: {}", e));
+                Err(EQI_ERROR)
+            }
+        }
+    })
+}
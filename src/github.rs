@@ -0,0 +1,171 @@
+use crate::Logger;
+use crate::download::{self, Checksum, DownloadOptions, Format, Prepared};
+use crate::feed;
+use crate::rollout;
+use serde::Deserialize;
+use std::io;
+
+/// GitHub Release 信息（仅解析需要的字段）
+#[derive(Debug, Clone, Deserialize)]
+struct Release {
+    tag_name: String,
+    /// Release 说明（Markdown）
+    body: Option<String>,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+    size: u64,
+    /// 形如 "sha256:<hex>"，旧的 Release 可能没有
+    digest: Option<String>,
+}
+
+fn platform_keywords() -> &'static [&'static str] {
+    match std::env::consts::OS {
+        "windows" => &["win", "windows"],
+        "macos" => &["mac", "darwin", "osx"],
+        _ => &["linux"],
+    }
+}
+
+fn arch_keywords() -> &'static [&'static str] {
+    match std::env::consts::ARCH {
+        "aarch64" => &["arm64", "aarch64"],
+        "x86" => &["ia32", "x86"],
+        _ => &["x64", "x86_64", "amd64"],
+    }
+}
+
+/// 选出当前平台指定格式的资源：平台+架构都匹配优先，其次仅平台匹配，最后是唯一的同格式资源
+fn pick_asset(assets: &[Asset], format: Format) -> Option<Asset> {
+    let candidates: Vec<&Asset> = assets.iter().filter(|a| format.matches(&a.name)).collect();
+    let matches = |a: &&Asset, words: &[&str]| {
+        let name = a.name.to_ascii_lowercase();
+        words.iter().any(|w| name.contains(w))
+    };
+
+    candidates
+        .iter()
+        .find(|a| matches(a, platform_keywords()) && matches(a, arch_keywords()))
+        .or_else(|| candidates.iter().find(|a| matches(a, platform_keywords())))
+        .or(if candidates.len() == 1 {
+            candidates.first()
+        } else {
+            None
+        })
+        .map(|a| (*a).clone())
+}
+
+/// 查询仓库最新 Release，下载并校验当前平台的更新包，返回可作为 input 使用的路径。
+/// 稳定渠道使用 /releases/latest（不含预发布）；其他渠道取最新的、带有该渠道 yml 的
+/// Release，没有 yml 时取最新的预发布版本。本机未命中灰度时返回 None
+pub fn prepare_input(
+    repo: &str,
+    channel: Option<&str>,
+    rollout: Option<f64>,
+    format: Format,
+    extract: bool,
+    opts: &DownloadOptions,
+    logger: &Logger,
+) -> io::Result<Option<Prepared>> {
+    let stable = matches!(channel, None | Some("stable") | Some("latest"));
+    let api = if stable {
+        format!("https://api.github.com/repos/{}/releases/latest", repo)
+    } else {
+        format!("https://api.github.com/repos/{}/releases?per_page=30", repo)
+    };
+    logger.log(&format!("Querying latest release: {}", api));
+
+    let mut headers = vec![
+        ("Accept", "application/vnd.github+json".to_string()),
+        ("User-Agent", "electron-quit-and-install".to_string()),
+    ];
+    // 私有仓库或需要更高的 API 限额时使用 GITHUB_TOKEN
+    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+        headers.push(("Authorization", format!("Bearer {}", token)));
+    }
+    let text = download::fetch_text(&api, &headers, opts, logger)?;
+    let parse_err = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    let feed_name = feed::channel_file(channel.unwrap_or("stable"));
+    let release: Release = if stable {
+        serde_json::from_str(&text).map_err(parse_err)?
+    } else {
+        let releases: Vec<Release> = serde_json::from_str(&text).map_err(parse_err)?;
+        releases
+            .iter()
+            .find(|r| r.assets.iter().any(|a| a.name == feed_name))
+            .or_else(|| releases.iter().find(|r| r.prerelease))
+            .cloned()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No release found for channel {}", channel.unwrap_or("")),
+                )
+            })?
+    };
+    logger.log(&format!("Latest release: {}", release.tag_name));
+
+    // electron-builder 发布的 Release 自带 latest*.yml（或渠道 yml），直接复用更新源流程
+    if let Some(yml) = release.assets.iter().find(|a| a.name == feed_name) {
+        return feed::prepare_input(
+            &yml.browser_download_url,
+            None,
+            rollout,
+            format,
+            extract,
+            opts,
+            logger,
+        );
+    }
+
+    if let Some(percentage) = rollout
+        && !rollout::is_selected(percentage, &release.tag_name, logger)
+    {
+        return Ok(None);
+    }
+
+    let asset = pick_asset(&release.assets, format).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "No {:?} asset for {}-{} in release {}",
+                format,
+                std::env::consts::OS,
+                std::env::consts::ARCH,
+                release.tag_name
+            ),
+        )
+    })?;
+    logger.log(&format!(
+        "Selected asset {} ({} bytes)",
+        asset.name, asset.size
+    ));
+
+    let cache =
+        download::cache_dir()?.join(download::path_component(&release.tag_name, "release tag")?);
+    let archive = cache.join(download::path_component(&asset.name, "asset name")?);
+    let checksum = asset
+        .digest
+        .as_deref()
+        .and_then(|d| d.strip_prefix("sha256:"))
+        .map(|d| Checksum::Sha256(d.to_string()));
+    download::download_verified(
+        &asset.browser_download_url,
+        &archive,
+        checksum.as_ref(),
+        opts,
+        logger,
+    )?;
+
+    Ok(Some(Prepared {
+        path: download::unpack(&archive, format, extract, logger)?,
+        version: Some(release.tag_name.trim_start_matches('v').to_string()),
+        release_notes: release.body.filter(|b| !b.trim().is_empty()),
+    }))
+}
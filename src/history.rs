@@ -0,0 +1,87 @@
+//! 更新历史：每次更新或回滚都向 output 旁的 <output>_history.jsonl 追加一行 JSON，
+//! 便于排查时一眼看出这台机器的完整更新轨迹
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// 历史记录中的一次操作
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    /// RFC 3339 格式的时间
+    pub time: String,
+    /// apply 或 rollback
+    pub action: String,
+    /// applied、handed_off、failed、rolled_back、nothing_to_roll_back 等
+    pub result: String,
+    /// 操作前已安装的版本
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_version: Option<String>,
+    /// 操作后已安装的版本
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_version: Option<String>,
+    /// 失败原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 已安装版本的更新说明，重新启动的应用可据此展示“新功能”
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_notes: Option<String>,
+    /// 审计日志在本次操作结束时的摘要（见 [`crate::audit`]）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_digest: Option<String>,
+}
+
+impl Entry {
+    pub fn new(action: &str, result: &str) -> Self {
+        Self {
+            time: Local::now().to_rfc3339(),
+            action: action.to_string(),
+            result: result.to_string(),
+            from_version: None,
+            to_version: None,
+            error: None,
+            release_notes: None,
+            audit_digest: None,
+        }
+    }
+}
+
+/// output 对应的历史文件路径
+pub fn path(output: &Path) -> PathBuf {
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push("_history.jsonl");
+    output.with_file_name(name)
+}
+
+/// 追加一条记录
+pub fn append(output: &Path, entry: &Entry) -> io::Result<()> {
+    write(&mut open(output)?, entry)
+}
+
+/// 以追加方式打开历史文件；降权前打开，降权后仍可写入
+pub fn open(output: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path(output))
+}
+
+/// 向已打开的历史文件追加一条记录
+pub fn write(file: &mut File, entry: &Entry) -> io::Result<()> {
+    let line = serde_json::to_string(entry).map_err(io::Error::other)?;
+    writeln!(file, "{}", line)
+}
+
+/// 读取全部记录，按时间先后排列；文件不存在时返回空列表，无法解析的行被跳过
+pub fn read(output: &Path) -> io::Result<Vec<Entry>> {
+    match fs::read_to_string(path(output)) {
+        Ok(text) => Ok(text
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
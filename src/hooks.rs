@@ -0,0 +1,361 @@
+//! 生命周期钩子：在更新流程的固定位置执行集成方的自定义步骤（迁移授权文件、清理缓存、修复快捷方式等）
+
+use crate::privilege::{self, Caller};
+use crate::{Logger, UpdateError, template};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 钩子位置
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hook {
+    /// 退出应用之前，失败时不做任何改动
+    PreKill,
+    /// 新版本文件已暂存完成，可修改暂存目录中的文件
+    PostStage,
+    /// 即将替换，最后一个可以中止的位置
+    PreSwap,
+    /// 已替换、重新启动应用之前（如迁移本地数据库），失败时恢复旧版本（仅 resources 布局，其他布局只记录警告）
+    PostSwap,
+    /// 更新完成并已重新启动应用，失败只记录警告
+    PostRestart,
+}
+
+impl Hook {
+    /// 命令行参数与环境变量中使用的名称
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreKill => "pre-kill",
+            Hook::PostStage => "post-stage",
+            Hook::PreSwap => "pre-swap",
+            Hook::PostSwap => "post-swap",
+            Hook::PostRestart => "post-restart",
+        }
+    }
+
+    /// 按名称查找钩子位置
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Hook::PreKill,
+            Hook::PostStage,
+            Hook::PreSwap,
+            Hook::PostSwap,
+            Hook::PostRestart,
+        ]
+        .into_iter()
+        .find(|hook| hook.name() == name)
+    }
+
+    /// 未指定时的失败处理：post-swap 恢复旧版本，post-restart 继续，其余中止
+    pub fn default_on_failure(self) -> OnFailure {
+        match self {
+            Hook::PostSwap => OnFailure::Rollback,
+            Hook::PostRestart => OnFailure::Continue,
+            _ => OnFailure::Abort,
+        }
+    }
+}
+
+/// 钩子失败时的处理方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnFailure {
+    /// 中止更新；替换前与 rollback 相同，替换后保留新版本与备份目录
+    Abort,
+    /// 只记录警告，继续更新
+    Continue,
+    /// 中止更新并恢复旧版本；替换后只有 resources 布局可以恢复（其他布局只记录警告），post-restart 时与 abort 相同
+    Rollback,
+}
+
+/// 钩子可用的信息
+pub struct HookContext<'a> {
+    pub output: &'a Path,
+    pub app: &'a Path,
+    /// 暂存的新版本（resources 为 <output>_new 目录，squirrel 为暂存的版本目录，
+    /// appimage 为暂存的 AppImage 文件，deb/rpm 为安装包），仅 PostStage、PreSwap 时有值；
+    /// 目录中未更新的文件是指向当前版本的硬链接，钩子只能替换不能原地修改
+    pub staging: Option<&'a Path>,
+    pub version: Option<&'a str>,
+    /// 更新前已安装的版本
+    pub old_version: Option<&'a str>,
+    /// 已降权时为切换到的用户（仅 PostRestart），启动的子进程应以其 HOME 等环境变量运行（见 [`privilege::run_as`]）
+    pub user: Option<&'a Caller>,
+    /// 钩子可以把输出写入更新日志
+    pub logger: &'a Logger,
+}
+
+/// 生命周期钩子；返回错误时按该位置的失败处理方式（[`OnFailure`]）中止、继续或回滚
+pub trait LifecycleHook: Send + Sync {
+    fn run(&self, hook: Hook, ctx: &HookContext) -> io::Result<()>;
+}
+
+impl LifecycleHook for Box<dyn LifecycleHook> {
+    fn run(&self, hook: Hook, ctx: &HookContext) -> io::Result<()> {
+        (**self).run(hook, ctx)
+    }
+}
+
+/// 钩子执行失败，用于区分钩子中止与其他失败
+#[derive(Debug)]
+pub struct HookFailed {
+    pub hook: Hook,
+    pub source: io::Error,
+}
+
+impl fmt::Display for HookFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} hook failed: {}", self.hook.name(), self.source)
+    }
+}
+
+impl std::error::Error for HookFailed {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// 等待子进程时的轮询间隔
+const POLL: Duration = Duration::from_millis(50);
+
+/// 子进程退出后继续收集输出的最长时间（其启动的后台进程可能仍持有输出管道）
+const DRAIN: Duration = Duration::from_millis(500);
+
+/// 通过 shell 执行命令的钩子（Windows 为 cmd /C，其他平台为 sh -c），
+/// 通过环境变量 EQI_HOOK、EQI_OUTPUT（OUTPUT_DIR）、EQI_APP、EQI_STAGING、EQI_VERSION（NEW_VERSION）、
+/// OLD_VERSION 传入信息，命令中的 `{output}` 等模板变量在执行前展开并按 shell 的规则加引号（见 [`template::expand_command`]），
+/// 退出码非 0 或超时视为失败；标准输出与标准错误逐行写入日志
+pub struct CommandHook {
+    commands: Vec<(Hook, String)>,
+    timeouts: Vec<(Hook, Duration)>,
+    env: Vec<(String, String)>,
+}
+
+impl CommandHook {
+    pub fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+            timeouts: Vec::new(),
+            env: Vec::new(),
+        }
+    }
+
+    /// 在 hook 位置执行 command，同一位置可添加多条，按添加顺序执行
+    pub fn with(mut self, hook: Hook, command: impl Into<String>) -> Self {
+        self.commands.push((hook, command.into()));
+        self
+    }
+
+    /// hook 位置每条命令的最长执行时间，超时后结束命令并视为失败
+    pub fn timeout(mut self, hook: Hook, timeout: Duration) -> Self {
+        self.timeouts.retain(|(h, _)| *h != hook);
+        self.timeouts.push((hook, timeout));
+        self
+    }
+
+    /// 为所有命令设置额外的环境变量
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl Default for CommandHook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LifecycleHook for CommandHook {
+    fn run(&self, hook: Hook, ctx: &HookContext) -> io::Result<()> {
+        for (_, command) in self.commands.iter().filter(|(h, _)| *h == hook) {
+            let command = &template::expand_command(command, |name| template::hook_var(ctx, name))?;
+            let mut cmd = shell(command);
+            cmd.envs(self.env.iter().map(|(k, v)| (k, v)))
+                .env("EQI_HOOK", hook.name())
+                .env("EQI_OUTPUT", ctx.output)
+                .env("OUTPUT_DIR", ctx.output)
+                .env("EQI_APP", ctx.app)
+                .env("EQI_STAGING", ctx.staging.unwrap_or(Path::new("")))
+                .env("EQI_VERSION", ctx.version.unwrap_or(""))
+                .env("NEW_VERSION", ctx.version.unwrap_or(""))
+                .env("OLD_VERSION", ctx.old_version.unwrap_or(""));
+            if let Some(user) = ctx.user {
+                privilege::run_as(&mut cmd, user);
+            }
+            let timeout = self
+                .timeouts
+                .iter()
+                .find(|(h, _)| *h == hook)
+                .map(|(_, t)| *t);
+            execute(cmd, command, hook.name(), timeout, ctx.logger)?;
+        }
+        Ok(())
+    }
+}
+
+/// 更新（或回滚）失败后执行的通知命令，如弹出原生错误对话框、通知值班频道；用户取消不算失败。
+/// 命令通过环境变量获得失败信息：`EQI_FAILED_PHASE`（阶段，见 [`UpdateError::phase`]）、
+/// `EQI_ERROR`（错误信息）、`EQI_EXIT_CODE`（命令行退出码）以及 `EQI_OUTPUT`、`EQI_APP`。
+/// 错误信息不做模板展开，以免拼入命令；命令失败只记录警告
+pub fn notify_failure(
+    commands: &[String],
+    error: &UpdateError,
+    output: &Path,
+    app: &Path,
+    logger: &Logger,
+) {
+    if matches!(error, UpdateError::Cancelled) {
+        return;
+    }
+    let path = |p: &Path| p.to_string_lossy().into_owned();
+    for command in commands {
+        let result = template::expand_command(command, |name| match name {
+            "output" => Some(path(output)),
+            "output_old" => Some(path(&template::output_old(output))),
+            "app" => Some(path(app)),
+            _ => template::common_var(name),
+        })
+        .and_then(|command| {
+            let mut cmd = shell(&command);
+            cmd.env("EQI_FAILED_PHASE", error.phase())
+                .env("EQI_ERROR", error.to_string())
+                .env("EQI_EXIT_CODE", error.exit_code().to_string())
+                .env("EQI_OUTPUT", output)
+                .env("EQI_APP", app);
+            execute(cmd, &command, "on-failure", None, logger)
+        });
+        if let Err(e) = result {
+            logger.warn(&format!("On-failure hook failed: {}", e));
+        }
+    }
+}
+
+/// 通过系统 shell 执行 command
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    // 按 Rust 的规则转义后 cmd 无法识别其中的引号，原样传入；/S 只去掉最外层的一对引号
+    let mut cmd = Command::new("cmd");
+    cmd.raw_arg(format!("/S /C \"{}\"", command));
+    cmd
+}
+
+/// 通过系统 shell 执行 command
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+/// 执行命令并等待结束，输出逐行以 `[label]` 为前缀写入日志；退出码非零或超时视为失败
+fn execute(
+    mut cmd: Command,
+    command: &str,
+    label: &str,
+    timeout: Option<Duration>,
+    logger: &Logger,
+) -> io::Result<()> {
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = cmd.spawn()?;
+    let output = capture(&mut child);
+    let log = |line: String| logger.log(&format!("[{}] {}", label, line));
+    let result = wait(&mut child, timeout, &output, &log, logger);
+    // 子进程已结束：收集剩余的输出
+    let deadline = Instant::now() + DRAIN;
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        match output.recv_timeout(left) {
+            Ok(line) => log(line),
+            Err(_) => break,
+        }
+    }
+    let status = match result {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("`{}` {}", command, e),
+            ));
+        }
+        Err(e) => return Err(e),
+    };
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "`{}` exited with {}",
+            command, status
+        )));
+    }
+    Ok(())
+}
+
+/// 在后台线程中逐行读取子进程的标准输出与标准错误
+fn capture(child: &mut Child) -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    let pipes: [Option<Box<dyn Read + Send>>; 2] = [
+        child.stdout.take().map(|p| Box::new(p) as _),
+        child.stderr.take().map(|p| Box::new(p) as _),
+    ];
+    for pipe in pipes.into_iter().flatten() {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    rx
+}
+
+/// 等待子进程退出，期间转发输出；超时或取消时结束子进程
+fn wait(
+    child: &mut Child,
+    timeout: Option<Duration>,
+    output: &Receiver<String>,
+    log: &dyn Fn(String),
+    logger: &Logger,
+) -> io::Result<std::process::ExitStatus> {
+    let started = Instant::now();
+    let mut open = true;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        let error = if logger.is_cancelled() {
+            Some(io::Error::new(io::ErrorKind::Interrupted, "cancelled"))
+        } else {
+            timeout.filter(|t| started.elapsed() >= *t).map(|t| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("timed out after {}s", t.as_secs_f64()),
+                )
+            })
+        };
+        if let Some(error) = error {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(error);
+        }
+        if !open {
+            thread::sleep(POLL);
+            continue;
+        }
+        match output.recv_timeout(POLL) {
+            Ok(line) => log(line),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => open = false,
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! 标准输入输出上的 JSON 协议（`--ipc-stdio`）：由 Electron 应用启动更新器后，
+//! 应用逐行发送命令、更新器逐行回报事件，应用可以精确控制何时被退出（类似 quitAndInstall 的握手）。
+//!
+//! [`Ipc`] 同时作为日志输出目标、进度观察者和生命周期钩子：日志与进度以事件发出，
+//! PreKill 时发出 `staged` 事件并等待 [`Ipc::approve`]（收到 apply 命令）或取消
+
+use crate::CancelToken;
+use crate::hooks::{Hook, HookContext, LifecycleHook};
+use crate::progress::{Phase, ProgressObserver};
+use crate::sink::{LogSink, Record};
+use serde_json::{Value, json};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// 事件通道，克隆后共享同一输出与状态
+#[derive(Clone)]
+pub struct Ipc(Arc<Inner>);
+
+struct Inner {
+    out: Mutex<Box<dyn Write + Send>>,
+    approved: Mutex<bool>,
+    changed: Condvar,
+    cancel: CancelToken,
+    /// 当前阶段：总量、已完成量、上次报告的百分比
+    progress: Mutex<(Option<u64>, u64, u64)>,
+    /// 正在复制的大文件与上次报告的百分比
+    copying: Mutex<(PathBuf, Option<u64>)>,
+}
+
+/// 等待 apply 时检查取消的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+impl Ipc {
+    /// 事件写入 out（通常为 stdout），cancel 为本次更新的取消标记
+    pub fn new(out: impl Write + Send + 'static, cancel: CancelToken) -> Self {
+        Self(Arc::new(Inner {
+            out: Mutex::new(Box::new(out)),
+            approved: Mutex::new(false),
+            changed: Condvar::new(),
+            cancel,
+            progress: Mutex::new((None, 0, 0)),
+            copying: Mutex::new((PathBuf::new(), None)),
+        }))
+    }
+
+    /// 发出一个事件（一行 JSON）；应用已退出导致写入失败时忽略
+    pub fn send(&self, event: Value) {
+        let mut out = self.0.out.lock().unwrap();
+        let _ = writeln!(out, "{}", event).and_then(|()| out.flush());
+    }
+
+    /// 允许退出应用：已暂存时立即继续，尚未暂存时暂存完成后直接继续
+    pub fn approve(&self) {
+        *self.0.approved.lock().unwrap() = true;
+        self.0.changed.notify_all();
+    }
+
+    pub fn is_approved(&self) -> bool {
+        *self.0.approved.lock().unwrap()
+    }
+
+    /// 取消更新：等待 apply 时立即中止，其余阶段在下一个安全点中止并回滚
+    pub fn cancel(&self) {
+        self.0.cancel.cancel();
+        self.0.changed.notify_all();
+    }
+
+    /// 等待 approve，被取消时返回 `Interrupted` 错误
+    fn wait_for_approval(&self) -> io::Result<()> {
+        let mut approved = self.0.approved.lock().unwrap();
+        while !*approved {
+            self.0.cancel.check()?;
+            approved = self
+                .0
+                .changed
+                .wait_timeout(approved, POLL_INTERVAL)
+                .unwrap()
+                .0;
+        }
+        Ok(())
+    }
+}
+
+impl LogSink for Ipc {
+    fn write(&self, record: &Record) {
+        self.send(json!({
+            "event": "log",
+            "time": record.time.to_rfc3339(),
+            "level": record.level.as_str(),
+            "message": record.message,
+        }));
+    }
+}
+
+impl ProgressObserver for Ipc {
+    fn phase_started(&self, phase: Phase, total: Option<u64>) {
+        *self.0.progress.lock().unwrap() = (total, 0, 0);
+        self.send(json!({"event": "phase", "phase": format!("{:?}", phase), "total": total}));
+    }
+
+    /// 总量已知时每前进 1% 报告一次
+    fn advanced(&self, delta: u64) {
+        let mut progress = self.0.progress.lock().unwrap();
+        let (Some(total), done, reported) = &mut *progress else {
+            return;
+        };
+        *done += delta;
+        let percent = (*done * 100).checked_div(*total).unwrap_or(100);
+        if percent > *reported {
+            *reported = percent;
+            let (done, total) = (*done, *total);
+            drop(progress);
+            self.send(
+                json!({"event": "progress", "done": done, "total": total, "percent": percent}),
+            );
+        }
+    }
+
+    /// 大文件的复制进度：开始、每前进 10% 与完成时各报告一次
+    fn copying(&self, path: &Path, copied: u64, total: u64) {
+        let percent = (copied * 100).checked_div(total).unwrap_or(100);
+        let mut copying = self.0.copying.lock().unwrap();
+        if copying.0 != path {
+            *copying = (path.to_path_buf(), None);
+        }
+        let due = match copying.1 {
+            None => true,
+            Some(reported) => percent >= reported + 10 || (percent == 100 && reported < 100),
+        };
+        if due {
+            copying.1 = Some(percent / 10 * 10);
+            drop(copying);
+            self.send(json!({"event": "copying", "path": path, "copied": copied, "total": total}));
+        }
+    }
+}
+
+impl LifecycleHook for Ipc {
+    fn run(&self, hook: Hook, ctx: &HookContext) -> io::Result<()> {
+        if hook != Hook::PreKill {
+            return Ok(());
+        }
+        self.send(json!({"event": "staged", "version": ctx.version}));
+        self.wait_for_approval()
+    }
+}
@@ -0,0 +1,172 @@
+//! 替换日志：resources 布局暂存与替换时在 output 旁写入 <output>_journal.json，记录进行到的阶段；
+//! 更新器中途崩溃或断电后，下次启动时据此完成替换、回滚或清理残留（见 [`recover`]），不必一律删除重来。
+//! 同一 output 同时只能有一个更新器操作（见 [`lock`]），否则另一个仍在运行的更新器的日志会被误当作中断
+
+use crate::vfs::FileSystem;
+use crate::{BACKUP_MARKER, Logger, remove_path, rollback_in, sibling};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 更新进行到的阶段
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum State {
+    /// 正在暂存到 <output>_new，其内容可能不完整
+    Staging,
+    /// <output>_new 已暂存完成，即将或正在替换
+    Staged,
+    /// 已替换为新版本，<output>_old 为备份
+    Swapped,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Journal {
+    state: State,
+    /// 正在安装的版本
+    version: Option<String>,
+    /// 写入时间（RFC 3339）
+    time: String,
+}
+
+/// output 对应的日志文件路径
+pub fn path(output: &Path) -> io::Result<PathBuf> {
+    sibling(output, "", "_journal.json")
+}
+
+/// 持有期间其他进程不能操作同一 output；drop 或进程退出（包括崩溃）时由系统释放
+#[derive(Debug)]
+pub struct Lock {
+    _file: File,
+}
+
+/// 锁定 output：在 `<output>_journal.lock` 上加排他锁，已被其他更新器持有时返回 WouldBlock。
+/// 锁文件保留在磁盘上，删除它会让先后两个进程锁住不同的文件
+pub fn lock(output: &Path) -> io::Result<Lock> {
+    let path = sibling(output, "", "_journal.lock")?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(Lock { _file: file }),
+        Err(TryLockError::WouldBlock) => Err(io::Error::new(
+            io::ErrorKind::WouldBlock,
+            format!("Another updater is updating {}", output.display()),
+        )),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// 记录进入 state；先写入临时文件再重命名，中断时不会留下不完整的内容
+pub fn write(output: &Path, state: State, version: Option<&str>) -> io::Result<()> {
+    let journal = Journal {
+        state,
+        version: version.map(str::to_string),
+        time: Local::now().to_rfc3339(),
+    };
+    let json = serde_json::to_string_pretty(&journal).map_err(io::Error::other)?;
+    let path = path(output)?;
+    let tmp = sibling(&path, "", ".tmp")?;
+    fs::write(&tmp, json)?;
+    fs::rename(tmp, path)
+}
+
+/// 删除日志，不存在时忽略
+pub fn remove(output: &Path) -> io::Result<()> {
+    match fs::remove_file(path(output)?) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// 读取上次更新留下的阶段；没有日志时返回 None，无法解析时记录警告并视为没有
+fn read(output: &Path, logger: &Logger) -> io::Result<Option<State>> {
+    let path = path(output)?;
+    match fs::read(&path) {
+        Ok(bytes) => match serde_json::from_slice::<Journal>(&bytes) {
+            Ok(journal) => {
+                logger.warn(&format!(
+                    "Found journal of an interrupted update (state {:?}, version {}, at {})",
+                    journal.state,
+                    journal.version.as_deref().unwrap_or("unknown"),
+                    journal.time
+                ));
+                Ok(Some(journal.state))
+            }
+            Err(e) => {
+                logger.warn(&format!("Ignoring unreadable {}: {}", path.display(), e));
+                Ok(None)
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// 处理上次中断的更新，完成后删除日志：
+/// - output 不存在（两次重命名之间中断）：已暂存完成时把 <output>_new 改为 output 完成替换，否则有备份时回滚；
+/// - 暂存未完成或尚未替换：删除残留的 <output>_new；
+/// - 已替换：保留新版本与备份。
+///
+/// 调用方需持有 [`lock`]；回滚失败时保留日志，下次再试。返回是否做了恢复
+pub fn recover(vfs: &dyn FileSystem, output: &Path, logger: &Logger) -> io::Result<bool> {
+    let state = read(output, logger)?;
+    let output_new = sibling(output, "", "_new")?;
+    let output_old = sibling(output, "", "_old")?;
+    let mut recovered = false;
+    if !vfs.exists(output) && state == Some(State::Staged) && vfs.exists(&output_new) {
+        logger.warn("Previous update was interrupted during the swap, finishing it");
+        vfs.rename(&output_new, output)?;
+        if vfs.is_dir(&output_old)
+            && let Err(e) = vfs.write_file(&output_old.join(BACKUP_MARKER), b"")
+        {
+            logger.warn(&format!("Failed to mark backup directory: {}", e));
+        }
+        logger.log(&format!("Finished swap of {}", output.display()));
+        recovered = true;
+    } else if !vfs.exists(output) && vfs.exists(&output_old) {
+        logger.warn("Previous update was interrupted during the swap, rolling back");
+        rollback_in(vfs, output, logger)?;
+        recovered = true;
+    } else if let Some(state) = state {
+        match state {
+            State::Staging | State::Staged if vfs.exists(&output_new) => {
+                logger.warn(&format!(
+                    "Removing {} left by the interrupted update",
+                    output_new.display()
+                ));
+                remove_path(vfs, &output_new)?;
+                recovered = true;
+            }
+            State::Swapped => logger.log(&format!(
+                "Previous update was applied before the interruption, keeping {}",
+                output.display()
+            )),
+            _ => {}
+        }
+    }
+    remove(output)?;
+    Ok(recovered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_excludes_other_updaters() {
+        let root = std::env::temp_dir().join(format!("eqi-journal-lock-{}", std::process::id()));
+        let output = root.join("app");
+        fs::create_dir_all(&output).unwrap();
+        let held = lock(&output).unwrap();
+        let err = lock(&output).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        drop(held);
+        lock(&output).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+//! launchd 任务：为监视模式或定时更新生成并加载 plist，
+//! 用户级任务（LaunchAgent）写入 ~/Library/LaunchAgents，系统级任务（LaunchDaemon）写入 /Library/LaunchDaemons
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 一个 launchd 任务
+#[derive(Clone, Debug)]
+pub struct LaunchdJob {
+    /// 任务标签（如 com.example.myapp.updater），同时作为 plist 与日志的文件名
+    pub label: String,
+    /// 传给更新器的参数（如 `watch --input ...`）
+    pub args: Vec<String>,
+    /// 定时运行的间隔（秒）；为空时常驻运行，退出后由 launchd 重新启动
+    pub interval: Option<u64>,
+    /// 系统级任务（LaunchDaemon，以 root 运行，需要管理员权限）
+    pub system: bool,
+}
+
+impl LaunchdJob {
+    /// plist 文件路径
+    pub fn plist_path(&self) -> io::Result<PathBuf> {
+        Ok(
+            dir(self.system, "LaunchDaemons", "LaunchAgents")?
+                .join(format!("{}.plist", self.label)),
+        )
+    }
+
+    /// 标准输出与标准错误的日志路径
+    pub fn log_path(&self) -> io::Result<PathBuf> {
+        Ok(dir(self.system, "Logs", "Logs")?.join(format!("{}.log", self.label)))
+    }
+
+    /// 生成 plist 内容，program 为更新器可执行文件路径
+    pub fn plist(&self, program: &Path) -> io::Result<String> {
+        let log = self.log_path()?;
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" ",
+            "\"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+            "<plist version=\"1.0\">\n<dict>\n",
+        ));
+        push_string(&mut xml, "Label", &self.label);
+        xml.push_str("    <key>ProgramArguments</key>\n    <array>\n");
+        for arg in std::iter::once(program.to_string_lossy().as_ref())
+            .chain(self.args.iter().map(String::as_str))
+        {
+            xml.push_str(&format!("        <string>{}</string>\n", escape(arg)));
+        }
+        xml.push_str("    </array>\n");
+        xml.push_str("    <key>RunAtLoad</key>\n    <true/>\n");
+        match self.interval {
+            Some(interval) => {
+                xml.push_str(&format!(
+                    "    <key>StartInterval</key>\n    <integer>{}</integer>\n",
+                    interval
+                ));
+            }
+            // 常驻运行：意外退出后重新启动，两次启动至少间隔 30 秒
+            None => xml.push_str(concat!(
+                "    <key>KeepAlive</key>\n    <true/>\n",
+                "    <key>ThrottleInterval</key>\n    <integer>30</integer>\n",
+            )),
+        }
+        xml.push_str("    <key>ProcessType</key>\n    <string>Background</string>\n");
+        push_string(&mut xml, "StandardOutPath", &log.to_string_lossy());
+        push_string(&mut xml, "StandardErrorPath", &log.to_string_lossy());
+        xml.push_str("</dict>\n</plist>\n");
+        Ok(xml)
+    }
+}
+
+/// 写入 plist；load 为 true 时通过 launchctl 加载（已加载的同名任务先卸载），返回 plist 路径
+pub fn install(job: &LaunchdJob, load: bool) -> io::Result<PathBuf> {
+    let path = job.plist_path()?;
+    let program = std::env::current_exe()?;
+    let plist = job.plist(&program)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Some(parent) = job.log_path()?.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, plist)?;
+    if load {
+        let _ = launchctl(&["bootout", &domain(job.system), &path.to_string_lossy()]);
+        launchctl(&["bootstrap", &domain(job.system), &path.to_string_lossy()])?;
+    }
+    Ok(path)
+}
+
+/// 卸载任务并删除 plist
+pub fn uninstall(label: &str, system: bool) -> io::Result<()> {
+    let job = LaunchdJob {
+        label: label.to_string(),
+        args: Vec::new(),
+        interval: None,
+        system,
+    };
+    let path = job.plist_path()?;
+    if !path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", path.display()),
+        ));
+    }
+    // 任务未加载时 bootout 失败，可以忽略
+    let _ = launchctl(&["bootout", &format!("{}/{}", domain(system), label)]);
+    fs::remove_file(path)
+}
+
+/// 系统级任务位于 /Library 下，用户级任务位于 ~/Library 下
+fn dir(system: bool, system_dir: &str, user_dir: &str) -> io::Result<PathBuf> {
+    if system {
+        return Ok(Path::new("/Library").join(system_dir));
+    }
+    let home = std::env::var_os("HOME")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+    Ok(Path::new(&home).join("Library").join(user_dir))
+}
+
+/// launchctl 的目标域：系统级为 system，用户级为当前用户的图形会话
+#[cfg(unix)]
+fn domain(system: bool) -> String {
+    if system {
+        "system".to_string()
+    } else {
+        // SAFETY: getuid 总是成功
+        format!("gui/{}", unsafe { libc::getuid() })
+    }
+}
+
+#[cfg(not(unix))]
+fn domain(_system: bool) -> String {
+    "system".to_string()
+}
+
+#[cfg(target_os = "macos")]
+fn launchctl(args: &[&str]) -> io::Result<()> {
+    let out = std::process::Command::new("launchctl")
+        .args(args)
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "launchctl {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn launchctl(_args: &[&str]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "launchctl is only available on macOS",
+    ))
+}
+
+fn push_string(xml: &mut String, key: &str, value: &str) {
+    xml.push_str(&format!(
+        "    <key>{}</key>\n    <string>{}</string>\n",
+        key,
+        escape(value)
+    ));
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
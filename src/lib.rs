@@ -0,0 +1,1524 @@
+//! 退出 Electron 应用、替换更新文件并重新启动的更新器。
+//! 命令行工具之外，也可以通过 [`Updater`] 在其他 Rust 程序中直接使用
+
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use sysinfo::{ProcessRefreshKind, ProcessStatus, ProcessesToUpdate, Signal, System};
+use vfs::{FileSystem, RealFs};
+use walk::Batch;
+
+mod appimage;
+mod arch;
+pub mod audit;
+pub mod batch;
+pub mod bench;
+mod bits;
+pub mod cancel;
+pub mod control;
+mod copier;
+pub mod diff;
+pub mod download;
+pub mod elevate;
+mod error;
+mod feed;
+pub mod ffi;
+mod github;
+pub mod history;
+pub mod hooks;
+pub mod ipc;
+mod journal;
+pub mod launchd;
+mod logger;
+mod macos;
+pub mod manifest;
+pub mod notify;
+pub mod pin;
+pub mod pipeline;
+#[cfg(feature = "wasm")]
+pub mod plugin;
+pub mod policy;
+#[cfg(windows)]
+mod powershell;
+pub mod privilege;
+pub mod progress;
+mod registry;
+mod rollout;
+pub mod s3;
+mod sandbox;
+pub mod schedule;
+mod secure;
+pub mod service;
+mod shortcuts;
+mod shred;
+mod signer;
+pub mod sink;
+pub mod source;
+pub mod splash;
+mod squirrel;
+pub mod stamp;
+mod syspkg;
+pub mod systemd;
+pub mod template;
+pub mod tray;
+mod updater;
+#[cfg(target_os = "linux")]
+mod uring;
+mod version;
+pub mod vfs;
+pub mod walk;
+pub mod watch;
+
+pub use arch::Arch;
+pub use cancel::CancelToken;
+pub use error::UpdateError;
+pub use hooks::{Hook, HookContext, LifecycleHook, OnFailure};
+pub use logger::Logger;
+pub use progress::{Phase, ProgressObserver};
+pub use sink::LogSink;
+pub use source::UpdateSource;
+pub use updater::{Backup, Layout, Outcome, Provider, UpdateReport, Updater, UpdaterBuilder};
+
+/// 杀掉多个指定进程名的所有实例，并等待退出确认
+fn kill_processes_by_names(targets: &[OsString], logger: &Logger) -> io::Result<()> {
+    if targets.is_empty() {
+        logger.log("No process names provided, skipping kill step.");
+        return Ok(());
+    }
+    logger.check_cancelled()?;
+
+    // 只需要进程名：枚举一次全部进程，之后只刷新匹配的 PID
+    // 比较原始名称（不区分 ASCII 大小写），非 UTF-8 的进程名不会因替换字符误匹配
+    let is_target = |process: &sysinfo::Process| {
+        targets
+            .iter()
+            .any(|t| process.name().eq_ignore_ascii_case(t))
+    };
+    let mut sys = System::new();
+    sys.refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::nothing());
+
+    // 先发送 Kill 信号
+    let mut pids = Vec::new();
+    for (pid, process) in sys.processes() {
+        if is_target(process) {
+            let pname = process.name();
+            logger.log(&format!("Killing process {:?} (pid {})", pname, pid));
+            if process.kill_with(Signal::Kill).is_none() {
+                logger.warn(&format!("Failed to send kill signal to {:?}", pname));
+            }
+            pids.push(*pid);
+        }
+    }
+
+    // 再等待确认退出
+    const MAX_WAIT_MS: u64 = 5000; // 最多等待 5 秒
+    const CHECK_INTERVAL_MS: u64 = 500;
+
+    logger.progress_start(Phase::WaitForExit, Some(MAX_WAIT_MS / CHECK_INTERVAL_MS));
+    let mut elapsed = 0;
+    loop {
+        thread::sleep(Duration::from_millis(CHECK_INTERVAL_MS));
+        elapsed += CHECK_INTERVAL_MS;
+        logger.progress_inc(1);
+        if let Err(e) = logger.check_cancelled() {
+            logger.progress_finish();
+            return Err(e);
+        }
+
+        // 已退出的进程从 sys 中移除（尚未被父进程回收的僵尸进程同样视为已退出）；PID 被重用时进程名不再匹配
+        sys.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&pids),
+            true,
+            ProcessRefreshKind::nothing(),
+        );
+        pids.retain(|pid| {
+            sys.process(*pid)
+                .is_some_and(|p| is_target(p) && p.status() != ProcessStatus::Zombie)
+        });
+
+        let alive: Vec<_> = pids
+            .iter()
+            .filter_map(|pid| sys.process(*pid))
+            .map(|p| p.name())
+            .collect();
+
+        if alive.is_empty() {
+            logger.progress_finish();
+            logger.log("All target processes have exited.");
+            break;
+        } else {
+            logger.log(&format!("Waiting for processes to exit: {:?}", alive));
+        }
+
+        if elapsed >= MAX_WAIT_MS {
+            logger.progress_finish();
+            logger.warn("Timeout waiting for processes to exit, continue anyway.");
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// 复制文件（保留目录结构，包括空目录），同名文件覆盖，不清空目标目录，完成后检查结构（见 [`check_structure`]）。
+/// 符号链接不跟随：指向 input 内部的链接按规范化后的相对路径重新创建，指向外部的链接拒绝复制
+fn copy_dir_recursive(
+    vfs: &dyn FileSystem,
+    input: &Path,
+    output: &Path,
+    ignores: &[PathBuf],
+    logger: &Logger,
+) -> io::Result<()> {
+    if !vfs.exists(input) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "Input directory not found",
+        ));
+    }
+    // 先创建目录与链接，文件按内存上限（见 walk::set_memory_limit）分批复制，由文件系统决定复制方式（见 vfs::CopyEngine）
+    let mut batch = Batch::default();
+    copy_entries(
+        vfs,
+        input,
+        input,
+        output,
+        ignores,
+        &mut |from, to| {
+            let cost = walk::path_cost(&from) + walk::path_cost(&to);
+            if batch.push((from, to), cost) {
+                copy_files(vfs, &batch.take(), logger)?;
+            }
+            Ok(())
+        },
+        logger,
+    )?;
+    copy_files(vfs, &batch.take(), logger)?;
+    check_structure(vfs, input, output, ignores, logger)
+}
+
+/// 与 copy_dir_recursive 相同，但文件以硬链接放到 output，不复制内容；
+/// 文件系统不支持硬链接（如 FAT32、网络共享）时剩余的文件回退为复制
+fn link_dir_recursive(
+    vfs: &dyn FileSystem,
+    input: &Path,
+    output: &Path,
+    ignores: &[PathBuf],
+    logger: &Logger,
+) -> io::Result<()> {
+    let mut batch = Batch::default();
+    let mut copying = false;
+    copy_entries(
+        vfs,
+        input,
+        input,
+        output,
+        ignores,
+        &mut |from, to| {
+            if !copying {
+                match vfs.hard_link(&from, &to) {
+                    Ok(()) => {
+                        logger.log(&format!("Linked file: {}", to.display()));
+                        logger.progress_inc(1);
+                        return logger.check_cancelled();
+                    }
+                    Err(e) => {
+                        logger.warn(&format!(
+                            "Failed to hard link {}: {}, copying remaining files",
+                            to.display(),
+                            e
+                        ));
+                        copying = true;
+                    }
+                }
+            }
+            let cost = walk::path_cost(&from) + walk::path_cost(&to);
+            if batch.push((from, to), cost) {
+                copy_files(vfs, &batch.take(), logger)?;
+            }
+            Ok(())
+        },
+        logger,
+    )?;
+    copy_files(vfs, &batch.take(), logger)
+}
+
+/// 与 copy_dir_recursive 相同，但文件从 input 重命名（移动）到 output，同一个卷上几乎不需要时间；
+/// 重命名失败（如 input 与 output 位于不同的卷）时剩余的文件回退为复制。移动后的文件不再位于 input 中
+fn move_dir_recursive(
+    vfs: &dyn FileSystem,
+    input: &Path,
+    output: &Path,
+    ignores: &[PathBuf],
+    logger: &Logger,
+) -> io::Result<()> {
+    // 移动后 input 中不再有这些条目，结构检查按移动前的列表进行
+    let mut entries = Vec::new();
+    list_entries(vfs, input, input, ignores, &mut entries)?;
+    let mut batch = Batch::default();
+    let mut copying = false;
+    copy_entries(
+        vfs,
+        input,
+        input,
+        output,
+        ignores,
+        &mut |from, to| {
+            if !copying {
+                match vfs.rename(&from, &to) {
+                    Ok(()) => {
+                        logger.log(&format!("Moved file: {}", to.display()));
+                        logger.progress_inc(1);
+                        return logger.check_cancelled();
+                    }
+                    Err(e) => {
+                        logger.warn(&format!(
+                            "Failed to move {}: {}, copying remaining files",
+                            to.display(),
+                            e
+                        ));
+                        copying = true;
+                    }
+                }
+            }
+            let cost = walk::path_cost(&from) + walk::path_cost(&to);
+            if batch.push((from, to), cost) {
+                copy_files(vfs, &batch.take(), logger)?;
+            }
+            Ok(())
+        },
+        logger,
+    )?;
+    copy_files(vfs, &batch.take(), logger)?;
+    verify_entries(vfs, &entries, output, logger)
+}
+
+/// 复制 copy_entries 收集的文件
+fn copy_files(
+    vfs: &dyn FileSystem,
+    files: &[(PathBuf, PathBuf)],
+    logger: &Logger,
+) -> io::Result<()> {
+    let progress = |dest: &Path, copied, total| logger.copying(dest, copied, total);
+    vfs.copy_many(files, &progress, &mut |dest, bytes| {
+        logger.log(&format!("Copied file: {}", dest.display()));
+        logger.file_copied(dest, bytes);
+        logger.progress_inc(1);
+        logger.check_cancelled()
+    })
+}
+
+/// 在 output 中创建 input 的目录与符号链接，文件逐个以（源路径，目标路径）交给 file 处理
+fn copy_entries(
+    vfs: &dyn FileSystem,
+    root: &Path,
+    input: &Path,
+    output: &Path,
+    ignores: &[PathBuf],
+    file: &mut dyn FnMut(PathBuf, PathBuf) -> io::Result<()>,
+    logger: &Logger,
+) -> io::Result<()> {
+    for path in vfs.read_dir(input)? {
+        logger.check_cancelled()?;
+        let relative = path.strip_prefix(input).unwrap();
+        let dest = output.join(relative);
+
+        // ✅ 检查是否在忽略列表中（忽略项相对于最外层的 root）
+        let from_root = path.strip_prefix(root).unwrap_or(&path);
+        if is_ignored(from_root, ignores) {
+            logger.log(&format!("Ignored: {}", from_root.display()));
+            continue;
+        }
+
+        // 目标位置已有的链接先删除，不经由链接写入
+        if vfs.is_symlink(&dest) {
+            vfs.remove_file(&dest)?;
+        }
+        if vfs.is_symlink(&path) {
+            let target = link_target(vfs, root, &path)?;
+            vfs.create_dir_all(dest.parent().unwrap())?;
+            if vfs.is_dir(&dest) {
+                vfs.remove_dir_all(&dest)?;
+            } else if vfs.exists(&dest) {
+                vfs.remove_file(&dest)?;
+            }
+            vfs.symlink(&target, &dest, vfs.is_dir(&path))?;
+            logger.log(&format!(
+                "Copied symlink: {} -> {}",
+                dest.display(),
+                target.display()
+            ));
+            logger.progress_inc(1);
+        } else if vfs.is_dir(&path) {
+            vfs.create_dir_all(&dest)?;
+            copy_entries(vfs, root, &path, &dest, ignores, file, logger)?;
+        } else {
+            vfs.create_dir_all(dest.parent().unwrap())?;
+            // 目标位置已有的文件可能是旧版本的硬链接（见 stage_resources），先删除，不在原处覆盖
+            if vfs.exists(&dest) && !vfs.is_dir(&dest) {
+                vfs.remove_file(&dest)?;
+            }
+            file(path, dest)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 复制后的结构检查：input 中（跳过 ignores）的每个文件、目录与符号链接在 output 中都应存在且类型相同。
+/// 逐项记录缺失或类型不同的条目，有任何不一致时返回 InvalidData 错误并报告条目数
+fn check_structure(
+    vfs: &dyn FileSystem,
+    input: &Path,
+    output: &Path,
+    ignores: &[PathBuf],
+    logger: &Logger,
+) -> io::Result<()> {
+    let mut entries = Vec::new();
+    list_entries(vfs, input, input, ignores, &mut entries)?;
+    verify_entries(vfs, &entries, output, logger)
+}
+
+/// 结构检查中条目的类型
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+fn entry_kind(vfs: &dyn FileSystem, path: &Path) -> Option<EntryKind> {
+    if vfs.is_symlink(path) {
+        Some(EntryKind::Symlink)
+    } else if vfs.is_dir(path) {
+        Some(EntryKind::Dir)
+    } else {
+        vfs.exists(path).then_some(EntryKind::File)
+    }
+}
+
+/// 收集 input 中（跳过 ignores）的条目，以相对 root 的路径与类型追加到 entries，目录排在其中的条目之前
+fn list_entries(
+    vfs: &dyn FileSystem,
+    root: &Path,
+    input: &Path,
+    ignores: &[PathBuf],
+    entries: &mut Vec<(PathBuf, EntryKind)>,
+) -> io::Result<()> {
+    for path in vfs.read_dir(input)? {
+        let from_root = path.strip_prefix(root).unwrap_or(&path);
+        if is_ignored(from_root, ignores) {
+            continue;
+        }
+        let Some(kind) = entry_kind(vfs, &path) else {
+            continue;
+        };
+        entries.push((from_root.to_path_buf(), kind));
+        if kind == EntryKind::Dir {
+            list_entries(vfs, root, &path, ignores, entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// 检查 list_entries 收集的条目在 output 中都存在且类型相同；缺失的目录只记录一次，其中的条目不再计数
+fn verify_entries(
+    vfs: &dyn FileSystem,
+    entries: &[(PathBuf, EntryKind)],
+    output: &Path,
+    logger: &Logger,
+) -> io::Result<()> {
+    let (mut expected, mut found) = (0, 0);
+    let mut missing_dir: Option<&Path> = None;
+    for (relative, kind) in entries {
+        if missing_dir.is_some_and(|dir| relative.starts_with(dir)) {
+            continue;
+        }
+        expected += 1;
+        if entry_kind(vfs, &output.join(relative)) == Some(*kind) {
+            found += 1;
+            continue;
+        }
+        logger.warn(&format!("Missing after copy: {}", relative.display()));
+        if *kind == EntryKind::Dir {
+            missing_dir = Some(relative);
+        }
+    }
+    if found != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Entry count mismatch in {}: {} of {} entries copied",
+                output.display(),
+                found,
+                expected
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// 符号链接 link 在 root 内的目标，以相对 link 所在目录的路径返回；
+/// 目标（按路径规范化 `.` 与 `..` 后）位于 root 之外时返回错误
+fn link_target(vfs: &dyn FileSystem, root: &Path, link: &Path) -> io::Result<PathBuf> {
+    let target = vfs.read_link(link)?;
+    let parent = link.parent().unwrap_or(root);
+    let resolved = normalize(&parent.join(&target));
+    let escapes = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Refusing to copy symlink {} -> {}: it points outside {}",
+                link.display(),
+                target.display(),
+                root.display()
+            ),
+        )
+    };
+    let inside = resolved
+        .strip_prefix(normalize(root))
+        .map_err(|_| escapes())?;
+    let depth = parent
+        .strip_prefix(root)
+        .map_err(|_| escapes())?
+        .components()
+        .count();
+    let mut relative: PathBuf = std::iter::repeat_n(Component::ParentDir, depth).collect();
+    relative.push(inside);
+    if relative.as_os_str().is_empty() {
+        relative.push(Component::CurDir);
+    }
+    Ok(relative)
+}
+
+/// 规范化用户提供的本地路径：转为绝对路径并按路径去掉 `.`、`..`、结尾与重复的分隔符（不解析符号链接，
+/// Windows 上不会因此得到 `\\?\` 前缀）。Windows 上 `/` 与 `\` 均可作为分隔符，
+/// UNC 路径（`\\server\share\...`）保留服务器与共享名，`..` 不会越过共享根目录
+pub(crate) fn resolve_path(path: &Path) -> io::Result<PathBuf> {
+    if path.as_os_str().is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Empty path"));
+    }
+    Ok(normalize(&std::path::absolute(path)?))
+}
+
+/// 规范化 output（见 [`resolve_path`]）。<output>_new、<output>_old 由最后一段路径名构造，
+/// 文件系统根目录（如 `C:\`、`\\server\share`、`/`）没有最后一段，返回错误
+pub(crate) fn resolve_output(output: &Path) -> io::Result<PathBuf> {
+    let resolved = resolve_path(output)?;
+    if resolved.file_name().is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Output {} is a filesystem root", output.display()),
+        ));
+    }
+    Ok(resolved)
+}
+
+/// 根据应用主程序的位置找到 Electron 的 resources 目录（未指定 output 时使用）：Windows 与 Linux 上为
+/// 主程序同级的 `resources`，macOS 上为 `.app` 中的 `Contents/Resources`（app 可以是 `.app` 本身或
+/// `Contents/MacOS` 中的可执行文件）。目录中没有 `app.asar` 或 `app` 时不像 Electron 安装，返回错误
+pub fn detect_resources(app: &Path) -> io::Result<PathBuf> {
+    let app = resolve_path(app)?;
+    let dir = if cfg!(target_os = "macos") {
+        if app.extension().is_some_and(|ext| ext == "app") {
+            Some(app.join("Contents").join("Resources"))
+        } else {
+            app.parent()
+                .and_then(Path::parent)
+                .map(|contents| contents.join("Resources"))
+        }
+    } else {
+        app.parent().map(|dir| dir.join("resources"))
+    };
+    let dir = dir.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Cannot locate resources directory from {}", app.display()),
+        )
+    })?;
+    if !dir.join("app.asar").is_file() && !dir.join("app").is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} does not look like an Electron resources directory (no app.asar or app)",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(dir)
+}
+
+/// 按路径规范化 `.` 与 `..`，不访问文件系统；根目录（含 UNC 共享根目录）之上的 `..` 丢弃，
+/// 相对路径开头的 `..` 保留
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if normalized.file_name().is_some() {
+                    normalized.pop();
+                } else if !normalized.has_root() {
+                    normalized.push(component);
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// 启动主程序
+fn restart_app(app: &Path, logger: &Logger) {
+    restart_app_as(app, None, logger);
+}
+
+/// 启动主程序，指定 caller 时以其身份运行（见 [`privilege::run_as`]）
+fn restart_app_as(app: &Path, caller: Option<&privilege::Caller>, logger: &Logger) {
+    logger.progress_start(Phase::Restart, None);
+    if app.exists() {
+        logger.log("Restarting main app...");
+        let mut command = Command::new(app);
+        command.stdout(Stdio::null()).stderr(Stdio::null());
+        if let Some(caller) = caller {
+            privilege::run_as(&mut command, caller);
+        }
+        match command.spawn() {
+            Ok(_) => logger.log("Main app restarted successfully"),
+            Err(e) => logger.error(&format!("Failed to start main app: {}", e)),
+        }
+    } else {
+        logger.warn("Main app not found, skip restart");
+    }
+    logger.progress_finish();
+}
+
+/// 统计待复制的文件数量（与 copy_dir_recursive 的忽略规则一致），用于进度条
+fn count_files(vfs: &dyn FileSystem, input: &Path, ignores: &[PathBuf]) -> u64 {
+    count_files_in(vfs, input, input, ignores)
+}
+
+fn count_files_in(vfs: &dyn FileSystem, root: &Path, dir: &Path, ignores: &[PathBuf]) -> u64 {
+    let Ok(entries) = vfs.read_dir(dir) else {
+        return 0;
+    };
+    let mut count = 0;
+    for path in entries {
+        if is_ignored(path.strip_prefix(root).unwrap_or(&path), ignores) {
+            continue;
+        }
+        if vfs.is_dir(&path) && !vfs.is_symlink(&path) {
+            count += count_files_in(vfs, root, &path, ignores);
+        } else {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// 规范化忽略列表中的一项为相对路径：`/` 与 `\` 均作为分隔符，去掉开头的 `./` 与 `/`、结尾与重复的分隔符；
+/// 规范化后为空时返回 None。按原始字节处理，不要求是有效的 UTF-8
+pub(crate) fn normalize_ignore(ignore: &OsStr) -> Option<PathBuf> {
+    let path: PathBuf = ignore
+        .as_encoded_bytes()
+        .trim_ascii()
+        .split(|b| matches!(b, b'/' | b'\\'))
+        .filter(|part| !part.is_empty() && *part != b".")
+        // SAFETY: 只在 ASCII 分隔符与空白处切分，各段仍是有效的编码字节
+        .map(|part| unsafe { OsStr::from_encoded_bytes_unchecked(part) })
+        .collect();
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+/// relative 是否为忽略列表中某项本身或位于其下；按路径段比较原始名称，`config` 不匹配 `config.json`
+pub(crate) fn is_ignored(relative: &Path, ignores: &[PathBuf]) -> bool {
+    ignores.iter().any(|ignore| relative.starts_with(ignore))
+}
+
+/// 与 path 同一目录、名称为 prefix + path 的名称 + suffix 的路径（如 `<output>_new`）；
+/// 直接拼接原始名称，非 UTF-8 的名称不会被替换字符改变。path 没有名称（如根目录）时返回错误
+pub(crate) fn sibling(path: &Path, prefix: &str, suffix: &str) -> io::Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid output path: {}", path.display()),
+        )
+    })?;
+    let mut sibling = OsString::from(prefix);
+    sibling.push(name);
+    sibling.push(suffix);
+    Ok(path.with_file_name(sibling))
+}
+
+/// 暂存单个 resources 目录：旧内容（跳过 ignore_existing）、更新文件与其后依次叠加的 layers（跳过 ignores）
+/// 先合并到 <output>_new 并调用 on_staged（生命周期钩子），返回 (<output>_new, <output>_old)。
+/// move_input 时更新文件移动而不是复制到 <output>_new（见 [`move_dir_recursive`]）；
+/// 失败或被取消时删除 <output>_new，output 保持不变
+#[allow(clippy::too_many_arguments)]
+fn stage_output(
+    vfs: &dyn FileSystem,
+    output_path: &Path,
+    input_path: &Path,
+    layers: &[PathBuf],
+    ignores: &[PathBuf],
+    ignore_existing: &[PathBuf],
+    move_input: bool,
+    on_staged: &dyn Fn(&Path) -> io::Result<()>,
+    logger: &Logger,
+) -> io::Result<(PathBuf, PathBuf)> {
+    if let Some(input_file) = single_file_input(vfs, output_path, input_path)? {
+        if !layers.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Layered inputs are not supported for single-file outputs",
+            ));
+        }
+        return stage_file(vfs, output_path, &input_file, on_staged, logger);
+    }
+    // 创建 output_new 临时目录
+    let output_new = sibling(output_path, "", "_new")?;
+    let output_old = sibling(output_path, "", "_old")?;
+
+    logger.log(&format!(
+        "Creating temporary update directory: {}",
+        output_new.display()
+    ));
+    if vfs.exists(&output_new) {
+        vfs.remove_dir_all(&output_new).unwrap_or_else(|e| {
+            logger.warn(&format!(
+                "Failed to remove existing temporary directory: {}",
+                e
+            ));
+        });
+    }
+    vfs.create_private_dir(&output_new)
+        .map_err(|e| context(e, "Failed to create temporary directory"))?;
+
+    if let Err(e) = stage_resources(
+        vfs,
+        output_path,
+        input_path,
+        layers,
+        &output_new,
+        ignores,
+        ignore_existing,
+        move_input,
+        logger,
+    )
+    .and_then(|()| on_staged(&output_new))
+    .and_then(|()| {
+        vfs.release_private_dir(&output_new, output_path)
+            .map_err(|e| context(e, "Failed to set permissions of temporary directory"))
+    }) {
+        logger.warn(&format!(
+            "Removing incomplete temporary directory: {}",
+            output_new.display()
+        ));
+        if let Err(e) = vfs.remove_dir_all(&output_new) {
+            logger.warn(&format!("Failed to remove temporary directory: {}", e));
+        }
+        return Err(e);
+    }
+    Ok((output_new, output_old))
+}
+
+/// 单文件目标（如只更新 app.asar）要复制的更新文件：output 为已有文件时取 input 本身，input 为目录时取其中的同名文件；
+/// output 不存在且 input 为单个文件（不是 zip 包）时取 input。其他情况为目录目标，返回 None
+pub(crate) fn single_file_input(
+    vfs: &dyn FileSystem,
+    output_path: &Path,
+    input_path: &Path,
+) -> io::Result<Option<PathBuf>> {
+    let is_file = |path: &Path| vfs.exists(path) && !vfs.is_dir(path);
+    if is_file(output_path) {
+        if !vfs.is_dir(input_path) {
+            return Ok(Some(input_path.to_path_buf()));
+        }
+        let name = output_path.file_name().unwrap_or_default();
+        let file = input_path.join(name);
+        if !is_file(&file) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "Output is a file but {} is not in the input",
+                    Path::new(name).display()
+                ),
+            ));
+        }
+        return Ok(Some(file));
+    }
+    if !is_file(input_path) || download::Format::Zip.matches(&input_path.to_string_lossy()) {
+        return Ok(None);
+    }
+    if vfs.exists(output_path) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Input {} is a single file but output is a directory",
+                input_path.display()
+            ),
+        ));
+    }
+    Ok(Some(input_path.to_path_buf()))
+}
+
+/// 暂存单文件目标：把 input_file 复制为 <output>_new 并调用 on_staged，返回 (<output>_new, <output>_old)，
+/// 之后与目录相同地替换、回滚与清理；失败或被取消时删除 <output>_new，output 保持不变
+fn stage_file(
+    vfs: &dyn FileSystem,
+    output_path: &Path,
+    input_file: &Path,
+    on_staged: &dyn Fn(&Path) -> io::Result<()>,
+    logger: &Logger,
+) -> io::Result<(PathBuf, PathBuf)> {
+    let output_new = sibling(output_path, "", "_new")?;
+    let output_old = sibling(output_path, "", "_old")?;
+
+    logger.log(&format!(
+        "Staging update file {} as {}",
+        input_file.display(),
+        output_new.display()
+    ));
+    if vfs.exists(&output_new) {
+        remove_path(vfs, &output_new).unwrap_or_else(|e| {
+            logger.warn(&format!("Failed to remove existing temporary file: {}", e));
+        });
+    }
+    logger.progress_start(Phase::CopyUpdate, Some(1));
+    let copied = vfs.copy(input_file, &output_new).map(|bytes| {
+        logger.log(&format!("Copied file: {}", output_new.display()));
+        logger.file_copied(&output_new, bytes);
+        logger.progress_inc(1);
+    });
+    logger.progress_finish();
+    if let Err(e) = copied
+        .map_err(|e| context(e, "File copy failed"))
+        .and_then(|()| {
+            vfs.strip_quarantine(&output_new, logger);
+            logger.check_cancelled()
+        })
+        .and_then(|()| on_staged(&output_new))
+    {
+        logger.warn(&format!(
+            "Removing incomplete temporary file: {}",
+            output_new.display()
+        ));
+        if vfs.exists(&output_new)
+            && let Err(e) = vfs.remove_file(&output_new)
+        {
+            logger.warn(&format!("Failed to remove temporary file: {}", e));
+        }
+        return Err(e);
+    }
+    Ok((output_new, output_old))
+}
+
+/// 删除目录（含其内容）或单个文件、符号链接
+pub(crate) fn remove_path(vfs: &dyn FileSystem, path: &Path) -> io::Result<()> {
+    if vfs.is_dir(path) && !vfs.is_symlink(path) {
+        vfs.remove_dir_all(path)
+    } else {
+        vfs.remove_file(path)
+    }
+}
+
+/// 将旧 output、更新文件与 layers 依次合并到 output_new，后合并的同名文件覆盖先合并的，完成后检查一次取消。
+/// 旧 output 中的文件以硬链接放入 output_new，只有更新文件需要复制；替换后未更新的文件与
+/// <output>_old 共享内容，更新文件则先删除链接再复制，不影响备份
+#[allow(clippy::too_many_arguments)]
+fn stage_resources(
+    vfs: &dyn FileSystem,
+    output_path: &Path,
+    input_path: &Path,
+    layers: &[PathBuf],
+    output_new: &Path,
+    ignores: &[PathBuf],
+    ignore_existing: &[PathBuf],
+    move_input: bool,
+    logger: &Logger,
+) -> io::Result<()> {
+    // 先把旧 output（如果存在）链接到 output_new
+    if vfs.exists(output_path) {
+        logger.log("Linking existing output to temporary directory...");
+        logger.progress_start(
+            Phase::CopyExisting,
+            Some(count_files(vfs, output_path, ignore_existing)),
+        );
+        let result = link_dir_recursive(vfs, output_path, output_new, ignore_existing, logger);
+        logger.progress_finish();
+        result.map_err(|e| context(e, "Failed to copy existing output"))?;
+    }
+
+    // 再拷贝 input 更新文件与各层到 output_new
+    logger.log("Copying update files to temporary directory...");
+    copy_input(vfs, input_path, output_new, ignores, move_input, logger)?;
+    for layer in layers {
+        logger.log(&format!("Applying overlay {}...", layer.display()));
+        copy_input(vfs, layer, output_new, ignores, move_input, logger)?;
+    }
+    vfs.strip_quarantine(output_new, logger);
+
+    // 替换目录前的最后一个安全点
+    logger.check_cancelled()
+}
+
+/// 把一份更新文件（目录，或 stream_extract 时未解压的 zip 包）合并到 output_new
+fn copy_input(
+    vfs: &dyn FileSystem,
+    input_path: &Path,
+    output_new: &Path,
+    ignores: &[PathBuf],
+    move_input: bool,
+    logger: &Logger,
+) -> io::Result<()> {
+    if !vfs.is_dir(input_path) {
+        // 直接解压到 output_new
+        let result = vfs.extract_zip(input_path, output_new, ignores, logger);
+        logger.progress_finish();
+        return result.map_err(|e| context(e, "Package extraction failed"));
+    }
+    logger.progress_start(
+        Phase::CopyUpdate,
+        Some(count_files(vfs, input_path, ignores)),
+    );
+    let result = if move_input {
+        move_dir_recursive(vfs, input_path, output_new, ignores, logger)
+    } else {
+        copy_dir_recursive(vfs, input_path, output_new, ignores, logger)
+    };
+    logger.progress_finish();
+    result.map_err(|e| context(e, "File copy failed"))
+}
+
+/// output → output_old，output_new → output；第二步失败时把 output_old 改回 output
+/// 每次重命名都在 retry 内重试（见 [`rename_with_retry`]）
+fn swap(
+    vfs: &dyn FileSystem,
+    output_path: &Path,
+    output_new: &Path,
+    output_old: &Path,
+    retry: Duration,
+    logger: &Logger,
+) -> io::Result<()> {
+    if vfs.exists(output_old) {
+        remove_path(vfs, output_old).unwrap_or_else(|e| {
+            logger.warn(&format!("Failed to remove old backup directory: {}", e));
+        });
+    }
+    if vfs.exists(output_path) {
+        wait_for_asar(vfs, output_path, logger);
+        rename_with_retry(vfs, output_path, output_old, retry, logger)
+            .map_err(|e| context(e, "Failed to rename output -> output_old"))?;
+        if vfs.is_dir(output_old)
+            && let Err(e) = vfs.write_file(&output_old.join(BACKUP_MARKER), b"")
+        {
+            logger.warn(&format!("Failed to mark backup directory: {}", e));
+        }
+    }
+
+    rename_with_retry(vfs, output_new, output_path, retry, logger).map_err(|e| {
+        if vfs.exists(output_old) && !vfs.exists(output_path) {
+            match rename_with_retry(vfs, output_old, output_path, retry, logger) {
+                Ok(()) => logger.warn("Restored original output after failed swap"),
+                Err(e) => logger.error(&format!("Failed to restore original output: {}", e)),
+            }
+        }
+        context(e, "Failed to rename temporary directory -> output")
+    })
+}
+
+/// 替换时写入 <output>_old 的标记文件，清理时只删除带有此标记的备份目录，回滚时删除
+pub(crate) const BACKUP_MARKER: &str = ".eqi-backup";
+
+/// 清理时能否删除（并覆盖，见 `--secure-delete`）path：更新器缓存中的路径总是可以；其他路径需通过
+/// [`policy::check_removable`]，backup 为 true 时目录中还需有替换时写入的 [`BACKUP_MARKER`]。
+/// 不满足且未指定 confirm 时记录警告并返回 false，指定时记录警告后仍然删除
+pub(crate) fn may_remove(
+    vfs: &dyn FileSystem,
+    path: &Path,
+    backup: bool,
+    confirm: bool,
+    logger: &Logger,
+) -> bool {
+    if download::cache_dir().is_ok_and(|cache| path.starts_with(cache)) {
+        return true;
+    }
+    let result = policy::check_removable(path).and_then(|()| {
+        if backup && vfs.is_dir(path) && !vfs.exists(&path.join(BACKUP_MARKER)) {
+            return Err(format!(
+                "Refusing to delete {}: it was not created by the updater",
+                path.display()
+            ));
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => true,
+        Err(reason) if confirm => {
+            logger.warn(&format!("{}, deleting anyway (--confirm-delete)", reason));
+            true
+        }
+        Err(reason) => {
+            logger.warn(&format!(
+                "{}, rerun with --confirm-delete to delete it",
+                reason
+            ));
+            false
+        }
+    }
+}
+
+/// 把备份 output_old 移动到 backup_dir 中以备份名称与当前时间命名的位置（如 `resources_old-20240101-120000`），
+/// 返回新位置；重命名失败（如位于不同的卷）时改为逐个移动文件，回退为复制后删除原备份。
+/// 之后与上一个同名归档去重（见 [`link_dest`]），去重失败只记录警告
+pub(crate) fn archive_backup(
+    vfs: &dyn FileSystem,
+    output_old: &Path,
+    backup_dir: &Path,
+    logger: &Logger,
+) -> io::Result<PathBuf> {
+    let name = output_old.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Path has no file name: {}", output_old.display()),
+        )
+    })?;
+    let mut archived = name.to_os_string();
+    archived.push(chrono::Local::now().format("-%Y%m%d-%H%M%S").to_string());
+    let dest = backup_dir.join(archived);
+    vfs.create_dir_all(backup_dir)?;
+    if let Err(e) = vfs.rename(output_old, &dest) {
+        logger.warn(&format!(
+            "Failed to rename {} -> {}: {}, moving files instead",
+            output_old.display(),
+            dest.display(),
+            e
+        ));
+        if vfs.is_dir(output_old) {
+            move_dir_recursive(vfs, output_old, &dest, &[], logger)?;
+        } else {
+            vfs.copy(output_old, &dest)?;
+        }
+        remove_path(vfs, output_old)?;
+    }
+    if let Some(previous) = previous_archive(vfs, backup_dir, name, &dest) {
+        match link_dest(vfs, &dest, &previous, logger) {
+            Ok(0) => {}
+            Ok(linked) => logger.log(&format!(
+                "Linked {} unchanged files against previous backup {}",
+                linked,
+                previous.display()
+            )),
+            Err(e) => logger.warn(&format!(
+                "Failed to link unchanged files against previous backup {}: {}",
+                previous.display(),
+                e
+            )),
+        }
+    }
+    Ok(dest)
+}
+
+/// backup_dir 中除 dest 以外最近的一个 name 的归档：名称为 `<name>-<时间>` 且带有 [`BACKUP_MARKER`] 的目录，
+/// 时间格式按字典序即按时间排序
+fn previous_archive(
+    vfs: &dyn FileSystem,
+    backup_dir: &Path,
+    name: &OsStr,
+    dest: &Path,
+) -> Option<PathBuf> {
+    let mut prefix = name.to_os_string();
+    prefix.push("-");
+    let prefix = prefix.to_string_lossy().into_owned();
+    vfs.read_dir(backup_dir)
+        .ok()?
+        .into_iter()
+        .filter(|path| path != dest && vfs.is_dir(path) && !vfs.is_symlink(path))
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with(&prefix))
+        })
+        .filter(|path| vfs.exists(&path.join(BACKUP_MARKER)))
+        .max()
+}
+
+/// 与 rsync --link-dest 相同：archived 中与 previous 同一相对路径、内容相同的文件替换为
+/// previous 中文件的硬链接，保留多个版本时未变的文件只占一份空间。先在旁边创建链接再重命名覆盖，
+/// 中途失败不会丢失文件；标记文件的修改时间是备份时间，不参与去重。返回链接的文件数
+pub(crate) fn link_dest(
+    vfs: &dyn FileSystem,
+    archived: &Path,
+    previous: &Path,
+    logger: &Logger,
+) -> io::Result<u64> {
+    link_dest_in(vfs, archived, archived, previous, logger)
+}
+
+fn link_dest_in(
+    vfs: &dyn FileSystem,
+    root: &Path,
+    dir: &Path,
+    previous: &Path,
+    logger: &Logger,
+) -> io::Result<u64> {
+    let mut linked = 0;
+    for path in vfs.read_dir(dir)? {
+        if vfs.is_symlink(&path) {
+            continue;
+        }
+        if vfs.is_dir(&path) {
+            linked += link_dest_in(vfs, root, &path, previous, logger)?;
+            continue;
+        }
+        let relative = path.strip_prefix(root).expect("path under root");
+        let same = previous.join(relative);
+        if relative == Path::new(BACKUP_MARKER)
+            || vfs.is_symlink(&same)
+            || !vfs.exists(&same)
+            || !vfs.same_content(&path, &same)?
+        {
+            continue;
+        }
+        let mut temp = path.clone().into_os_string();
+        temp.push(".eqi-link");
+        let temp = PathBuf::from(temp);
+        vfs.hard_link(&same, &temp)?;
+        if let Err(e) = vfs.rename(&temp, &path) {
+            let _ = vfs.remove_file(&temp);
+            return Err(e);
+        }
+        logger.log(&format!("Linked unchanged file: {}", path.display()));
+        linked += 1;
+    }
+    Ok(linked)
+}
+
+/// 备份是否已超过 days 天：以替换时写入的 [`BACKUP_MARKER`] 的修改时间为备份时间，
+/// 没有标记（不是更新器创建的备份）时为 false
+pub(crate) fn backup_expired(path: &Path, days: u32) -> bool {
+    let max_age = Duration::from_secs(u64::from(days) * 24 * 60 * 60);
+    std::fs::metadata(path.join(BACKUP_MARKER))
+        .and_then(|meta| meta.modified())
+        .is_ok_and(|time| time.elapsed().is_ok_and(|age| age > max_age))
+}
+
+/// 替换与回滚中重命名失败时默认的重试时长
+pub const DEFAULT_RENAME_RETRY: Duration = Duration::from_secs(10);
+
+/// 重命名失败时的首次重试间隔，之后每次翻倍，最长 RENAME_BACKOFF_MAX
+const RENAME_BACKOFF: Duration = Duration::from_millis(100);
+const RENAME_BACKOFF_MAX: Duration = Duration::from_secs(2);
+
+/// 重命名 from → to，失败时退避重试，直到成功或累计超过 retry（为零时不重试）。
+/// Windows 上杀毒软件扫描目录时重命名常暂时失败（拒绝访问、共享冲突），稍后即可成功；
+/// from 不存在时立即返回错误
+fn rename_with_retry(
+    vfs: &dyn FileSystem,
+    from: &Path,
+    to: &Path,
+    retry: Duration,
+    logger: &Logger,
+) -> io::Result<()> {
+    let deadline = std::time::Instant::now() + retry;
+    let mut delay = RENAME_BACKOFF;
+    loop {
+        let e = match vfs.rename(from, to) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        let now = std::time::Instant::now();
+        if e.kind() == io::ErrorKind::NotFound || now >= deadline {
+            return Err(e);
+        }
+        logger.warn(&format!(
+            "Failed to rename {} -> {}: {}, retrying",
+            from.display(),
+            to.display(),
+            e
+        ));
+        thread::sleep(delay.min(deadline - now));
+        delay = (delay * 2).min(RENAME_BACKOFF_MAX);
+    }
+}
+
+/// 等待 output 中 `.asar` 文件解除占用的最长时间
+const ASAR_UNLOCK_WAIT: Duration = Duration::from_secs(5);
+
+/// 替换前等待 output 中（单文件目标时为 output 本身）的 `*.asar`（通常为 app.asar）解除占用：应用退出后系统或杀毒软件往往还会
+/// 持有它几百毫秒，此时整个目录无法重命名。以退避间隔重试，超过 ASAR_UNLOCK_WAIT 仍被占用时
+/// 记录警告后继续（由 rename_with_retry 继续重试）
+fn wait_for_asar(vfs: &dyn FileSystem, output: &Path, logger: &Logger) {
+    let paths = if !vfs.is_dir(output) {
+        vec![output.to_path_buf()]
+    } else if let Ok(paths) = vfs.read_dir(output) {
+        paths
+    } else {
+        return;
+    };
+    let deadline = std::time::Instant::now() + ASAR_UNLOCK_WAIT;
+    for path in paths {
+        if path.extension() != Some(OsStr::new("asar")) || vfs.is_dir(&path) {
+            continue;
+        }
+        let started = std::time::Instant::now();
+        let mut delay = RENAME_BACKOFF;
+        while vfs.is_locked(&path) {
+            let now = std::time::Instant::now();
+            if now >= deadline {
+                logger.warn(&format!("{} is still in use", path.display()));
+                break;
+            }
+            thread::sleep(delay.min(deadline - now));
+            delay = (delay * 2).min(RENAME_BACKOFF_MAX);
+        }
+        if started.elapsed() >= RENAME_BACKOFF {
+            logger.log(&format!(
+                "Waited {} ms for {} to be released",
+                started.elapsed().as_millis(),
+                path.display()
+            ));
+        }
+    }
+}
+
+/// 回滚未完成的替换（如更新中途崩溃或断电）：存在 <output>_old 时将其恢复为 output，
+/// 并删除残留的 <output>_new；返回是否执行了恢复。更新成功后备份已被清理，无法再回滚
+/// 结果会追加到更新历史（见 [`history`]）
+pub fn rollback(output_path: &Path, logger: &Logger) -> io::Result<bool> {
+    let _lock = journal::lock(output_path)?;
+    let from_version = version::package_version(output_path).ok().flatten();
+    let result = rollback_in(&RealFs::default(), output_path, logger);
+    // 失败时保留替换日志，下次更新时再恢复
+    if result.is_ok()
+        && let Err(e) = journal::remove(output_path)
+    {
+        logger.warn(&format!("Failed to remove update journal: {}", e));
+    }
+    let mut entry = match &result {
+        Ok(true) => history::Entry::new("rollback", "rolled_back"),
+        Ok(false) => history::Entry::new("rollback", "nothing_to_roll_back"),
+        Err(e) => history::Entry {
+            error: Some(e.to_string()),
+            ..history::Entry::new("rollback", "failed")
+        },
+    };
+    entry.from_version = from_version;
+    entry.to_version = version::package_version(output_path).ok().flatten();
+    if let Err(e) = history::append(output_path, &entry) {
+        logger.warn(&format!("Failed to write update history: {}", e));
+    }
+    result
+}
+
+/// 在指定的文件系统上执行 [`rollback`]；重命名失败时在 [`DEFAULT_RENAME_RETRY`] 内重试
+pub fn rollback_in(vfs: &dyn FileSystem, output_path: &Path, logger: &Logger) -> io::Result<bool> {
+    let output_new = sibling(output_path, "", "_new")?;
+    let output_old = sibling(output_path, "", "_old")?;
+
+    if vfs.exists(&output_new) {
+        remove_path(vfs, &output_new)
+            .map_err(|e| context(e, "Failed to remove temporary directory"))?;
+        logger.log(&format!("Removed {}", output_new.display()));
+    }
+    if !vfs.exists(&output_old) {
+        logger.log("No backup directory found, nothing to roll back");
+        return Ok(false);
+    }
+
+    // 已替换为新版本时先移开，恢复成功后再删除
+    let rejected = sibling(output_path, "", "_rejected")?;
+    if vfs.exists(output_path) {
+        if vfs.exists(&rejected) {
+            remove_path(vfs, &rejected)?;
+        }
+        rename_with_retry(vfs, output_path, &rejected, DEFAULT_RENAME_RETRY, logger)
+            .map_err(|e| context(e, "Failed to move aside current output"))?;
+    }
+    rename_with_retry(vfs, &output_old, output_path, DEFAULT_RENAME_RETRY, logger)
+        .map_err(|e| context(e, "Failed to rename output_old -> output"))?;
+    let marker = output_path.join(BACKUP_MARKER);
+    if vfs.exists(&marker) {
+        vfs.remove_file(&marker)?;
+    }
+    if vfs.exists(&rejected)
+        && let Err(e) = remove_path(vfs, &rejected)
+    {
+        logger.warn(&format!("Failed to remove {}: {}", rejected.display(), e));
+    }
+    logger.log(&format!("Restored {}", output_path.display()));
+    Ok(true)
+}
+
+/// 为错误附加说明，保留原错误类型
+fn context(e: io::Error, msg: &str) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", msg, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vfs::{MemoryFs, Op};
+
+    /// resources 目录为 1.0 版本、更新文件为 2.0 版本的内存文件系统
+    fn installed() -> MemoryFs {
+        let vfs = MemoryFs::new();
+        vfs.write(Path::new("/app/resources/app.asar"), "1.0")
+            .unwrap();
+        vfs.write(Path::new("/app/resources/locales/en.pak"), "en")
+            .unwrap();
+        vfs.write(Path::new("/update/app.asar"), "2.0").unwrap();
+        vfs.write(Path::new("/update/new.dll"), "new").unwrap();
+        vfs
+    }
+
+    fn stage(vfs: &MemoryFs, logger: &Logger) -> io::Result<(PathBuf, PathBuf)> {
+        let no_hooks = |_: &Path| Ok(());
+        stage_output(
+            vfs,
+            Path::new("/app/resources"),
+            Path::new("/update"),
+            &[],
+            &[],
+            &[],
+            false,
+            &no_hooks,
+            logger,
+        )
+    }
+
+    fn read(vfs: &MemoryFs, path: &str) -> Option<String> {
+        vfs.read(Path::new(path))
+            .map(|bytes| String::from_utf8(bytes).unwrap())
+    }
+
+    #[test]
+    fn stage_swap_and_rollback() {
+        let (vfs, logger) = (installed(), Logger::new(None).unwrap());
+        let (output_new, output_old) = stage(&vfs, &logger).unwrap();
+        assert_eq!(output_new, Path::new("/app/resources_new"));
+        assert_eq!(read(&vfs, "/app/resources_new/app.asar").unwrap(), "2.0");
+        assert_eq!(
+            read(&vfs, "/app/resources_new/locales/en.pak").unwrap(),
+            "en"
+        );
+        assert_eq!(read(&vfs, "/app/resources_new/new.dll").unwrap(), "new");
+        assert_eq!(read(&vfs, "/app/resources/app.asar").unwrap(), "1.0");
+
+        let output = Path::new("/app/resources");
+        swap(
+            &vfs,
+            output,
+            &output_new,
+            &output_old,
+            Duration::ZERO,
+            &logger,
+        )
+        .unwrap();
+        assert!(!vfs.exists(&output_new));
+        assert_eq!(read(&vfs, "/app/resources/app.asar").unwrap(), "2.0");
+        assert_eq!(read(&vfs, "/app/resources_old/app.asar").unwrap(), "1.0");
+        assert!(vfs.exists(&output_old.join(BACKUP_MARKER)));
+
+        assert!(rollback_in(&vfs, output, &logger).unwrap());
+        assert_eq!(read(&vfs, "/app/resources/app.asar").unwrap(), "1.0");
+        assert!(!vfs.exists(&output.join("new.dll")));
+        assert!(!vfs.exists(&output.join(BACKUP_MARKER)));
+        for leftover in ["/app/resources_old", "/app/resources_rejected"] {
+            assert!(!vfs.exists(Path::new(leftover)), "{}", leftover);
+        }
+    }
+
+    #[test]
+    fn failed_stage_leaves_output_untouched() {
+        let (vfs, logger) = (installed(), Logger::new(None).unwrap());
+        vfs.fail_on(Op::Copy, 2);
+        assert!(stage(&vfs, &logger).is_err());
+        assert!(!vfs.exists(Path::new("/app/resources_new")));
+        assert_eq!(vfs.files(Path::new("/app")).len(), 2);
+    }
+
+    #[test]
+    fn failed_hard_link_falls_back_to_copy() {
+        let (vfs, logger) = (installed(), Logger::new(None).unwrap());
+        vfs.fail_on(Op::HardLink, 1);
+        stage(&vfs, &logger).unwrap();
+        assert_eq!(
+            read(&vfs, "/app/resources_new/locales/en.pak").unwrap(),
+            "en"
+        );
+        assert_eq!(vfs.files(Path::new("/app/resources_new")).len(), 3);
+    }
+
+    #[test]
+    fn failed_swap_restores_output() {
+        let (vfs, logger) = (installed(), Logger::new(None).unwrap());
+        let (output_new, output_old) = stage(&vfs, &logger).unwrap();
+        // 第一次重命名 output → <output>_old 成功，第二次 <output>_new → output 失败
+        vfs.fail_on(Op::Rename, 2);
+        let output = Path::new("/app/resources");
+        assert!(
+            swap(
+                &vfs,
+                output,
+                &output_new,
+                &output_old,
+                Duration::ZERO,
+                &logger
+            )
+            .is_err()
+        );
+        assert_eq!(read(&vfs, "/app/resources/app.asar").unwrap(), "1.0");
+        assert!(!vfs.exists(&output_old));
+        assert!(vfs.exists(&output_new));
+
+        // 之后回滚只删除残留的 <output>_new
+        assert!(!rollback_in(&vfs, output, &logger).unwrap());
+        assert!(!vfs.exists(&output_new));
+        assert_eq!(read(&vfs, "/app/resources/app.asar").unwrap(), "1.0");
+    }
+
+    #[test]
+    fn failed_rollback_keeps_backup() {
+        let (vfs, logger) = (installed(), Logger::new(None).unwrap());
+        let (output_new, output_old) = stage(&vfs, &logger).unwrap();
+        let output = Path::new("/app/resources");
+        swap(
+            &vfs,
+            output,
+            &output_new,
+            &output_old,
+            Duration::ZERO,
+            &logger,
+        )
+        .unwrap();
+        vfs.fail_on(Op::Remove, 1);
+        vfs.write(Path::new("/app/resources_new/partial"), "x")
+            .unwrap();
+        assert!(rollback_in(&vfs, output, &logger).is_err());
+        assert_eq!(read(&vfs, "/app/resources_old/app.asar").unwrap(), "1.0");
+        assert_eq!(read(&vfs, "/app/resources/app.asar").unwrap(), "2.0");
+    }
+
+    #[test]
+    fn move_checks_entries_listed_before_moving() {
+        let (vfs, logger) = (installed(), Logger::new(None).unwrap());
+        vfs.write(Path::new("/update/locales/fr.pak"), "fr")
+            .unwrap();
+        let mut entries = Vec::new();
+        list_entries(
+            &vfs,
+            Path::new("/update"),
+            Path::new("/update"),
+            &[],
+            &mut entries,
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 4);
+
+        // 只移动了一部分时，即使 input 已不完整，检查仍按移动前的列表报告缺失
+        vfs.create_dir_all(Path::new("/moved")).unwrap();
+        vfs.rename(Path::new("/update/app.asar"), Path::new("/moved/app.asar"))
+            .unwrap();
+        let err = verify_entries(&vfs, &entries, Path::new("/moved"), &logger).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("1 of 3"), "{}", err);
+
+        vfs.write(Path::new("/update/app.asar"), "2.0").unwrap();
+        move_dir_recursive(&vfs, Path::new("/update"), Path::new("/out"), &[], &logger).unwrap();
+        assert_eq!(read(&vfs, "/out/locales/fr.pak").as_deref(), Some("fr"));
+        assert!(!vfs.exists(Path::new("/update/new.dll")));
+    }
+
+    #[test]
+    fn normalize_paths() {
+        for (path, expected) in [
+            ("/a/./b/../c", "/a/c"),
+            ("/a/b/../../..", "/"),
+            ("a/../../b", "../b"),
+            ("../a/..", ".."),
+            ("a//b/", "a/b"),
+        ] {
+            assert_eq!(normalize(Path::new(path)), Path::new(expected), "{}", path);
+        }
+    }
+
+    #[test]
+    fn link_target_stays_inside_root() {
+        let vfs = MemoryFs::new();
+        let root = Path::new("/r");
+        vfs.create_dir_all(Path::new("/r/a/deep")).unwrap();
+        for (link, target, expected) in [
+            ("/r/a/sibling", "../b/x", Some("../b/x")),
+            ("/r/a/deep/up", "../../c", Some("../../c")),
+            ("/r/a/abs", "/r/b", Some("../b")),
+            ("/r/self", ".", Some(".")),
+            ("/r/a/out", "../../etc/passwd", None),
+            ("/r/a/abs_out", "/etc", None),
+        ] {
+            vfs.symlink(Path::new(target), Path::new(link), false)
+                .unwrap();
+            let result = link_target(&vfs, root, Path::new(link));
+            assert_eq!(result.ok(), expected.map(PathBuf::from), "{}", link);
+        }
+    }
+
+    #[test]
+    fn link_dest_links_unchanged_files() {
+        let vfs = MemoryFs::new();
+        let backups = Path::new("/backups");
+        let previous = backups.join("resources_old-20240101-120000");
+        let archived = backups.join("resources_old-20240201-120000");
+        for (dir, changed) in [(&previous, "1.0"), (&archived, "2.0")] {
+            vfs.write(&dir.join(BACKUP_MARKER), changed).unwrap();
+            vfs.write(&dir.join("app.asar"), changed).unwrap();
+            vfs.write(&dir.join("locales/en.pak"), "en").unwrap();
+        }
+        vfs.write(&archived.join("new.dll"), "new").unwrap();
+        vfs.write(&backups.join("other_old-20240301-120000/app.asar"), "x")
+            .unwrap();
+        vfs.write(&backups.join("resources_old-20231201-120000/app.asar"), "x")
+            .unwrap();
+        vfs.write(
+            &backups
+                .join("resources_old-20231201-120000")
+                .join(BACKUP_MARKER),
+            "",
+        )
+        .unwrap();
+
+        assert_eq!(
+            previous_archive(&vfs, backups, OsStr::new("resources_old"), &archived),
+            Some(previous.clone())
+        );
+        let logger = Logger::new(None).unwrap();
+        assert_eq!(link_dest(&vfs, &archived, &previous, &logger).unwrap(), 1);
+        assert_eq!(vfs.read(&archived.join("app.asar")).unwrap(), b"2.0");
+        assert_eq!(vfs.read(&archived.join(BACKUP_MARKER)).unwrap(), b"2.0");
+        assert_eq!(vfs.read(&archived.join("locales/en.pak")).unwrap(), b"en");
+        assert_eq!(vfs.files(&archived).len(), 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn link_dest_shares_inodes_on_disk() {
+        use std::os::unix::fs::MetadataExt;
+        let root = std::env::temp_dir().join(format!("eqi-link-dest-{}", std::process::id()));
+        let (previous, archived) = (root.join("old-1"), root.join("old-2"));
+        for dir in [&previous, &archived] {
+            std::fs::create_dir_all(dir.join("sub")).unwrap();
+            std::fs::write(dir.join("sub/same.bin"), [7u8; 300_000]).unwrap();
+        }
+        std::fs::write(previous.join("diff.bin"), "a").unwrap();
+        std::fs::write(archived.join("diff.bin"), "b").unwrap();
+
+        let logger = Logger::new(None).unwrap();
+        let linked = link_dest(&RealFs::default(), &archived, &previous, &logger);
+        let ino = |path: PathBuf| std::fs::metadata(path).unwrap().ino();
+        let shared = ino(previous.join("sub/same.bin")) == ino(archived.join("sub/same.bin"));
+        let separate = ino(previous.join("diff.bin")) != ino(archived.join("diff.bin"));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(linked.unwrap(), 1);
+        assert!(shared && separate);
+    }
+}
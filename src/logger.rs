@@ -0,0 +1,165 @@
+use crate::cancel::CancelToken;
+use crate::progress::{Phase, ProgressObserver};
+use crate::sink::{ConsoleSink, FileSink, Level, LogSink, Record};
+use chrono::Local;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// 日志器：格式化日志并依次交给各个 [`LogSink`]，同时转发进度事件
+pub struct Logger {
+    sinks: Vec<Box<dyn LogSink>>,
+    /// 进度观察者
+    observer: Mutex<Option<Arc<dyn ProgressObserver>>>,
+    /// 进行中的阶段
+    phase: Mutex<Option<Phase>>,
+    /// 取消标记
+    cancel: Mutex<CancelToken>,
+}
+
+impl Logger {
+    /// 创建输出到终端和日志文件的日志器，未指定路径时写入当前 exe 同级目录下的 updater.log。
+    /// 明确指定的 log_path 无法打开时返回错误；默认位置无法写入（如只读的安装目录）时只输出到终端，并记录一条警告
+    pub fn new(log_path: Option<&str>) -> io::Result<Self> {
+        let file = Self::file_path(log_path)
+            .and_then(|path| FileSink::open(&path).map_err(|e| annotate_path(e, &path)));
+        match file {
+            Ok(file) => Ok(Self::with_sinks(vec![
+                Box::new(ConsoleSink),
+                Box::new(file),
+            ])),
+            Err(e) if log_path.is_none() => {
+                let logger = Self::with_sinks(vec![Box::new(ConsoleSink)]);
+                logger.warn(&format!(
+                    "Failed to open log file {}, logging to console only",
+                    e
+                ));
+                Ok(logger)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 日志文件路径：log_path 未指定时为当前 exe 同级目录下的 updater.log
+    pub fn file_path(log_path: Option<&str>) -> io::Result<PathBuf> {
+        if let Some(path) = log_path {
+            return Ok(PathBuf::from(path));
+        }
+        let exe = std::env::current_exe()?;
+        Ok(exe
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("updater.log"))
+    }
+
+    /// 使用指定的输出目标创建日志器，便于接入嵌入程序已有的日志框架
+    pub fn with_sinks(sinks: Vec<Box<dyn LogSink>>) -> Self {
+        Self {
+            sinks,
+            observer: Mutex::new(None),
+            phase: Mutex::new(None),
+            cancel: Mutex::new(CancelToken::new()),
+        }
+    }
+
+    /// 追加一个输出目标
+    pub fn with_sink(mut self, sink: impl LogSink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// 普通信息/进度
+    pub fn log(&self, msg: &str) {
+        self.write(Level::Info, msg);
+    }
+
+    /// 警告
+    pub fn warn(&self, msg: &str) {
+        self.write(Level::Warn, msg);
+    }
+
+    /// 错误
+    pub fn error(&self, msg: &str) {
+        self.write(Level::Error, msg);
+    }
+
+    fn write(&self, level: Level, msg: &str) {
+        // 终端上正在展示进度时，交给输出到终端的 sink 协调
+        let observer = self
+            .observer()
+            .filter(|o| o.draws_on_console() && self.phase.lock().unwrap().is_some());
+        let record = Record {
+            time: Local::now(),
+            level,
+            message: msg,
+            console: observer.as_deref(),
+        };
+        for sink in &self.sinks {
+            sink.write(&record);
+        }
+    }
+}
+
+impl Logger {
+    /// 设置进度观察者
+    pub fn set_observer(&self, observer: Arc<dyn ProgressObserver>) {
+        *self.observer.lock().unwrap() = Some(observer);
+    }
+
+    /// 设置取消标记
+    pub fn set_cancel_token(&self, token: CancelToken) {
+        *self.cancel.lock().unwrap() = token;
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel.lock().unwrap().is_cancelled()
+    }
+
+    /// 安全点：已取消时返回 `Interrupted` 错误
+    pub(crate) fn check_cancelled(&self) -> io::Result<()> {
+        self.cancel.lock().unwrap().check()
+    }
+
+    fn observer(&self) -> Option<Arc<dyn ProgressObserver>> {
+        self.observer.lock().unwrap().clone()
+    }
+
+    /// 开始一个阶段
+    pub fn progress_start(&self, phase: Phase, total: Option<u64>) {
+        *self.phase.lock().unwrap() = Some(phase);
+        if let Some(o) = self.observer() {
+            o.phase_started(phase, total);
+        }
+    }
+
+    pub fn progress_inc(&self, delta: u64) {
+        if let Some(o) = self.observer() {
+            o.advanced(delta);
+        }
+    }
+
+    pub fn copying(&self, path: &Path, copied: u64, total: u64) {
+        if let Some(o) = self.observer() {
+            o.copying(path, copied, total);
+        }
+    }
+
+    pub fn file_copied(&self, path: &Path, bytes: u64) {
+        if let Some(o) = self.observer() {
+            o.file_copied(path, bytes);
+        }
+    }
+
+    /// 结束当前阶段
+    pub fn progress_finish(&self) {
+        let phase = self.phase.lock().unwrap().take();
+        if let (Some(phase), Some(o)) = (phase, self.observer()) {
+            o.phase_finished(phase);
+        }
+    }
+}
+
+/// 在错误信息中附上日志文件路径，保留原错误类型
+fn annotate_path(e: io::Error, path: &Path) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,117 @@
+use crate::Logger;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 移除目录下所有文件的 com.apple.quarantine 扩展属性，
+/// 避免从下载的压缩包中解出的文件在更新后触发 Gatekeeper 提示或无法启动
+#[cfg(target_os = "macos")]
+pub fn strip_quarantine(dir: &Path, logger: &Logger) {
+    match std::process::Command::new("xattr")
+        .args(["-r", "-d", "com.apple.quarantine"])
+        .arg(dir)
+        .output()
+    {
+        Ok(out) if out.status.success() => logger.log(&format!(
+            "Removed quarantine attributes from {}",
+            dir.display()
+        )),
+        Ok(out) => logger.warn(&format!(
+            "Failed to remove quarantine attributes: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        )),
+        Err(e) => logger.warn(&format!("Failed to run xattr: {}", e)),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn strip_quarantine(_dir: &Path, _logger: &Logger) {}
+
+/// 应用所在位置的状态
+#[derive(Debug, PartialEq, Eq)]
+pub enum Location {
+    /// 可以原地更新
+    Writable,
+    /// 被 Gatekeeper 转移（App Translocation）到随机的只读路径运行
+    Translocated,
+    /// 位于只读位置（例如挂载的 DMG）
+    ReadOnly,
+}
+
+/// 检查输出目录所在位置：转移后的路径中带有 AppTranslocation，
+/// 只读位置通过在输出目录旁尝试创建文件判断
+pub fn check_location(output: &Path) -> Location {
+    if output.to_string_lossy().contains("/AppTranslocation/") {
+        return Location::Translocated;
+    }
+    let parent = output.parent().unwrap_or_else(|| Path::new("."));
+    let probe = parent.join(format!(".updater-probe-{}", std::process::id()));
+    match std::fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Location::Writable
+        }
+        Err(_) => Location::ReadOnly,
+    }
+}
+
+/// 将 app 所在的 .app 包复制到 /Applications，返回复制后对应的 app 与输出目录路径
+#[cfg(target_os = "macos")]
+pub fn move_to_applications(
+    app: &Path,
+    output: &Path,
+    logger: &Logger,
+) -> io::Result<(PathBuf, PathBuf)> {
+    let bundle = app
+        .ancestors()
+        .find(|p| p.extension().is_some_and(|e| e == "app"))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not inside an .app bundle", app.display()),
+            )
+        })?;
+    let dest = Path::new("/Applications").join(bundle.file_name().unwrap_or_default());
+    let relocate = |p: &Path| {
+        p.strip_prefix(bundle)
+            .map(|rel| dest.join(rel))
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not inside {}", p.display(), bundle.display()),
+                )
+            })
+    };
+    let (new_app, new_output) = (relocate(app)?, relocate(output)?);
+
+    if dest.exists() {
+        logger.log(&format!("Using existing {}", dest.display()));
+    } else {
+        logger.log(&format!(
+            "Copying {} to {}",
+            bundle.display(),
+            dest.display()
+        ));
+        // ditto 保留符号链接、权限与签名所需的扩展属性
+        let status = std::process::Command::new("ditto")
+            .arg(bundle)
+            .arg(&dest)
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("ditto failed with {}", status)));
+        }
+        strip_quarantine(&dest, logger);
+    }
+    Ok((new_app, new_output))
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn move_to_applications(
+    _app: &Path,
+    _output: &Path,
+    _logger: &Logger,
+) -> io::Result<(PathBuf, PathBuf)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--move-to-applications is only available on macOS",
+    ))
+}
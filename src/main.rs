@@ -1,28 +1,90 @@
-use chrono::Local;
-use clap::Parser;
-use std::fs::{self, File, OpenOptions};
-use std::io::{self, Write};
+use clap::{CommandFactory, Parser, Subcommand};
+use electron_quit_and_install::audit::{self, AuditSink};
+use electron_quit_and_install::batch;
+use electron_quit_and_install::bench;
+use electron_quit_and_install::control::Control;
+use electron_quit_and_install::diff;
+use electron_quit_and_install::download::{self, Backend, DownloadOptions};
+use electron_quit_and_install::elevate;
+use electron_quit_and_install::hooks::{self, CommandHook, OnFailure};
+use electron_quit_and_install::ipc::Ipc;
+use electron_quit_and_install::launchd;
+use electron_quit_and_install::notify::Notification;
+use electron_quit_and_install::pipeline::Step;
+#[cfg(feature = "wasm")]
+use electron_quit_and_install::plugin::WasmPlugin;
+use electron_quit_and_install::policy::Policy;
+use electron_quit_and_install::privilege::{self, Handoff};
+use electron_quit_and_install::s3::S3Config;
+use electron_quit_and_install::schedule::Schedule;
+use electron_quit_and_install::service;
+#[cfg(windows)]
+use electron_quit_and_install::sink;
+use electron_quit_and_install::sink::{ConsoleSink, FileSink, LogSink, Record};
+use electron_quit_and_install::splash::Splash;
+use electron_quit_and_install::systemd;
+use electron_quit_and_install::template;
+use electron_quit_and_install::tray::Tray;
+use electron_quit_and_install::vfs::{CopyEngine, Granularity};
+use electron_quit_and_install::walk;
+use electron_quit_and_install::watch;
+use electron_quit_and_install::{
+    Backup, CancelToken, Hook, Layout, LifecycleHook, Logger, Outcome, Phase, ProgressObserver,
+    Provider, UpdateError, UpdateReport, Updater, UpdaterBuilder, pin,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Deserialize;
+use serde_json::json;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, IsTerminal};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
 use std::time::Duration;
-use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, Signal, System};
 
 /// 命令行参数解析
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(
+    author,
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    /// 要杀掉的进程名 (例如: yourApp.exe,otherApp.exe)
-    #[arg(long)]
-    ps: String,
+    #[command(subcommand)]
+    command: Option<Commands>,
 
-    /// 更新输入目录 (更新文件所在目录)
-    #[arg(long)]
-    input: String,
+    /// 由父进程（Electron 应用）通过标准输入输出上的 JSON 协议控制更新，参数通过 configure 命令传入
+    #[arg(long, exclusive = true)]
+    ipc_stdio: bool,
 
-    /// 输出目录 (一般为 app 的 resources 目录)
-    #[arg(long)]
+    /// 更新输入目录 (更新文件所在目录)，或 electron-builder 的 latest.yml 路径/URL；
+    /// 可重复指定，之后的每一项（目录、zip 包路径或 URL）依次叠加，覆盖之前的同名文件
+    #[arg(long, required = true)]
+    input: Vec<String>,
+
+    #[command(flatten)]
+    target: Option<TargetArgs>,
+
+    #[command(flatten)]
+    download: DownloadArgs,
+}
+
+/// 更新目标相关参数
+#[derive(clap::Args, Clone, Debug)]
+struct TargetArgs {
+    /// 要杀掉的进程名 (例如: yourApp.exe,otherApp.exe)，可重复指定，重复指定时每个值可以包含逗号
+    #[arg(long, required = true)]
+    ps: Vec<OsString>,
+
+    /// 输出目录 (一般为 app 的 resources 目录)；省略时由 --app 的位置推断
+    #[arg(long = "output", value_name = "OUTPUT")]
+    output_arg: Option<String>,
+
+    /// 展开模板变量或推断后的输出目录
+    #[arg(skip)]
     output: String,
 
     /// Electron 应用主程序路径
@@ -33,302 +95,1787 @@ struct Args {
     #[arg(long)]
     log: Option<String>,
 
-    /// 要忽略复制的文件/目录（以逗号分隔，路径相对于 input）
+    /// 要忽略复制的文件/目录（以逗号分隔，路径相对于 input），可重复指定
     #[arg(long)]
-    ignore: Option<String>,
-}
+    ignore: Vec<OsString>,
+
+    /// 复制旧 output 时跳过的文件/目录（以逗号分隔，路径相对于 output），如旧日志、缓存，可重复指定
+    #[arg(long)]
+    ignore_existing: Vec<OsString>,
+
+    /// 安装目录结构：resources（直接替换 --output 目录）、squirrel（--output 为安装根目录，按 app-<version> 安装）
+    /// 、appimage（--output 为 AppImage 文件，整体替换）或 deb/rpm（交给系统包管理器安装）
+    #[arg(long, value_enum, default_value_t = Layout::Resources)]
+    layout: Layout,
+
+    /// 更新后刷新开始菜单与桌面中指向 --app 的快捷方式（仅 Windows）
+    #[arg(long)]
+    refresh_shortcuts: bool,
+
+    /// 更新完成或失败后向当前用户显示系统通知（Windows 与 macOS）
+    #[arg(long)]
+    notify: bool,
+
+    /// 通知中的应用名称，默认为 --app 的文件名
+    #[arg(long, value_name = "NAME")]
+    notify_app_name: Option<String>,
+
+    /// 通知中显示的图标（图片文件，仅 Windows）
+    #[arg(long, value_name = "PATH")]
+    notify_icon: Option<PathBuf>,
+
+    /// 发送通知使用的 AppUserModelID（如 com.example.myapp），默认以 Windows PowerShell 的身份发送
+    #[arg(long, value_name = "ID")]
+    notify_app_id: Option<String>,
+
+    /// 从关闭应用到重新启动之间显示进度窗口（标题、状态文字与进度条），仅 Windows
+    #[arg(long)]
+    splash: bool,
+
+    /// 进度窗口的标题，默认为「Updating <--app 的文件名>」
+    #[arg(long, value_name = "TITLE")]
+    splash_title: Option<String>,
+
+    /// 更新期间在通知区域显示托盘图标（提示文字为进度），失败时显示错误图标并可查看日志，仅 Windows、macOS
+    #[arg(long)]
+    tray: bool,
+
+    /// 托盘图标提示文字中的名称，默认为 --app 的文件名
+    #[arg(long, value_name = "NAME")]
+    tray_title: Option<String>,
+
+    /// 更新后写入「程序和功能」中的版本信息：Uninstall 注册表项名称（例如 {GUID} 或 yourApp），仅 Windows
+    #[arg(long)]
+    uninstall_key: Option<String>,
+
+    /// macOS 上应用被转移运行（App Translocation）或位于只读位置（如 DMG）时，先复制到 /Applications 再更新
+    #[arg(long)]
+    move_to_applications: bool,
+
+    /// 应用为 Flatpak / Snap / Microsoft Store 安装时，交给对应的更新机制，而不是报错退出
+    #[arg(long)]
+    sandbox_handoff: bool,
+
+    /// 新版本号，squirrel 布局下用于命名 app-<version> 目录；更新源和 Release 会自带版本号
+    #[arg(long)]
+    app_version: Option<String>,
+
+    /// 模拟运行：在内存中执行暂存、替换和清理并输出日志，不退出应用、不修改任何文件（仅 resources 布局）
+    #[arg(long)]
+    simulate: bool,
+
+    /// 更新文件的版本不高于已安装版本、或 CPU 架构与已安装应用不一致时仍然更新
+    #[arg(long)]
+    force: bool,
+
+    /// 更新文件中主程序的签名者与已安装主程序不同时仍然更新（退出码 13）
+    #[arg(long)]
+    allow_publisher_change: bool,
+
+    /// 更新说明文件，优先于更新源中的 releaseNotes，写入更新历史
+    #[arg(long)]
+    release_notes: Option<PathBuf>,
+
+    /// 正在安装的版本：替换后核对已安装应用的版本号，不一致时回滚（退出码 11）
+    #[arg(long)]
+    expected_version: Option<String>,
+
+    /// 文件被占用等原因无法替换时，把本次命令写入 RunOnce，在下次登录时自动重新更新（退出码 12），仅 Windows
+    #[arg(long)]
+    retry_at_logon: bool,
+
+    /// 没有写入 output 的权限时不自动以管理员身份重新启动（默认弹出 UAC 确认），仅 Windows
+    #[arg(long)]
+    no_elevate: bool,
+
+    /// 审计模式：日志每条记录附带与上一条记录串联的哈希，更新结束后把最新摘要写入 <日志>.digest
+    #[arg(long)]
+    audit: bool,
 
-/// 日志器结构体
-struct Logger {
-    file: Option<Arc<Mutex<File>>>,
+    /// 清理 input 与旧版本备份时先以零覆盖再删除的文件（以逗号分隔，支持 * 和 ? 通配），如 *.lic,config/secrets.json
+    #[arg(long, value_name = "PATTERNS")]
+    secure_delete: Option<String>,
+
+    /// 清理时仍然删除未通过安全检查的 input 或旧版本备份（如层级过浅、位于用户主目录下一级、不是更新器创建的备份）
+    #[arg(long)]
+    confirm_delete: bool,
+
+    /// 更新成功后保留 input（更新文件或解包后的更新包），不在清理时删除
+    #[arg(long)]
+    keep_input: bool,
+
+    /// 更新成功后旧版本备份（<output>_old）的处理方式：delete（删除）、keep（保留到下一次更新）或 archive（移动到 --backup-dir）
+    #[arg(long, value_enum, default_value_t = Backup::Delete)]
+    backup: Backup,
+
+    /// --backup archive 时存放备份的目录
+    #[arg(long)]
+    backup_dir: Option<PathBuf>,
+
+    /// 保留的备份超过此天数后，在之后运行更新器时删除
+    #[arg(long, value_name = "DAYS")]
+    backup_days: Option<u32>,
+
+    /// 更新流程的步骤与顺序（以逗号分隔），默认 kill,stage,swap,verify,cleanup,restart；可调整顺序、省略步骤或重复 verify
+    #[arg(long, value_name = "STEPS", value_delimiter = ',')]
+    pipeline: Option<Vec<Step>>,
+
+    /// 暂存时复制文件的方式：std（逐个复制）、readahead（预读后续文件）或 uring（io_uring 批量读写），后两者仅 Linux
+    #[arg(long, value_enum, default_value_t = CopyEngine::Std)]
+    copy_engine: CopyEngine,
+
+    /// 以 std 方式暂存时并行复制文件的线程数，默认 1 表示依次复制
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    copy_jobs: usize,
+
+    /// 并行复制时分配给各线程的单位：file（相邻的小文件成批分配，适合本地 NVMe/SSD）或 dir（按目录分配，适合网络共享）
+    #[arg(long, value_enum, default_value_t = Granularity::File)]
+    copy_granularity: Granularity,
+
+    /// 并行计算文件摘要的线程数，默认 0 表示使用全部核心
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    hash_jobs: usize,
+
+    /// zip 更新包在暂存时直接解压到 <output>_new，不先解压到缓存目录
+    #[arg(long)]
+    stream_extract: bool,
+
+    /// 暂存时把更新文件从 input 移动到 <output>_new 而不是复制，不同卷时自动回退为复制
+    #[arg(long = "move")]
+    move_input: bool,
+
+    /// 替换时重命名失败（如杀毒软件正在扫描目录）后的重试时长（秒），0 表示不重试
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    rename_retry: u64,
+
+    /// 遍历目录时文件列表（暂存、计算摘要）可占用的内存上限（MB），超过时分批处理
+    #[arg(long, value_name = "MB", default_value_t = 64)]
+    memory_limit: usize,
+
+    /// 允许以 root（Unix）或 SYSTEM（Windows）运行，默认拒绝（退出码 2）
+    #[arg(long)]
+    allow_privileged: bool,
+
+    /// 由自动提权启动的进程，避免再次提权
+    #[arg(long, hide = true)]
+    elevated: bool,
+
+    /// 替换完成后仍以管理员身份重新启动应用与执行 post-restart 钩子（默认先降权）
+    #[arg(long)]
+    keep_privileges: bool,
+
+    /// 自动提权的进程把重新启动交接给原进程时使用的交接文件
+    #[arg(long, hide = true)]
+    restart_handoff: Option<PathBuf>,
+
+    /// 已安装版本低于此版本时中止更新（退出码 9）
+    #[arg(long)]
+    min_installed_version: Option<String>,
+
+    /// 退出应用前执行的命令，失败时中止更新，可重复指定
+    #[arg(long, value_name = "COMMAND", visible_alias = "pre-hook")]
+    hook_pre_kill: Vec<String>,
+
+    /// 新版本暂存完成后执行的命令（EQI_STAGING 为暂存位置），失败时中止并回滚，可重复指定
+    #[arg(long, value_name = "COMMAND")]
+    hook_post_stage: Vec<String>,
+
+    /// 替换前执行的命令，失败时中止并回滚，可重复指定
+    #[arg(long, value_name = "COMMAND")]
+    hook_pre_swap: Vec<String>,
+
+    /// 替换后、重新启动应用前执行的命令（如迁移数据库），失败时恢复旧版本并重新启动，可重复指定
+    #[arg(long, value_name = "COMMAND", visible_alias = "post-hook")]
+    hook_post_swap: Vec<String>,
+
+    /// 重新启动应用后执行的命令，失败只记录警告，可重复指定
+    #[arg(long, value_name = "COMMAND")]
+    hook_post_restart: Vec<String>,
+
+    /// WASM 插件（实现 wit/plugin.wit 的组件），在每个钩子位置于沙箱中执行，可重复指定；需要 wasm 特性
+    #[arg(long, value_name = "PATH")]
+    plugin: Vec<PathBuf>,
+
+    /// 钩子命令的超时，如 post-swap=120（秒），超时视为失败，可重复指定
+    #[arg(long, value_name = "HOOK=SECONDS", value_parser = parse_hook_timeout)]
+    hook_timeout: Vec<(Hook, Duration)>,
+
+    /// 钩子失败时的处理：abort、continue 或 rollback，如 post-stage=continue，可重复指定
+    #[arg(long, value_name = "HOOK=POLICY", value_parser = parse_hook_failure)]
+    hook_on_failure: Vec<(Hook, OnFailure)>,
+
+    /// 传给钩子命令的环境变量，如 DB_PATH=/var/lib/myapp/db，可重复指定
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_env)]
+    hook_env: Vec<(String, String)>,
+
+    /// 更新或回滚失败时执行的命令（如弹出错误对话框、通知值班），通过 EQI_FAILED_PHASE、EQI_ERROR 等环境变量获得失败信息，可重复指定
+    #[arg(long, value_name = "COMMAND")]
+    on_failure_hook: Vec<String>,
 }
 
-impl Logger {
-    fn new(log_path: Option<&str>) -> io::Result<Self> {
-        let file = if let Some(path) = log_path {
-            Some(Arc::new(Mutex::new(
-                OpenOptions::new().create(true).append(true).open(path)?,
-            )))
-        } else {
-            // 默认路径：当前 exe 同级目录 / updater.log
-            let exe = std::env::current_exe()?;
-            let default_path = exe
-                .parent()
-                .unwrap_or_else(|| Path::new("."))
-                .join("updater.log");
-            Some(Arc::new(Mutex::new(
-                OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(default_path)?,
-            )))
-        };
-        Ok(Self { file })
+impl TargetArgs {
+    /// 展开 text 中的模板变量：{output}、{output_old}、{app}、{version}（--app-version）、{temp}
+    fn expand(&self, text: &str) -> String {
+        template::expand(text, |name| match name {
+            "output" => Some(self.output.clone()),
+            "output_old" => Some(
+                template::output_old(Path::new(&self.output))
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            "app" => Some(self.app.clone()),
+            "version" => self.app_version.clone(),
+            _ => template::common_var(name),
+        })
     }
 
-    fn log(&self, msg: &str) {
-        let now = Local::now();
-        let line = format!("[{}] {}\n", now.format("%Y-%m-%d %H:%M:%S"), msg);
-        print!("{}", line);
+    /// 依次展开 --output、--app、--log、--release-notes 中的模板变量；
+    /// 未指定 --output 时由 --app 推断，推断失败时返回用法错误
+    fn expand_paths(&mut self) -> Result<(), clap::Error> {
+        match self.output_arg.clone() {
+            Some(output) => {
+                self.output = self.expand(&output);
+                self.app = self.expand(&self.app);
+            }
+            None => {
+                self.app = self.expand(&self.app);
+                let detected = electron_quit_and_install::detect_resources(Path::new(&self.app))
+                    .map_err(|e| {
+                        Args::command().error(
+                            clap::error::ErrorKind::MissingRequiredArgument,
+                            format!("--output is required: {}", e),
+                        )
+                    })?;
+                self.output = detected.to_string_lossy().into_owned();
+            }
+        }
+        self.log = self.log.as_deref().map(|log| self.expand(log));
+        self.release_notes = self
+            .release_notes
+            .as_deref()
+            .map(|path| PathBuf::from(self.expand(&path.to_string_lossy())));
+        self.backup_dir = self
+            .backup_dir
+            .as_deref()
+            .map(|path| PathBuf::from(self.expand(&path.to_string_lossy())));
+        self.notify_icon = self
+            .notify_icon
+            .as_deref()
+            .map(|path| PathBuf::from(self.expand(&path.to_string_lossy())));
+        Ok(())
+    }
 
-        if let Some(f) = &self.file {
-            let mut f = f.lock().unwrap();
-            let _ = f.write_all(line.as_bytes());
+    /// 命令行指定的钩子命令
+    fn hooks(&self) -> CommandHook {
+        let mut hooks = CommandHook::new();
+        for (hook, commands) in [
+            (Hook::PreKill, &self.hook_pre_kill),
+            (Hook::PostStage, &self.hook_post_stage),
+            (Hook::PreSwap, &self.hook_pre_swap),
+            (Hook::PostSwap, &self.hook_post_swap),
+            (Hook::PostRestart, &self.hook_post_restart),
+        ] {
+            for command in commands {
+                hooks = hooks.with(hook, command);
+            }
         }
+        for (hook, timeout) in &self.hook_timeout {
+            hooks = hooks.timeout(*hook, *timeout);
+        }
+        for (name, value) in &self.hook_env {
+            hooks = hooks.env(name, value);
+        }
+        hooks
     }
 }
 
-/// 杀掉多个指定进程名的所有实例（支持逗号分隔），并等待退出确认
-fn kill_processes_by_names(names: &str, logger: &Logger) {
-    let targets: Vec<String> = names
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
+/// 读取并编译 --plugin 指定的插件
+#[cfg(feature = "wasm")]
+fn load_plugins(paths: &[PathBuf]) -> io::Result<Vec<Box<dyn LifecycleHook>>> {
+    paths
+        .iter()
+        .map(|path| Ok(Box::new(WasmPlugin::load(path)?) as Box<dyn LifecycleHook>))
+        .collect()
+}
 
-    if targets.is_empty() {
-        logger.log("No process names provided, skipping kill step.");
-        return;
+#[cfg(not(feature = "wasm"))]
+fn load_plugins(paths: &[PathBuf]) -> io::Result<Vec<Box<dyn LifecycleHook>>> {
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--plugin requires a build with the wasm feature",
+    ))
+}
+
+/// 拆分 `<钩子位置>=<值>`
+fn split_hook(s: &str) -> Result<(Hook, &str), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected <hook>=<value>, got {}", s))?;
+    let hook = Hook::from_name(name.trim()).ok_or_else(|| {
+        format!(
+            "unknown hook {}, expected pre-kill, post-stage, pre-swap, post-swap or post-restart",
+            name
+        )
+    })?;
+    Ok((hook, value.trim()))
+}
+
+fn parse_hook_timeout(s: &str) -> Result<(Hook, Duration), String> {
+    let (hook, value) = split_hook(s)?;
+    let seconds = value
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite() && *v > 0.0)
+        .ok_or_else(|| format!("expected a positive number of seconds, got {}", value))?;
+    Ok((hook, Duration::from_secs_f64(seconds)))
+}
+
+fn parse_hook_failure(s: &str) -> Result<(Hook, OnFailure), String> {
+    let (hook, value) = split_hook(s)?;
+    let policy = <OnFailure as clap::ValueEnum>::from_str(value, true)?;
+    Ok((hook, policy))
+}
+
+/// 可重复指定的列表参数：只指定一次时按 `,` 拆分（兼容以逗号连接的写法），
+/// 重复指定时每个值原样作为一项，可以包含逗号。按原始字节处理，值不必是有效的 UTF-8
+fn split_list(values: &[OsString]) -> Vec<OsString> {
+    let items: Vec<&[u8]> = match values {
+        [single] => single.as_encoded_bytes().split(|b| *b == b',').collect(),
+        _ => values.iter().map(|v| v.as_encoded_bytes()).collect(),
+    };
+    items
+        .into_iter()
+        .map(<[u8]>::trim_ascii)
+        .filter(|item| !item.is_empty())
+        // SAFETY: 只在 ASCII 的逗号与空白处切分，各段仍是有效的编码字节
+        .map(|item| unsafe { OsStr::from_encoded_bytes_unchecked(item) }.to_owned())
+        .collect()
+}
+
+fn parse_env(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got {}", s)),
     }
+}
 
-    let mut sys = System::new_all();
-    sys.refresh_processes_specifics(
-        ProcessesToUpdate::All,
-        true,
-        ProcessRefreshKind::everything(),
-    );
+/// 下载相关参数（--input 为 URL 或使用远程提供方时生效）
+#[derive(clap::Args, Clone, Debug)]
+struct DownloadArgs {
+    /// 下载失败后的重试次数
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
 
-    // 先发送 Kill 信号
-    for (pid, process) in sys.processes() {
-        let pname = process.name().to_string_lossy().to_string();
-        if targets.iter().any(|t| pname.eq_ignore_ascii_case(t)) {
-            logger.log(&format!("Killing process {:?} (pid {})", pname, pid));
-            if process.kill_with(Signal::Kill).is_none() {
-                logger.log(&format!("Failed to send kill signal to {:?}", pname));
-            }
+    /// 单次请求超时（秒）
+    #[arg(long, default_value_t = 300)]
+    timeout: u64,
+
+    /// 更新渠道 (例如: stable, beta, alpha)，对应 electron-builder 的 latest.yml / beta.yml / alpha.yml
+    #[arg(long)]
+    channel: Option<String>,
+
+    /// 灰度发布百分比 (0-100)，覆盖更新源中的 stagingPercentage；按本机标识稳定分组
+    #[arg(long, value_parser = parse_percentage)]
+    rollout: Option<f64>,
+
+    /// 下载限速，单位字节/秒，支持 K/M/G 后缀 (例如: 500K, 2M)
+    #[arg(long, value_parser = download::parse_rate)]
+    max_download_rate: Option<u64>,
+
+    /// 下载方式：http（内置客户端）或 bits（仅 Windows，下载可跨重启/注销继续）
+    #[arg(long, value_enum, default_value_t = Backend::Http)]
+    download_backend: Backend,
+
+    /// 直接下载的更新包的 sha256 校验值（十六进制）
+    #[arg(long)]
+    sha256: Option<String>,
+
+    /// s3:// 地址使用的服务地址（例如阿里云 OSS: https://oss-cn-hangzhou.aliyuncs.com），默认读取 AWS_ENDPOINT_URL
+    #[arg(long)]
+    s3_endpoint: Option<String>,
+
+    /// s3:// 地址使用的区域，默认读取 AWS_REGION，否则为 us-east-1
+    #[arg(long)]
+    s3_region: Option<String>,
+
+    /// s3:// 地址使用 path-style 访问（MinIO 等自建服务）
+    #[arg(long)]
+    s3_path_style: bool,
+
+    /// 下载使用的代理 (例如: http://proxy:8080, socks5://proxy:1080)，默认读取 HTTPS_PROXY/HTTP_PROXY/ALL_PROXY
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// 不使用代理的主机（以逗号分隔），"*" 表示完全不使用代理，默认读取 NO_PROXY
+    #[arg(long)]
+    no_proxy: Option<String>,
+
+    /// 固定更新服务器证书：证书公钥（SPKI）或证书本身的 base64 sha256，可重复指定
+    #[arg(long)]
+    pin_sha256: Vec<String>,
+}
+
+fn parse_percentage(s: &str) -> Result<f64, String> {
+    match s.trim_end_matches('%').parse::<f64>() {
+        Ok(v) if (0.0..=100.0).contains(&v) => Ok(v),
+        _ => Err(format!(
+            "expected a percentage between 0 and 100, got {}",
+            s
+        )),
+    }
+}
+
+impl DownloadArgs {
+    fn options(&self) -> io::Result<DownloadOptions> {
+        let pins = self
+            .pin_sha256
+            .iter()
+            .map(|p| pin::parse_pin(p))
+            .collect::<io::Result<Vec<_>>>()?;
+        if self.download_backend == Backend::Bits && !cfg!(windows) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "--download-backend bits is only available on Windows",
+            ));
+        }
+        if self.download_backend == Backend::Bits && !pins.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--pin-sha256 is not supported with --download-backend bits",
+            ));
         }
+        Ok(DownloadOptions {
+            retries: self.retries,
+            timeout: Duration::from_secs(self.timeout),
+            s3: S3Config {
+                endpoint: self
+                    .s3_endpoint
+                    .clone()
+                    .or_else(|| std::env::var("AWS_ENDPOINT_URL").ok()),
+                region: self
+                    .s3_region
+                    .clone()
+                    .or_else(|| std::env::var("AWS_REGION").ok())
+                    .unwrap_or_else(|| "us-east-1".to_string()),
+                path_style: self.s3_path_style,
+            },
+            proxy: self.proxy.clone(),
+            no_proxy: self
+                .no_proxy
+                .clone()
+                .or_else(|| std::env::var("NO_PROXY").ok())
+                .or_else(|| std::env::var("no_proxy").ok())
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            pins,
+            max_rate: self.max_download_rate,
+            backend: self.download_backend,
+        })
     }
+}
 
-    // 再等待确认退出
-    const MAX_WAIT_MS: u64 = 5000; // 最多等待 5 秒
-    const CHECK_INTERVAL_MS: u64 = 500;
+/// 监视模式参数
+#[derive(clap::Args, Debug)]
+struct WatchArgs {
+    /// 监视的输入目录，文件同步工具把更新文件放入此目录
+    #[arg(long)]
+    input: PathBuf,
 
-    let mut elapsed = 0;
-    loop {
-        thread::sleep(Duration::from_millis(CHECK_INTERVAL_MS));
-        elapsed += CHECK_INTERVAL_MS;
+    /// 更新文件全部写入后放置的标记文件名，不会被复制到 output
+    #[arg(long, default_value = watch::DEFAULT_SENTINEL)]
+    sentinel: String,
+
+    /// 控制接口：Unix 套接字路径或 Windows 命名管道名（如 \\.\pipe\eqi），接收 status、apply、cancel、defer 命令
+    #[arg(long)]
+    control: Option<PathBuf>,
+
+    /// 维护窗口：更新文件就绪后等到窗口内才更新，如 02:30、01:00-05:00 或 cron 表达式 "* 1-4 * * 1-5"
+    #[arg(long)]
+    apply_at: Option<Schedule>,
+
+    #[command(flatten)]
+    target: TargetArgs,
+
+    #[command(flatten)]
+    download: DownloadArgs,
+}
+
+impl WatchArgs {
+    /// 展开 --input 与更新目标参数中的模板变量
+    fn expand_paths(&mut self) -> Result<(), clap::Error> {
+        self.target.expand_paths()?;
+        self.input = PathBuf::from(self.target.expand(&self.input.to_string_lossy()));
+        Ok(())
+    }
+}
+
+/// Windows 服务操作
+#[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)]
+enum ServiceAction {
+    /// 注册为开机自动启动的服务，`--` 之后为 watch 子命令的参数
+    Install {
+        /// 服务名称，同时作为事件日志来源
+        #[arg(long, default_value = DEFAULT_SERVICE_NAME)]
+        name: String,
+
+        /// 服务显示名称，默认同服务名称
+        #[arg(long)]
+        display_name: Option<String>,
+
+        /// watch 子命令的参数
+        #[arg(last = true, required = true)]
+        args: Vec<String>,
+    },
+    /// 停止并删除服务
+    Uninstall {
+        /// 服务名称
+        #[arg(long, default_value = DEFAULT_SERVICE_NAME)]
+        name: String,
+    },
+    /// 作为服务运行监视模式（由服务控制管理器调用）
+    Run {
+        /// 服务名称
+        #[arg(long, default_value = DEFAULT_SERVICE_NAME)]
+        name: String,
+
+        #[command(flatten)]
+        watch: WatchArgs,
+    },
+}
+
+/// 默认的服务名称
+const DEFAULT_SERVICE_NAME: &str = "ElectronQuitAndInstall";
+
+/// 子命令
+#[derive(Subcommand, Debug)]
+// 只在启动时解析一次，不必为节省内存装箱
+#[allow(clippy::large_enum_variant)]
+enum Commands {
+    /// 从远程提供方查询最新版本，下载校验后应用更新
+    CheckRemote {
+        /// 更新提供方
+        #[arg(long, value_enum)]
+        provider: Provider,
+
+        /// 仓库 (例如: owner/name)
+        #[arg(long)]
+        repo: String,
+
+        #[command(flatten)]
+        target: TargetArgs,
+
+        #[command(flatten)]
+        download: DownloadArgs,
+    },
+    /// 常驻监视输入目录，更新文件就绪（出现标记文件）后自动执行更新，完成后继续监视
+    Watch(WatchArgs),
+    /// 以 Windows 服务运行监视模式，没有用户登录时也能更新
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+    /// 生成并加载 macOS launchd 任务，常驻运行监视模式或定时执行更新
+    LaunchdInstall {
+        /// 任务标签（如 com.example.myapp.updater）
+        #[arg(long)]
+        label: String,
+
+        /// 系统级任务（LaunchDaemon，以 root 运行），默认为当前用户的 LaunchAgent
+        #[arg(long)]
+        system: bool,
+
+        /// 每隔多少秒运行一次（定时更新）；不指定时常驻运行，退出后自动重新启动
+        #[arg(long)]
+        interval: Option<u64>,
+
+        /// 只写入 plist，不加载
+        #[arg(long)]
+        no_load: bool,
+
+        /// 更新器参数，如 `watch --input ...` 或 `check-remote ...`
+        #[arg(last = true, required = true)]
+        args: Vec<String>,
+    },
+    /// 卸载 launchd 任务并删除 plist
+    LaunchdUninstall {
+        /// 任务标签
+        #[arg(long)]
+        label: String,
+
+        /// 系统级任务
+        #[arg(long)]
+        system: bool,
+    },
+    /// 生成并启用 systemd 单元，常驻运行监视模式或定时执行更新
+    SystemdInstall {
+        /// 单元名称（如 myapp-updater）
+        #[arg(long)]
+        name: String,
+
+        /// 系统级单元（以 root 运行），默认为当前用户的单元
+        #[arg(long)]
+        system: bool,
+
+        /// 每隔多少秒运行一次（定时更新，另外生成 timer）；不指定时常驻运行，失败退出后自动重新启动
+        #[arg(long)]
+        interval: Option<u64>,
+
+        /// 只写入单元文件，不启用
+        #[arg(long)]
+        no_enable: bool,
+
+        /// 更新器参数，如 `watch --input ...` 或 `check-remote ...`
+        #[arg(last = true, required = true)]
+        args: Vec<String>,
+    },
+    /// 停用 systemd 单元并删除单元文件
+    SystemdUninstall {
+        /// 单元名称
+        #[arg(long)]
+        name: String,
+
+        /// 系统级单元
+        #[arg(long)]
+        system: bool,
+    },
+    /// 批量更新多个目标：全部暂存成功后才依次替换，任一失败时全部回滚
+    Batch {
+        /// 批量任务文件（YAML 或 JSON），jobs 中每项包含 ps、input、output、app、ignore、sha256
+        #[arg(long)]
+        file: PathBuf,
+
+        /// 日志文件路径，默认为当前 exe 同级目录下的 updater.log
+        #[arg(long)]
+        log: Option<String>,
 
-        sys.refresh_processes_specifics(
-            ProcessesToUpdate::All,
-            true,
-            ProcessRefreshKind::everything(),
+        /// 允许以 root（Unix）或 SYSTEM（Windows）运行
+        #[arg(long)]
+        allow_privileged: bool,
+
+        #[command(flatten)]
+        download: DownloadArgs,
+    },
+    /// 检查 --audit 写入的日志哈希链与摘要文件，日志被修改时以非 0 退出
+    VerifyLog {
+        /// 日志文件路径，默认为当前 exe 同级目录下的 updater.log
+        #[arg(long)]
+        log: Option<String>,
+    },
+    /// 在目标卷上测量复制、摘要计算与解压的吞吐量，输出推荐的 --copy-engine、--hash-jobs 与 --stream-extract
+    Bench {
+        /// 测试文件的位置，应与 --output 位于同一个卷；默认为临时目录
+        #[arg(long)]
+        dir: Option<PathBuf>,
+
+        /// 小文件（32 KB）数量
+        #[arg(long, default_value_t = 2000)]
+        small_files: usize,
+
+        /// 大文件（64 MB）数量
+        #[arg(long, default_value_t = 2)]
+        large_files: usize,
+
+        /// 以 JSON 输出结果
+        #[arg(long)]
+        json: bool,
+    },
+    /// 列出以 --input 更新 --output 时会新增、替换、删除与忽略的文件，不做任何改动
+    Diff {
+        /// 更新文件：本地目录、更新包或 URL；重复指定时后面的依次叠加
+        #[arg(long, required = true)]
+        input: Vec<String>,
+
+        /// 已安装的目录或文件
+        #[arg(long)]
+        output: PathBuf,
+
+        /// 要忽略复制的文件/目录（以逗号分隔，路径相对于 input），可重复指定
+        #[arg(long)]
+        ignore: Vec<OsString>,
+
+        /// 复制旧 output 时跳过的文件/目录（以逗号分隔，路径相对于 output），可重复指定
+        #[arg(long)]
+        ignore_existing: Vec<OsString>,
+
+        /// 比较文件内容的线程数，默认 0 表示使用全部核心
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        hash_jobs: usize,
+
+        /// 以 JSON 输出结果
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        download: DownloadArgs,
+    },
+}
+
+/// 终端进度条（stdout 为 TTY 时启用）
+#[derive(Default)]
+struct TtyProgress {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl ProgressObserver for TtyProgress {
+    fn phase_started(&self, phase: Phase, total: Option<u64>) {
+        // 没有总量的阶段很短，不单独展示
+        let Some(total) = total else {
+            return;
+        };
+        let template = match phase {
+            Phase::Download => {
+                "{msg:<24} [{bar:40}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})"
+            }
+            _ => "{msg:<24} [{bar:40}] {pos}/{len} ({elapsed})",
+        };
+        let pb = ProgressBar::new(total);
+        pb.set_style(
+            ProgressStyle::with_template(template)
+                .unwrap()
+                .progress_chars("=> "),
         );
+        pb.set_message(phase.label());
+        *self.bar.lock().unwrap() = Some(pb);
+    }
 
-        let alive: Vec<_> = sys
-            .processes()
-            .values()
-            .filter(|p| {
-                let pname = p.name().to_string_lossy();
-                targets.iter().any(|t| pname.eq_ignore_ascii_case(t))
-            })
-            .map(|p| p.name().to_string_lossy().to_string())
-            .collect();
+    fn advanced(&self, delta: u64) {
+        if let Some(pb) = self.bar.lock().unwrap().as_ref() {
+            pb.inc(delta);
+        }
+    }
 
-        if alive.is_empty() {
-            logger.log("All target processes have exited.");
-            break;
-        } else {
-            logger.log(&format!("Waiting for processes to exit: {:?}", alive));
+    fn phase_finished(&self, _phase: Phase) {
+        if let Some(pb) = self.bar.lock().unwrap().take() {
+            pb.finish();
         }
+    }
 
-        if elapsed >= MAX_WAIT_MS {
-            logger.log("Timeout waiting for processes to exit, continue anyway.");
-            break;
+    fn draws_on_console(&self) -> bool {
+        self.bar.lock().unwrap().is_some()
+    }
+
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        match self.bar.lock().unwrap().as_ref() {
+            Some(pb) => pb.suspend(f),
+            None => f(),
         }
     }
 }
 
-/// 复制文件（保留目录结构），同名文件覆盖，不清空目标目录
-fn copy_dir_recursive(
-    input: &Path,
-    output: &Path,
-    ignores: &[String],
-    logger: &Logger,
-) -> io::Result<()> {
-    if !input.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "Input directory not found",
-        ));
-    }
+/// 应用为沙箱安装且未指定 --sandbox-handoff 时的退出码
+const EXIT_SANDBOXED: i32 = 3;
 
-    for entry in fs::read_dir(input)? {
-        let entry = entry?;
-        let path = entry.path();
-        let relative = path.strip_prefix(input).unwrap();
-        let relative_str = relative.to_string_lossy().replace('\\', "/"); // ✅ 统一路径分隔符
-        let dest = output.join(relative);
+/// 已安排在下次登录时重新更新时的退出码
+const EXIT_RETRY_AT_LOGON: i32 = 12;
 
-        // ✅ 检查是否在忽略列表中
-        if ignores
-            .iter()
-            .any(|ignore| relative_str.starts_with(ignore))
-        {
-            logger.log(&format!("Ignored: {}", relative_str));
-            continue;
-        }
+/// Ctrl-C 处理函数中使用的取消标记
+static INTERRUPT: OnceLock<CancelToken> = OnceLock::new();
 
-        if path.is_dir() {
-            fs::create_dir_all(&dest)?;
-            copy_dir_recursive(&path, &dest, ignores, logger)?;
-        } else {
-            fs::create_dir_all(dest.parent().unwrap())?;
-            fs::copy(&path, &dest)?;
-            logger.log(&format!("Copied file: {}", dest.display()));
+/// Ctrl-C（及 Windows 的 Ctrl-Break）时取消更新，更新器在下一个安全点中止并回滚
+#[cfg(unix)]
+fn install_interrupt_handler(token: CancelToken) {
+    extern "C" fn on_signal(_: libc::c_int) {
+        if let Some(token) = INTERRUPT.get() {
+            token.cancel();
         }
     }
+    if INTERRUPT.set(token).is_ok() {
+        let handler: extern "C" fn(libc::c_int) = on_signal;
+        // SAFETY: 处理函数只做原子写入，是异步信号安全的
+        unsafe {
+            libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+            libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+        }
+    }
+}
 
-    Ok(())
+#[cfg(windows)]
+fn install_interrupt_handler(token: CancelToken) {
+    use windows_sys::Win32::Foundation::{FALSE, TRUE};
+    use windows_sys::Win32::System::Console::{
+        CTRL_BREAK_EVENT, CTRL_C_EVENT, SetConsoleCtrlHandler,
+    };
+
+    unsafe extern "system" fn on_ctrl(ctrl_type: u32) -> i32 {
+        match (ctrl_type, INTERRUPT.get()) {
+            (CTRL_C_EVENT | CTRL_BREAK_EVENT, Some(token)) => {
+                token.cancel();
+                TRUE
+            }
+            _ => FALSE,
+        }
+    }
+    if INTERRUPT.set(token).is_ok() {
+        // SAFETY: 注册的是静态函数，处理函数只做原子写入
+        unsafe {
+            SetConsoleCtrlHandler(Some(on_ctrl), TRUE);
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
+    if args.ipc_stdio {
+        std::process::exit(run_ipc());
+    }
+
+    let (builder, target, download_args) = match args.command {
+        Some(Commands::CheckRemote {
+            provider,
+            repo,
+            mut target,
+            download,
+        }) => {
+            target.expand_paths().unwrap_or_else(|e| e.exit());
+            (Updater::builder().remote(provider, repo), target, download)
+        }
+        Some(Commands::Watch(mut watch)) => {
+            watch.expand_paths().unwrap_or_else(|e| e.exit());
+            enable_audit(&watch.target);
+            relocate_if_inside(Path::new(&watch.target.output), watch.target.log.as_deref());
+            let cancel = CancelToken::new();
+            install_interrupt_handler(cancel.clone());
+            let code = match run_watch(watch, cancel) {
+                Ok(()) => 0,
+                Err(e) => e.exit_code(),
+            };
+            std::process::exit(code);
+        }
+        Some(Commands::Service { action }) => std::process::exit(run_service(action)),
+        Some(Commands::LaunchdInstall {
+            label,
+            system,
+            interval,
+            no_load,
+            args,
+        }) => {
+            check_args(std::iter::empty(), &args);
+            let job = launchd::LaunchdJob {
+                label,
+                args,
+                interval,
+                system,
+            };
+            let result = launchd::install(&job, !no_load).map(|path| {
+                println!("Wrote {}", path.display());
+                if !no_load {
+                    println!("Loaded {}", job.label);
+                }
+            });
+            std::process::exit(report(result));
+        }
+        Some(Commands::SystemdInstall {
+            name,
+            system,
+            interval,
+            no_enable,
+            args,
+        }) => {
+            check_args(std::iter::empty(), &args);
+            let unit = systemd::SystemdUnit {
+                name,
+                args,
+                interval,
+                system,
+            };
+            let result = systemd::install(&unit, !no_enable).map(|written| {
+                for path in written {
+                    println!("Wrote {}", path.display());
+                }
+                if !no_enable {
+                    println!("Enabled {}", unit.name);
+                }
+            });
+            std::process::exit(report(result));
+        }
+        Some(Commands::SystemdUninstall { name, system }) => {
+            let result = systemd::uninstall(&name, system).map(|()| println!("Removed {}", name));
+            std::process::exit(report(result));
+        }
+        Some(Commands::LaunchdUninstall { label, system }) => {
+            let result = launchd::uninstall(&label, system).map(|()| println!("Removed {}", label));
+            std::process::exit(report(result));
+        }
+        Some(Commands::Batch {
+            file,
+            log,
+            allow_privileged,
+            download,
+        }) => {
+            let code = match run_batch(&file, log.as_deref(), allow_privileged, &download) {
+                Ok(()) => 0,
+                Err(e) => e.exit_code(),
+            };
+            std::process::exit(code);
+        }
+        Some(Commands::VerifyLog { log }) => std::process::exit(report(verify_log(log.as_deref()))),
+        Some(Commands::Bench {
+            dir,
+            small_files,
+            large_files,
+            json,
+        }) => {
+            let opts = bench::Options {
+                dir: dir.unwrap_or_else(std::env::temp_dir),
+                small_files,
+                large_files,
+            };
+            std::process::exit(report(run_bench(&opts, json)));
+        }
+        Some(Commands::Diff {
+            input,
+            output,
+            ignore,
+            ignore_existing,
+            hash_jobs,
+            json,
+            download,
+        }) => {
+            let result = download.options().and_then(|options| {
+                let lists = (split_list(&ignore), split_list(&ignore_existing));
+                run_diff(&input, &output, lists, hash_jobs, json, &options)
+            });
+            std::process::exit(report(result));
+        }
+        None => match (args.input.split_first(), args.target) {
+            (Some((input, overlays)), Some(mut target)) => {
+                target.expand_paths().unwrap_or_else(|e| e.exit());
+                let builder = layered_input(&target, input, overlays);
+                (builder, target, args.download)
+            }
+            _ => Args::command()
+                .error(
+                    clap::error::ErrorKind::MissingRequiredArgument,
+                    "--input, --ps and --app are required",
+                )
+                .exit(),
+        },
+    };
 
-    let logger = Logger::new(args.log.as_deref()).unwrap_or_else(|e| {
+    enable_audit(&target);
+    relocate_if_inside(Path::new(&target.output), target.log.as_deref());
+    if !target.no_elevate && !target.elevated {
+        elevate_if_needed(&target);
+    }
+    let cancel = CancelToken::new();
+    install_interrupt_handler(cancel.clone());
+    let code = exit_code(&run(builder, target, download_args, cancel));
+    std::process::exit(code);
+}
+
+/// 执行批量更新；错误已写入日志
+fn run_batch(
+    file: &Path,
+    log: Option<&str>,
+    allow_privileged: bool,
+    download_args: &DownloadArgs,
+) -> Result<(), UpdateError> {
+    let logger = Logger::new(log).inspect_err(|e| {
         eprintln!("Failed to initialize logger: {}", e);
-        std::process::exit(1);
+    })?;
+    let prepared = check_privileged(allow_privileged, &logger)
+        .and_then(|()| {
+            download_args
+                .options()
+                .and_then(|options| Ok((options, batch::load(file)?, Policy::load_default()?)))
+                .map_err(|e| UpdateError::InvalidConfig(e.to_string()))
+        })
+        .and_then(|(options, jobs, policy)| {
+            for job in &jobs {
+                policy
+                    .check(&job.output)
+                    .map_err(UpdateError::InvalidConfig)?;
+            }
+            Ok((options, jobs))
+        });
+    let result = prepared.and_then(|(options, jobs)| {
+        let cancel = CancelToken::new();
+        install_interrupt_handler(cancel.clone());
+        logger.set_cancel_token(cancel);
+        if io::stdout().is_terminal() {
+            logger.set_observer(Arc::new(TtyProgress::default()));
+        }
+        batch::run(&jobs, &options, &logger)
     });
+    if let Err(e) = &result {
+        logger.error(&e.to_string());
+    }
+    result
+}
 
-    logger.log("Updater started");
-    logger.log(&format!("App path: {}", args.app));
-    logger.log(&format!("Process name(s): {}", args.ps));
-    logger.log(&format!("Input dir: {}", args.input));
-    logger.log(&format!("Output dir: {}", args.output));
-
-    // ✅ 解析忽略路径
-    let ignores: Vec<String> = args
-        .ignore
-        .as_deref()
-        .unwrap_or("")
-        .split(',')
-        .map(|s| s.trim().replace('\\', "/")) // 统一路径分隔符
-        .filter(|s| !s.is_empty())
-        .collect();
+/// 以 root/SYSTEM 运行时要求显式指定 --allow-privileged：错误的 --output 在此身份下可能删除系统文件
+fn check_privileged(allow: bool, logger: &Logger) -> Result<(), UpdateError> {
+    let Some(user) = privilege::privileged_user() else {
+        return Ok(());
+    };
+    if !allow {
+        return Err(UpdateError::InvalidConfig(format!(
+            "Refusing to run as {}, run as a regular user or pass --allow-privileged",
+            user
+        )));
+    }
+    logger.warn(&format!(
+        "Running as {} because --allow-privileged is set",
+        user
+    ));
+    Ok(())
+}
+
+/// 执行服务操作，返回退出码
+fn run_service(action: ServiceAction) -> i32 {
+    let result = match action {
+        ServiceAction::Install {
+            name,
+            display_name,
+            args,
+        } => {
+            check_args(["service", "run", "--name", name.as_str()], &args);
+            service::install(&name, display_name.as_deref().unwrap_or(&name), &args)
+                .map(|()| println!("Service {} installed", name))
+        }
+        ServiceAction::Uninstall { name } => {
+            service::uninstall(&name).map(|()| println!("Service {} removed", name))
+        }
+        ServiceAction::Run { name, mut watch } => {
+            watch.expand_paths().unwrap_or_else(|e| e.exit());
+            let _ = EVENT_SOURCE.set(name.clone());
+            service::run(&name, move |cancel| match run_watch(watch, cancel) {
+                Ok(()) | Err(UpdateError::Cancelled) => 0,
+                Err(e) => e.exit_code(),
+            })
+        }
+    };
+    report(result)
+}
 
-    if !ignores.is_empty() {
-        logger.log(&format!("Ignore list: {:?}", ignores));
+/// 注册常驻任务前先校验参数，避免任务启动后才发现参数错误；参数无效时退出
+fn check_args<'a>(prefix: impl IntoIterator<Item = &'a str>, args: &'a [String]) {
+    let command = std::iter::once("electron-quit-and-install")
+        .chain(prefix)
+        .chain(args.iter().map(String::as_str));
+    if let Err(e) = Args::try_parse_from(command) {
+        e.exit();
     }
+}
 
-    kill_processes_by_names(&args.ps, &logger);
+/// 输出错误并返回退出码
+fn report(result: io::Result<()>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            UpdateError::Io(e).exit_code()
+        }
+    }
+}
 
-    // 执行文件复制
-    let input_path = PathBuf::from(&args.input);
-    let output_path = PathBuf::from(&args.output);
-    // 创建 output_new 临时目录
-    let output_new = output_path.with_file_name(format!(
-        "{}_new",
-        output_path.file_name().unwrap().to_string_lossy()
-    ));
-    let output_old = output_path.with_file_name(format!(
-        "{}_old",
-        output_path.file_name().unwrap().to_string_lossy()
-    ));
+/// 输出日志检查结果，链断开或与摘要文件不符时返回错误
+fn verify_log(log: Option<&str>) -> io::Result<()> {
+    let path = Logger::file_path(log)?;
+    let verification = audit::verify(&path)?;
+    println!("{}: {} audit records", path.display(), verification.records);
+    match (verification.digest_record, &verification.error) {
+        (Some(record), _) => println!("Digest file matches record {}", record),
+        (None, None) => println!("No digest file"),
+        (None, Some(_)) => {}
+    }
+    match verification.error {
+        None => Ok(()),
+        Some((line, reason)) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Line {}: {}", line, reason),
+        )),
+    }
+}
+
+/// 执行基准测试并输出结果与推荐设置
+fn run_bench(opts: &bench::Options, json: bool) -> io::Result<()> {
+    eprintln!("Benchmarking in {}...", opts.dir.display());
+    let report = bench::run(opts)?;
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).map_err(io::Error::other)?
+        );
+        return Ok(());
+    }
+    println!(
+        "{} files, {:.1} MB",
+        report.files,
+        report.bytes as f64 / 1e6
+    );
+    for m in &report.copy {
+        println!("copy    {:<20} {:>9.1} MB/s", m.name, m.throughput);
+    }
+    for m in &report.hash {
+        println!("hash    jobs={:<15} {:>9.1} MB/s", m.name, m.throughput);
+    }
+    println!(
+        "extract {:<20} {:>9.1} MB/s",
+        report.extract.name, report.extract.throughput
+    );
+    println!();
+    println!("Recommended settings:");
+    println!("  --copy-engine={}", report.copy_engine);
+    if report.copy_jobs > 1 {
+        println!("  --copy-jobs={}", report.copy_jobs);
+        println!("  --copy-granularity={}", report.copy_granularity);
+    }
+    println!("  --hash-jobs={}", report.hash_jobs);
+    if report.stream_extract {
+        println!("  --stream-extract");
+    }
+    Ok(())
+}
+
+/// 输出更新计划；日志写到 stderr，stdout 只有计划本身
+fn run_diff(
+    input: &[String],
+    output: &Path,
+    (ignore, ignore_existing): (Vec<OsString>, Vec<OsString>),
+    hash_jobs: usize,
+    json: bool,
+    options: &DownloadOptions,
+) -> io::Result<()> {
+    let stderr = |record: &Record| eprint!("{}", record.line());
+    let logger = Logger::with_sinks(vec![Box::new(stderr)]);
+    let inputs = diff::prepare_inputs(input, options, &logger)?;
+    let plan = diff::plan(&inputs, output, &ignore, &ignore_existing, hash_jobs)?;
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&plan).map_err(io::Error::other)?
+        );
+        return Ok(());
+    }
+    for path in &plan.added {
+        println!("+ {}", path);
+    }
+    for path in &plan.replaced {
+        println!("~ {}", path);
+    }
+    for path in &plan.deleted {
+        println!("- {}", path);
+    }
+    for path in &plan.ignored {
+        println!("! {}", path);
+    }
+    println!();
+    println!(
+        "{} added, {} replaced, {} deleted, {} ignored, {} unchanged",
+        plan.added.len(),
+        plan.replaced.len(),
+        plan.deleted.len(),
+        plan.ignored.len(),
+        plan.unchanged
+    );
+    Ok(())
+}
+
+/// 从缓存目录重新启动的更新器带有此环境变量，避免再次转移
+const RELOCATED_ENV: &str = "EQI_RELOCATED";
 
+/// 更新器位于 output 中（常随应用放在 resources 下）时，正在运行的 exe 会阻止重命名或删除 output：
+/// 此时把自身复制到缓存目录，从那里以相同参数重新启动并直接退出；转移失败时记录警告后继续
+fn relocate_if_inside(output: &Path, log: Option<&str>) {
+    if std::env::var_os(RELOCATED_ENV).is_some() || !exe_inside(output) {
+        return;
+    }
+    let (Ok(exe), Ok(logger)) = (std::env::current_exe(), new_logger(log)) else {
+        return;
+    };
     logger.log(&format!(
-        "Creating temporary update directory: {}",
-        output_new.display()
+        "Updater {} is inside the output directory, relocating to the cache directory",
+        exe.display()
     ));
-    if output_new.exists() {
-        fs::remove_dir_all(&output_new).unwrap_or_else(|e| {
-            logger.log(&format!(
-                "Failed to remove existing temporary directory: {}",
-                e
-            ));
-        });
+    match relocate(&exe) {
+        Ok(relocated) => {
+            logger.log(&format!("Continuing from {}", relocated.display()));
+            std::process::exit(0);
+        }
+        Err(e) => logger.warn(&format!("Failed to relocate the updater: {}", e)),
     }
-    fs::create_dir_all(&output_new).unwrap_or_else(|e| {
-        logger.log(&format!("Failed to create temporary directory: {}", e));
-        std::process::exit(1);
-    });
+}
 
-    // 先拷贝旧 output（如果存在）到 output_new
-    if output_path.exists() {
-        logger.log("Copying existing output to temporary directory...");
-        if let Err(e) = copy_dir_recursive(&output_path, &output_new, &[], &logger) {
-            logger.log(&format!("Failed to copy existing output: {}", e));
-            std::process::exit(1);
+/// 没有替换 output 的权限时（如按机器安装在 Program Files 下），以管理员身份重新启动并以其退出码退出；
+/// 用户拒绝或无法提权时记录警告后继续
+fn elevate_if_needed(target: &TargetArgs) {
+    if !cfg!(windows) || elevate::can_replace(Path::new(&target.output)) {
+        return;
+    }
+    let Ok(logger) = new_logger(target.log.as_deref()) else {
+        return;
+    };
+    logger.log("Output is not writable, relaunching the updater elevated");
+    let mut args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    args.push("--elevated".into());
+    // 提权进程只做文件操作，重新启动应用与 post-restart 钩子交回本进程以原有权限执行
+    let handoff = (!target.keep_privileges)
+        .then(|| std::env::temp_dir().join(format!("eqi-handoff-{}.json", std::process::id())));
+    if let Some(path) = &handoff {
+        args.push("--restart-handoff".into());
+        args.push(path.into());
+    }
+    let code = match elevate::run_elevated(&args) {
+        Ok(code) => code,
+        Err(e) => {
+            logger.warn(&format!("Failed to relaunch elevated: {}", e));
+            return;
         }
+    };
+    logger.log(&format!("Elevated updater exited with code {}", code));
+    match handoff.as_deref().map(Handoff::take) {
+        Some(Ok(Some(handoff))) => handoff.complete(&[&target.hooks()], &logger),
+        Some(Err(e)) => logger.warn(&format!("Failed to read restart handoff: {}", e)),
+        _ => {}
     }
+    std::process::exit(code);
+}
 
-    // 再拷贝 input 更新文件到 output_new
-    logger.log("Copying update files to temporary directory...");
-    if let Err(e) = copy_dir_recursive(&input_path, &output_new, &ignores, &logger) {
-        logger.log(&format!("File copy failed: {}", e));
-        std::process::exit(1);
+/// 当前 exe 是否位于 output 中
+fn exe_inside(output: &Path) -> bool {
+    match (
+        std::env::current_exe().and_then(fs::canonicalize),
+        fs::canonicalize(output),
+    ) {
+        (Ok(exe), Ok(output)) => exe.starts_with(output),
+        _ => false,
     }
+}
 
-    // output → output_old
-    if output_old.exists() {
-        fs::remove_dir_all(&output_old).unwrap_or_else(|e| {
-            logger.log(&format!("Failed to remove old backup directory: {}", e));
-        });
+/// 复制 exe 到缓存目录并以相同参数启动，返回副本路径；顺带删除以前转移留下的目录
+fn relocate(exe: &Path) -> io::Result<PathBuf> {
+    // 放在只有当前用户可以访问的缓存目录中，其他用户无法在启动前替换副本
+    let cache = download::cache_dir()?;
+    if let Ok(entries) = fs::read_dir(&cache) {
+        for entry in entries.flatten() {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("relocated-")
+            {
+                // 仍在运行的副本无法删除，忽略
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
     }
-    if output_path.exists() {
-        fs::rename(&output_path, &output_old).unwrap_or_else(|e| {
-            logger.log(&format!("Failed to rename output -> output_old: {}", e));
-            std::process::exit(1);
-        });
+    let dir = cache.join(format!("relocated-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let relocated = dir.join(exe.file_name().unwrap_or_default());
+    fs::copy(exe, &relocated)?;
+    std::process::Command::new(&relocated)
+        .args(std::env::args_os().skip(1))
+        .env(RELOCATED_ENV, "1")
+        .spawn()?;
+    Ok(relocated)
+}
+
+/// 更新结果对应的退出码
+fn exit_code(result: &Result<UpdateReport, UpdateError>) -> i32 {
+    match result {
+        Ok(report) => match report.outcome {
+            Outcome::SandboxRefused => EXIT_SANDBOXED,
+            Outcome::RetryAtLogon => EXIT_RETRY_AT_LOGON,
+            _ => 0,
+        },
+        Err(e) => e.exit_code(),
     }
+}
 
-    // output_new → output
-    fs::rename(&output_new, &output_path).unwrap_or_else(|e| {
-        logger.log(&format!(
-            "Failed to rename temporary directory -> output: {}",
-            e
-        ));
-        std::process::exit(1);
-    });
+/// --ipc-stdio 的一条命令
+#[derive(Deserialize)]
+struct IpcRequest {
+    /// configure、stage、apply 或 cancel
+    command: String,
+    /// configure 的参数，与命令行参数相同（直接更新或 check-remote）
+    #[serde(default)]
+    args: Vec<String>,
+}
 
-    logger.log("Update applied successfully");
-
-    // ✅ 启动主程序前清理 input 和 output_old
-    logger.log("Cleaning up old files before restarting app...");
-    if input_path.exists() {
-        if let Err(e) = fs::remove_dir_all(&input_path) {
-            logger.log(&format!("Failed to remove input directory: {}", e));
-        } else {
-            logger.log(&format!(
-                "Removed input directory: {}",
-                input_path.display()
-            ));
+/// 审计模式，设置后日志文件以哈希链格式写入
+static AUDIT: OnceLock<()> = OnceLock::new();
+
+fn enable_audit(target: &TargetArgs) {
+    if target.audit {
+        let _ = AUDIT.set(());
+    }
+}
+
+/// --ipc-stdio 模式下的事件通道，设置后日志与进度以事件发出
+static IPC: OnceLock<Ipc> = OnceLock::new();
+
+/// 逐行读取标准输入中的命令并执行，返回退出码；更新结束时发出 result 或 error 事件后直接退出
+fn run_ipc() -> i32 {
+    let cancel = CancelToken::new();
+    install_interrupt_handler(cancel.clone());
+    let ipc = Ipc::new(io::stdout(), cancel.clone());
+    let _ = IPC.set(ipc.clone());
+    ipc.send(json!({"event": "ready", "version": env!("CARGO_PKG_VERSION")}));
+    let reject = |message: &str| ipc.send(json!({"event": "rejected", "message": message}));
+
+    let mut configured = None;
+    let mut worker = None;
+    for line in io::stdin().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: IpcRequest = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                reject(&format!("Invalid request: {}", e));
+                continue;
+            }
+        };
+        match request.command.as_str() {
+            "configure" if worker.is_some() => reject("The update has already started"),
+            "configure" => {
+                let command =
+                    std::iter::once("electron-quit-and-install".to_string()).chain(request.args);
+                match Args::try_parse_from(command).and_then(update_target) {
+                    // 无法转移：转移后的进程没有与应用相连的标准输入输出
+                    Ok(Some((_, target, _))) if exe_inside(Path::new(&target.output)) => reject(
+                        "The updater is inside the output directory, copy it elsewhere before starting it with --ipc-stdio",
+                    ),
+                    Ok(Some(target)) => {
+                        enable_audit(&target.1);
+                        configured = Some(target);
+                        ipc.send(json!({"event": "configured"}));
+                    }
+                    Ok(None) => {
+                        reject("configure accepts a direct update or check-remote command line")
+                    }
+                    Err(e) => reject(e.render().to_string().trim()),
+                }
+            }
+            command @ ("stage" | "apply") => {
+                if command == "apply" {
+                    ipc.approve();
+                }
+                if worker.is_some() {
+                    continue;
+                }
+                let Some((builder, target, download_args)) = configured.take() else {
+                    reject("Send configure first");
+                    continue;
+                };
+                let (ipc, cancel) = (ipc.clone(), cancel.clone());
+                worker = Some(thread::spawn(move || {
+                    let result = run(builder, target, download_args, cancel);
+                    let code = exit_code(&result);
+                    ipc.send(match result {
+                        Ok(report) => json!({
+                            "event": "result",
+                            "outcome": format!("{:?}", report.outcome),
+                            "code": code,
+                            "audit_digest": report.audit_digest,
+                        }),
+                        Err(e) => json!({"event": "error", "message": e.to_string(), "code": code}),
+                    });
+                    std::process::exit(code);
+                }));
+            }
+            "cancel" => ipc.cancel(),
+            other => reject(&format!("Unknown command: {}", other)),
+        }
+    }
+
+    // 标准输入已关闭：应用在允许退出前就已退出或放弃了更新，取消更新
+    if !ipc.is_approved() {
+        ipc.cancel();
+    }
+    match worker.map(thread::JoinHandle::join) {
+        Some(Ok(())) | None => 0,
+        Some(Err(_)) => 1,
+    }
+}
+
+/// 以第一个 --input 为更新文件、其余依次作为叠加层的构建器（均展开模板变量）
+fn layered_input(target: &TargetArgs, input: &str, overlays: &[String]) -> UpdaterBuilder {
+    overlays.iter().fold(
+        Updater::builder().input(target.expand(input)),
+        |builder, overlay| builder.overlay(target.expand(overlay)),
+    )
+}
+
+/// 从解析后的命令行中取出直接更新或 check-remote 的参数
+fn update_target(
+    args: Args,
+) -> Result<Option<(UpdaterBuilder, TargetArgs, DownloadArgs)>, clap::Error> {
+    match args.command {
+        Some(Commands::CheckRemote {
+            provider,
+            repo,
+            mut target,
+            download,
+        }) => {
+            target.expand_paths()?;
+            Ok(Some((
+                Updater::builder().remote(provider, repo),
+                target,
+                download,
+            )))
+        }
+        None if !args.ipc_stdio => {
+            let (Some(mut target), Some((input, overlays))) =
+                (args.target, args.input.split_first())
+            else {
+                return Ok(None);
+            };
+            target.expand_paths()?;
+            let builder = layered_input(&target, input, overlays);
+            Ok(Some((builder, target, args.download)))
         }
+        _ => Ok(None),
     }
+}
+
+/// 以服务运行时的事件日志来源，设置后日志同时写入 Windows 事件日志
+static EVENT_SOURCE: OnceLock<String> = OnceLock::new();
 
-    if output_old.exists() {
-        if let Err(e) = fs::remove_dir_all(&output_old) {
-            logger.log(&format!("Failed to remove output_old directory: {}", e));
-        } else {
-            logger.log(&format!(
-                "Removed backup directory: {}",
-                output_old.display()
+/// 创建日志器，以服务运行时追加事件日志输出
+/// 明确指定的 --log 或审计日志无法打开时返回错误；默认位置无法写入时不写文件，并记录一条警告
+fn new_logger(log: Option<&str>) -> io::Result<Logger> {
+    let file = Logger::file_path(log).and_then(|path| {
+        let file: io::Result<Box<dyn LogSink>> = match AUDIT.get() {
+            Some(()) => AuditSink::open(path.clone()).map(|sink| Box::new(sink) as _),
+            None => FileSink::open(&path).map(|sink| Box::new(sink) as _),
+        };
+        file.map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    });
+    let (file, failed) = match file {
+        Ok(file) => (Some(file), None),
+        Err(e) if log.is_none() && AUDIT.get().is_none() => (None, Some(e)),
+        Err(e) => return Err(e),
+    };
+    let warn = |logger: Logger| {
+        if let Some(e) = &failed {
+            logger.warn(&format!(
+                "Failed to open log file {}, continuing without it",
+                e
             ));
         }
+        logger
+    };
+    // 标准输出用于协议，日志只写入文件并以事件发出
+    if let Some(ipc) = IPC.get() {
+        let sinks = file
+            .into_iter()
+            .chain([Box::new(ipc.clone()) as _])
+            .collect();
+        return Ok(warn(Logger::with_sinks(sinks)));
+    }
+    let sinks = std::iter::once(Box::new(ConsoleSink) as _)
+        .chain(file)
+        .collect();
+    let logger = Logger::with_sinks(sinks);
+    #[cfg(windows)]
+    if let Some(source) = EVENT_SOURCE.get() {
+        return Ok(warn(
+            logger.with_sink(sink::EventLogSink::register(source)?),
+        ));
+    }
+    Ok(warn(logger))
+}
+
+/// 监视输入目录，每次出现标记文件时执行一次更新；只在被取消或参数无效时结束
+fn run_watch(args: WatchArgs, cancel: CancelToken) -> Result<(), UpdateError> {
+    let WatchArgs {
+        input,
+        sentinel,
+        control: control_path,
+        apply_at,
+        mut target,
+        download: download_args,
+    } = args;
+    let (input, sentinel) = (input.as_path(), sentinel.as_str());
+    // 控制接口的监听线程同样记录日志
+    let logger = Arc::new(new_logger(target.log.as_deref()).inspect_err(|e| {
+        eprintln!("Failed to initialize logger: {}", e);
+    })?);
+    // 提前检查，而不是等到第一次更新
+    check_privileged(target.allow_privileged, &logger)
+        .inspect_err(|e| logger.error(&e.to_string()))?;
+    target.ignore = split_list(&target.ignore);
+    target.ignore.push(sentinel.into());
+    // 常驻进程降权后无法完成之后的更新
+    target.keep_privileges = true;
+    let control = Control::new();
+    if let Some(path) = &control_path {
+        control.serve(path, logger.clone()).map_err(|e| {
+            logger.error(&format!(
+                "Failed to open control interface {}: {}",
+                path.display(),
+                e
+            ));
+            UpdateError::Io(e)
+        })?;
+        logger.log(&format!("Control interface: {}", path.display()));
+    }
+    if let Some(schedule) = apply_at {
+        logger.log(&format!("Updates are applied only at {}", schedule));
+        control.set_schedule(schedule);
     }
 
-    // 启动主程序
-    if Path::new(&args.app).exists() {
-        logger.log("Restarting main app...");
-        match Command::new(&args.app)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
+    loop {
+        logger.log(&format!("Watching {} for {}", input.display(), sentinel));
+        match watch::wait_for_sentinel(input, sentinel, &cancel, Some(&control)) {
+            Ok(true) => {}
+            Ok(false) => {
+                logger.log("Watch cancelled");
+                return Err(UpdateError::Cancelled);
+            }
+            Err(e) => {
+                logger.error(&format!("Failed to watch {}: {}", input.display(), e));
+                return Err(UpdateError::Io(e));
+            }
+        }
+        logger.log("Update files are ready");
+        // 先移除标记，更新失败时等待下一次放置，而不是反复重试
+        if let Err(e) = fs::remove_file(input.join(sentinel))
+            && e.kind() != io::ErrorKind::NotFound
         {
-            Ok(_) => logger.log("Main app restarted successfully"),
-            Err(e) => logger.log(&format!("Failed to start main app: {}", e)),
+            logger.warn(&format!("Failed to remove {}: {}", sentinel, e));
+        }
+        let builder = Updater::builder().input(input.to_string_lossy());
+        // 其他失败已写入日志，继续等待下一次放置
+        let token = control.begin_update(&cancel);
+        let result = run(builder, target.clone(), download_args.clone(), token);
+        control.end_update(match &result {
+            Ok(report) => format!("{:?}", report.outcome),
+            Err(e) => e.to_string(),
+        });
+        match result {
+            // 控制接口的 cancel 只取消这一次更新，Ctrl-C 则结束监视
+            Err(UpdateError::Cancelled) if !cancel.is_cancelled() => {
+                logger.log("Update cancelled through the control interface");
+            }
+            Err(e @ (UpdateError::Cancelled | UpdateError::InvalidConfig(_))) => return Err(e),
+            _ => {}
+        }
+    }
+}
+
+/// 按命令行参数配置并执行更新；错误已写入日志
+fn run(
+    builder: UpdaterBuilder,
+    target: TargetArgs,
+    download_args: DownloadArgs,
+    cancel: CancelToken,
+) -> Result<UpdateReport, UpdateError> {
+    let commands = target.on_failure_hook.clone();
+    let (output, app, log) = (
+        target.output.clone(),
+        target.app.clone(),
+        target.log.clone(),
+    );
+    let result = update(builder, target, download_args, cancel);
+    if let Err(e) = &result
+        && !commands.is_empty()
+    {
+        match new_logger(log.as_deref()) {
+            Ok(logger) => {
+                hooks::notify_failure(&commands, e, Path::new(&output), Path::new(&app), &logger)
+            }
+            Err(e) => eprintln!("Failed to initialize logger: {}", e),
+        }
+    }
+    result
+}
+
+fn update(
+    builder: UpdaterBuilder,
+    target: TargetArgs,
+    download_args: DownloadArgs,
+    cancel: CancelToken,
+) -> Result<UpdateReport, UpdateError> {
+    let logger = new_logger(target.log.as_deref()).inspect_err(|e| {
+        eprintln!("Failed to initialize logger: {}", e);
+    })?;
+    check_privileged(target.allow_privileged, &logger)
+        .inspect_err(|e| logger.error(&e.to_string()))?;
+    let options = download_args.options().map_err(|e| {
+        let error = UpdateError::InvalidConfig(e.to_string());
+        logger.error(&error.to_string());
+        error
+    })?;
+    let policy = Policy::load_default()
+        .map_err(|e| e.to_string())
+        .and_then(|policy| policy.check(Path::new(&target.output)).map(|()| policy))
+        .map_err(|e| {
+            let error = UpdateError::InvalidConfig(e);
+            logger.error(&error.to_string());
+            error
+        })?;
+    let release_notes = match &target.release_notes {
+        Some(path) => Some(fs::read_to_string(path).map_err(|e| {
+            let error = UpdateError::InvalidConfig(format!(
+                "Failed to read release notes {}: {}",
+                path.display(),
+                e
+            ));
+            logger.error(&error.to_string());
+            error
+        })?),
+        None => None,
+    };
+    let plugins = load_plugins(&target.plugin).map_err(|e| {
+        let error = UpdateError::InvalidConfig(e.to_string());
+        logger.error(&error.to_string());
+        error
+    })?;
+
+    let mut observer: Option<Arc<dyn ProgressObserver>> = match IPC.get() {
+        Some(ipc) => Some(Arc::new(ipc.clone())),
+        None if io::stdout().is_terminal() => Some(Arc::new(TtyProgress::default())),
+        None => None,
+    };
+    let app_name = Path::new(&target.app)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    if target.splash {
+        let title = target
+            .splash_title
+            .clone()
+            .unwrap_or_else(|| format!("Updating {}", app_name));
+        match Splash::new(&title, observer.clone()) {
+            Ok(splash) => observer = Some(Arc::new(splash)),
+            Err(e) => logger.warn(&format!("Failed to open splash window: {}", e)),
+        }
+    }
+    let mut tray = None;
+    if target.tray {
+        let title = target.tray_title.clone().unwrap_or(app_name);
+        match Tray::new(&title, Path::new(&target.app), observer.clone()) {
+            Ok(icon) => {
+                let icon = Arc::new(icon);
+                observer = Some(icon.clone());
+                tray = Some(icon);
+            }
+            Err(e) => logger.warn(&format!("Failed to show tray icon: {}", e)),
         }
-    } else {
-        logger.log("Main app not found, skip restart");
     }
 
-    logger.log("Updater finished");
+    walk::set_memory_limit(target.memory_limit.saturating_mul(1024 * 1024));
+    let mut builder = builder
+        .processes(split_list(&target.ps))
+        .output(&target.output)
+        .policy(policy)
+        .app(&target.app)
+        .ignores(split_list(&target.ignore))
+        .ignore_existing(split_list(&target.ignore_existing))
+        .layout(target.layout)
+        .download_options(options)
+        .refresh_shortcuts(target.refresh_shortcuts)
+        .move_to_applications(target.move_to_applications)
+        .sandbox_handoff(target.sandbox_handoff)
+        .simulate(target.simulate)
+        .force(target.force)
+        .allow_publisher_change(target.allow_publisher_change)
+        .secure_delete(target.secure_delete.as_deref().unwrap_or("").split(','))
+        .confirm_delete(target.confirm_delete)
+        .keep_input(target.keep_input)
+        .backup(target.backup)
+        .copy_engine(target.copy_engine)
+        .copy_jobs(target.copy_jobs, target.copy_granularity)
+        .hash_jobs(target.hash_jobs)
+        .stream_extract(target.stream_extract)
+        .move_input(target.move_input)
+        .rename_retry(Duration::from_secs(target.rename_retry))
+        .cancel_token(cancel)
+        .logger(logger);
+    if let Some(observer) = observer {
+        builder = builder.observer(observer);
+    }
+    if let Some(ipc) = IPC.get() {
+        builder = builder.hook(ipc.clone());
+    }
+    let hooks = target.hooks();
+    if !hooks.is_empty() {
+        builder = builder.hook(hooks);
+    }
+    for plugin in plugins {
+        builder = builder.hook(plugin);
+    }
+    for (hook, policy) in &target.hook_on_failure {
+        builder = builder.on_hook_failure(*hook, *policy);
+    }
+    if let Some(steps) = target.pipeline {
+        builder = builder.pipeline(steps);
+    }
+    if let Some(version) = target.app_version {
+        builder = builder.app_version(version);
+    }
+    if let Some(notes) = release_notes {
+        builder = builder.release_notes(notes);
+    }
+    if let Some(version) = target.expected_version {
+        builder = builder.expected_version(version);
+    }
+    if target.notify {
+        builder = builder.notify(Notification {
+            app_name: target.notify_app_name,
+            icon: target.notify_icon,
+            app_id: target.notify_app_id,
+        });
+    }
+    if let Some(dir) = target.backup_dir {
+        builder = builder.backup_dir(dir);
+    }
+    if let Some(days) = target.backup_days {
+        builder = builder.backup_days(days);
+    }
+    if let Some(version) = target.min_installed_version {
+        builder = builder.min_installed_version(version);
+    }
+    if let Some(key) = target.uninstall_key {
+        builder = builder.uninstall_key(key);
+    }
+    builder = builder.drop_privileges(!target.keep_privileges);
+    if let Some(path) = target.restart_handoff {
+        builder = builder.restart_handoff(path);
+    }
+    if target.retry_at_logon {
+        let exe = std::env::current_exe().map_err(UpdateError::Io)?;
+        builder = builder.retry_at_logon(
+            std::iter::once(exe.to_string_lossy().into_owned()).chain(std::env::args().skip(1)),
+        );
+    }
+    if let Some(channel) = download_args.channel {
+        builder = builder.channel(channel);
+    }
+    if let Some(rollout) = download_args.rollout {
+        builder = builder.rollout(rollout);
+    }
+    if let Some(sha256) = download_args.sha256 {
+        builder = builder.sha256(sha256);
+    }
+
+    let log_path = Logger::file_path(target.log.as_deref());
+    if AUDIT.get().is_some() {
+        builder = builder.audit_log(log_path.as_ref().map_err(|e| {
+            UpdateError::InvalidConfig(format!("Invalid --log for --audit: {}", e))
+        })?);
+    }
+
+    // 构建失败时日志器已移入构建器，直接输出到 stderr
+    let updater = builder.build().inspect_err(|e| eprintln!("{}", e))?;
+    let result = updater.run();
+    if let (Some(tray), Err(e)) = (&tray, &result)
+        && !matches!(e, UpdateError::Cancelled)
+    {
+        tray.failed(log_path.ok().as_deref());
+    }
+    result
 }
@@ -0,0 +1,113 @@
+//! 目录清单：目录中每个文件的相对路径、大小与 sha256。文件在工作窃取线程池中并行计算摘要，
+//! 包含大量文件的目录上校验与比较不再受限于单个核心。目录以流式遍历（见 [`crate::walk`]），
+//! 按内存上限分批计算，只需要摘要时（[`digest_of`]）不保存完整的清单
+
+use crate::download::hex;
+use crate::walk::{self, Batch, Walk};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// 清单中的一个文件
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// 相对路径，以 / 分隔
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// 按相对路径排序的文件清单
+#[derive(Clone, Debug, Default)]
+pub struct Manifest {
+    pub entries: Vec<Entry>,
+}
+
+impl Manifest {
+    /// 计算 root 下所有文件的清单（跟随符号链接）；jobs 为线程数，0 表示使用全部核心
+    pub fn build(root: &Path, jobs: usize) -> io::Result<Self> {
+        let mut entries = Vec::new();
+        for_each_entry(root, jobs, &mut |entry| entries.push(entry))?;
+        Ok(Self { entries })
+    }
+
+    /// 清单的摘要：依次计入每个文件的相对路径与其 sha256，与所在位置和平台无关
+    pub fn digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        for entry in &self.entries {
+            update(&mut hasher, entry);
+        }
+        hex(&hasher.finalize())
+    }
+
+    /// 按相对路径查找
+    pub fn get(&self, path: &str) -> Option<&Entry> {
+        self.entries
+            .binary_search_by(|e| e.path.as_str().cmp(path))
+            .ok()
+            .map(|i| &self.entries[i])
+    }
+}
+
+/// root 的清单摘要（同 [`Manifest::digest`]），不在内存中保存完整的清单
+pub fn digest_of(root: &Path, jobs: usize) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    for_each_entry(root, jobs, &mut |entry| update(&mut hasher, &entry))?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn update(hasher: &mut Sha256, entry: &Entry) {
+    hasher.update(entry.path.as_bytes());
+    hasher.update([0]);
+    hasher.update(entry.sha256.as_bytes());
+    hasher.update([b'\n']);
+}
+
+/// 按相对路径顺序对 root 下的每个文件调用 f；文件按内存上限分批，每批在 jobs 个线程中并行计算摘要
+fn for_each_entry(root: &Path, jobs: usize, f: &mut dyn FnMut(Entry)) -> io::Result<()> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(io::Error::other)?;
+    let mut hash_batch = |files: Vec<PathBuf>| {
+        let entries = pool.install(|| {
+            files
+                .into_par_iter()
+                .map(|relative| {
+                    let (size, sha256) = hash_file(&root.join(&relative))?;
+                    let path = walk::slash_path(&relative);
+                    Ok(Entry { path, size, sha256 })
+                })
+                .collect::<io::Result<Vec<_>>>()
+        })?;
+        entries.into_iter().for_each(&mut *f);
+        io::Result::Ok(())
+    };
+    let mut batch = Batch::default();
+    for path in Walk::new(root)? {
+        let path = path?;
+        let cost = walk::path_cost(&path);
+        if batch.push(path, cost) {
+            hash_batch(batch.take())?;
+        }
+    }
+    hash_batch(batch.take())
+}
+
+/// 单个文件的大小与 sha256
+pub fn hash_file(path: &Path) -> io::Result<(u64, String)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok((size, hex(&hasher.finalize())));
+        }
+        size += n as u64;
+        hasher.update(&buf[..n]);
+    }
+}
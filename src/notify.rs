@@ -0,0 +1,139 @@
+//! 更新结果通知：后台静默更新时没有可见的应用界面，更新完成或失败后向当前用户显示一条系统通知。
+//! Windows 上通过 PowerShell 调用 WinRT 的 Toast 接口，以服务（SYSTEM）运行时没有用户会话，通知不可见；
+//! macOS 上通过 osascript 发送通知中心横幅，以 root 运行（LaunchDaemon）时发送到当前登录控制台的用户
+
+use crate::Logger;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 通知设置
+#[derive(Clone, Debug, Default)]
+pub struct Notification {
+    /// 通知标题中的应用名称，默认为主程序的文件名（不含扩展名）
+    pub app_name: Option<String>,
+    /// 通知中显示的图标（png、ico 等图片文件，仅 Windows；macOS 上为脚本编辑器的图标）
+    pub icon: Option<PathBuf>,
+    /// Windows 上发送通知使用的 AppUserModelID（如应用安装时注册的 com.example.myapp），
+    /// 默认以 Windows PowerShell 的身份发送
+    pub app_id: Option<String>,
+}
+
+impl Notification {
+    /// 通知中的应用名称
+    pub fn name(&self, app: &Path) -> String {
+        self.app_name.clone().unwrap_or_else(|| {
+            app.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+}
+
+/// 显示一条通知，失败只记录警告
+pub fn show(notification: &Notification, title: &str, body: &str, logger: &Logger) {
+    match native(notification, title, body) {
+        Ok(()) => logger.log(&format!("Notification shown: {}", body)),
+        Err(e) => logger.warn(&format!("Failed to show notification: {}", e)),
+    }
+}
+
+/// Windows PowerShell 的 AppUserModelID，未注册 AppUserModelID 的程序借用它发送通知
+#[cfg(windows)]
+const POWERSHELL_APP_ID: &str =
+    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+#[cfg(windows)]
+fn native(notification: &Notification, title: &str, body: &str) -> io::Result<()> {
+    use crate::powershell::{self, quote};
+
+    let image = match &notification.icon {
+        Some(icon) => format!(
+            r#"<image placement="appLogoOverride" src="{}"/>"#,
+            xml_escape(&std::path::absolute(icon)?.to_string_lossy())
+        ),
+        None => String::new(),
+    };
+    let xml = format!(
+        r#"<toast><visual><binding template="ToastGeneric"><text>{}</text><text>{}</text>{}</binding></visual></toast>"#,
+        xml_escape(title),
+        xml_escape(body),
+        image
+    );
+    let script = format!(
+        r#"$null = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]
+$null = [Windows.Data.Xml.Dom.XmlDocument, Windows.Data.Xml.Dom.XmlDocument, ContentType = WindowsRuntime]
+$xml = New-Object Windows.Data.Xml.Dom.XmlDocument
+$xml.LoadXml({xml})
+$toast = New-Object Windows.UI.Notifications.ToastNotification $xml
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier({app_id}).Show($toast)"#,
+        xml = quote(&xml),
+        app_id = quote(notification.app_id.as_deref().unwrap_or(POWERSHELL_APP_ID)),
+    );
+    powershell::run(&script).map(drop)
+}
+
+#[cfg(target_os = "macos")]
+fn native(_notification: &Notification, title: &str, body: &str) -> io::Result<()> {
+    let script = format!(
+        "display notification {} with title {}",
+        applescript_string(body),
+        applescript_string(title)
+    );
+    let out = console_command("osascript")?
+        .args(["-e", &script])
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "osascript failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn native(_notification: &Notification, _title: &str, _body: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "notifications are only supported on Windows and macOS",
+    ))
+}
+
+/// 运行 program 的命令；root 没有图形会话，此时在控制台用户的会话中以其身份运行
+#[cfg(target_os = "macos")]
+pub(crate) fn console_command(program: &str) -> io::Result<std::process::Command> {
+    use std::os::unix::fs::MetadataExt;
+    use std::process::Command;
+
+    if crate::privilege::privileged_user().is_none() {
+        return Ok(Command::new(program));
+    }
+    let uid = std::fs::metadata("/dev/console")?.uid();
+    let mut command = Command::new("launchctl");
+    command.args([
+        "asuser",
+        &uid.to_string(),
+        "sudo",
+        "-u",
+        &format!("#{}", uid),
+        program,
+    ]);
+    Ok(command)
+}
+
+/// AppleScript 字符串字面量
+#[cfg(target_os = "macos")]
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// 转义 XML 文本与属性值中的特殊字符
+#[cfg(windows)]
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,350 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, StreamOwned};
+use sha2::{Digest, Sha256};
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use ureq::unversioned::resolver::DefaultResolver;
+use ureq::unversioned::transport::{
+    Buffers, ConnectProxyConnector, ConnectionDetails, Connector, Either, LazyBuffers, NextTimeout,
+    SocksConnector, TcpConnector, Transport, TransportAdapter,
+};
+
+/// 解析 --pin-sha256 的值：base64 编码的 sha256（可带 "sha256//" 前缀，与 curl 的写法一致）
+pub fn parse_pin(value: &str) -> io::Result<[u8; 32]> {
+    let b64 = value.trim().trim_start_matches("sha256//");
+    STANDARD
+        .decode(b64)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid --pin-sha256 value (expected base64 sha256): {}",
+                    value
+                ),
+            )
+        })
+}
+
+/// 创建启用证书固定的请求客户端：在正常证书校验通过后，
+/// 还要求服务器证书（end-entity）的公钥（SPKI）或证书本身的 sha256 命中固定值。
+/// 服务器发送的中间证书未经校验、任何人都可以附带，不参与匹配
+pub fn pinned_agent(config: ureq::config::Config, pins: &[[u8; 32]]) -> io::Result<ureq::Agent> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let roots = Arc::new(RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    });
+    let inner = WebPkiServerVerifier::builder_with_provider(roots, provider.clone())
+        .build()
+        .map_err(io::Error::other)?;
+    let verifier = PinnedVerifier {
+        inner,
+        pins: pins.to_vec(),
+    };
+    let tls = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+
+    // 与 ureq 默认连接链一致（SOCKS / CONNECT 代理 / TCP），仅替换 TLS 层
+    let connector =
+        ().chain(SocksConnector::default())
+            .chain(ConnectProxyConnector::default())
+            .chain(TcpConnector::default())
+            .chain(PinnedTlsConnector {
+                config: Arc::new(tls),
+            });
+    Ok(ureq::Agent::with_parts(
+        config,
+        connector,
+        DefaultResolver::default(),
+    ))
+}
+
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl PinnedVerifier {
+    fn matches(&self, cert: &CertificateDer<'_>) -> bool {
+        let cert_hash: [u8; 32] = Sha256::digest(cert.as_ref()).into();
+        let spki_hash: Option<[u8; 32]> = spki(cert.as_ref()).map(|s| Sha256::digest(s).into());
+        self.pins
+            .iter()
+            .any(|pin| *pin == cert_hash || Some(*pin) == spki_hash)
+    }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        if self.matches(end_entity) {
+            Ok(verified)
+        } else {
+            Err(rustls::Error::General(
+                "server certificate does not match --pin-sha256".to_string(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// 从 DER 证书中取出 SubjectPublicKeyInfo（含 TLV 头）
+fn spki(cert: &[u8]) -> Option<&[u8]> {
+    // Certificate ::= SEQUENCE { tbsCertificate, ... }
+    let (_, cert_body, _) = der_tlv(cert)?;
+    let (_, mut tbs, _) = der_tlv(cert_body)?;
+    // 跳过可选的 [0] version
+    if tbs.first() == Some(&0xa0) {
+        tbs = der_tlv(tbs)?.2;
+    }
+    // serialNumber, signature, issuer, validity, subject
+    for _ in 0..5 {
+        tbs = der_tlv(tbs)?.2;
+    }
+    let (whole, _, _) = der_tlv(tbs)?;
+    Some(whole)
+}
+
+/// 解析一个 DER TLV，返回 (完整 TLV, 内容, 剩余字节)
+fn der_tlv(data: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let first = *data.get(1)?;
+    let (len, header) = if first < 0x80 {
+        (first as usize, 2)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let len = data
+            .get(2..2 + n)?
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, 2 + n)
+    };
+    let end = header.checked_add(len)?;
+    Some((data.get(..end)?, data.get(header..end)?, data.get(end..)?))
+}
+
+struct PinnedTlsConnector {
+    config: Arc<ClientConfig>,
+}
+
+impl std::fmt::Debug for PinnedTlsConnector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PinnedTlsConnector").finish()
+    }
+}
+
+impl<In: Transport> Connector<In> for PinnedTlsConnector {
+    type Out = Either<In, PinnedTlsTransport>;
+
+    fn connect(
+        &self,
+        details: &ConnectionDetails,
+        chained: Option<In>,
+    ) -> Result<Option<Self::Out>, ureq::Error> {
+        let Some(transport) = chained else {
+            return Ok(None);
+        };
+        if !details.needs_tls() || transport.is_tls() {
+            return Ok(Some(Either::A(transport)));
+        }
+
+        let host = details
+            .uri
+            .host()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let name = ServerName::try_from(host).map_err(|_| ureq::Error::Tls("invalid dns name"))?;
+
+        let mut conn = ClientConnection::new(self.config.clone(), name)?;
+        let mut sock = TransportAdapter::new(transport.boxed());
+        sock.set_timeout(details.timeout);
+        conn.complete_io(&mut sock)?;
+
+        Ok(Some(Either::B(PinnedTlsTransport {
+            buffers: LazyBuffers::new(
+                details.config.input_buffer_size(),
+                details.config.output_buffer_size(),
+            ),
+            stream: StreamOwned { conn, sock },
+        })))
+    }
+}
+
+struct PinnedTlsTransport {
+    buffers: LazyBuffers,
+    stream: StreamOwned<ClientConnection, TransportAdapter>,
+}
+
+impl std::fmt::Debug for PinnedTlsTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PinnedTlsTransport").finish()
+    }
+}
+
+impl Transport for PinnedTlsTransport {
+    fn buffers(&mut self) -> &mut dyn Buffers {
+        &mut self.buffers
+    }
+
+    fn transmit_output(&mut self, amount: usize, timeout: NextTimeout) -> Result<(), ureq::Error> {
+        self.stream.get_mut().set_timeout(timeout);
+        let output = &self.buffers.output()[..amount];
+        self.stream.write_all(output)?;
+        Ok(())
+    }
+
+    fn await_input(&mut self, timeout: NextTimeout) -> Result<bool, ureq::Error> {
+        self.stream.get_mut().set_timeout(timeout);
+        let input = self.buffers.input_append_buf();
+        let amount = self.stream.read(input)?;
+        self.buffers.input_appended(amount);
+        Ok(amount > 0)
+    }
+
+    fn is_open(&mut self) -> bool {
+        self.stream.get_mut().get_mut().is_open()
+    }
+
+    fn is_tls(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 以短格式或长格式长度编码一个 TLV
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        match content.len() {
+            len @ 0..0x80 => out.push(len as u8),
+            len @ 0x80..0x100 => out.extend([0x81, len as u8]),
+            len => out.extend([0x82, (len >> 8) as u8, len as u8]),
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    fn cert(with_version: bool, spki: &[u8]) -> Vec<u8> {
+        let mut tbs = Vec::new();
+        if with_version {
+            tbs.extend(tlv(0xa0, &tlv(0x02, &[2])));
+        }
+        tbs.extend(tlv(0x02, &[1, 2, 3])); // serialNumber
+        tbs.extend(tlv(0x30, &tlv(0x06, &[0x2a, 0x86, 0x48]))); // signature
+        tbs.extend(tlv(0x30, b"issuer")); // issuer
+        tbs.extend(tlv(0x30, &[0x17; 200])); // validity，长格式长度
+        tbs.extend(tlv(0x30, b"subject")); // subject
+        tbs.extend_from_slice(spki);
+        tbs.extend(tlv(0xa3, b"extensions"));
+        let mut body = tlv(0x30, &tbs);
+        body.extend(tlv(0x30, &tlv(0x06, &[0x2a, 0x86, 0x48])));
+        body.extend(tlv(0x03, &[0, 1, 2, 3]));
+        tlv(0x30, &body)
+    }
+
+    #[test]
+    fn der_tlv_short_and_long_form() {
+        let data = [0x04, 0x02, 0xaa, 0xbb, 0xff];
+        assert_eq!(der_tlv(&data), Some((&data[..4], &data[2..4], &data[4..])));
+        let long = tlv(0x04, &[7; 300]);
+        let (whole, content, rest) = der_tlv(&long).unwrap();
+        assert_eq!((whole.len(), content.len(), rest.len()), (304, 300, 0));
+    }
+
+    #[test]
+    fn der_tlv_rejects_malformed() {
+        // 缺少长度、内容被截断、长度字节数为 0 或超过 4、长度字节被截断
+        for data in [
+            &[0x30][..],
+            &[0x30, 0x05, 0x00],
+            &[0x30, 0x80],
+            &[0x30, 0x85, 1, 1, 1, 1, 1],
+            &[0x30, 0x82, 0x01],
+            &[0x30, 0x84, 0xff, 0xff, 0xff, 0xff],
+        ] {
+            assert_eq!(der_tlv(data), None, "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn spki_of_certificate() {
+        let key = tlv(
+            0x30,
+            &[tlv(0x30, &[0x06, 0x01, 0x2a]), tlv(0x03, &[0, 9, 9])].concat(),
+        );
+        assert_eq!(spki(&cert(true, &key)), Some(&key[..]));
+        assert_eq!(spki(&cert(false, &key)), Some(&key[..]));
+    }
+
+    #[test]
+    fn spki_of_malformed_certificate() {
+        let key = tlv(0x30, &[0x05, 0x00]);
+        let der = cert(true, &key);
+        // 截断在任何位置都不能 panic，且截断到 SPKI 之前时返回 None
+        for len in 0..der.len() {
+            let _ = spki(&der[..len]);
+        }
+        assert_eq!(spki(&der[..20]), None);
+        assert_eq!(spki(&[]), None);
+        assert_eq!(spki(&tlv(0x30, &tlv(0x30, b""))), None);
+    }
+
+    #[test]
+    fn parse_pin_accepts_curl_prefix() {
+        let pin = STANDARD.encode([5u8; 32]);
+        assert_eq!(parse_pin(&format!("sha256//{}", pin)).unwrap(), [5; 32]);
+        assert_eq!(parse_pin(&pin).unwrap(), [5; 32]);
+        assert!(parse_pin("sha256//AAAA").is_err());
+        assert!(parse_pin("not base64!").is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! 可配置的更新流程：默认依次为 kill → stage → swap → verify → cleanup → restart，
+//! 可以调整顺序、省略步骤或重复 verify，如先暂存再退出应用（`stage,kill,swap,...`）以缩短停机时间
+
+use crate::Layout;
+
+/// 更新流程中的步骤
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Step {
+    /// 执行 pre-kill 钩子并退出应用
+    Kill,
+    /// 把更新文件暂存到 `<output>_new` 并执行 post-stage 钩子；省略时在 swap 中暂存
+    Stage,
+    /// 执行 pre-swap 钩子并替换，之后执行 post-swap 钩子（在紧随其后的 verify 之后）
+    Swap,
+    /// 核对版本号（`expected_version`）：swap 之前检查暂存目录，之后检查 output，不一致时回滚
+    Verify,
+    /// 删除 input 与旧版本备份；省略时保留，可通过 rollback 恢复
+    Cleanup,
+    /// 重新启动应用并执行 post-restart 钩子
+    Restart,
+}
+
+/// 默认流程
+pub const DEFAULT: [Step; 6] = [
+    Step::Kill,
+    Step::Stage,
+    Step::Swap,
+    Step::Verify,
+    Step::Cleanup,
+    Step::Restart,
+];
+
+impl Step {
+    pub fn name(self) -> &'static str {
+        match self {
+            Step::Kill => "kill",
+            Step::Stage => "stage",
+            Step::Swap => "swap",
+            Step::Verify => "verify",
+            Step::Cleanup => "cleanup",
+            Step::Restart => "restart",
+        }
+    }
+}
+
+/// 检查流程是否可以执行：swap 恰好一次，kill、stage 在 swap 之前，cleanup、restart 在其之后，
+/// 除 verify 外的步骤最多一次；swap 之后的 verify 必须在 cleanup、restart 之前（回滚需要备份，
+/// 且应用尚未启动）。只有 resources 布局能单独暂存，其他布局的 stage 必须紧挨 swap，swap 前不能 verify
+pub fn check(steps: &[Step], layout: Layout) -> Result<(), String> {
+    let invalid = |reason: String| {
+        let names: Vec<_> = steps.iter().map(|s| s.name()).collect();
+        Err(format!("Invalid pipeline {}: {}", names.join(","), reason))
+    };
+    let position = |step: Step| steps.iter().position(|s| *s == step);
+    for step in DEFAULT.into_iter().filter(|s| *s != Step::Verify) {
+        if steps.iter().filter(|s| **s == step).count() > 1 {
+            return invalid(format!("{} appears more than once", step.name()));
+        }
+    }
+    let Some(swap) = position(Step::Swap) else {
+        return invalid("swap is required".to_string());
+    };
+    for step in [Step::Kill, Step::Stage] {
+        if position(step).is_some_and(|i| i > swap) {
+            return invalid(format!("{} must come before swap", step.name()));
+        }
+    }
+    for step in [Step::Cleanup, Step::Restart] {
+        if position(step).is_some_and(|i| i < swap) {
+            return invalid(format!("{} must come after swap", step.name()));
+        }
+    }
+    let stage = position(Step::Stage);
+    let finishing = steps[swap + 1..]
+        .iter()
+        .position(|s| *s != Step::Verify)
+        .map_or(steps.len(), |i| swap + 1 + i);
+    for (i, step) in steps.iter().enumerate() {
+        if *step != Step::Verify {
+            continue;
+        }
+        if i > finishing {
+            return invalid("verify must come before cleanup and restart".to_string());
+        }
+        if i < swap && stage.is_none_or(|s| s > i) {
+            return invalid("verify before swap requires stage first".to_string());
+        }
+    }
+    if layout != Layout::Resources
+        && (stage.is_some_and(|s| s + 1 != swap) || steps[..swap].contains(&Step::Verify))
+    {
+        return invalid("only the resources layout can stage separately from swap".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Step::*;
+
+    #[test]
+    fn accepts_valid_pipelines() {
+        for steps in [
+            &DEFAULT[..],
+            &[Stage, Kill, Swap, Verify, Restart, Cleanup],
+            &[Kill, Stage, Verify, Swap, Verify, Cleanup, Restart],
+            &[Swap],
+        ] {
+            assert_eq!(check(steps, Layout::Resources), Ok(()), "{:?}", steps);
+        }
+        assert_eq!(
+            check(&[Kill, Stage, Swap, Restart], Layout::Squirrel),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_pipelines() {
+        for (steps, reason) in [
+            (&[Kill, Stage, Cleanup, Restart][..], "swap is required"),
+            (&[Kill, Kill, Swap], "kill appears more than once"),
+            (&[Swap, Stage], "stage must come before swap"),
+            (&[Cleanup, Swap], "cleanup must come after swap"),
+            (&[Swap, Restart, Verify], "verify must come before"),
+            (&[Verify, Stage, Swap], "verify before swap requires stage"),
+        ] {
+            let error = check(steps, Layout::Resources).unwrap_err();
+            assert!(error.contains(reason), "{:?}: {}", steps, error);
+        }
+        for steps in [&[Stage, Kill, Swap][..], &[Stage, Verify, Swap]] {
+            let error = check(steps, Layout::Squirrel).unwrap_err();
+            assert!(error.contains("only the resources layout"), "{}", error);
+        }
+    }
+}
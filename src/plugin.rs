@@ -0,0 +1,158 @@
+//! WASM 插件：以 WebAssembly 组件实现的钩子（接口见 `wit/plugin.wit`），用于自定义校验、遥测、
+//! 授权文件迁移等步骤，而不必允许执行任意命令。插件运行在沙箱中：没有 WASI，不能访问网络或执行命令，
+//! 只能通过宿主提供的函数写日志、读写 output 与暂存位置之内的文件；内存与执行的指令数都有上限
+
+use crate::hooks::{Hook, HookContext, LifecycleHook};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use wasmtime::component::{Component, HasSelf, Linker, bindgen};
+use wasmtime::{Config, Engine, Store, StoreLimits, StoreLimitsBuilder};
+
+bindgen!({ path: "wit", world: "plugin" });
+
+/// 每次调用可执行的指令预算（fuel），防止插件死循环
+const FUEL: u64 = 5_000_000_000;
+
+/// 插件可使用的内存上限
+const MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+
+/// 已编译的插件，每个钩子位置在新的实例中调用其 `run`
+pub struct WasmPlugin {
+    name: String,
+    engine: Engine,
+    component: Component,
+    linker: Linker<State>,
+}
+
+impl WasmPlugin {
+    /// 读取并编译插件（WASM 组件的二进制文件）
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |e: wasmtime::Error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid plugin {}: {}", path.display(), e),
+            )
+        };
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(invalid)?;
+        let component = Component::new(&engine, fs::read(path)?).map_err(invalid)?;
+        let mut linker = Linker::new(&engine);
+        Plugin::add_to_linker::<_, HasSelf<_>>(&mut linker, |state| state).map_err(invalid)?;
+        Ok(Self {
+            name: path
+                .file_stem()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+            engine,
+            component,
+            linker,
+        })
+    }
+}
+
+impl LifecycleHook for WasmPlugin {
+    fn run(&self, hook: Hook, ctx: &HookContext) -> io::Result<()> {
+        let roots = [Some(ctx.output), ctx.staging]
+            .into_iter()
+            .flatten()
+            .filter_map(|p| fs::canonicalize(p).ok())
+            .collect();
+        let state = State {
+            roots,
+            messages: Vec::new(),
+            limits: StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build(),
+        };
+        let mut store = Store::new(&self.engine, state);
+        store.limiter(|state| &mut state.limits);
+        // 陷入（trap）的完整信息带有多行的 WASM 调用栈，只保留原因
+        let failed = |e: wasmtime::Error| {
+            io::Error::other(format!("plugin {}: {}", self.name, e.root_cause()))
+        };
+        store.set_fuel(FUEL).map_err(failed)?;
+        let plugin =
+            Plugin::instantiate(&mut store, &self.component, &self.linker).map_err(failed)?;
+        let string = |p: &Path| p.to_string_lossy().into_owned();
+        let context = Context {
+            hook: hook.name().to_string(),
+            output: string(ctx.output),
+            app: string(ctx.app),
+            staging: ctx.staging.map(string),
+            version: ctx.version.map(str::to_string),
+            old_version: ctx.old_version.map(str::to_string),
+        };
+        let result = plugin.call_run(&mut store, &context);
+        for message in store.data_mut().messages.drain(..) {
+            ctx.logger.log(&format!("[{}] {}", self.name, message));
+        }
+        match result.map_err(failed)? {
+            Ok(()) => Ok(()),
+            Err(message) => Err(io::Error::other(format!(
+                "plugin {}: {}",
+                self.name, message
+            ))),
+        }
+    }
+}
+
+/// 一次调用的宿主状态
+struct State {
+    /// 插件可以读写的目录（已解析符号链接）
+    roots: Vec<PathBuf>,
+    /// 调用结束后写入日志的消息
+    messages: Vec<String>,
+    limits: StoreLimits,
+}
+
+impl State {
+    /// 解析插件传入的路径，不在 output 或暂存位置之内、或为符号链接时拒绝
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let path = Path::new(path);
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(format!("Invalid path: {}", path.display()));
+        };
+        let dir = fs::canonicalize(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        let resolved = dir.join(name);
+        if !self
+            .roots
+            .iter()
+            .any(|root| dir.starts_with(root) || resolved == *root)
+        {
+            return Err(format!(
+                "{} is outside the output and staging directories",
+                path.display()
+            ));
+        }
+        if resolved.is_symlink() {
+            return Err(format!("{} is a symbolic link", path.display()));
+        }
+        Ok(resolved)
+    }
+}
+
+impl eqi::plugin::host::Host for State {
+    fn log(&mut self, message: String) {
+        self.messages.push(message);
+    }
+
+    fn read_file(&mut self, path: String) -> Result<Vec<u8>, String> {
+        let resolved = self.resolve(&path)?;
+        fs::read(resolved).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn write_file(&mut self, path: String, data: Vec<u8>) -> Result<(), String> {
+        let resolved = self.resolve(&path)?;
+        // 暂存目录中未更新的文件是旧版本的硬链接，先删除再写入，不修改备份
+        match fs::remove_file(&resolved) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(format!("{}: {}", path, e));
+            }
+            _ => {}
+        }
+        fs::write(resolved, data).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+impl eqi::plugin::types::Host for State {}
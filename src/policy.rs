@@ -0,0 +1,325 @@
+//! 输出路径策略：防止错误的调用（或被注入的启动参数）把 output 指向系统目录。
+//!
+//! 内置规则总是生效：系统目录（如 C:\Windows、/etc、/usr/bin）及其子目录、
+//! 常见的上级目录本身（如文件系统根目录、C:\Program Files、/usr、用户主目录）不能作为 output。
+//! 更新器同级目录下的 `eqi-policy.yml` 可以追加拒绝的目录，或把 output 限制在允许的目录之下：
+//!
+//! ```yaml
+//! allow:
+//!   - C:\Program Files\MyApp
+//! deny:
+//!   - D:\Data
+//! ```
+
+use serde::Deserialize;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 策略文件名，位于更新器同级目录
+pub const FILE_NAME: &str = "eqi-policy.yml";
+
+/// 输出路径策略；默认只有内置规则
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Policy {
+    /// 非空时 output 必须位于其中某个目录之下（含目录本身）
+    #[serde(default)]
+    pub allow: Vec<PathBuf>,
+    /// 拒绝的目录（含其子目录）
+    #[serde(default)]
+    pub deny: Vec<PathBuf>,
+}
+
+impl Policy {
+    /// 读取策略文件（YAML 或 JSON）
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_yaml::from_str(&text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid policy file {}: {}", path.display(), e),
+            )
+        })
+    }
+
+    /// 读取更新器同级目录下的策略文件，不存在时返回默认策略
+    pub fn load_default() -> io::Result<Self> {
+        let exe = std::env::current_exe()?;
+        let path = exe.with_file_name(FILE_NAME);
+        match Self::load(&path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            result => result,
+        }
+    }
+
+    /// 检查 output 是否允许更新，不允许时返回原因
+    pub fn check(&self, output: &Path) -> Result<(), String> {
+        let path = key(output);
+        let denied = |reason: &str| {
+            Err(format!(
+                "Output {} is not permitted: {}",
+                output.display(),
+                reason
+            ))
+        };
+        // SAFETY: key 由路径的编码字节（Windows 上为 UTF-8 字符串）得到，去掉的只是 ASCII 分隔符
+        let root = Path::new(unsafe { OsStr::from_encoded_bytes_unchecked(&path) })
+            .parent()
+            .is_none();
+        if path.is_empty() || root {
+            return denied("it is a filesystem root");
+        }
+        for dir in protected_dirs() {
+            if within(&path, &key(&dir)) {
+                return denied(&format!("{} is a system directory", dir.display()));
+            }
+        }
+        for dir in parent_dirs() {
+            if path == key(&dir) {
+                return denied(&format!(
+                    "{} holds other applications or data",
+                    dir.display()
+                ));
+            }
+        }
+        for dir in &self.deny {
+            if within(&path, &key(dir)) {
+                return denied(&format!("{} is denied by policy", dir.display()));
+            }
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|dir| within(&path, &key(dir))) {
+            return denied("it is outside the directories allowed by policy");
+        }
+        Ok(())
+    }
+}
+
+/// 递归删除的路径至少应有的层级（根目录下的名称个数），如 `C:\Temp`、`/tmp` 过浅
+const MIN_REMOVABLE_DEPTH: usize = 2;
+
+/// 清理（删除 input 与备份）前检查 path 是否可以删除，不可以时返回原因：文件系统根目录、系统目录及其子目录、
+/// 存放其他应用或数据的上级目录与其上级、用户主目录的直接子目录（如 Documents、Desktop）以及层级过浅的路径。
+/// 防止 `--input` 写错（如误写为用户主目录）时删除无关的数据
+pub fn check_removable(path: &Path) -> Result<(), String> {
+    let path_key = key(path);
+    let refused =
+        |reason: String| Err(format!("Refusing to delete {}: {}", path.display(), reason));
+    for dir in protected_dirs() {
+        if within(&path_key, &key(&dir)) {
+            return refused(format!("{} is a system directory", dir.display()));
+        }
+    }
+    for dir in parent_dirs() {
+        if within(&key(&dir), &path_key) {
+            return refused(format!("it holds {}", dir.display()));
+        }
+    }
+    let home = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    if let Some(home) = std::env::var_os(home).map(PathBuf::from)
+        && std::path::absolute(path)
+            .map(|p| crate::normalize(&p))
+            .is_ok_and(|p| p.parent() == Some(&home))
+    {
+        return refused("it is a folder in the home directory".to_string());
+    }
+    // SAFETY: 同 Policy::check
+    let resolved = Path::new(unsafe { OsStr::from_encoded_bytes_unchecked(&path_key) });
+    let depth = resolved
+        .components()
+        .filter(|c| matches!(c, std::path::Component::Normal(_)))
+        .count();
+    if depth < MIN_REMOVABLE_DEPTH {
+        return refused("the path is too close to the filesystem root".to_string());
+    }
+    Ok(())
+}
+
+/// 检查 input、output、app 的位置关系：input 与 output、`<output>_new`、`<output>_old` 互不相同且互不包含
+/// （否则复制时无限递归，或清理时删除更新文件），app 不能位于 input、`<output>_new`、`<output>_old` 之内；
+/// app_in_output 为 false 时 app 也不能是 output 本身。app 位于 output 之内是允许的（整个安装目录作为 output）
+pub fn check_overlap(
+    input: &Path,
+    output: &Path,
+    app: &Path,
+    app_in_output: bool,
+) -> Result<(), String> {
+    let sibling = |suffix: &str| {
+        let mut path = output.as_os_str().to_owned();
+        path.push(suffix);
+        PathBuf::from(path)
+    };
+    let (output_new, output_old) = (sibling("_new"), sibling("_old"));
+    for (name, path) in [
+        ("output", output),
+        ("<output>_new", &output_new),
+        ("<output>_old", &output_old),
+    ] {
+        if overlaps(input, path) {
+            return Err(format!(
+                "Input {} overlaps {} {}",
+                input.display(),
+                name,
+                path.display()
+            ));
+        }
+    }
+    let app_key = key(app);
+    for (name, path) in [
+        ("input", input),
+        ("<output>_new", &output_new),
+        ("<output>_old", &output_old),
+    ] {
+        if within(&app_key, &key(path)) {
+            return Err(format!(
+                "App {} is inside {} {}",
+                app.display(),
+                name,
+                path.display()
+            ));
+        }
+    }
+    if !app_in_output && app_key == key(output) {
+        return Err(format!("App {} is the output itself", app.display()));
+    }
+    Ok(())
+}
+
+/// 两个路径是否相同或一个位于另一个之内
+pub fn overlaps(a: &Path, b: &Path) -> bool {
+    let (a, b) = (key(a), key(b));
+    within(&a, &b) || within(&b, &a)
+}
+
+/// path 是否为 dir 本身或位于其下
+fn within(path: &[u8], dir: &[u8]) -> bool {
+    let sep = std::path::MAIN_SEPARATOR as u8;
+    let dir = dir.strip_suffix(&[sep]).unwrap_or(dir);
+    path == dir
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.first() == Some(&sep))
+}
+
+/// 用于比较的路径：存在时解析符号链接，否则按路径规范化；Windows 上不区分大小写，
+/// 其他平台比较原始字节（非 UTF-8 的路径不会因替换字符被视为相同）
+fn key(path: &Path) -> Vec<u8> {
+    let resolved = fs::canonicalize(path).unwrap_or_else(|_| {
+        let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        crate::normalize(&absolute)
+    });
+    if cfg!(windows) {
+        let key = resolved.to_string_lossy();
+        let key = key.strip_prefix(r"\\?\").unwrap_or(&key);
+        let key = key.trim_end_matches(std::path::MAIN_SEPARATOR);
+        return key.replace('/', "\\").to_lowercase().into_bytes();
+    }
+    // 根目录保留 /，其他路径去掉结尾的分隔符
+    let key = resolved.as_os_str().as_encoded_bytes();
+    match key.strip_suffix(b"/") {
+        Some(rest) if !rest.is_empty() => rest.to_vec(),
+        _ => key.to_vec(),
+    }
+}
+
+/// 系统目录，本身及其子目录都不能作为 output
+fn protected_dirs() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        let root = std::env::var_os("SystemRoot").unwrap_or_else(|| r"C:\Windows".into());
+        vec![PathBuf::from(root)]
+    } else {
+        [
+            "/bin",
+            "/boot",
+            "/dev",
+            "/etc",
+            "/lib",
+            "/lib32",
+            "/lib64",
+            "/proc",
+            "/sbin",
+            "/sys",
+            "/usr/bin",
+            "/usr/sbin",
+            "/System",
+            "/private/etc",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect()
+    }
+}
+
+/// 存放其他应用或数据的上级目录，本身不能作为 output，其子目录可以
+fn parent_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = if cfg!(windows) {
+        [
+            "ProgramFiles",
+            "ProgramFiles(x86)",
+            "ProgramData",
+            "LOCALAPPDATA",
+            "APPDATA",
+        ]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from)
+        .chain(
+            std::env::var_os("USERPROFILE")
+                .map(PathBuf::from)
+                .and_then(|p| p.parent().map(Path::to_path_buf)),
+        )
+        .collect()
+    } else {
+        [
+            "/usr",
+            "/usr/lib",
+            "/usr/local",
+            "/usr/share",
+            "/opt",
+            "/var",
+            "/home",
+            "/Users",
+            "/Applications",
+            "/Library",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect()
+    };
+    let home = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    dirs.extend(std::env::var_os(home).map(PathBuf::from));
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_overlap_of_paths() {
+        let root = std::env::temp_dir().join(format!("eqi-overlap-{}", std::process::id()));
+        let output = root.join("app/resources");
+        let app = root.join("app/MyApp");
+        for (input, app, app_in_output, ok) in [
+            (root.join("update"), app.clone(), false, true),
+            (root.join("app/resources_newer"), app.clone(), false, true),
+            (output.join("update"), app.clone(), false, false),
+            (root.join("app"), app.clone(), false, false),
+            (root.join("app/resources_new"), app.clone(), false, false),
+            (root.join("app/resources_old/x"), app.clone(), false, false),
+            (root.join("update"), root.join("update/MyApp"), false, false),
+            (
+                root.join("update"),
+                root.join("app/resources_old/MyApp"),
+                false,
+                false,
+            ),
+            (root.join("update"), output.join("MyApp"), false, true),
+            (root.join("update"), output.clone(), false, false),
+            (root.join("update"), output.clone(), true, true),
+        ] {
+            let result = check_overlap(&input, &output, &app, app_in_output);
+            assert_eq!(result.is_ok(), ok, "{:?} {:?}: {:?}", input, app, result);
+        }
+    }
+}
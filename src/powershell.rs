@@ -0,0 +1,20 @@
+use std::io;
+
+/// 执行 PowerShell 命令，返回去掉首尾空白的标准输出
+pub fn run(script: &str) -> io::Result<String> {
+    let out = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "PowerShell failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// PowerShell 单引号字符串字面量
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
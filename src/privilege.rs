@@ -0,0 +1,260 @@
+//! 降权：以管理员身份替换文件后，重新启动应用与 post-restart 钩子不应继续以管理员身份运行。
+//!
+//! - Unix 上通过 sudo/pkexec 以 root 运行时，最后一个需要权限的步骤完成后把整个进程切换回调用者
+//!   （SUDO_UID/PKEXEC_UID）；此前重新启动的应用单独以调用者身份启动（见 [`run_as`]）。
+//!   本进程的环境变量保持不变，调用者的 HOME 等只设置在之后启动的子进程上
+//! - Windows 上无法在进程内放弃管理员令牌：自动提权的进程把重新启动交接给未提权的原进程，
+//!   由其读取交接文件后完成（见 [`Handoff`]）
+
+use crate::hooks::{Hook, HookContext, LifecycleHook};
+use crate::{Logger, restart_app};
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 提权进程交给未提权进程完成的步骤
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Handoff {
+    pub output: PathBuf,
+    /// 需要重新启动的应用
+    pub app: PathBuf,
+    pub version: Option<String>,
+    /// 更新前已安装的版本
+    #[serde(default)]
+    pub old_version: Option<String>,
+    /// 更新已完成，重新启动后还需执行 post-restart 钩子
+    pub post_restart: bool,
+}
+
+impl Handoff {
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec(self)?)
+    }
+
+    /// 读取并删除交接文件；提权进程没有写入（未退出应用或已失败）时返回 None
+    pub fn take(path: &Path) -> io::Result<Option<Self>> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let _ = fs::remove_file(path);
+        Ok(Some(serde_json::from_slice(&data)?))
+    }
+
+    /// 以当前（未提权的）身份重新启动应用，更新已完成时接着执行 post-restart 钩子
+    pub fn complete(&self, hooks: &[&dyn LifecycleHook], logger: &Logger) {
+        restart_app(&self.app, logger);
+        if !self.post_restart {
+            return;
+        }
+        let ctx = HookContext {
+            output: &self.output,
+            app: &self.app,
+            staging: None,
+            version: self.version.as_deref(),
+            old_version: self.old_version.as_deref(),
+            user: None,
+            logger,
+        };
+        for hook in hooks {
+            logger.log(&format!("Running {} hook", Hook::PostRestart.name()));
+            if let Err(e) = hook.run(Hook::PostRestart, &ctx) {
+                logger.warn(&format!("{} hook failed: {}", Hook::PostRestart.name(), e));
+            }
+        }
+    }
+}
+
+/// 以 sudo/pkexec 提权运行本程序的用户
+#[derive(Clone, Debug)]
+pub struct Caller {
+    pub uid: u32,
+    pub gid: u32,
+    /// 用户名，查不到用户记录时为 None
+    pub name: Option<CString>,
+    pub home: Option<PathBuf>,
+}
+
+/// 以 root 运行且能确定调用者时返回调用者
+#[cfg(unix)]
+pub fn caller() -> Option<Caller> {
+    use std::ffi::CStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    // SAFETY: geteuid 总是成功
+    if unsafe { libc::geteuid() } != 0 {
+        return None;
+    }
+    let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u32>().ok());
+    let uid = var("SUDO_UID")
+        .or_else(|| var("PKEXEC_UID"))
+        .filter(|&uid| uid != 0)?;
+    // SAFETY: getpwuid 返回的记录在下一次调用前有效，此处立即复制所需字段
+    unsafe {
+        let pw = libc::getpwuid(uid);
+        if pw.is_null() {
+            return Some(Caller {
+                uid,
+                gid: var("SUDO_GID").unwrap_or(uid),
+                name: None,
+                home: None,
+            });
+        }
+        let home = CStr::from_ptr((*pw).pw_dir).to_bytes();
+        Some(Caller {
+            uid,
+            gid: (*pw).pw_gid,
+            name: Some(CStr::from_ptr((*pw).pw_name).to_owned()),
+            home: Some(PathBuf::from(std::ffi::OsStr::from_bytes(home))),
+        })
+    }
+}
+
+#[cfg(not(unix))]
+pub fn caller() -> Option<Caller> {
+    None
+}
+
+/// 以 root 运行且能确定调用者时，把进程的用户、组切换为调用者；返回切换到的调用者，
+/// 之后启动的子进程通过 [`run_as`] 获得其 HOME 等环境变量。切换不可逆
+#[cfg(unix)]
+pub fn drop_privileges(logger: &Logger) -> io::Result<Option<Caller>> {
+    let Some(caller) = caller() else {
+        return Ok(None);
+    };
+    // SAFETY: 先设置附加组（initgroups 按用户记录，查不到用户时只保留其主组）与组，最后放弃用户；
+    // name 是有效的 C 字符串，任一步失败都返回错误
+    unsafe {
+        let groups = match &caller.name {
+            Some(name) => libc::initgroups(name.as_ptr(), caller.gid as _),
+            None => libc::setgroups(1, &caller.gid),
+        };
+        if groups != 0 || libc::setgid(caller.gid) != 0 || libc::setuid(caller.uid) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    logger.log(&format!(
+        "Dropped privileges to uid {} gid {}",
+        caller.uid, caller.gid
+    ));
+    Ok(Some(caller))
+}
+
+#[cfg(not(unix))]
+pub fn drop_privileges(_logger: &Logger) -> io::Result<Option<Caller>> {
+    Ok(None)
+}
+
+/// 让 command 启动的子进程以 caller 的身份运行（附加组、组、用户与 HOME 等环境变量）。
+/// 本进程仍保留权限时（之后还有步骤需要权限）在子进程中切换身份，已降权（见 [`drop_privileges`]）时只设置环境变量
+#[cfg(unix)]
+pub fn run_as(command: &mut Command, caller: &Caller) {
+    use std::os::unix::process::CommandExt;
+
+    if let (Some(name), Some(home)) = (&caller.name, &caller.home) {
+        let name = name.to_string_lossy();
+        command
+            .env("HOME", home)
+            .env("USER", &*name)
+            .env("LOGNAME", &*name);
+    }
+    // SAFETY: geteuid 总是成功
+    if unsafe { libc::geteuid() } != 0 {
+        return;
+    }
+    let groups = match &caller.name {
+        Some(name) => group_list(name, caller.gid),
+        None => vec![caller.gid],
+    };
+    let (uid, gid) = (caller.uid, caller.gid);
+    // SAFETY: 闭包在 fork 之后、exec 之前运行，只调用异步信号安全的 setgroups/setgid/setuid，
+    // 组列表在 fork 之前已准备好，不分配内存
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setgroups(groups.len() as _, groups.as_ptr()) != 0
+                || libc::setgid(gid) != 0
+                || libc::setuid(uid) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn run_as(_command: &mut Command, _caller: &Caller) {}
+
+/// 用户所属的全部组（含主组 gid），查询失败时只返回 gid
+#[cfg(unix)]
+fn group_list(name: &std::ffi::CStr, gid: u32) -> Vec<libc::gid_t> {
+    let mut count: libc::c_int = 64;
+    while count <= 65536 {
+        let mut groups: Vec<libc::gid_t> = vec![0; count as usize];
+        // SAFETY: groups 有 count 个元素，getgrouplist 最多写入 count 个，并在 count 中返回实际数量；
+        // macOS 上的组类型为 c_int，与 gid_t 大小相同
+        let ret = unsafe {
+            libc::getgrouplist(
+                name.as_ptr(),
+                gid as _,
+                groups.as_mut_ptr().cast(),
+                &mut count,
+            )
+        };
+        if ret >= 0 {
+            groups.truncate(count as usize);
+            return groups;
+        }
+        // 不够时 Linux 在 count 中返回所需数量，macOS 不更新
+        count = count.max(groups.len() as libc::c_int * 2);
+    }
+    vec![gid]
+}
+
+/// 以 root（Unix）或 SYSTEM（Windows）运行时返回有效用户的描述，否则返回 None
+#[cfg(unix)]
+pub fn privileged_user() -> Option<String> {
+    // SAFETY: geteuid 总是成功
+    (unsafe { libc::geteuid() } == 0).then(|| "root (uid 0)".to_string())
+}
+
+#[cfg(windows)]
+pub fn privileged_user() -> Option<String> {
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, IsWellKnownSid, TOKEN_QUERY, TOKEN_USER, TokenUser, WinLocalSystemSid,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    // SAFETY: buffer 按 u64 对齐且足够容纳 TOKEN_USER 及其 SID；令牌句柄使用后关闭
+    unsafe {
+        let mut token: HANDLE = std::ptr::null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return None;
+        }
+        let mut buffer = [0u64; 64];
+        let mut length = 0;
+        let ok = GetTokenInformation(
+            token,
+            TokenUser,
+            buffer.as_mut_ptr().cast(),
+            std::mem::size_of_val(&buffer) as u32,
+            &mut length,
+        );
+        CloseHandle(token);
+        if ok == 0 {
+            return None;
+        }
+        let user = &*(buffer.as_ptr() as *const TOKEN_USER);
+        (IsWellKnownSid(user.User.Sid, WinLocalSystemSid) != 0).then(|| "SYSTEM".to_string())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn privileged_user() -> Option<String> {
+    None
+}
@@ -0,0 +1,65 @@
+use std::path::Path;
+
+/// 更新过程的阶段
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// 下载更新包，进度单位为字节
+    Download,
+    /// 等待应用进程退出，进度单位为检查次数
+    WaitForExit,
+    /// 复制现有文件到暂存目录，进度单位为文件数
+    CopyExisting,
+    /// 复制更新文件到暂存目录，进度单位为文件数
+    CopyUpdate,
+    /// 用暂存目录替换目标
+    Swap,
+    /// 清理临时文件
+    Cleanup,
+    /// 重新启动应用
+    Restart,
+}
+
+impl Phase {
+    /// 用于进度展示的简短说明
+    pub fn label(self) -> &'static str {
+        match self {
+            Phase::Download => "Downloading",
+            Phase::WaitForExit => "Waiting for exit",
+            Phase::CopyExisting => "Copying existing files",
+            Phase::CopyUpdate => "Copying update files",
+            Phase::Swap => "Swapping",
+            Phase::Cleanup => "Cleaning up",
+            Phase::Restart => "Restarting app",
+        }
+    }
+}
+
+/// 进度观察者：嵌入方实现后通过 [`crate::UpdaterBuilder::observer`] 注册，
+/// 接收阶段变化、逐文件事件和字节数；所有方法都有空的默认实现
+pub trait ProgressObserver: Send + Sync {
+    /// 进入新阶段，total 为该阶段的总量，未知时为 None
+    fn phase_started(&self, _phase: Phase, _total: Option<u64>) {}
+
+    /// 当前阶段前进 delta（字节、文件或检查次数，取决于阶段）
+    fn advanced(&self, _delta: u64) {}
+
+    /// 正在复制的大文件（不小于 1 MB）已复制的字节数，由系统复制接口回调（Windows、macOS），
+    /// 其他平台只在复制完成时报告一次
+    fn copying(&self, _path: &Path, _copied: u64, _total: u64) {}
+
+    /// 已复制一个文件
+    fn file_copied(&self, _path: &Path, _bytes: u64) {}
+
+    /// 阶段结束
+    fn phase_finished(&self, _phase: Phase) {}
+
+    /// 是否在终端上绘制进度；为 true 时阶段进行中普通日志只写入日志文件
+    fn draws_on_console(&self) -> bool {
+        false
+    }
+
+    /// 临时隐藏终端上的进度后执行 f（用于输出警告和错误）
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        f()
+    }
+}
@@ -0,0 +1,150 @@
+use crate::Logger;
+use std::io;
+use std::path::Path;
+
+/// 更新「应用和功能 / 程序和功能」中的卸载信息：
+/// 在已有的 Uninstall\<key> 中写入 DisplayVersion、EstimatedSize（KB）和 InstallDate（yyyyMMdd），
+/// 依次查找当前用户、本机及 32 位视图下的注册表项，都不存在时跳过
+#[cfg(windows)]
+pub fn update_uninstall_entry(
+    key: &str,
+    install_dir: &Path,
+    version: Option<&str>,
+    logger: &Logger,
+) -> io::Result<()> {
+    use std::process::Command;
+
+    const UNINSTALL: &str = r"Software\Microsoft\Windows\CurrentVersion\Uninstall";
+    let candidates = [
+        format!(r"HKCU\{}\{}", UNINSTALL, key),
+        format!(r"HKLM\{}\{}", UNINSTALL, key),
+        format!(
+            r"HKLM\Software\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\{}",
+            key
+        ),
+    ];
+    let Some(path) = candidates.iter().find(|p| {
+        Command::new("reg")
+            .args(["query", p.as_str()])
+            .output()
+            .is_ok_and(|o| o.status.success())
+    }) else {
+        logger.warn(&format!(
+            "Uninstall registry key {} not found, skipping",
+            key
+        ));
+        return Ok(());
+    };
+
+    let size_kb = (dir_size(install_dir) / 1024).min(u32::MAX as u64);
+    let mut values = vec![
+        ("EstimatedSize", "REG_DWORD", size_kb.to_string()),
+        (
+            "InstallDate",
+            "REG_SZ",
+            chrono::Local::now().format("%Y%m%d").to_string(),
+        ),
+    ];
+    match version {
+        Some(version) => values.push(("DisplayVersion", "REG_SZ", version.to_string())),
+        None => logger.warn("Version unknown, DisplayVersion not updated"),
+    }
+
+    for (name, kind, data) in &values {
+        let out = Command::new("reg")
+            .args(["add", path, "/v", name, "/t", kind, "/d", data, "/f"])
+            .output()?;
+        if !out.status.success() {
+            return Err(io::Error::other(format!(
+                "Failed to write {}\\{}: {}",
+                path,
+                name,
+                String::from_utf8_lossy(&out.stderr).trim()
+            )));
+        }
+        logger.log(&format!("Set {}\\{} = {}", path, name, data));
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn update_uninstall_entry(
+    _key: &str,
+    _install_dir: &Path,
+    _version: Option<&str>,
+    logger: &Logger,
+) -> io::Result<()> {
+    logger.warn("Uninstall registry entries are only available on Windows, skipping");
+    Ok(())
+}
+
+/// 把 command 写入 RunOnce，下次登录时执行一次：优先写入本机（HKLM，需要管理员权限），
+/// 否则写入当前用户（HKCU）；值名称按 output 区分，同一应用只保留最后一次
+#[cfg(windows)]
+pub fn register_run_once(output: &Path, command: &[String], logger: &Logger) -> io::Result<()> {
+    use sha2::{Digest, Sha256};
+    use std::process::Command;
+
+    const RUN_ONCE: &str = r"Software\Microsoft\Windows\CurrentVersion\RunOnce";
+    let digest = Sha256::digest(output.to_string_lossy().as_bytes());
+    let name = format!(
+        "ElectronQuitAndInstall-{}",
+        &crate::download::hex(&digest)[..8]
+    );
+    let data = command
+        .iter()
+        .map(|arg| crate::service::quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    // RunOnce 不执行超过 260 个字符的命令
+    if data.len() > 260 {
+        logger.warn(&format!(
+            "RunOnce command is {} characters long, Windows may not run it",
+            data.len()
+        ));
+    }
+
+    let mut last_error = String::new();
+    for root in ["HKLM", "HKCU"] {
+        let key = format!(r"{}\{}", root, RUN_ONCE);
+        let out = Command::new("reg")
+            .args(["add", &key, "/v", &name, "/t", "REG_SZ", "/d", &data, "/f"])
+            .output()?;
+        if out.status.success() {
+            logger.log(&format!("Registered {}\\{} = {}", key, name, data));
+            return Ok(());
+        }
+        last_error = String::from_utf8_lossy(&out.stderr).trim().to_string();
+    }
+    Err(io::Error::other(format!(
+        "Failed to write RunOnce: {}",
+        last_error
+    )))
+}
+
+#[cfg(not(windows))]
+pub fn register_run_once(_output: &Path, _command: &[String], _logger: &Logger) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "RunOnce is only available on Windows",
+    ))
+}
+
+/// 目录下所有文件的总大小（字节）
+#[cfg(windows)]
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                dir_size(&path)
+            } else {
+                entry.metadata().map(|m| m.len()).unwrap_or(0)
+            }
+        })
+        .sum()
+}
@@ -0,0 +1,104 @@
+use crate::Logger;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+/// 读取本机稳定标识：优先使用系统的机器 ID，取不到时在更新器同级目录生成并保存一个
+fn machine_id() -> String {
+    if let Some(id) = system_machine_id() {
+        return id;
+    }
+
+    let exe = std::env::current_exe().unwrap_or_default();
+    let id_file = exe
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(".updater-id");
+    if let Ok(id) = fs::read_to_string(&id_file)
+        && !id.trim().is_empty()
+    {
+        return id.trim().to_string();
+    }
+    // 无随机数依赖，用时间、进程号与路径的哈希生成一次性 ID 后持久化
+    let seed = format!(
+        "{:?}-{}-{}",
+        std::time::SystemTime::now(),
+        std::process::id(),
+        exe.display()
+    );
+    let id: String = Sha256::digest(seed.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let _ = fs::write(&id_file, &id);
+    id
+}
+
+#[cfg(target_os = "linux")]
+fn system_machine_id() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|p| fs::read_to_string(p).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(target_os = "macos")]
+fn system_machine_id() -> Option<String> {
+    let out = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .find(|l| l.contains("IOPlatformUUID"))
+        .and_then(|l| l.split('"').nth(3))
+        .map(|s| s.to_string())
+}
+
+#[cfg(windows)]
+fn system_machine_id() -> Option<String> {
+    let out = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKLM\SOFTWARE\Microsoft\Cryptography",
+            "/v",
+            "MachineGuid",
+        ])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .find(|l| l.contains("MachineGuid"))
+        .and_then(|l| l.split_whitespace().last())
+        .map(|s| s.to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn system_machine_id() -> Option<String> {
+    None
+}
+
+/// 本机在某个版本灰度中的位置（0.0 ~ 100.0），同一机器、同一版本结果稳定
+fn machine_bucket(version: &str) -> f64 {
+    let hash = Sha256::digest(format!("{}:{}", machine_id(), version).as_bytes());
+    let n = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]);
+    n as f64 / u32::MAX as f64 * 100.0
+}
+
+/// 按灰度百分比判断本机是否应立即应用该版本
+pub fn is_selected(percentage: f64, version: &str, logger: &Logger) -> bool {
+    if percentage >= 100.0 {
+        return true;
+    }
+    let bucket = machine_bucket(version);
+    let selected = bucket < percentage;
+    logger.log(&format!(
+        "Staged rollout {}% for {}: machine bucket {:.2}, {}",
+        percentage,
+        version,
+        bucket,
+        if selected { "selected" } else { "not selected" }
+    ));
+    selected
+}
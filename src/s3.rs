@@ -0,0 +1,163 @@
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::{Digest, Sha256};
+use std::io;
+
+/// 预签名 URL 的有效期（秒）
+const PRESIGN_EXPIRES: u64 = 3600;
+
+/// S3 兼容对象存储（AWS S3、阿里云 OSS、MinIO 等）访问配置
+#[derive(Clone, Debug)]
+pub struct S3Config {
+    /// 服务地址，例如 https://oss-cn-hangzhou.aliyuncs.com；为空时使用 AWS 默认地址
+    pub endpoint: Option<String>,
+    pub region: String,
+    /// 使用 path-style（endpoint/bucket/key）而不是虚拟主机风格（bucket.endpoint/key）
+    pub path_style: bool,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            region: "us-east-1".to_string(),
+            path_style: false,
+        }
+    }
+}
+
+/// 访问密钥，来自环境变量
+struct Credentials {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+impl Credentials {
+    /// 依次读取 AWS_* 与阿里云 OSS_* 环境变量
+    fn from_env() -> Option<Self> {
+        let env = |names: &[&str]| names.iter().find_map(|n| std::env::var(n).ok());
+        Some(Self {
+            access_key: env(&["AWS_ACCESS_KEY_ID", "OSS_ACCESS_KEY_ID"])?,
+            secret_key: env(&["AWS_SECRET_ACCESS_KEY", "OSS_ACCESS_KEY_SECRET"])?,
+            session_token: env(&["AWS_SESSION_TOKEN", "OSS_SESSION_TOKEN"]),
+        })
+    }
+}
+
+/// 判断是否为 s3://bucket/key 地址
+pub fn is_s3_url(s: &str) -> bool {
+    s.to_ascii_lowercase().starts_with("s3://")
+}
+
+impl S3Config {
+    /// 将 s3://bucket/key 转换为可直接 GET 的 https 地址；
+    /// 环境变量中有访问密钥时生成 SigV4 预签名 URL，否则按公开读对象处理
+    pub fn presign(&self, url: &str) -> io::Result<String> {
+        let rest = &url["s3://".len()..];
+        let (bucket, key) = rest
+            .split_once('/')
+            .filter(|(b, k)| !b.is_empty() && !k.is_empty())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid S3 URL (expected s3://bucket/key): {}", url),
+                )
+            })?;
+
+        let endpoint = self
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", self.region));
+        let (scheme, endpoint_host) = endpoint
+            .split_once("://")
+            .unwrap_or(("https", endpoint.as_str()));
+        let endpoint_host = endpoint_host.trim_end_matches('/');
+        let (host, path) = if self.path_style {
+            (endpoint_host.to_string(), format!("/{}/{}", bucket, key))
+        } else {
+            (format!("{}.{}", bucket, endpoint_host), format!("/{}", key))
+        };
+        let canonical_uri = path
+            .split('/')
+            .map(uri_encode)
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let Some(creds) = Credentials::from_env() else {
+            return Ok(format!("{}://{}{}", scheme, host, canonical_uri));
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+
+        let mut query = vec![
+            ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_string()),
+            (
+                "X-Amz-Credential",
+                format!("{}/{}", creds.access_key, scope),
+            ),
+            ("X-Amz-Date", amz_date.clone()),
+            ("X-Amz-Expires", PRESIGN_EXPIRES.to_string()),
+            ("X-Amz-SignedHeaders", "host".to_string()),
+        ];
+        if let Some(token) = &creds.session_token {
+            query.push(("X-Amz-Security-Token", token.clone()));
+        }
+        query.sort_by(|a, b| a.0.cmp(b.0));
+        let canonical_query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", uri_encode(k), uri_encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let canonical_request = format!(
+            "GET\n{}\n{}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD",
+            canonical_uri, canonical_query, host
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let k_date = hmac(
+            format!("AWS4{}", creds.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        let k_region = hmac(&k_date, self.region.as_bytes());
+        let k_service = hmac(&k_region, b"s3");
+        let k_signing = hmac(&k_service, b"aws4_request");
+        let signature = hex(&hmac(&k_signing, string_to_sign.as_bytes()));
+
+        Ok(format!(
+            "{}://{}{}?{}&X-Amz-Signature={}",
+            scheme, host, canonical_uri, canonical_query, signature
+        ))
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// SigV4 要求的 URI 编码：仅保留非保留字符 A-Z a-z 0-9 - _ . ~
+fn uri_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,78 @@
+use crate::Logger;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// 由商店/沙箱管理、不能直接覆盖文件的安装方式
+#[derive(Debug)]
+pub enum Sandbox {
+    /// Flatpak 应用 ID
+    Flatpak(String),
+    /// Snap 包名
+    Snap(String),
+    /// Microsoft Store / MSIX 包全名
+    Msix(String),
+}
+
+/// 根据应用路径判断是否为沙箱安装
+pub fn detect(path: &Path) -> Option<Sandbox> {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let after = |marker: &str| {
+        path.split_once(marker)
+            .and_then(|(_, rest)| rest.split('/').next())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    };
+    if let Some(id) = after("/flatpak/app/") {
+        return Some(Sandbox::Flatpak(id));
+    }
+    // 在 Flatpak 沙箱内运行时应用位于 /app
+    if path.starts_with("/app/")
+        && let Ok(id) = std::env::var("FLATPAK_ID")
+    {
+        return Some(Sandbox::Flatpak(id));
+    }
+    if path.starts_with("/snap/") {
+        return after("/snap/").map(Sandbox::Snap);
+    }
+    after("/WindowsApps/").map(Sandbox::Msix)
+}
+
+/// 交给对应的更新机制：flatpak update、snap refresh，或打开 Microsoft Store 中该应用的页面
+pub fn hand_off(sandbox: &Sandbox, logger: &Logger) -> io::Result<()> {
+    let mut cmd = match sandbox {
+        Sandbox::Flatpak(id) => {
+            let mut cmd = Command::new("flatpak");
+            cmd.args(["update", "-y", "--noninteractive", id]);
+            cmd
+        }
+        Sandbox::Snap(name) => {
+            let mut cmd = Command::new("snap");
+            cmd.args(["refresh", name]);
+            cmd
+        }
+        Sandbox::Msix(full_name) => {
+            // 包全名为 Name_Version_Arch_ResourceId_PublisherId，商店页面使用 Name_PublisherId
+            let parts: Vec<&str> = full_name.split('_').collect();
+            let url = match (parts.first(), parts.last()) {
+                (Some(name), Some(publisher)) if parts.len() > 1 => {
+                    format!("ms-windows-store://pdp/?PFN={}_{}", name, publisher)
+                }
+                _ => "ms-windows-store://downloadsandupdates".to_string(),
+            };
+            let mut cmd = Command::new("cmd");
+            cmd.args(["/C", "start", "", &url]);
+            cmd
+        }
+    };
+    logger.log(&format!("Handing off update: {:?}", cmd));
+    let status = cmd.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "Update hand-off exited with {}",
+            status
+        )))
+    }
+}
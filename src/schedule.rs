@@ -0,0 +1,166 @@
+//! 维护窗口：监视模式只在允许的时间内应用更新，工作时间内不动应用。
+//!
+//! 支持三种写法：
+//! - `02:30`：每天 02:30 这一分钟
+//! - `01:00-05:00`：每天的时间段，结束时间早于开始时间时跨过午夜
+//! - 5 段 cron 表达式 `分 时 日 月 周`（如 `* 1-4 * * 1-5`），每段支持 `*`、数字、`a-b`、`,` 与 `/步长`
+
+use chrono::{DateTime, Datelike, Local, NaiveTime, Timelike};
+use std::fmt;
+use std::str::FromStr;
+
+/// 允许应用更新的时间
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    spec: String,
+    kind: Kind,
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
+    Window { start: NaiveTime, end: NaiveTime },
+    Cron(Cron),
+}
+
+/// cron 表达式，每段为允许取值的位图
+#[derive(Clone, Debug)]
+struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// 日与周是否为 `*`：两者都被限定时满足其一即可（与 cron 一致）
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// time 是否在允许的时间内
+    pub fn allows(&self, time: &DateTime<Local>) -> bool {
+        match &self.kind {
+            Kind::Window { start, end } => {
+                let now = time.time();
+                if start <= end {
+                    *start <= now && now < *end
+                } else {
+                    now >= *start || now < *end
+                }
+            }
+            Kind::Cron(cron) => cron.matches(time),
+        }
+    }
+}
+
+impl Cron {
+    fn matches(&self, time: &DateTime<Local>) -> bool {
+        let bit = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day = bit(self.days, time.day());
+        let weekday = bit(self.weekdays, time.weekday().num_days_from_sunday());
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        bit(self.minutes, time.minute())
+            && bit(self.hours, time.hour())
+            && bit(self.months, time.month())
+            && day_matches
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spec = s.trim();
+        let kind = if let Some((start, end)) = spec.split_once('-')
+            && !spec.contains(' ')
+        {
+            Kind::Window {
+                start: parse_time(start)?,
+                end: parse_time(end)?,
+            }
+        } else if !spec.contains(' ') {
+            let time = parse_time(spec)?;
+            Kind::Cron(Cron {
+                minutes: 1 << time.minute(),
+                hours: 1 << time.hour(),
+                days: u64::MAX,
+                months: u64::MAX,
+                weekdays: u64::MAX,
+                any_day: true,
+                any_weekday: true,
+            })
+        } else {
+            let fields: Vec<&str> = spec.split_whitespace().collect();
+            let [minutes, hours, days, months, weekdays] = fields[..] else {
+                return Err(format!(
+                    "Invalid cron expression {:?}: expected 5 fields (minute hour day month weekday)",
+                    spec
+                ));
+            };
+            let mut weekday_mask = parse_field(weekdays, 0, 7)?;
+            // 0 与 7 都表示周日
+            if weekday_mask & (1 << 7) != 0 {
+                weekday_mask |= 1;
+            }
+            Kind::Cron(Cron {
+                minutes: parse_field(minutes, 0, 59)?,
+                hours: parse_field(hours, 0, 23)?,
+                days: parse_field(days, 1, 31)?,
+                months: parse_field(months, 1, 12)?,
+                weekdays: weekday_mask,
+                any_day: days == "*",
+                any_weekday: weekdays == "*",
+            })
+        };
+        Ok(Self {
+            spec: spec.to_string(),
+            kind,
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.spec)
+    }
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M")
+        .map_err(|_| format!("Invalid time {:?}, expected HH:MM", s))
+}
+
+/// 解析 cron 的一段，返回 min..=max 中允许取值的位图
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = || format!("Invalid cron field {:?}", field);
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (
+                    a.parse().map_err(|_| invalid())?,
+                    b.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    // `5/15` 表示从 5 开始每 15 个
+                    (value, if step > 1 { max } else { value })
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
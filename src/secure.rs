@@ -0,0 +1,149 @@
+//! 私有目录：暂存目录与下载缓存在替换前只允许当前用户（Windows 上另加 SYSTEM 与管理员）访问，
+//! 其他本地用户无法在暂存与替换之间篡改其中的文件
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// 创建只有当前用户可以访问的目录（父目录按默认权限创建）；目录已存在时收紧其权限，
+/// 已存在的目录是符号链接或属于其他用户时返回错误
+#[cfg(unix)]
+pub fn create_private_dir(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::DirBuilder::new().mode(0o700).create(path) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        result => result?,
+    }
+    let meta = fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a directory", path.display()),
+        ));
+    }
+    // SAFETY: geteuid 总是成功
+    if meta.uid() != unsafe { libc::geteuid() } {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is owned by another user", path.display()),
+        ));
+    }
+    if meta.mode() & 0o077 != 0 {
+        fs::set_permissions(path, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// 替换前把暂存目录的权限恢复为与 like（原有目录）相同，like 不存在时为 0755
+#[cfg(unix)]
+pub fn release(path: &Path, like: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let permissions = match fs::metadata(like) {
+        Ok(meta) => meta.permissions(),
+        Err(_) => fs::Permissions::from_mode(0o755),
+    };
+    fs::set_permissions(path, permissions)
+}
+
+/// 只允许所有者、SYSTEM 与管理员完全控制，不继承上级目录的权限
+#[cfg(windows)]
+const PRIVATE_SDDL: &str = "D:P(A;OICI;FA;;;OW)(A;OICI;FA;;;SY)(A;OICI;FA;;;BA)";
+
+#[cfg(windows)]
+pub fn create_private_dir(path: &Path) -> io::Result<()> {
+    use windows_sys::Win32::Foundation::{ERROR_ALREADY_EXISTS, LocalFree};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+    };
+    use windows_sys::Win32::Security::{
+        DACL_SECURITY_INFORMATION, PROTECTED_DACL_SECURITY_INFORMATION, SECURITY_ATTRIBUTES,
+        SetFileSecurityW,
+    };
+    use windows_sys::Win32::Storage::FileSystem::CreateDirectoryW;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let wide_path = wide(path.as_os_str());
+    let sddl = wide(PRIVATE_SDDL.as_ref());
+    let mut descriptor = std::ptr::null_mut();
+    // SAFETY: sddl 以 NUL 结尾；descriptor 由系统分配，使用后通过 LocalFree 释放
+    unsafe {
+        if ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl.as_ptr(),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            std::ptr::null_mut(),
+        ) == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        let attributes = SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: descriptor,
+            bInheritHandle: 0,
+        };
+        let mut result = Ok(());
+        if CreateDirectoryW(wide_path.as_ptr(), &attributes) == 0 {
+            let error = io::Error::last_os_error();
+            result = if error.raw_os_error() == Some(ERROR_ALREADY_EXISTS as i32) {
+                // 已存在的目录：替换为同样的权限
+                if SetFileSecurityW(
+                    wide_path.as_ptr(),
+                    DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+                    descriptor,
+                ) == 0
+                {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(())
+                }
+            } else {
+                Err(error)
+            };
+        }
+        LocalFree(descriptor);
+        result
+    }
+}
+
+/// 替换前恢复为继承上级目录的权限（与原有目录一致），并应用到其中的所有文件
+#[cfg(windows)]
+pub fn release(path: &Path, _like: &Path) -> io::Result<()> {
+    use windows_sys::Win32::Security::Authorization::{SE_FILE_OBJECT, SetNamedSecurityInfoW};
+    use windows_sys::Win32::Security::{
+        ACL, ACL_REVISION, DACL_SECURITY_INFORMATION, InitializeAcl,
+        UNPROTECTED_DACL_SECURITY_INFORMATION,
+    };
+
+    let wide_path = wide(path.as_os_str());
+    // SAFETY: acl 为足够大小的空 ACL；SetNamedSecurityInfoW 不保留传入的指针
+    unsafe {
+        let mut acl: ACL = std::mem::zeroed();
+        if InitializeAcl(&mut acl, std::mem::size_of::<ACL>() as u32, ACL_REVISION) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let status = SetNamedSecurityInfoW(
+            wide_path.as_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION | UNPROTECTED_DACL_SECURITY_INFORMATION,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &acl,
+            std::ptr::null(),
+        );
+        if status != 0 {
+            return Err(io::Error::from_raw_os_error(status as i32));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn wide(s: &std::ffi::OsStr) -> Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+    s.encode_wide().chain(Some(0)).collect()
+}
@@ -0,0 +1,312 @@
+//! Windows 服务：把监视模式注册为开机自动启动的系统服务（LocalSystem），
+//! 没有用户登录时也能更新按机器安装的应用
+
+use crate::CancelToken;
+use std::io;
+
+/// 注册服务：服务启动时执行 `<当前 exe> service run --name <name> <args>`，
+/// args 为 watch 子命令的参数
+#[cfg(windows)]
+pub fn install(name: &str, display_name: &str, args: &[String]) -> io::Result<()> {
+    use windows_sys::Win32::System::Services::{
+        CreateServiceW, SC_MANAGER_CREATE_SERVICE, SERVICE_ALL_ACCESS, SERVICE_AUTO_START,
+        SERVICE_ERROR_NORMAL, SERVICE_WIN32_OWN_PROCESS,
+    };
+
+    let exe = std::env::current_exe()?;
+    let mut command = vec![
+        exe.to_string_lossy().into_owned(),
+        "service".to_string(),
+        "run".to_string(),
+        "--name".to_string(),
+        name.to_string(),
+    ];
+    command.extend(args.iter().cloned());
+    let command_line = command
+        .iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let scm = ScHandle::open_manager(SC_MANAGER_CREATE_SERVICE)?;
+    let name = wide(name);
+    let display_name = wide(display_name);
+    let command_line = wide(&command_line);
+    // SAFETY: 字符串均以 NUL 结尾，在调用期间有效；空指针表示以 LocalSystem 运行、无依赖
+    let service = unsafe {
+        CreateServiceW(
+            scm.0,
+            name.as_ptr(),
+            display_name.as_ptr(),
+            SERVICE_ALL_ACCESS,
+            SERVICE_WIN32_OWN_PROCESS,
+            SERVICE_AUTO_START,
+            SERVICE_ERROR_NORMAL,
+            command_line.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            std::ptr::null(),
+            std::ptr::null(),
+        )
+    };
+    ScHandle::new(service).map(drop)
+}
+
+/// 停止（如正在运行）并删除服务
+#[cfg(windows)]
+pub fn uninstall(name: &str) -> io::Result<()> {
+    use windows_sys::Win32::Storage::FileSystem::DELETE;
+    use windows_sys::Win32::System::Services::{
+        ControlService, DeleteService, OpenServiceW, SC_MANAGER_CONNECT, SERVICE_CONTROL_STOP,
+        SERVICE_QUERY_STATUS, SERVICE_STATUS, SERVICE_STOP,
+    };
+
+    let scm = ScHandle::open_manager(SC_MANAGER_CONNECT)?;
+    let name = wide(name);
+    // SAFETY: scm 为有效句柄，name 以 NUL 结尾
+    let service = ScHandle::new(unsafe {
+        OpenServiceW(
+            scm.0,
+            name.as_ptr(),
+            SERVICE_STOP | SERVICE_QUERY_STATUS | DELETE,
+        )
+    })?;
+    let mut status = SERVICE_STATUS::default();
+    // SAFETY: service 为有效句柄；服务未运行时失败，可以忽略
+    unsafe {
+        ControlService(service.0, SERVICE_CONTROL_STOP, &mut status);
+    }
+    // SAFETY: service 为有效句柄
+    if unsafe { DeleteService(service.0) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// 作为服务运行：连接服务控制管理器，在服务线程中执行 body，
+/// 服务被停止（或系统关机）时取消 body 收到的标记；body 的返回值作为服务的退出码。
+/// 必须由服务控制管理器启动，直接运行时返回错误
+#[cfg(windows)]
+pub fn run(name: &str, body: impl FnOnce(CancelToken) -> i32 + Send + 'static) -> io::Result<()> {
+    use windows_sys::Win32::System::Services::{SERVICE_TABLE_ENTRYW, StartServiceCtrlDispatcherW};
+
+    let mut name = wide(name);
+    let state = ServiceState {
+        name: name.clone(),
+        cancel: CancelToken::new(),
+        body: std::sync::Mutex::new(Some(Box::new(body))),
+        handle: std::sync::atomic::AtomicPtr::new(std::ptr::null_mut()),
+    };
+    if SERVICE.set(state).is_err() {
+        return Err(io::Error::other("Service is already running"));
+    }
+    let table = [
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: name.as_mut_ptr(),
+            lpServiceProc: Some(service_main),
+        },
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: std::ptr::null_mut(),
+            lpServiceProc: None,
+        },
+    ];
+    // SAFETY: table 以空项结尾，在服务停止（函数返回）前有效
+    if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn install(_name: &str, _display_name: &str, _args: &[String]) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(windows))]
+pub fn uninstall(_name: &str) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(windows))]
+pub fn run(_name: &str, _body: impl FnOnce(CancelToken) -> i32 + Send + 'static) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(windows))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Windows services are only available on Windows",
+    )
+}
+
+#[cfg(windows)]
+type ServiceBody = Box<dyn FnOnce(CancelToken) -> i32 + Send>;
+
+/// 服务线程与控制处理函数共享的状态（服务入口为无上下文的静态函数）
+#[cfg(windows)]
+struct ServiceState {
+    name: Vec<u16>,
+    cancel: CancelToken,
+    body: std::sync::Mutex<Option<ServiceBody>>,
+    handle: std::sync::atomic::AtomicPtr<core::ffi::c_void>,
+}
+
+#[cfg(windows)]
+static SERVICE: std::sync::OnceLock<ServiceState> = std::sync::OnceLock::new();
+
+#[cfg(windows)]
+unsafe extern "system" fn service_main(_argc: u32, _argv: *mut windows_sys::core::PWSTR) {
+    use std::sync::atomic::Ordering;
+    use windows_sys::Win32::Foundation::{ERROR_SERVICE_SPECIFIC_ERROR, NO_ERROR};
+    use windows_sys::Win32::System::Services::{
+        RegisterServiceCtrlHandlerExW, SERVICE_RUNNING, SERVICE_STOPPED,
+    };
+
+    let Some(state) = SERVICE.get() else {
+        return;
+    };
+    // SAFETY: 名称以 NUL 结尾，处理函数为静态函数
+    let handle = unsafe {
+        RegisterServiceCtrlHandlerExW(state.name.as_ptr(), Some(control_handler), std::ptr::null())
+    };
+    if handle.is_null() {
+        return;
+    }
+    state.handle.store(handle, Ordering::SeqCst);
+    set_status(SERVICE_RUNNING, NO_ERROR, 0);
+
+    let code = match state.body.lock().unwrap().take() {
+        Some(body) => body(state.cancel.clone()),
+        None => 0,
+    };
+    match code {
+        0 => set_status(SERVICE_STOPPED, NO_ERROR, 0),
+        code => set_status(SERVICE_STOPPED, ERROR_SERVICE_SPECIFIC_ERROR, code as u32),
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn control_handler(
+    control: u32,
+    _event_type: u32,
+    _event_data: *mut core::ffi::c_void,
+    _context: *mut core::ffi::c_void,
+) -> u32 {
+    use windows_sys::Win32::Foundation::{ERROR_CALL_NOT_IMPLEMENTED, NO_ERROR};
+    use windows_sys::Win32::System::Services::{
+        SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP,
+        SERVICE_STOP_PENDING,
+    };
+
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            if let Some(state) = SERVICE.get() {
+                set_status(SERVICE_STOP_PENDING, NO_ERROR, 0);
+                state.cancel.cancel();
+            }
+            NO_ERROR
+        }
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+        _ => ERROR_CALL_NOT_IMPLEMENTED,
+    }
+}
+
+/// 向服务控制管理器报告状态
+#[cfg(windows)]
+fn set_status(current_state: u32, exit_code: u32, service_exit_code: u32) {
+    use std::sync::atomic::Ordering;
+    use windows_sys::Win32::System::Services::{
+        SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_RUNNING, SERVICE_STATUS,
+        SERVICE_WIN32_OWN_PROCESS, SetServiceStatus,
+    };
+
+    let Some(state) = SERVICE.get() else {
+        return;
+    };
+    let status = SERVICE_STATUS {
+        dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+        dwCurrentState: current_state,
+        dwControlsAccepted: if current_state == SERVICE_RUNNING {
+            SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+        } else {
+            0
+        },
+        dwWin32ExitCode: exit_code,
+        dwServiceSpecificExitCode: service_exit_code,
+        dwCheckPoint: 0,
+        // 停止时需等待当前更新到达安全点并回滚
+        dwWaitHint: 30_000,
+    };
+    // SAFETY: 句柄由 RegisterServiceCtrlHandlerExW 返回，无需关闭
+    unsafe {
+        SetServiceStatus(state.handle.load(Ordering::SeqCst), &status);
+    }
+}
+
+/// 服务控制管理器句柄，离开作用域时关闭
+#[cfg(windows)]
+struct ScHandle(windows_sys::Win32::System::Services::SC_HANDLE);
+
+#[cfg(windows)]
+impl ScHandle {
+    fn new(handle: windows_sys::Win32::System::Services::SC_HANDLE) -> io::Result<Self> {
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(handle))
+    }
+
+    fn open_manager(access: u32) -> io::Result<Self> {
+        use windows_sys::Win32::System::Services::OpenSCManagerW;
+        // SAFETY: 空指针表示本机的默认数据库
+        Self::new(unsafe { OpenSCManagerW(std::ptr::null(), std::ptr::null(), access) })
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ScHandle {
+    fn drop(&mut self) {
+        // SAFETY: 句柄有效且只关闭一次
+        unsafe {
+            windows_sys::Win32::System::Services::CloseServiceHandle(self.0);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+/// 按 Windows 命令行解析规则为参数加引号
+#[cfg(windows)]
+pub(crate) fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // 引号前的反斜杠与引号本身都需转义
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    // 结尾的反斜杠后紧跟闭合引号，需要加倍
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,67 @@
+use crate::Logger;
+use std::io;
+use std::path::Path;
+
+/// 刷新开始菜单与桌面（当前用户及所有用户）中指向本应用的快捷方式：
+/// 目标文件名与 app 相同的 .lnk 改为指向新的 app 路径，并更新工作目录、图标和说明中的版本号
+#[cfg(windows)]
+pub fn refresh(app: &Path, version: Option<&str>, logger: &Logger) -> io::Result<()> {
+    use crate::powershell::{self, quote};
+
+    let app = std::path::absolute(app)?;
+    let name = app.file_name().unwrap_or_default().to_string_lossy();
+    let stem = app.file_stem().unwrap_or_default().to_string_lossy();
+    let dir = app.parent().unwrap_or_else(|| Path::new("."));
+    let description = version.map_or_else(String::new, |v| format!("{} {}", stem, v));
+    let script = format!(
+        r#"$app = {app}; $name = {name}; $dir = {dir}; $desc = {desc}
+$shell = New-Object -ComObject WScript.Shell
+$folders = 'Programs', 'CommonPrograms', 'Desktop', 'CommonDesktopDirectory' | ForEach-Object {{ [Environment]::GetFolderPath($_) }}
+foreach ($folder in $folders) {{
+  if (-not $folder -or -not (Test-Path $folder)) {{ continue }}
+  Get-ChildItem -Path $folder -Filter *.lnk -Recurse -ErrorAction SilentlyContinue | ForEach-Object {{
+    $path = $_.FullName
+    $lnk = $shell.CreateShortcut($path)
+    if ([IO.Path]::GetFileName($lnk.TargetPath) -ieq $name) {{
+      $lnk.TargetPath = $app
+      $lnk.WorkingDirectory = $dir
+      $lnk.IconLocation = "$app,0"
+      if ($desc) {{ $lnk.Description = $desc }}
+      try {{ $lnk.Save(); "updated|$path" }} catch {{ "failed|$path|$($_.Exception.Message)" }}
+    }}
+  }}
+}}"#,
+        app = quote(&app.to_string_lossy()),
+        name = quote(&name),
+        dir = quote(&dir.to_string_lossy()),
+        desc = quote(&description),
+    );
+
+    let output = powershell::run(&script)?;
+    let mut updated = 0;
+    for line in output.lines() {
+        let mut fields = line.splitn(3, '|');
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some("updated"), Some(path), _) => {
+                updated += 1;
+                logger.log(&format!("Updated shortcut {}", path));
+            }
+            (Some("failed"), Some(path), reason) => logger.warn(&format!(
+                "Failed to update shortcut {}: {}",
+                path,
+                reason.unwrap_or("")
+            )),
+            _ => {}
+        }
+    }
+    if updated == 0 {
+        logger.log(&format!("No shortcuts pointing to {} found", name));
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn refresh(_app: &Path, _version: Option<&str>, logger: &Logger) -> io::Result<()> {
+    logger.warn("Refreshing shortcuts is only supported on Windows, skipping");
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! 安全删除：清理 input 与 output_old 前，先用零覆盖匹配的文件（如内嵌凭据、授权文件）并写入磁盘，
+//! 删除后磁盘上不再留有原内容。仍有其他硬链接的文件（新版本中未更新、与备份共享内容的文件）不覆盖。SSD 的磨损均衡与写时复制文件系统（APFS、Btrfs）上覆盖不一定落在原位置
+
+use crate::Logger;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// 每次写入的块大小
+const CHUNK: usize = 64 * 1024;
+
+/// 文件是否匹配某个模式。模式支持 `*`（不跨目录）与 `?`；不含 `/` 时匹配文件名，否则匹配相对路径。
+/// 按原始字节比较，非 UTF-8 的文件名不会因替换字符误匹配
+pub fn matches(patterns: &[String], relative: &Path) -> bool {
+    let parts: Vec<&[u8]> = relative
+        .components()
+        .map(|c| c.as_os_str().as_encoded_bytes())
+        .collect();
+    let name = parts.last().copied().unwrap_or_default();
+    let relative = parts.join(&b'/');
+    patterns.iter().any(|pattern| {
+        let target = if pattern.contains('/') {
+            &relative[..]
+        } else {
+            name
+        };
+        wildcard(pattern.as_bytes(), target)
+    })
+}
+
+fn wildcard(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard(&pattern[1..], text)
+                || (text.first().is_some_and(|&c| c != b'/') && wildcard(pattern, &text[1..]))
+        }
+        (Some(b'?'), Some(&c)) if c != b'/' => wildcard(&pattern[1..], &text[1..]),
+        (Some(p), Some(c)) if p == c => wildcard(&pattern[1..], &text[1..]),
+        _ => false,
+    }
+}
+
+/// 覆盖 path（文件，或目录中所有匹配的文件）；不跟随符号链接。返回覆盖的文件数
+pub fn overwrite_matching(path: &Path, patterns: &[String], logger: &Logger) -> io::Result<u64> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.is_file() {
+        let name = Path::new(path.file_name().unwrap_or_default());
+        if !matches(patterns, name) || shared(path)? {
+            return Ok(0);
+        }
+        overwrite(path, meta.len())?;
+        logger.log(&format!("Overwrote {}", path.display()));
+        return Ok(1);
+    }
+    let mut count = 0;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let entry_path = entry.path();
+            if file_type.is_dir() {
+                pending.push(entry_path);
+                continue;
+            }
+            if !file_type.is_file() {
+                continue;
+            }
+            let relative = entry_path.strip_prefix(path).unwrap_or(&entry_path);
+            if matches(patterns, relative) && !shared(&entry_path)? {
+                overwrite(&entry_path, entry.metadata()?.len())?;
+                logger.log(&format!("Overwrote {}", entry_path.display()));
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// 文件是否还有其他硬链接
+#[cfg(unix)]
+fn shared(path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(fs::symlink_metadata(path)?.nlink() > 1)
+}
+
+#[cfg(windows)]
+fn shared(path: &Path) -> io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{
+        BY_HANDLE_FILE_INFORMATION, GetFileInformationByHandle,
+    };
+
+    let file = fs::File::open(path)?;
+    // SAFETY: 句柄在 file 存活期间有效，info 为足够大小的输出缓冲区
+    let info = unsafe {
+        let mut info: BY_HANDLE_FILE_INFORMATION = std::mem::zeroed();
+        if GetFileInformationByHandle(file.as_raw_handle(), &mut info) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        info
+    };
+    Ok(info.nNumberOfLinks > 1)
+}
+
+/// 以零覆盖文件的全部内容并写入磁盘
+fn overwrite(path: &Path, len: u64) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    if permissions.readonly() {
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(path, permissions)?;
+    }
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; CHUNK];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(CHUNK as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()
+}
@@ -0,0 +1,98 @@
+//! 代码签名身份：替换前比较新旧主程序的签名者，发布者变化时拒绝更新，防止更新包被替换。
+//! Windows 读取有效（链到受信任的根证书）的 Authenticode 签名证书的主题与颁发者，
+//! macOS 读取 codesign 的 TeamIdentifier（没有时为第一个 Authority），并用 `codesign --verify` 校验新主程序；
+//! 其他平台没有可用的代码签名
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 可执行文件的签名者（证书主题与颁发者）；未签名或签名无效（被篡改、自签名、证书不受信任）时返回 None
+#[cfg(windows)]
+pub fn publisher(path: &Path) -> io::Result<Option<String>> {
+    use crate::powershell;
+    let subject = powershell::run(&format!(
+        "$s = Get-AuthenticodeSignature -LiteralPath {}; \
+         if ($s.SignerCertificate -and $s.Status -eq 'Valid') {{ \
+         '{{0}} (issued by {{1}})' -f $s.SignerCertificate.Subject, $s.SignerCertificate.Issuer }}",
+        powershell::quote(&path.to_string_lossy())
+    ))?;
+    Ok(Some(subject).filter(|s| !s.is_empty()))
+}
+
+/// path 是否有 publisher 的有效签名
+#[cfg(windows)]
+pub fn verify(path: &Path, publisher: &str) -> io::Result<bool> {
+    Ok(self::publisher(path)?.as_deref() == Some(publisher))
+}
+
+#[cfg(target_os = "macos")]
+pub fn publisher(path: &Path) -> io::Result<Option<String>> {
+    let out = std::process::Command::new("codesign")
+        .args(["-d", "--verbose=2"])
+        .arg(path)
+        .output()?;
+    // codesign 把签名信息写到标准错误；未签名时以非 0 退出
+    let info = String::from_utf8_lossy(&out.stderr);
+    if !out.status.success() {
+        return if info.contains("not signed") {
+            Ok(None)
+        } else {
+            Err(io::Error::other(format!(
+                "codesign failed: {}",
+                info.trim()
+            )))
+        };
+    }
+    let field = |name: &str| {
+        info.lines()
+            .find_map(|line| line.strip_prefix(name))
+            .map(str::trim)
+            .filter(|v| !v.is_empty() && *v != "not set")
+            .map(str::to_string)
+    };
+    Ok(field("TeamIdentifier=").or_else(|| field("Authority=")))
+}
+
+/// path 是否有 publisher 的有效签名：签名完整（`--strict`）且由 Apple 颁发给 publisher 的证书签署，
+/// publisher 为 TeamIdentifier（证书的 OU）或 Authority（证书的 CN）
+#[cfg(target_os = "macos")]
+pub fn verify(path: &Path, publisher: &str) -> io::Result<bool> {
+    let quoted = publisher.replace('\\', "\\\\").replace('"', "\\\"");
+    let requirement = format!(
+        "=anchor apple generic and (certificate leaf[subject.OU] = \"{0}\" or certificate leaf[subject.CN] = \"{0}\")",
+        quoted
+    );
+    let out = std::process::Command::new("codesign")
+        .args(["--verify", "--strict", "-R", &requirement])
+        .arg(path)
+        .output()?;
+    Ok(out.status.success())
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn publisher(_path: &Path) -> io::Result<Option<String>> {
+    Ok(None)
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn verify(_path: &Path, _publisher: &str) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// 在更新文件目录中查找与主程序同名的文件
+pub fn find_app(dir: &Path, app_name: &OsStr) -> io::Result<Option<PathBuf>> {
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() && !path.is_symlink() {
+                pending.push(path);
+            } else if path.file_name().is_some_and(|n| n == app_name) {
+                return Ok(Some(path));
+            }
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,195 @@
+//! 日志输出目标。[`crate::Logger`] 负责格式化与进度协调，每条日志依次交给注册的 [`LogSink`]
+
+use crate::progress::ProgressObserver;
+use chrono::{DateTime, Local};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 日志级别
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// 一条日志
+pub struct Record<'a> {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub message: &'a str,
+    /// 正在终端上绘制进度的观察者；输出到终端的 sink 需通过它输出，避免打乱进度条
+    pub console: Option<&'a dyn ProgressObserver>,
+}
+
+impl Record<'_> {
+    /// 文本格式：`[2024-01-01 12:00:00] WARN: message`
+    pub fn line(&self) -> String {
+        let tag = match self.level {
+            Level::Info => "",
+            Level::Warn => "WARN: ",
+            Level::Error => "ERROR: ",
+        };
+        format!(
+            "[{}] {}{}\n",
+            self.time.format("%Y-%m-%d %H:%M:%S"),
+            tag,
+            self.message
+        )
+    }
+}
+
+/// 日志输出目标；闭包 `Fn(&Record)` 也可直接作为回调 sink 使用
+pub trait LogSink: Send + Sync {
+    fn write(&self, record: &Record);
+}
+
+impl<F> LogSink for F
+where
+    F: Fn(&Record) + Send + Sync,
+{
+    fn write(&self, record: &Record) {
+        self(record)
+    }
+}
+
+/// 普通信息输出到 stdout，警告和错误输出到 stderr；
+/// 终端上有进度展示时普通信息不输出，由进度展示进度
+pub struct ConsoleSink;
+
+impl LogSink for ConsoleSink {
+    fn write(&self, record: &Record) {
+        let line = record.line();
+        match (record.level, record.console) {
+            (Level::Info, Some(_)) => {}
+            (Level::Info, None) => print!("{}", line),
+            (Level::Warn | Level::Error, Some(o)) => o.suspend(&mut || eprint!("{}", line)),
+            (Level::Warn | Level::Error, None) => eprint!("{}", line),
+        }
+    }
+}
+
+/// 以文本格式追加写入日志文件
+pub struct FileSink(Mutex<File>);
+
+impl FileSink {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self(Mutex::new(file)))
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&self, record: &Record) {
+        let _ = self.0.lock().unwrap().write_all(record.line().as_bytes());
+    }
+}
+
+/// 每条日志一行 JSON：`{"time":"...","level":"warn","message":"..."}`，便于日志采集
+pub struct JsonSink(Mutex<Box<dyn Write + Send>>);
+
+impl JsonSink {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self(Mutex::new(Box::new(writer)))
+    }
+
+    /// 追加写入文件
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(
+            OpenOptions::new().create(true).append(true).open(path)?,
+        ))
+    }
+}
+
+impl LogSink for JsonSink {
+    fn write(&self, record: &Record) {
+        let value = serde_json::json!({
+            "time": record.time.to_rfc3339(),
+            "level": record.level.as_str(),
+            "message": record.message,
+        });
+        let mut writer = self.0.lock().unwrap();
+        let _ = writeln!(writer, "{}", value);
+        let _ = writer.flush();
+    }
+}
+
+/// 写入 Windows 事件日志（应用程序日志），source 为事件来源名称
+#[cfg(windows)]
+pub struct EventLogSink {
+    handle: windows_sys::Win32::Foundation::HANDLE,
+}
+
+// SAFETY: 事件日志句柄可在线程间共享，ReportEventW 是线程安全的
+#[cfg(windows)]
+unsafe impl Send for EventLogSink {}
+#[cfg(windows)]
+unsafe impl Sync for EventLogSink {}
+
+#[cfg(windows)]
+impl EventLogSink {
+    pub fn register(source: &str) -> io::Result<Self> {
+        use windows_sys::Win32::System::EventLog::RegisterEventSourceW;
+
+        let name: Vec<u16> = source.encode_utf16().chain(Some(0)).collect();
+        // SAFETY: name 以 NUL 结尾，在调用期间有效
+        let handle = unsafe { RegisterEventSourceW(std::ptr::null(), name.as_ptr()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { handle })
+    }
+}
+
+#[cfg(windows)]
+impl LogSink for EventLogSink {
+    fn write(&self, record: &Record) {
+        use windows_sys::Win32::System::EventLog::{
+            EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE, ReportEventW,
+        };
+
+        let kind = match record.level {
+            Level::Info => EVENTLOG_INFORMATION_TYPE,
+            Level::Warn => EVENTLOG_WARNING_TYPE,
+            Level::Error => EVENTLOG_ERROR_TYPE,
+        };
+        let message: Vec<u16> = record.message.encode_utf16().chain(Some(0)).collect();
+        let strings = [message.as_ptr()];
+        // SAFETY: 句柄有效，字符串以 NUL 结尾且在调用期间有效
+        unsafe {
+            ReportEventW(
+                self.handle,
+                kind,
+                0,
+                0,
+                std::ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                std::ptr::null(),
+            );
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for EventLogSink {
+    fn drop(&mut self) {
+        // SAFETY: 句柄由 RegisterEventSourceW 返回，只释放一次
+        unsafe {
+            windows_sys::Win32::System::EventLog::DeregisterEventSource(self.handle);
+        }
+    }
+}
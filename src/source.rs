@@ -0,0 +1,164 @@
+use crate::download::{self, Checksum, DownloadOptions, Format, Prepared};
+use crate::{Logger, feed, github, rollout};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 更新文件来源：负责取得（下载、校验、解包）新版本文件，交给暂存/替换流程使用。
+/// 新的来源（对象存储、自建 CDN 等）实现此 trait 后通过 [`crate::UpdaterBuilder::source`] 注册
+pub trait UpdateSource: Send + Sync {
+    /// 来源说明，用于日志
+    fn describe(&self) -> String;
+
+    /// 准备更新文件，本机未命中灰度时返回 None
+    fn prepare(&self, ctx: &SourceContext) -> io::Result<Option<Prepared>>;
+}
+
+/// 准备更新文件时可用的参数
+pub struct SourceContext<'a> {
+    /// 安装方式需要的包格式
+    pub format: Format,
+    /// 是否把 zip 包解压到缓存目录；为 false 时返回 zip 文件本身，暂存时直接解压到 `<output>_new`
+    pub extract: bool,
+    /// 更新渠道
+    pub channel: Option<&'a str>,
+    /// 灰度百分比（覆盖更新源中的设置）
+    pub rollout: Option<f64>,
+    pub download: &'a DownloadOptions,
+    pub logger: &'a Logger,
+}
+
+impl SourceContext<'_> {
+    /// 没有版本信息的来源以 key 作为灰度分组的键
+    fn in_rollout(&self, key: &str) -> bool {
+        self.rollout
+            .is_none_or(|p| rollout::is_selected(p, key, self.logger))
+    }
+}
+
+/// 根据 --input 选择来源：更新源（.yml 或以 / 结尾的目录 URL）、更新包文件或 URL、本地目录
+pub fn from_input(input: &str, sha256: Option<&str>) -> Box<dyn UpdateSource> {
+    if feed::is_feed_input(input) {
+        Box::new(Feed(input.to_string()))
+    } else if download::is_url(input) || Path::new(input).is_file() {
+        Box::new(Archive {
+            location: input.to_string(),
+            sha256: sha256.map(str::to_string),
+        })
+    } else {
+        Box::new(LocalDir(PathBuf::from(input)))
+    }
+}
+
+/// 已解压好的本地更新文件目录
+pub struct LocalDir(pub PathBuf);
+
+impl UpdateSource for LocalDir {
+    fn describe(&self) -> String {
+        format!("directory {}", self.0.display())
+    }
+
+    fn prepare(&self, ctx: &SourceContext) -> io::Result<Option<Prepared>> {
+        if !ctx.in_rollout(&self.0.to_string_lossy()) {
+            return Ok(None);
+        }
+        Ok(Some(Prepared {
+            path: self.0.clone(),
+            version: None,
+            release_notes: None,
+        }))
+    }
+}
+
+/// 单个更新包（zip、AppImage、deb/rpm），可以是本地文件或 http(s)/s3 地址
+pub struct Archive {
+    pub location: String,
+    /// 下载或使用前校验的 sha256
+    pub sha256: Option<String>,
+}
+
+impl UpdateSource for Archive {
+    fn describe(&self) -> String {
+        format!("package {}", self.location)
+    }
+
+    fn prepare(&self, ctx: &SourceContext) -> io::Result<Option<Prepared>> {
+        if !ctx.in_rollout(&self.location) {
+            return Ok(None);
+        }
+        let path = if download::is_url(&self.location) {
+            download::prepare_package(
+                &self.location,
+                self.sha256.as_deref(),
+                ctx.format,
+                ctx.extract,
+                ctx.download,
+                ctx.logger,
+            )?
+        } else {
+            let file = Path::new(&self.location);
+            if let Some(sha256) = &self.sha256 {
+                let checksum = Checksum::Sha256(sha256.clone());
+                checksum.verify(file)?;
+                ctx.logger.log(&format!("Checksum verified: {}", checksum));
+            }
+            match ctx.format {
+                // 不是 zip 包的单个文件（如 app.asar）为单文件目标的更新文件，原样使用
+                Format::Zip if ctx.extract && Format::Zip.matches(&self.location) => {
+                    let unpacked = download::cache_dir()?.join("local").join("unpacked");
+                    download::extract_zip(file, &unpacked)?;
+                    ctx.logger
+                        .log(&format!("Extracted package to {}", unpacked.display()));
+                    unpacked
+                }
+                Format::Zip | Format::AppImage | Format::Deb | Format::Rpm => file.to_path_buf(),
+            }
+        };
+        Ok(Some(Prepared {
+            path,
+            version: None,
+            release_notes: None,
+        }))
+    }
+}
+
+/// electron-builder 更新源：latest.yml 的本地路径或 URL，或以 / 结尾的更新源目录 URL
+pub struct Feed(pub String);
+
+impl UpdateSource for Feed {
+    fn describe(&self) -> String {
+        format!("feed {}", self.0)
+    }
+
+    fn prepare(&self, ctx: &SourceContext) -> io::Result<Option<Prepared>> {
+        feed::prepare_input(
+            &self.0,
+            ctx.channel,
+            ctx.rollout,
+            ctx.format,
+            ctx.extract,
+            ctx.download,
+            ctx.logger,
+        )
+    }
+}
+
+/// GitHub 仓库（owner/name）的最新 Release
+pub struct GithubRelease(pub String);
+
+impl UpdateSource for GithubRelease {
+    fn describe(&self) -> String {
+        format!("GitHub release of {}", self.0)
+    }
+
+    fn prepare(&self, ctx: &SourceContext) -> io::Result<Option<Prepared>> {
+        github::prepare_input(
+            &self.0,
+            ctx.channel,
+            ctx.rollout,
+            ctx.format,
+            ctx.extract,
+            ctx.download,
+            ctx.logger,
+        )
+    }
+}
@@ -0,0 +1,357 @@
+//! 进度窗口：从关闭应用到重新启动之间用户看不到任何界面，更新耗时较长时会以为应用消失了。
+//! [`Splash`] 作为进度观察者显示一个置顶的小窗口（标题、状态文字与进度条），
+//! 下载完成、开始等待应用退出时出现，重新启动应用时（或更新结束时）关闭。仅 Windows
+
+use crate::progress::{Phase, ProgressObserver};
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// 窗口显示的内容，由观察者线程写入、窗口线程读取
+#[derive(Debug, Default)]
+struct State {
+    /// 是否显示窗口
+    visible: bool,
+    /// 当前阶段
+    phase: Option<Phase>,
+    /// 当前阶段已完成的量
+    done: u64,
+    /// 当前阶段的总量，未知时为 None
+    total: Option<u64>,
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl State {
+    /// 状态文字，如「Copying update files (12/340)」
+    fn status(&self) -> String {
+        let Some(phase) = self.phase else {
+            return String::new();
+        };
+        match self.total {
+            Some(total) if phase == Phase::Download => format!(
+                "{} ({} / {} MB)",
+                phase.label(),
+                self.done / (1024 * 1024),
+                total / (1024 * 1024)
+            ),
+            Some(total) => format!("{} ({}/{})", phase.label(), self.done, total),
+            None => phase.label().to_string(),
+        }
+    }
+
+    /// 进度的千分比，总量未知时为 None
+    fn permille(&self) -> Option<u32> {
+        let total = self.total.filter(|&total| total > 0)?;
+        Some((self.done.min(total) * 1000 / total) as u32)
+    }
+}
+
+/// 进度窗口；同时把所有事件转发给 inner（如终端进度条），可以与其他观察者叠加使用
+pub struct Splash {
+    inner: Option<Arc<dyn ProgressObserver>>,
+    state: Arc<Mutex<State>>,
+    window: Mutex<Option<window::Window>>,
+}
+
+impl Splash {
+    /// 创建标题为 title 的窗口（先隐藏）；不支持的平台或无法创建窗口（如没有桌面会话）时返回错误
+    pub fn new(title: &str, inner: Option<Arc<dyn ProgressObserver>>) -> io::Result<Splash> {
+        let state = Arc::new(Mutex::new(State::default()));
+        let window = window::Window::open(title, state.clone())?;
+        Ok(Splash {
+            inner,
+            state,
+            window: Mutex::new(Some(window)),
+        })
+    }
+
+    /// 修改状态后刷新窗口
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        f(&mut self.state.lock().unwrap());
+        if let Some(window) = self.window.lock().unwrap().as_ref() {
+            window.refresh();
+        }
+    }
+
+    /// 关闭窗口，之后的事件不再显示
+    fn close(&self) {
+        self.window.lock().unwrap().take();
+    }
+}
+
+impl ProgressObserver for Splash {
+    fn phase_started(&self, phase: Phase, total: Option<u64>) {
+        if let Some(inner) = &self.inner {
+            inner.phase_started(phase, total);
+        }
+        if phase == Phase::Restart {
+            self.close();
+            return;
+        }
+        self.update(|state| {
+            // 下载时应用仍在运行，不显示
+            state.visible |= phase != Phase::Download;
+            state.phase = Some(phase);
+            state.done = 0;
+            state.total = total;
+        });
+    }
+
+    fn advanced(&self, delta: u64) {
+        if let Some(inner) = &self.inner {
+            inner.advanced(delta);
+        }
+        self.update(|state| state.done = state.done.saturating_add(delta));
+    }
+
+    fn copying(&self, path: &Path, copied: u64, total: u64) {
+        if let Some(inner) = &self.inner {
+            inner.copying(path, copied, total);
+        }
+    }
+
+    fn file_copied(&self, path: &Path, bytes: u64) {
+        if let Some(inner) = &self.inner {
+            inner.file_copied(path, bytes);
+        }
+    }
+
+    fn phase_finished(&self, phase: Phase) {
+        if let Some(inner) = &self.inner {
+            inner.phase_finished(phase);
+        }
+    }
+
+    fn draws_on_console(&self) -> bool {
+        self.inner
+            .as_ref()
+            .is_some_and(|inner| inner.draws_on_console())
+    }
+
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        match &self.inner {
+            Some(inner) => inner.suspend(f),
+            None => f(),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod window {
+    use super::State;
+    use std::io;
+    use std::mem;
+    use std::ptr::{null, null_mut};
+    use std::sync::{Arc, Mutex, mpsc};
+    use std::thread::{self, JoinHandle};
+    use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows_sys::Win32::Graphics::Gdi::{
+        COLOR_BTNFACE, DEFAULT_GUI_FONT, GetStockObject, GetSysColorBrush,
+    };
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows_sys::Win32::UI::Controls::{
+        ICC_PROGRESS_CLASS, INITCOMMONCONTROLSEX, InitCommonControlsEx, PBM_SETPOS, PBM_SETRANGE32,
+        PROGRESS_CLASSW,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, GetSystemMetrics,
+        IDC_ARROW, LoadCursorW, MSG, PostMessageW, PostQuitMessage, RegisterClassW, SM_CXSCREEN,
+        SM_CYSCREEN, SW_HIDE, SW_SHOWNOACTIVATE, SendMessageW, SetWindowTextW, ShowWindow,
+        TranslateMessage, WM_APP, WM_CLOSE, WM_DESTROY, WM_SETFONT, WNDCLASSW, WS_CAPTION,
+        WS_CHILD, WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_POPUP, WS_VISIBLE,
+    };
+
+    /// 通知窗口线程重新读取 State
+    const WM_REFRESH: u32 = WM_APP + 1;
+    const WIDTH: i32 = 420;
+    const HEIGHT: i32 = 120;
+    const MARGIN: i32 = 16;
+
+    /// 在独立线程中运行消息循环的窗口；drop 时关闭窗口并等待线程结束
+    pub struct Window {
+        /// HWND 不是 Send，以整数保存
+        hwnd: usize,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Window {
+        pub fn open(title: &str, state: Arc<Mutex<State>>) -> io::Result<Window> {
+            let title = wide(title);
+            let (tx, rx) = mpsc::channel();
+            let thread = thread::Builder::new()
+                .name("splash".to_string())
+                .spawn(move || run(&title, &state, &tx))?;
+            match rx.recv() {
+                Ok(Ok(hwnd)) => Ok(Window {
+                    hwnd,
+                    thread: Some(thread),
+                }),
+                Ok(Err(e)) => {
+                    let _ = thread.join();
+                    Err(e)
+                }
+                Err(_) => Err(io::Error::other("Splash window thread exited")),
+            }
+        }
+
+        pub fn refresh(&self) {
+            // SAFETY: PostMessageW 可以从任意线程调用，窗口已销毁时只返回失败
+            unsafe { PostMessageW(self.hwnd as HWND, WM_REFRESH, 0, 0) };
+        }
+    }
+
+    impl Drop for Window {
+        fn drop(&mut self) {
+            // SAFETY: 同 refresh
+            unsafe { PostMessageW(self.hwnd as HWND, WM_CLOSE, 0, 0) };
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(Some(0)).collect()
+    }
+
+    /// # Safety
+    /// 只能由系统在窗口线程中为 run 创建的窗口调用
+    unsafe extern "system" fn window_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        if msg == WM_DESTROY {
+            // SAFETY: 在窗口线程中调用，结束本线程的消息循环
+            unsafe { PostQuitMessage(0) };
+            return 0;
+        }
+        // SAFETY: 参数原样来自系统
+        unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+    }
+
+    /// 创建窗口（先隐藏），把 HWND 或错误发送给 tx，然后运行消息循环直到窗口关闭；
+    /// 窗口只在调用线程中使用
+    fn run(title: &[u16], state: &Mutex<State>, tx: &mpsc::Sender<io::Result<usize>>) {
+        // SAFETY: 传入的字符串均以 NUL 结尾且在各调用期间有效（临时的 wide 值存活到语句结束）；
+        // wc 中未使用的句柄为 null；status、bar 是本窗口的子窗口，向其发送的是对应控件的消息；
+        // MSG 是可以全零初始化的普通结构体；窗口过程只在本线程的消息循环中被调用
+        unsafe {
+            let controls = INITCOMMONCONTROLSEX {
+                dwSize: mem::size_of::<INITCOMMONCONTROLSEX>() as u32,
+                dwICC: ICC_PROGRESS_CLASS,
+            };
+            InitCommonControlsEx(&controls);
+            let instance = GetModuleHandleW(null());
+            let class = wide("ElectronQuitAndInstallSplash");
+            let wc = WNDCLASSW {
+                style: 0,
+                lpfnWndProc: Some(window_proc),
+                cbClsExtra: 0,
+                cbWndExtra: 0,
+                hInstance: instance,
+                hIcon: null_mut(),
+                hCursor: LoadCursorW(null_mut(), IDC_ARROW),
+                hbrBackground: GetSysColorBrush(COLOR_BTNFACE),
+                lpszMenuName: null(),
+                lpszClassName: class.as_ptr(),
+            };
+            // 已注册过（同一进程第二次创建）时失败，可以忽略
+            RegisterClassW(&wc);
+            // 只有标题栏、没有关闭按钮：更新进行中不能关闭
+            let hwnd = CreateWindowExW(
+                WS_EX_TOPMOST | WS_EX_TOOLWINDOW,
+                class.as_ptr(),
+                title.as_ptr(),
+                WS_POPUP | WS_CAPTION,
+                (GetSystemMetrics(SM_CXSCREEN) - WIDTH) / 2,
+                (GetSystemMetrics(SM_CYSCREEN) - HEIGHT) / 2,
+                WIDTH,
+                HEIGHT,
+                null_mut(),
+                null_mut(),
+                instance,
+                null(),
+            );
+            if hwnd.is_null() {
+                let _ = tx.send(Err(io::Error::last_os_error()));
+                return;
+            }
+            let empty = wide("");
+            let status = CreateWindowExW(
+                0,
+                wide("STATIC").as_ptr(),
+                empty.as_ptr(),
+                WS_CHILD | WS_VISIBLE,
+                MARGIN,
+                MARGIN,
+                WIDTH - 3 * MARGIN,
+                20,
+                hwnd,
+                null_mut(),
+                instance,
+                null(),
+            );
+            SendMessageW(
+                status,
+                WM_SETFONT,
+                GetStockObject(DEFAULT_GUI_FONT) as WPARAM,
+                0,
+            );
+            let bar = CreateWindowExW(
+                0,
+                PROGRESS_CLASSW,
+                empty.as_ptr(),
+                WS_CHILD | WS_VISIBLE,
+                MARGIN,
+                MARGIN + 28,
+                WIDTH - 3 * MARGIN,
+                18,
+                hwnd,
+                null_mut(),
+                instance,
+                null(),
+            );
+            SendMessageW(bar, PBM_SETRANGE32, 0, 1000);
+            let _ = tx.send(Ok(hwnd as usize));
+
+            let mut shown = false;
+            let mut msg: MSG = mem::zeroed();
+            while GetMessageW(&mut msg, null_mut(), 0, 0) > 0 {
+                if msg.hwnd == hwnd && msg.message == WM_REFRESH {
+                    let state = state.lock().unwrap();
+                    SetWindowTextW(status, wide(&state.status()).as_ptr());
+                    SendMessageW(bar, PBM_SETPOS, state.permille().unwrap_or(0) as WPARAM, 0);
+                    if state.visible != shown {
+                        shown = state.visible;
+                        ShowWindow(hwnd, if shown { SW_SHOWNOACTIVATE } else { SW_HIDE });
+                    }
+                    continue;
+                }
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod window {
+    use super::State;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    pub struct Window;
+
+    impl Window {
+        pub fn open(_title: &str, _state: Arc<Mutex<State>>) -> io::Result<Window> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the splash window is only supported on Windows",
+            ))
+        }
+
+        pub fn refresh(&self) {}
+    }
+}
@@ -0,0 +1,180 @@
+use crate::download;
+use crate::vfs::RealFs;
+use crate::{Logger, Phase, copy_dir_recursive, count_files, link_dir_recursive, secure};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 按 Squirrel.Windows 的目录结构安装新版本：
+/// root 下每个版本一个 app-<version> 目录，root 中的启动器（stub）总是启动版本号最高的目录。
+/// 以当前版本目录为基础叠加更新文件，暂存完成后改名为 app-<version>，
+/// 再刷新 root 中的启动器并移除其他 app-* 目录；暂存完成后调用 on_staged
+pub fn apply(
+    root: &Path,
+    input: &Path,
+    version: &str,
+    ignores: &[PathBuf],
+    ignore_existing: &[PathBuf],
+    on_staged: &dyn Fn(&Path) -> io::Result<()>,
+    logger: &Logger,
+) -> io::Result<()> {
+    // 版本号可能来自更新源，拼入目录名前检查
+    download::path_component(version, "version")?;
+    fs::create_dir_all(root)?;
+    let target = root.join(format!("app-{}", version));
+    let staging = root.join(format!("staging-app-{}", version));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    secure::create_private_dir(&staging)?;
+
+    if let Err(e) = stage(
+        root,
+        &target,
+        &staging,
+        input,
+        version,
+        ignores,
+        ignore_existing,
+        logger,
+    )
+    .and_then(|()| on_staged(&staging))
+    .and_then(|()| secure::release(&staging, root))
+    {
+        logger.warn(&format!(
+            "Removing incomplete staging directory: {}",
+            staging.display()
+        ));
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    // 重新安装同一版本时，先移开已有目录
+    if target.exists() {
+        let replaced = root.join(format!("replaced-app-{}", version));
+        if replaced.exists() {
+            fs::remove_dir_all(&replaced)?;
+        }
+        fs::rename(&target, &replaced)?;
+        fs::rename(&staging, &target)?;
+        let _ = fs::remove_dir_all(&replaced);
+    } else {
+        fs::rename(&staging, &target)?;
+    }
+    logger.log(&format!("Installed {}", target.display()));
+
+    update_stubs(root, &target, logger)?;
+
+    for (old_version, dir) in app_dirs(root)? {
+        if dir == target {
+            continue;
+        }
+        match fs::remove_dir_all(&dir) {
+            Ok(()) => logger.log(&format!("Retired version {}", old_version)),
+            Err(e) => logger.warn(&format!(
+                "Failed to remove old version directory {}: {}",
+                dir.display(),
+                e
+            )),
+        }
+    }
+    Ok(())
+}
+
+/// 以当前版本目录为基础，在 staging 中叠加更新文件
+#[allow(clippy::too_many_arguments)]
+fn stage(
+    root: &Path,
+    target: &Path,
+    staging: &Path,
+    input: &Path,
+    version: &str,
+    ignores: &[PathBuf],
+    ignore_existing: &[PathBuf],
+    logger: &Logger,
+) -> io::Result<()> {
+    let current = app_dirs(root)?
+        .into_iter()
+        .filter(|(_, dir)| dir != target)
+        .max_by(|(a, _), (b, _)| version_key(a).cmp(&version_key(b)));
+    if let Some((current_version, dir)) = &current {
+        logger.log(&format!(
+            "Copying current version {} to {}",
+            current_version,
+            staging.display()
+        ));
+        logger.progress_start(
+            Phase::CopyExisting,
+            Some(count_files(&RealFs::default(), dir, ignore_existing)),
+        );
+        let result = link_dir_recursive(&RealFs::default(), dir, staging, ignore_existing, logger);
+        logger.progress_finish();
+        result?;
+    }
+
+    logger.log(&format!(
+        "Staging version {} in {}",
+        version,
+        staging.display()
+    ));
+    logger.progress_start(
+        Phase::CopyUpdate,
+        Some(count_files(&RealFs::default(), input, ignores)),
+    );
+    let result = copy_dir_recursive(&RealFs::default(), input, staging, ignores, logger);
+    logger.progress_finish();
+    result?;
+
+    logger.check_cancelled()
+}
+
+/// 新版本中的 <name>_ExecutionStub.exe 复制为 root 下的 <name>.exe（与 Squirrel 的做法一致）
+fn update_stubs(root: &Path, app_dir: &Path, logger: &Logger) -> io::Result<()> {
+    for entry in fs::read_dir(app_dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(stem) = name.strip_suffix("_ExecutionStub.exe") {
+            let stub = root.join(format!("{}.exe", stem));
+            fs::copy(&path, &stub)?;
+            logger.log(&format!("Updated launcher {}", stub.display()));
+        }
+    }
+    Ok(())
+}
+
+/// 当前安装的（版本号最高的）app-<version> 目录
+pub fn current_dir(root: &Path) -> io::Result<Option<PathBuf>> {
+    Ok(app_dirs(root)?
+        .into_iter()
+        .max_by(|(a, _), (b, _)| version_key(a).cmp(&version_key(b)))
+        .map(|(_, dir)| dir))
+}
+
+/// 列出 root 下所有 app-<version> 目录
+fn app_dirs(root: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir()
+            && let Some(version) = name.strip_prefix("app-")
+        {
+            dirs.push((version.to_string(), path.clone()));
+        }
+    }
+    Ok(dirs)
+}
+
+/// 版本号排序键：按 . 和 - 分段比较各段开头的数字
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(['.', '-'])
+        .map(|part| {
+            part.chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect::<String>()
+                .parse()
+                .unwrap_or(0)
+        })
+        .collect()
+}
@@ -0,0 +1,67 @@
+//! 版本戳：替换成功后在 output 中写入 .eqi-version，记录实际安装的内容，供应用与校验工具读取
+
+use crate::manifest;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// 版本戳文件名
+pub const FILE_NAME: &str = ".eqi-version";
+
+/// 版本戳内容
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Stamp {
+    /// 已安装的版本号，无法确定时为空
+    pub version: Option<String>,
+    /// 更新文件的 sha256（见 [`tree_sha256`]）
+    pub package_sha256: String,
+    /// 安装时间（RFC 3339）
+    pub installed_at: String,
+    /// 执行安装的更新器版本
+    pub updater_version: String,
+}
+
+impl Stamp {
+    pub fn new(version: Option<String>, package_sha256: String) -> Self {
+        Self {
+            version,
+            package_sha256,
+            installed_at: Local::now().to_rfc3339(),
+            updater_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// 写入 output 下的版本戳
+pub fn write(output: &Path, stamp: &Stamp) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(stamp).map_err(io::Error::other)?;
+    let path = output.join(FILE_NAME);
+    // 旧版本戳可能与 <output>_old 中的共享内容（硬链接），先删除再写入
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    fs::write(path, json)
+}
+
+/// 读取 output 下的版本戳，不存在时返回 None
+pub fn read(output: &Path) -> io::Result<Option<Stamp>> {
+    match fs::read(output.join(FILE_NAME)) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// 更新文件的 sha256：单个文件时为文件本身的摘要；目录时为其清单的摘要（见 [`manifest::Manifest::digest`]），
+/// 以 jobs 个线程（0 表示全部核心）并行计算
+pub fn tree_sha256(path: &Path, jobs: usize) -> io::Result<String> {
+    if path.is_file() {
+        return manifest::hash_file(path).map(|(_, sha256)| sha256);
+    }
+    manifest::digest_of(path, jobs)
+}
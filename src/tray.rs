@@ -0,0 +1,708 @@
+//! 托盘图标：[`Splash`](crate::splash::Splash) 之外的另一种进度展示，不遮挡桌面。
+//! 更新开始后在通知区域显示应用的图标，提示文字为当前阶段与百分比；
+//! 更新失败时（[`Tray::failed`]）换成错误图标并弹出气泡，点击气泡或图标、或在右键菜单中选择「View log」打开日志。
+//! macOS 上为菜单栏中的状态项，由 osascript 运行的 JXA 脚本显示，进度通过状态文件传递。仅 Windows、macOS
+
+use crate::progress::{Phase, ProgressObserver};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// 更新失败后图标保留的时间（秒），用户点击「Dismiss」或超时后移除，更新器才退出
+pub const FAILURE_LINGER_SECS: u32 = 120;
+
+/// 图标显示的内容，由观察者线程写入、窗口线程读取
+#[derive(Debug, Default)]
+struct State {
+    /// 当前阶段
+    phase: Option<Phase>,
+    /// 当前阶段已完成的量
+    done: u64,
+    /// 当前阶段的总量，未知时为 None
+    total: Option<u64>,
+    /// 更新是否失败
+    failed: bool,
+    /// 日志文件
+    log: Option<PathBuf>,
+}
+
+#[cfg_attr(not(any(windows, target_os = "macos")), allow(dead_code))]
+impl State {
+    /// 提示文字中的状态，如「Copying update files 42%」
+    fn status(&self) -> String {
+        if self.failed {
+            return "Update failed".to_string();
+        }
+        let Some(phase) = self.phase else {
+            return String::new();
+        };
+        match self.total.filter(|&total| total > 0) {
+            Some(total) => format!("{} {}%", phase.label(), self.done.min(total) * 100 / total),
+            None => phase.label().to_string(),
+        }
+    }
+}
+
+/// 托盘图标；同时把所有事件转发给 inner（如终端进度条、进度窗口），可以与其他观察者叠加使用
+pub struct Tray {
+    inner: Option<Arc<dyn ProgressObserver>>,
+    state: Arc<Mutex<State>>,
+    window: Mutex<Option<window::Window>>,
+}
+
+impl Tray {
+    /// 创建提示文字以 title 开头、使用 app 图标的托盘图标（第一个阶段开始时显示）；
+    /// 不支持的平台或无法创建时返回错误
+    pub fn new(
+        title: &str,
+        app: &Path,
+        inner: Option<Arc<dyn ProgressObserver>>,
+    ) -> io::Result<Tray> {
+        let state = Arc::new(Mutex::new(State::default()));
+        let window = window::Window::open(title, app, state.clone())?;
+        Ok(Tray {
+            inner,
+            state,
+            window: Mutex::new(Some(window)),
+        })
+    }
+
+    /// 更新失败：显示错误图标与气泡，log 为点击时打开的日志文件。
+    /// 之后图标保留到用户关闭或 [`FAILURE_LINGER_SECS`] 秒后，最后一个引用 drop 时等待图标移除
+    pub fn failed(&self, log: Option<&Path>) {
+        self.update(|state| {
+            state.failed = true;
+            state.log = log.map(Path::to_path_buf);
+        });
+    }
+
+    /// 修改状态后刷新图标
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        f(&mut self.state.lock().unwrap());
+        if let Some(window) = self.window.lock().unwrap().as_ref() {
+            window.refresh();
+        }
+    }
+}
+
+impl ProgressObserver for Tray {
+    fn phase_started(&self, phase: Phase, total: Option<u64>) {
+        if let Some(inner) = &self.inner {
+            inner.phase_started(phase, total);
+        }
+        self.update(|state| {
+            state.phase = Some(phase);
+            state.done = 0;
+            state.total = total;
+        });
+    }
+
+    fn advanced(&self, delta: u64) {
+        if let Some(inner) = &self.inner {
+            inner.advanced(delta);
+        }
+        self.update(|state| state.done = state.done.saturating_add(delta));
+    }
+
+    fn copying(&self, path: &Path, copied: u64, total: u64) {
+        if let Some(inner) = &self.inner {
+            inner.copying(path, copied, total);
+        }
+    }
+
+    fn file_copied(&self, path: &Path, bytes: u64) {
+        if let Some(inner) = &self.inner {
+            inner.file_copied(path, bytes);
+        }
+    }
+
+    fn phase_finished(&self, phase: Phase) {
+        if let Some(inner) = &self.inner {
+            inner.phase_finished(phase);
+        }
+    }
+
+    fn draws_on_console(&self) -> bool {
+        self.inner
+            .as_ref()
+            .is_some_and(|inner| inner.draws_on_console())
+    }
+
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        match &self.inner {
+            Some(inner) => inner.suspend(f),
+            None => f(),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod window {
+    use super::{FAILURE_LINGER_SECS, State};
+    use std::cell::RefCell;
+    use std::ffi::OsStr;
+    use std::io;
+    use std::mem;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::ptr::{null, null_mut};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex, mpsc};
+    use std::thread::{self, JoinHandle};
+    use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM};
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows_sys::Win32::UI::Shell::{
+        ExtractIconW, NIF_ICON, NIF_INFO, NIF_MESSAGE, NIF_TIP, NIIF_ERROR, NIM_ADD, NIM_DELETE,
+        NIM_MODIFY, NIN_BALLOONUSERCLICK, NOTIFYICONDATAW, Shell_NotifyIconW, ShellExecuteW,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyIcon, DestroyMenu,
+        DestroyWindow, DispatchMessageW, GetCursorPos, GetMessageW, HICON, IDI_APPLICATION,
+        IDI_ERROR, LoadIconW, MF_STRING, MSG, PostMessageW, PostQuitMessage, RegisterClassW,
+        SW_SHOWNORMAL, SetForegroundWindow, SetTimer, TPM_RETURNCMD, TPM_RIGHTBUTTON,
+        TrackPopupMenu, TranslateMessage, WM_APP, WM_CLOSE, WM_DESTROY, WM_LBUTTONUP, WM_RBUTTONUP,
+        WM_TIMER, WNDCLASSW,
+    };
+
+    /// 通知窗口线程重新读取 State
+    const WM_REFRESH: u32 = WM_APP + 1;
+    /// 托盘图标的回调消息
+    const WM_TRAY: u32 = WM_APP + 2;
+    const MENU_VIEW_LOG: usize = 1;
+    const MENU_DISMISS: usize = 2;
+
+    /// 在独立线程中运行消息循环的隐藏窗口，托盘图标的事件发送给它；
+    /// drop 时关闭窗口并等待线程结束（失败后图标保留时会等到用户关闭或超时）
+    pub struct Window {
+        /// HWND 不是 Send，以整数保存
+        hwnd: usize,
+        /// 已投递、尚未处理的刷新消息，进度事件频繁时合并为一次
+        pending: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Window {
+        pub fn open(title: &str, app: &Path, state: Arc<Mutex<State>>) -> io::Result<Window> {
+            let title = title.to_string();
+            let app = wide(app.as_os_str());
+            let pending = Arc::new(AtomicBool::new(false));
+            let (tx, rx) = mpsc::channel();
+            let shared = pending.clone();
+            let thread = thread::Builder::new()
+                .name("tray".to_string())
+                .spawn(move || {
+                    // 句柄不是 Send，在窗口线程中创建
+                    let context = Context {
+                        title,
+                        state,
+                        pending: shared,
+                        data: NOTIFYICONDATAW::default(),
+                        icon: null_mut(),
+                        added: false,
+                        failed: false,
+                        lingering: false,
+                    };
+                    run(context, &app, &tx)
+                })?;
+            match rx.recv() {
+                Ok(Ok(hwnd)) => Ok(Window {
+                    hwnd,
+                    pending,
+                    thread: Some(thread),
+                }),
+                Ok(Err(e)) => {
+                    let _ = thread.join();
+                    Err(e)
+                }
+                Err(_) => Err(io::Error::other("Tray icon thread exited")),
+            }
+        }
+
+        pub fn refresh(&self) {
+            if !self.pending.swap(true, Ordering::AcqRel) {
+                // SAFETY: PostMessageW 可以从任意线程调用，窗口已被用户关闭时只返回失败
+                unsafe { PostMessageW(self.hwnd as HWND, WM_REFRESH, 0, 0) };
+            }
+        }
+    }
+
+    impl Drop for Window {
+        fn drop(&mut self) {
+            // SAFETY: 同 refresh
+            unsafe { PostMessageW(self.hwnd as HWND, WM_CLOSE, 0, 0) };
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// 窗口线程的状态
+    struct Context {
+        title: String,
+        state: Arc<Mutex<State>>,
+        pending: Arc<AtomicBool>,
+        data: NOTIFYICONDATAW,
+        /// 从 app 提取的图标，没有时为 null
+        icon: HICON,
+        /// 图标是否已添加到通知区域
+        added: bool,
+        /// 是否已显示失败
+        failed: bool,
+        /// 更新器已结束、因失败而保留图标
+        lingering: bool,
+    }
+
+    thread_local! {
+        static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+    }
+
+    fn wide(s: &OsStr) -> Vec<u16> {
+        s.encode_wide().chain(Some(0)).collect()
+    }
+
+    /// 复制到定长的 UTF-16 缓冲区，超出时截断
+    fn copy_to(buffer: &mut [u16], s: &str) {
+        let text: Vec<u16> = s.encode_utf16().take(buffer.len() - 1).collect();
+        buffer[..text.len()].copy_from_slice(&text);
+        buffer[text.len()] = 0;
+    }
+
+    impl Context {
+        /// 按 State 更新图标、提示文字，第一次失败时弹出气泡
+        fn refresh(&mut self) {
+            self.pending.store(false, Ordering::Release);
+            let state = self.state.lock().unwrap();
+            let mut tip = format!("{}: {}", self.title, state.status());
+            if state.failed && state.log.is_some() {
+                tip.push_str(" (click to view the log)");
+            }
+            copy_to(&mut self.data.szTip, &tip);
+            self.data.uFlags = NIF_MESSAGE | NIF_ICON | NIF_TIP;
+            if state.failed && !self.failed {
+                self.failed = true;
+                // SAFETY: 系统预定义的图标，不需要释放
+                self.data.hIcon = unsafe { LoadIconW(null_mut(), IDI_ERROR) };
+                self.data.uFlags |= NIF_INFO;
+                self.data.dwInfoFlags = NIIF_ERROR;
+                copy_to(
+                    &mut self.data.szInfoTitle,
+                    &format!("{}: Update failed", self.title),
+                );
+                copy_to(
+                    &mut self.data.szInfo,
+                    if state.log.is_some() {
+                        "Click to view the log"
+                    } else {
+                        "See the updater log for details"
+                    },
+                );
+            }
+            drop(state);
+            let message = if self.added { NIM_MODIFY } else { NIM_ADD };
+            // SAFETY: data 在 run 中已设置 cbSize、hWnd 与 uID，文字均以 NUL 结尾
+            self.added |= unsafe { Shell_NotifyIconW(message, &self.data) } != 0;
+        }
+
+        /// 处理发给窗口的消息，返回需要在释放 CONTEXT 后执行的操作；未处理时返回 None
+        fn handle(&mut self, hwnd: HWND, msg: u32, lparam: LPARAM) -> Option<Action> {
+            let log = || self.state.lock().unwrap().log.clone();
+            match msg {
+                WM_REFRESH => self.refresh(),
+                WM_TRAY => match lparam as u32 {
+                    WM_LBUTTONUP | NIN_BALLOONUSERCLICK if self.failed => {
+                        return log().map(Action::ViewLog);
+                    }
+                    WM_RBUTTONUP => {
+                        return Some(Action::Menu {
+                            log: log(),
+                            dismiss: self.lingering,
+                        });
+                    }
+                    _ => {}
+                },
+                // 失败后更新器结束：保留图标，用户关闭或超时后再移除
+                WM_CLOSE if self.failed && !self.lingering => {
+                    self.lingering = true;
+                    // SAFETY: hwnd 是本线程的窗口，到期时向其发送 WM_TIMER
+                    unsafe { SetTimer(hwnd, 1, FAILURE_LINGER_SECS * 1000, None) };
+                }
+                WM_TIMER => return Some(Action::Destroy),
+                // SAFETY: 窗口销毁时移除本窗口添加的图标、释放 run 中提取的图标（各一次），再结束消息循环
+                WM_DESTROY => unsafe {
+                    if self.added {
+                        Shell_NotifyIconW(NIM_DELETE, &self.data);
+                    }
+                    if !self.icon.is_null() {
+                        DestroyIcon(self.icon);
+                    }
+                    PostQuitMessage(0);
+                },
+                _ => return None,
+            }
+            Some(Action::None)
+        }
+    }
+
+    /// 窗口过程中会再次进入窗口过程的操作（弹出菜单、打开文件、销毁窗口），在释放 CONTEXT 后执行
+    enum Action {
+        None,
+        ViewLog(PathBuf),
+        Menu { log: Option<PathBuf>, dismiss: bool },
+        Destroy,
+    }
+
+    impl Action {
+        fn perform(self, hwnd: HWND) {
+            // SAFETY: hwnd 是本线程的窗口；菜单创建后检查非 null、用完销毁，
+            // 菜单文字在 AppendMenuW 调用期间有效（系统会复制）
+            unsafe {
+                match self {
+                    Action::None => {}
+                    Action::ViewLog(log) => view_log(&log),
+                    Action::Menu { log, dismiss } => {
+                        let menu = CreatePopupMenu();
+                        if menu.is_null() {
+                            return;
+                        }
+                        if log.is_some() {
+                            let text = wide(OsStr::new("View log"));
+                            AppendMenuW(menu, MF_STRING, MENU_VIEW_LOG, text.as_ptr());
+                        }
+                        if dismiss {
+                            let text = wide(OsStr::new("Dismiss"));
+                            AppendMenuW(menu, MF_STRING, MENU_DISMISS, text.as_ptr());
+                        }
+                        let mut point = POINT { x: 0, y: 0 };
+                        GetCursorPos(&mut point);
+                        // 否则点击菜单外时菜单不会消失
+                        SetForegroundWindow(hwnd);
+                        let command = TrackPopupMenu(
+                            menu,
+                            TPM_RETURNCMD | TPM_RIGHTBUTTON,
+                            point.x,
+                            point.y,
+                            0,
+                            hwnd,
+                            null(),
+                        ) as usize;
+                        DestroyMenu(menu);
+                        match (command, log) {
+                            (MENU_VIEW_LOG, Some(log)) => view_log(&log),
+                            (MENU_DISMISS, _) => {
+                                DestroyWindow(hwnd);
+                            }
+                            _ => {}
+                        }
+                    }
+                    Action::Destroy => {
+                        DestroyWindow(hwnd);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 用默认程序打开日志
+    fn view_log(log: &Path) {
+        let open = wide(OsStr::new("open"));
+        let log = wide(log.as_os_str());
+        // SAFETY: 两个字符串均以 NUL 结尾，在调用期间有效；其余参数可为 null
+        unsafe {
+            ShellExecuteW(
+                null_mut(),
+                open.as_ptr(),
+                log.as_ptr(),
+                null(),
+                null(),
+                SW_SHOWNORMAL,
+            )
+        };
+    }
+
+    /// # Safety
+    /// 只能由系统在窗口线程中为 run 创建的窗口调用
+    unsafe extern "system" fn window_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        // Shell_NotifyIconW 等调用等待期间可能再次进入窗口过程，此时 CONTEXT 已借出，交给默认处理
+        let action = CONTEXT.with(|context| {
+            let mut context = context.try_borrow_mut().ok()?;
+            context.as_mut()?.handle(hwnd, msg, lparam)
+        });
+        match action {
+            Some(action) => {
+                action.perform(hwnd);
+                0
+            }
+            // SAFETY: 参数原样来自系统
+            None => unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
+        }
+    }
+
+    /// 创建隐藏窗口，把 HWND 或错误发送给 tx，然后运行消息循环直到窗口关闭；
+    /// 窗口与 CONTEXT 只在调用线程中使用
+    fn run(mut context: Context, app: &[u16], tx: &mpsc::Sender<io::Result<usize>>) {
+        // SAFETY: 类名、app 以 NUL 结尾且在调用期间有效；wc 中未使用的句柄为 null；
+        // MSG 是可以全零初始化的普通结构体；窗口过程只在本线程的消息循环中被调用
+        unsafe {
+            let instance = GetModuleHandleW(null());
+            let class = wide(OsStr::new("ElectronQuitAndInstallTray"));
+            let wc = WNDCLASSW {
+                style: 0,
+                lpfnWndProc: Some(window_proc),
+                cbClsExtra: 0,
+                cbWndExtra: 0,
+                hInstance: instance,
+                hIcon: null_mut(),
+                hCursor: null_mut(),
+                hbrBackground: null_mut(),
+                lpszMenuName: null(),
+                lpszClassName: class.as_ptr(),
+            };
+            // 已注册过（同一进程第二次创建）时失败，可以忽略
+            RegisterClassW(&wc);
+            let hwnd = CreateWindowExW(
+                0,
+                class.as_ptr(),
+                class.as_ptr(),
+                0,
+                0,
+                0,
+                0,
+                0,
+                null_mut(),
+                null_mut(),
+                instance,
+                null(),
+            );
+            if hwnd.is_null() {
+                let _ = tx.send(Err(io::Error::last_os_error()));
+                return;
+            }
+            // 文件没有图标时返回 null，不是可执行文件时返回 1
+            let icon = ExtractIconW(instance, app.as_ptr(), 0);
+            if icon as usize > 1 {
+                context.icon = icon;
+            }
+            context.data.cbSize = mem::size_of::<NOTIFYICONDATAW>() as u32;
+            context.data.hWnd = hwnd;
+            context.data.uID = 1;
+            context.data.uCallbackMessage = WM_TRAY;
+            context.data.hIcon = if context.icon.is_null() {
+                LoadIconW(null_mut(), IDI_APPLICATION)
+            } else {
+                context.icon
+            };
+            CONTEXT.set(Some(context));
+            let _ = tx.send(Ok(hwnd as usize));
+
+            let mut msg: MSG = mem::zeroed();
+            while GetMessageW(&mut msg, null_mut(), 0, 0) > 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+            CONTEXT.set(None);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod window {
+    use super::{FAILURE_LINGER_SECS, State};
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::process::{Child, Stdio};
+    use std::sync::{Arc, Mutex};
+
+    /// 在菜单栏显示状态项的 JXA 脚本，参数为标题、状态文件、应用与保留秒数；
+    /// 每 0.5 秒读取状态文件，文件被删除或更新器结束（未失败）时退出
+    const SCRIPT: &str = r#"
+ObjC.import('Cocoa');
+var item, statePath, lingerSecs, logPath = null, failed = false, closedAt = null;
+ObjC.registerSubclass({
+    name: 'EQITrayController',
+    methods: {
+        'tick:': { types: ['void', ['id']], implementation: function () { tick(); } },
+        'viewLog:': { types: ['void', ['id']], implementation: function () {
+            if (logPath) $.NSWorkspace.sharedWorkspace.openFile(logPath);
+        } },
+        'dismiss:': { types: ['void', ['id']], implementation: function () { $.NSApp.terminate(null); } },
+    },
+});
+var controller = $.EQITrayController.alloc.init;
+function addItem(menu, title, action) {
+    var entry = $.NSMenuItem.alloc.initWithTitleActionKeyEquivalent(title, action, '');
+    entry.target = controller;
+    menu.addItem(entry);
+}
+function tick() {
+    var text = $.NSString.stringWithContentsOfFileEncodingError(statePath, $.NSUTF8StringEncoding, null);
+    if (text.isNil()) { $.NSApp.terminate(null); return; }
+    var state = JSON.parse(ObjC.unwrap(text));
+    item.button.title = state.label;
+    item.button.toolTip = state.tip;
+    if (state.failed && !failed) {
+        failed = true;
+        logPath = state.log;
+        var menu = $.NSMenu.alloc.init;
+        if (logPath) addItem(menu, 'View log', 'viewLog:');
+        addItem(menu, 'Dismiss', 'dismiss:');
+        item.menu = menu;
+        var app = Application.currentApplication();
+        app.includeStandardAdditions = true;
+        app.displayNotification(state.log ? 'Click the menu bar icon to view the log' : 'See the updater log for details', { withTitle: state.title });
+    }
+    if (state.closed) {
+        if (!failed) { $.NSApp.terminate(null); return; }
+        if (closedAt === null) closedAt = Date.now();
+        if (Date.now() - closedAt >= lingerSecs * 1000) $.NSApp.terminate(null);
+    }
+}
+function run(argv) {
+    statePath = argv[1];
+    lingerSecs = Number(argv[3]);
+    $.NSApplication.sharedApplication;
+    $.NSApp.setActivationPolicy($.NSApplicationActivationPolicyAccessory);
+    item = $.NSStatusBar.systemStatusBar.statusItemWithLength($.NSVariableStatusItemLength);
+    var icon = $.NSWorkspace.sharedWorkspace.iconForFile(argv[2]);
+    icon.setSize($.NSMakeSize(18, 18));
+    item.button.image = icon;
+    item.button.imagePosition = $.NSImageLeft;
+    item.button.toolTip = argv[0];
+    tick();
+    $.NSTimer.scheduledTimerWithTimeIntervalTargetSelectorUserInfoRepeats(0.5, controller, 'tick:', null, true);
+    $.NSApp.run;
+}
+"#;
+
+    /// 运行 SCRIPT 的 osascript 进程，状态写入它轮询的状态文件
+    pub struct Window {
+        title: String,
+        state: Arc<Mutex<State>>,
+        path: PathBuf,
+        /// 上一次写入的内容，进度事件频繁时只在显示的内容变化时写入
+        written: Mutex<String>,
+        child: Child,
+    }
+
+    impl Window {
+        pub fn open(title: &str, app: &Path, state: Arc<Mutex<State>>) -> io::Result<Window> {
+            // 图标取 --app 所在的 .app 包，以 root 运行时脚本以控制台用户身份运行，状态文件放在其可读的位置
+            let bundle = app
+                .ancestors()
+                .find(|p| p.extension().is_some_and(|ext| ext == "app"))
+                .unwrap_or(app);
+            let dir = if crate::privilege::privileged_user().is_some() {
+                PathBuf::from("/tmp")
+            } else {
+                std::env::temp_dir()
+            };
+            let path = dir.join(format!("eqi-tray-{}.json", std::process::id()));
+            // 脚本启动时读取状态文件，不存在时直接退出
+            let content = render(title, &state.lock().unwrap(), false);
+            write_atomic(&path, &content)?;
+            let child = crate::notify::console_command("osascript")?
+                .args(["-l", "JavaScript", "-e", SCRIPT, title])
+                .arg(&path)
+                .arg(bundle)
+                .arg(FAILURE_LINGER_SECS.to_string())
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .inspect_err(|_| {
+                    let _ = fs::remove_file(&path);
+                })?;
+            Ok(Window {
+                title: title.to_string(),
+                state,
+                path,
+                written: Mutex::new(content),
+                child,
+            })
+        }
+
+        pub fn refresh(&self) {
+            let _ = self.write(false);
+        }
+
+        /// 写入当前状态，closed 表示更新器即将结束
+        fn write(&self, closed: bool) -> io::Result<()> {
+            let content = render(&self.title, &self.state.lock().unwrap(), closed);
+            let mut written = self.written.lock().unwrap();
+            if *written != content {
+                write_atomic(&self.path, &content)?;
+                *written = content;
+            }
+            Ok(())
+        }
+    }
+
+    /// 脚本读取的状态；菜单栏空间有限，状态项旁只显示百分比或失败
+    fn render(title: &str, state: &State, closed: bool) -> String {
+        let mut tip = format!("{}: {}", title, state.status());
+        if state.failed && state.log.is_some() {
+            tip.push_str(" (click to view the log)");
+        }
+        let label = if state.failed {
+            "Update failed".to_string()
+        } else {
+            match state.total.filter(|&total| total > 0) {
+                Some(total) => format!("{}%", state.done.min(total) * 100 / total),
+                None => String::new(),
+            }
+        };
+        serde_json::json!({
+            "title": format!("{}: Update failed", title),
+            "label": label,
+            "tip": tip,
+            "failed": state.failed,
+            "log": state.log,
+            "closed": closed,
+        })
+        .to_string()
+    }
+
+    /// 先写临时文件再重命名，脚本不会读到写了一半的内容
+    fn write_atomic(path: &Path, content: &str) -> io::Result<()> {
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, content)?;
+        fs::rename(&temp, path)
+    }
+
+    impl Drop for Window {
+        fn drop(&mut self) {
+            // 失败时脚本保留状态项到用户关闭或超时，之后才退出
+            if self.write(true).is_err() {
+                let _ = self.child.kill();
+            }
+            let _ = self.child.wait();
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+mod window {
+    use super::State;
+    use std::io;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    pub struct Window;
+
+    impl Window {
+        pub fn open(_title: &str, _app: &Path, _state: Arc<Mutex<State>>) -> io::Result<Window> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the tray icon is only supported on Windows and macOS",
+            ))
+        }
+
+        pub fn refresh(&self) {}
+    }
+}
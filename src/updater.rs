@@ -0,0 +1,1978 @@
+use crate::download::{self, DownloadOptions, Format, Prepared};
+use crate::history::{self, Entry};
+use crate::hooks::{Hook, HookContext, HookFailed, LifecycleHook, OnFailure};
+use crate::journal;
+use crate::manifest;
+use crate::notify::{self, Notification};
+use crate::pipeline::{self, Step};
+use crate::policy::{self, Policy};
+use crate::privilege::{self, Handoff};
+use crate::source::{self, GithubRelease, SourceContext, UpdateSource};
+use crate::vfs::{CopyEngine, FileSystem, Granularity, MemoryFs, RealFs};
+use crate::{
+    CancelToken, DEFAULT_RENAME_RETRY, Logger, Phase, ProgressObserver, UpdateError, appimage,
+    arch, detect_resources, kill_processes_by_names, macos, may_remove, registry, remove_path,
+    resolve_output, resolve_path, restart_app, restart_app_as, sandbox, shortcuts, shred, signer,
+    single_file_input, squirrel, stage_output, stamp, swap, syspkg, version,
+};
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 远程更新提供方
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Provider {
+    Github,
+}
+
+/// 安装目录结构
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Layout {
+    /// 单个 resources 目录，更新时整体替换
+    Resources,
+    /// Squirrel.Windows 的 app-<version> 并列目录
+    Squirrel,
+    /// Linux 单文件 AppImage
+    Appimage,
+    /// 通过 dpkg 安装的 deb 包
+    Deb,
+    /// 通过 rpm 安装的 rpm 包
+    Rpm,
+}
+
+/// 更新成功后旧版本备份（`<output>_old`）的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backup {
+    /// 清理时删除
+    #[default]
+    Delete,
+    /// 保留到下一次更新（或超过保留天数），期间可以回滚
+    Keep,
+    /// 移动到备份目录中以时间命名的位置
+    Archive,
+}
+
+/// 构建器中记录的更新文件来源，build 时转换为 [`UpdateSource`]
+enum Source {
+    /// 本地目录、更新包 URL 或 latest.yml
+    Input(String),
+    /// 远程提供方
+    Remote(Provider, String),
+    /// 嵌入方实现的来源
+    Custom(Box<dyn UpdateSource>),
+}
+
+/// 更新结果
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// 已替换文件并重启应用
+    Applied,
+    /// 本机未命中灰度，未做任何改动
+    NotInRollout,
+    /// 沙箱安装，已交给商店/包管理器更新
+    HandedOff,
+    /// 沙箱安装且未允许交接，拒绝更新
+    SandboxRefused,
+    /// 模拟模式，只在内存中执行，未做任何改动
+    Simulated,
+    /// 更新文件的版本不高于已安装版本，未做任何改动
+    UpToDate,
+    /// 文件被占用无法替换，已安排在下次登录时重新执行更新（见 [`UpdaterBuilder::retry_at_logon`]）
+    RetryAtLogon,
+}
+
+/// 一次更新的结果报告
+#[derive(Clone, Debug)]
+pub struct UpdateReport {
+    pub outcome: Outcome,
+    /// 应用的版本号（更新源、Release 或 app_version 提供时）
+    pub version: Option<String>,
+    /// 实际更新的应用路径（macOS 上可能被移动到 /Applications）
+    pub app: PathBuf,
+    /// 更新说明（release_notes 设置，或更新源、Release 提供时）
+    pub release_notes: Option<String>,
+    pub elapsed: Duration,
+    /// 设置 [`UpdaterBuilder::audit_log`] 时审计日志最终的摘要，另行保存后可与摘要文件核对
+    pub audit_digest: Option<String>,
+}
+
+/// 更新器：退出应用、替换文件并重新启动
+pub struct Updater {
+    source: Box<dyn UpdateSource>,
+    overlays: Vec<Box<dyn UpdateSource>>,
+    processes: Vec<OsString>,
+    output: PathBuf,
+    app: PathBuf,
+    ignores: Vec<PathBuf>,
+    ignore_existing: Vec<PathBuf>,
+    layout: Layout,
+    app_version: Option<String>,
+    channel: Option<String>,
+    rollout: Option<f64>,
+    download: DownloadOptions,
+    refresh_shortcuts: bool,
+    notify: Option<Notification>,
+    uninstall_key: Option<String>,
+    move_to_applications: bool,
+    sandbox_handoff: bool,
+    hooks: Vec<Box<dyn LifecycleHook>>,
+    on_hook_failure: Vec<(Hook, OnFailure)>,
+    simulate: bool,
+    force: bool,
+    min_installed_version: Option<String>,
+    expected_version: Option<String>,
+    release_notes: Option<String>,
+    retry_at_logon: Option<Vec<String>>,
+    drop_privileges: bool,
+    restart_handoff: Option<PathBuf>,
+    allow_publisher_change: bool,
+    secure_delete: Vec<String>,
+    confirm_delete: bool,
+    keep_input: bool,
+    backup: Backup,
+    backup_dir: Option<PathBuf>,
+    backup_days: Option<u32>,
+    pipeline: Vec<Step>,
+    copy_engine: CopyEngine,
+    copy_jobs: usize,
+    copy_granularity: Granularity,
+    hash_jobs: usize,
+    stream_extract: bool,
+    move_input: bool,
+    rename_retry: Duration,
+    audit_log: Option<PathBuf>,
+    /// 降权前打开的历史文件
+    history: Mutex<Option<File>>,
+    logger: Logger,
+}
+
+/// [`Updater`] 构建器
+#[derive(Default)]
+pub struct UpdaterBuilder {
+    source: Option<Source>,
+    overlays: Vec<String>,
+    processes: Vec<OsString>,
+    output: Option<PathBuf>,
+    app: Option<PathBuf>,
+    ignores: Vec<PathBuf>,
+    ignore_existing: Vec<PathBuf>,
+    layout: Option<Layout>,
+    app_version: Option<String>,
+    channel: Option<String>,
+    rollout: Option<f64>,
+    sha256: Option<String>,
+    download: Option<DownloadOptions>,
+    refresh_shortcuts: bool,
+    notify: Option<Notification>,
+    uninstall_key: Option<String>,
+    move_to_applications: bool,
+    sandbox_handoff: bool,
+    log_file: Option<String>,
+    logger: Option<Logger>,
+    observer: Option<Arc<dyn ProgressObserver>>,
+    cancel: Option<CancelToken>,
+    hooks: Vec<Box<dyn LifecycleHook>>,
+    on_hook_failure: Vec<(Hook, OnFailure)>,
+    simulate: bool,
+    force: bool,
+    min_installed_version: Option<String>,
+    expected_version: Option<String>,
+    release_notes: Option<String>,
+    retry_at_logon: Option<Vec<String>>,
+    drop_privileges: bool,
+    restart_handoff: Option<PathBuf>,
+    allow_publisher_change: bool,
+    secure_delete: Vec<String>,
+    confirm_delete: bool,
+    keep_input: bool,
+    backup: Backup,
+    backup_dir: Option<PathBuf>,
+    backup_days: Option<u32>,
+    pipeline: Option<Vec<Step>>,
+    copy_engine: CopyEngine,
+    copy_jobs: usize,
+    copy_granularity: Granularity,
+    hash_jobs: usize,
+    stream_extract: bool,
+    move_input: bool,
+    rename_retry: Option<Duration>,
+    audit_log: Option<PathBuf>,
+    policy: Option<Policy>,
+}
+
+impl UpdaterBuilder {
+    /// 更新前要结束的进程名
+    pub fn processes<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.processes = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 更新文件来源：本地目录、zip/AppImage 等更新包地址，或 electron-builder 的 latest.yml
+    pub fn input(mut self, input: impl Into<String>) -> Self {
+        self.source = Some(Source::Input(input.into()));
+        self
+    }
+
+    /// 叠加在更新文件之上的一层（如热修复、语言包）：本地目录、zip 包路径或地址，可多次调用。
+    /// 各层按调用顺序在 input 之后合并到暂存目录，后面的层覆盖前面的同名文件（仅 resources 布局）
+    pub fn overlay(mut self, input: impl Into<String>) -> Self {
+        self.overlays.push(input.into());
+        self
+    }
+
+    /// 从远程提供方查询最新版本作为更新来源
+    pub fn remote(mut self, provider: Provider, repo: impl Into<String>) -> Self {
+        self.source = Some(Source::Remote(provider, repo.into()));
+        self
+    }
+
+    /// 使用自定义的更新文件来源
+    pub fn source(mut self, source: impl UpdateSource + 'static) -> Self {
+        self.source = Some(Source::Custom(Box::new(source)));
+        self
+    }
+
+    /// 更新目标（resources 目录、Squirrel 安装根目录或 AppImage 文件）；resources 布局可以省略，
+    /// 由 app 的位置推断（见 [`crate::detect_resources`]）
+    pub fn output(mut self, output: impl Into<PathBuf>) -> Self {
+        self.output = Some(output.into());
+        self
+    }
+
+    /// 更新完成后启动的应用主程序
+    pub fn app(mut self, app: impl Into<PathBuf>) -> Self {
+        self.app = Some(app.into());
+        self
+    }
+
+    /// 不复制的文件/目录（相对于 input）
+    pub fn ignores<I, S>(mut self, ignores: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.ignores = normalize_ignores(ignores);
+        self
+    }
+
+    /// 复制旧 output 到暂存目录时跳过的文件/目录（相对于 output），如旧日志、缓存，不带入新版本
+    pub fn ignore_existing<I, S>(mut self, ignores: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.ignore_existing = normalize_ignores(ignores);
+        self
+    }
+
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// 新版本号，更新源与 Release 自带版本号时可不设置
+    pub fn app_version(mut self, version: impl Into<String>) -> Self {
+        self.app_version = Some(version.into());
+        self
+    }
+
+    /// 更新渠道（stable、beta、alpha 等）
+    pub fn channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    /// 灰度百分比（0-100），覆盖更新源中的 stagingPercentage
+    pub fn rollout(mut self, percentage: f64) -> Self {
+        self.rollout = Some(percentage);
+        self
+    }
+
+    /// 直接下载的更新包的 sha256
+    pub fn sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.sha256 = Some(sha256.into());
+        self
+    }
+
+    pub fn download_options(mut self, options: DownloadOptions) -> Self {
+        self.download = Some(options);
+        self
+    }
+
+    /// 更新后刷新指向 app 的快捷方式（仅 Windows）
+    pub fn refresh_shortcuts(mut self, enabled: bool) -> Self {
+        self.refresh_shortcuts = enabled;
+        self
+    }
+
+    /// 更新完成或失败后向当前用户显示系统通知（Windows 与 macOS），用于没有应用界面可见的后台更新
+    pub fn notify(mut self, notification: Notification) -> Self {
+        self.notify = Some(notification);
+        self
+    }
+
+    /// 更新后写入版本信息的 Uninstall 注册表项（仅 Windows）
+    pub fn uninstall_key(mut self, key: impl Into<String>) -> Self {
+        self.uninstall_key = Some(key.into());
+        self
+    }
+
+    /// 退出应用或替换文件失败（如文件被占用）时，把 command（程序路径及参数）写入 RunOnce，
+    /// 在下次登录时重新执行更新，而不是直接失败（仅 Windows）
+    pub fn retry_at_logon<I, S>(mut self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.retry_at_logon = Some(command.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// 文件替换完成后放弃 root 权限（通过 sudo/pkexec 运行时切换回调用者），
+    /// 重新启动应用与 post-restart 钩子以调用者身份运行（见 [`privilege::drop_privileges`]）
+    pub fn drop_privileges(mut self, enabled: bool) -> Self {
+        self.drop_privileges = enabled;
+        self
+    }
+
+    /// 不自己重新启动应用，而是写入交接文件 path，由未提权的进程完成重新启动与 post-restart 钩子
+    /// （见 [`privilege::Handoff`]）
+    pub fn restart_handoff(mut self, path: impl Into<PathBuf>) -> Self {
+        self.restart_handoff = Some(path.into());
+        self
+    }
+
+    /// 更新文件中主程序的签名者与已安装主程序不同时仍然更新（默认拒绝，见 [`UpdateError::PublisherChanged`]）
+    pub fn allow_publisher_change(mut self, enabled: bool) -> Self {
+        self.allow_publisher_change = enabled;
+        self
+    }
+
+    /// 清理 input 与 output_old 时先以零覆盖再删除的文件：`*`、`?` 通配，不含 `/` 时匹配文件名，否则匹配相对路径
+    pub fn secure_delete<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.secure_delete = patterns
+            .into_iter()
+            .map(|s| s.into().trim().replace('\\', "/"))
+            .filter(|s| !s.is_empty())
+            .collect();
+        self
+    }
+
+    /// 清理时删除未通过安全检查的 input 或备份（如层级过浅、位于用户主目录下一级、不是更新器创建的备份，
+    /// 见 [`crate::policy::check_removable`]）；默认跳过这些路径并记录警告
+    pub fn confirm_delete(mut self, enabled: bool) -> Self {
+        self.confirm_delete = enabled;
+        self
+    }
+
+    /// 清理时保留 input（更新文件或解包后的更新包），供其他机器重装等使用；旧版本备份照常删除
+    pub fn keep_input(mut self, enabled: bool) -> Self {
+        self.keep_input = enabled;
+        self
+    }
+
+    /// 更新成功后旧版本备份的处理方式，默认删除；[`Backup::Archive`] 需要设置 [`UpdaterBuilder::backup_dir`]
+    pub fn backup(mut self, backup: Backup) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// [`Backup::Archive`] 时存放备份的目录
+    pub fn backup_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.backup_dir = Some(dir.into());
+        self
+    }
+
+    /// 保留的备份超过 days 天后，在之后运行更新器时删除（[`Backup::Keep`] 或 [`Backup::Archive`]）
+    pub fn backup_days(mut self, days: u32) -> Self {
+        self.backup_days = Some(days);
+        self
+    }
+
+    /// 更新流程的步骤与顺序，默认见 [`pipeline::DEFAULT`]；不能执行的流程 build 时返回错误
+    pub fn pipeline(mut self, steps: impl IntoIterator<Item = Step>) -> Self {
+        self.pipeline = Some(steps.into_iter().collect());
+        self
+    }
+
+    /// 暂存时复制文件的方式（resources 布局），默认逐个复制
+    pub fn copy_engine(mut self, engine: CopyEngine) -> Self {
+        self.copy_engine = engine;
+        self
+    }
+
+    /// 暂存时并行复制文件的线程数与分配方式（仅 std 复制方式），默认依次复制
+    pub fn copy_jobs(mut self, jobs: usize, granularity: Granularity) -> Self {
+        self.copy_jobs = jobs;
+        self.copy_granularity = granularity;
+        self
+    }
+
+    /// 计算更新文件摘要（版本戳）时使用的线程数，默认 0 表示使用全部核心
+    pub fn hash_jobs(mut self, jobs: usize) -> Self {
+        self.hash_jobs = jobs;
+        self
+    }
+
+    /// 替换时重命名失败（如杀毒软件正在扫描目录）后的重试时长，默认 10 秒；为零时不重试
+    pub fn rename_retry(mut self, retry: Duration) -> Self {
+        self.rename_retry = Some(retry);
+        self
+    }
+
+    /// zip 更新包不先解压到缓存目录，暂存时直接解压到 `<output>_new`（resources 布局，模拟运行时不生效），
+    /// 省去一次复制与同等大小的临时空间；此时不读取更新包中的版本号、架构与签名
+    pub fn stream_extract(mut self, stream: bool) -> Self {
+        self.stream_extract = stream;
+        self
+    }
+
+    /// 暂存时把更新文件从 input 移动（重命名）到 `<output>_new` 而不是复制（resources 布局）：input 与 output
+    /// 位于同一个卷时几乎不需要时间，不同卷时自动回退为复制。移动后 input 中不再有这些文件，
+    /// 暂存或替换失败时需要重新准备更新文件，因此不能与 retry_at_logon 同时使用
+    pub fn move_input(mut self, enabled: bool) -> Self {
+        self.move_input = enabled;
+        self
+    }
+
+    /// 输出路径策略，默认只有内置规则（见 [`Policy`]）；output 不被允许时 build 返回错误
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// macOS 上应用位于转移/只读位置时先复制到 /Applications
+    pub fn move_to_applications(mut self, enabled: bool) -> Self {
+        self.move_to_applications = enabled;
+        self
+    }
+
+    /// 沙箱安装时交给对应的更新机制，而不是拒绝更新
+    pub fn sandbox_handoff(mut self, enabled: bool) -> Self {
+        self.sandbox_handoff = enabled;
+        self
+    }
+
+    /// 日志文件路径，默认为当前 exe 同级目录下的 updater.log
+    pub fn log_file(mut self, path: impl Into<String>) -> Self {
+        self.log_file = Some(path.into());
+        self
+    }
+
+    /// 使用已创建的日志器（优先于 log_file）
+    pub fn logger(mut self, logger: Logger) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// 接收阶段变化、逐文件事件和下载字节数
+    pub fn observer(mut self, observer: Arc<dyn ProgressObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// 以 [`crate::audit::AuditSink`] 写入的日志文件：更新结束后把其最新摘要写入摘要文件，
+    /// 并记入 [`UpdateReport::audit_digest`] 与更新历史
+    pub fn audit_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.audit_log = Some(path.into());
+        self
+    }
+
+    /// 模拟模式：在内存中执行暂存、替换和清理并写日志，不退出应用、不改动磁盘上的文件（仅 resources 布局）
+    pub fn simulate(mut self, simulate: bool) -> Self {
+        self.simulate = simulate;
+        self
+    }
+
+    /// 更新文件的版本不高于已安装版本、或 CPU 架构与已安装应用不一致时仍然更新
+    /// （默认分别跳过更新、以 [`UpdateError::ArchMismatch`] 中止）
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// 要求已安装版本不低于 version（semver），否则在下载前以 [`UpdateError::InstalledTooOld`] 中止
+    pub fn min_installed_version(mut self, version: impl Into<String>) -> Self {
+        self.min_installed_version = Some(version.into());
+        self
+    }
+
+    /// 调用方认为正在安装的版本：替换后读取已安装应用的 package.json，不一致时回滚并返回
+    /// [`UpdateError::UnexpectedVersion`]（仅 resources、squirrel 布局；squirrel 在替换前检查暂存目录）
+    pub fn expected_version(mut self, version: impl Into<String>) -> Self {
+        self.expected_version = Some(version.into());
+        self
+    }
+
+    /// 更新说明，优先于更新源中的 releaseNotes；随结果写入 [`UpdateReport`] 与更新历史
+    pub fn release_notes(mut self, notes: impl Into<String>) -> Self {
+        self.release_notes = Some(notes.into());
+        self
+    }
+
+    /// 添加生命周期钩子，可多次调用，按添加顺序执行
+    pub fn hook(mut self, hook: impl LifecycleHook + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// hook 位置的钩子失败时的处理方式，默认见 [`Hook::default_on_failure`]
+    pub fn on_hook_failure(mut self, hook: Hook, policy: OnFailure) -> Self {
+        self.on_hook_failure.retain(|(h, _)| *h != hook);
+        self.on_hook_failure.push((hook, policy));
+        self
+    }
+
+    /// 取消标记，取消后在下一个安全点中止并回滚
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    pub fn build(self) -> Result<Updater, UpdateError> {
+        let missing = |name: &str| UpdateError::InvalidConfig(format!("Updater requires {}", name));
+        let layout = self.layout.unwrap_or(Layout::Resources);
+        if self.simulate && layout != Layout::Resources {
+            return Err(UpdateError::InvalidConfig(
+                "Simulation is only supported with the resources layout".to_string(),
+            ));
+        }
+        if let Some(min) = &self.min_installed_version
+            && version::compare(min, min).is_none()
+        {
+            return Err(UpdateError::InvalidConfig(format!(
+                "Invalid minimum installed version: {}",
+                min
+            )));
+        }
+        if self.expected_version.is_some()
+            && !matches!(layout, Layout::Resources | Layout::Squirrel)
+        {
+            return Err(UpdateError::InvalidConfig(
+                "--expected-version is only supported with the resources and squirrel layouts"
+                    .to_string(),
+            ));
+        }
+        if !self.overlays.is_empty() && layout != Layout::Resources {
+            return Err(UpdateError::InvalidConfig(
+                "Layered inputs are only supported with the resources layout".to_string(),
+            ));
+        }
+        let overlays = self
+            .overlays
+            .into_iter()
+            .map(|input| {
+                resolve_input(input)
+                    .map(|input| source::from_input(&input, None))
+                    .map_err(|e| UpdateError::InvalidConfig(format!("Invalid input: {}", e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if self.move_input && layout != Layout::Resources {
+            return Err(UpdateError::InvalidConfig(
+                "--move is only supported with the resources layout".to_string(),
+            ));
+        }
+        match (self.backup, &self.backup_dir) {
+            (Backup::Archive, None) => {
+                return Err(UpdateError::InvalidConfig(
+                    "--backup archive requires --backup-dir".to_string(),
+                ));
+            }
+            (Backup::Delete | Backup::Keep, Some(_)) => {
+                return Err(UpdateError::InvalidConfig(
+                    "--backup-dir is only used with --backup archive".to_string(),
+                ));
+            }
+            _ => {}
+        }
+        if self.backup == Backup::Delete && self.backup_days.is_some() {
+            return Err(UpdateError::InvalidConfig(
+                "--backup-days requires --backup keep or archive".to_string(),
+            ));
+        }
+        let backup_dir = self
+            .backup_dir
+            .map(|dir| resolve_path(&dir))
+            .transpose()
+            .map_err(|e| UpdateError::InvalidConfig(format!("Invalid backup dir: {}", e)))?;
+        if self.move_input && self.keep_input {
+            return Err(UpdateError::InvalidConfig(
+                "--move cannot be combined with --keep-input, moving empties the input".to_string(),
+            ));
+        }
+        if self.move_input && self.retry_at_logon.is_some() {
+            return Err(UpdateError::InvalidConfig(
+                "--move cannot be combined with --retry-at-logon, which needs the update files again"
+                    .to_string(),
+            ));
+        }
+        let pipeline = self.pipeline.unwrap_or_else(|| pipeline::DEFAULT.to_vec());
+        pipeline::check(&pipeline, layout).map_err(UpdateError::InvalidConfig)?;
+        let app = resolve_path(&self.app.ok_or_else(|| missing("an app"))?)
+            .map_err(|e| UpdateError::InvalidConfig(format!("Invalid app: {}", e)))?;
+        let output = match self.output {
+            Some(output) => output,
+            // resources 布局可以由主程序的位置推断
+            None if layout == Layout::Resources => detect_resources(&app).map_err(|e| {
+                UpdateError::InvalidConfig(format!("No output given and detection failed: {}", e))
+            })?,
+            None => return Err(missing("an output")),
+        };
+        let output = resolve_output(&output)
+            .map_err(|e| UpdateError::InvalidConfig(format!("Invalid output: {}", e)))?;
+        self.policy
+            .unwrap_or_default()
+            .check(&output)
+            .map_err(UpdateError::InvalidConfig)?;
+        let logger = match self.logger {
+            Some(logger) => logger,
+            None => Logger::new(self.log_file.as_deref())?,
+        };
+        if let Some(observer) = self.observer {
+            logger.set_observer(observer);
+        }
+        if let Some(token) = self.cancel {
+            logger.set_cancel_token(token);
+        }
+        Ok(Updater {
+            source: match self
+                .source
+                .ok_or_else(|| missing("an input or remote source"))?
+            {
+                Source::Input(input) => {
+                    let input = resolve_input(input)
+                        .map_err(|e| UpdateError::InvalidConfig(format!("Invalid input: {}", e)))?;
+                    source::from_input(&input, self.sha256.as_deref())
+                }
+                Source::Remote(Provider::Github, repo) => Box::new(GithubRelease(repo)),
+                Source::Custom(source) => source,
+            },
+            overlays,
+            processes: self.processes,
+            output,
+            app,
+            ignores: self.ignores,
+            ignore_existing: self.ignore_existing,
+            layout,
+            app_version: self.app_version,
+            channel: self.channel,
+            rollout: self.rollout,
+            download: self.download.unwrap_or_default(),
+            refresh_shortcuts: self.refresh_shortcuts,
+            notify: self.notify,
+            uninstall_key: self.uninstall_key,
+            move_to_applications: self.move_to_applications,
+            sandbox_handoff: self.sandbox_handoff,
+            hooks: self.hooks,
+            on_hook_failure: self.on_hook_failure,
+            simulate: self.simulate,
+            force: self.force,
+            min_installed_version: self.min_installed_version,
+            expected_version: self.expected_version,
+            release_notes: self.release_notes,
+            retry_at_logon: self.retry_at_logon,
+            drop_privileges: self.drop_privileges,
+            restart_handoff: self.restart_handoff,
+            allow_publisher_change: self.allow_publisher_change,
+            secure_delete: self.secure_delete,
+            confirm_delete: self.confirm_delete,
+            keep_input: self.keep_input,
+            backup: self.backup,
+            backup_dir,
+            backup_days: self.backup_days,
+            pipeline,
+            copy_engine: self.copy_engine,
+            copy_jobs: self.copy_jobs,
+            copy_granularity: self.copy_granularity,
+            hash_jobs: self.hash_jobs,
+            stream_extract: self.stream_extract,
+            move_input: self.move_input,
+            rename_retry: self.rename_retry.unwrap_or(DEFAULT_RENAME_RETRY),
+            audit_log: self.audit_log,
+            history: Mutex::new(None),
+            logger,
+        })
+    }
+
+    /// 构建并执行更新
+    pub fn run(self) -> Result<UpdateReport, UpdateError> {
+        self.build()?.run()
+    }
+
+    /// 构建并在 tokio 的阻塞线程池中执行更新
+    #[cfg(feature = "async")]
+    pub async fn run_async(self) -> Result<UpdateReport, UpdateError> {
+        self.build()?.run_async().await
+    }
+}
+
+impl Updater {
+    pub fn builder() -> UpdaterBuilder {
+        UpdaterBuilder::default()
+    }
+
+    /// 执行更新；失败时错误已写入日志，结果追加到更新历史（见 [`history`]）
+    pub fn run(&self) -> Result<UpdateReport, UpdateError> {
+        let started = Instant::now();
+        let from_version = self.installed_version(&self.output);
+        let mut result = self.execute(started, from_version.as_deref());
+        if let Err(e) = &result {
+            self.logger.error(&e.to_string());
+        }
+        let digest = self.write_audit_digest();
+        if let Ok(report) = &mut result {
+            report.audit_digest = digest.clone();
+        }
+        self.record(&result, from_version.clone(), digest);
+        self.notify(&result, from_version.as_deref());
+        result
+    }
+
+    /// 把审计日志最新的摘要写入摘要文件并返回；失败时写到 stderr（写入日志会延长哈希链）
+    fn write_audit_digest(&self) -> Option<String> {
+        let log = self.audit_log.as_deref()?;
+        crate::audit::write_digest(log)
+            .inspect_err(|e| eprintln!("Failed to write audit digest: {}", e))
+            .ok()
+    }
+
+    /// 按设置显示更新结果的通知：只在更新完成或失败时显示，未做改动（已是最新、未命中灰度、模拟等）与取消时不显示
+    fn notify(&self, result: &Result<UpdateReport, UpdateError>, from_version: Option<&str>) {
+        let Some(notification) = self.notify.as_ref().filter(|_| !self.simulate) else {
+            return;
+        };
+        let name = notification.name(&self.app);
+        let installed = self.installed_version(&self.output);
+        let body = match result {
+            Ok(report) if report.outcome == Outcome::Applied => {
+                match installed.or_else(|| report.version.clone()) {
+                    Some(version) => format!("{} was updated to {}", name, version),
+                    None => format!("{} was updated", name),
+                }
+            }
+            Ok(_) | Err(UpdateError::Cancelled) => return,
+            Err(
+                UpdateError::Apply(_)
+                | UpdateError::Hook(_)
+                | UpdateError::UnexpectedVersion { .. },
+            ) if from_version.is_some() && installed.as_deref() == from_version => {
+                "Update failed, previous version restored".to_string()
+            }
+            Err(_) => "Update failed".to_string(),
+        };
+        notify::show(notification, &name, &body, &self.logger);
+    }
+
+    /// 把实际更新或失败的结果追加到更新历史；未做任何改动的结果（未命中灰度、已是最新、模拟等）不记录
+    fn record(
+        &self,
+        result: &Result<UpdateReport, UpdateError>,
+        from_version: Option<String>,
+        audit_digest: Option<String>,
+    ) {
+        if self.simulate {
+            return;
+        }
+        let mut entry = match result {
+            Ok(report) => match report.outcome {
+                Outcome::Applied => Entry::new("apply", "applied"),
+                Outcome::HandedOff => Entry::new("apply", "handed_off"),
+                Outcome::RetryAtLogon => Entry::new("apply", "retry_at_logon"),
+                _ => return,
+            },
+            Err(UpdateError::Cancelled) => Entry::new("apply", "cancelled"),
+            Err(e) => Entry {
+                error: Some(e.to_string()),
+                ..Entry::new("apply", "failed")
+            },
+        };
+        entry.from_version = from_version;
+        entry.audit_digest = audit_digest;
+        entry.release_notes = result.as_ref().ok().and_then(|r| r.release_notes.clone());
+        entry.to_version = self
+            .installed_version(&self.output)
+            .or_else(|| result.as_ref().ok().and_then(|r| r.version.clone()));
+        let written = match self.history.lock().unwrap().as_mut() {
+            Some(file) => history::write(file, &entry),
+            None => history::append(&self.output, &entry),
+        };
+        if let Err(e) = written {
+            self.logger
+                .warn(&format!("Failed to write update history: {}", e));
+        }
+    }
+
+    /// 在 tokio 的阻塞线程池中执行更新，不阻塞宿主程序的异步运行时；
+    /// 需要在 tokio 运行时中调用，取消可通过 [`CancelToken`] 实现
+    #[cfg(feature = "async")]
+    pub async fn run_async(self) -> Result<UpdateReport, UpdateError> {
+        tokio::task::spawn_blocking(move || self.run())
+            .await
+            .map_err(|e| UpdateError::Io(io::Error::other(e)))?
+    }
+
+    fn execute(
+        &self,
+        started: Instant,
+        old_version: Option<&str>,
+    ) -> Result<UpdateReport, UpdateError> {
+        let logger = &self.logger;
+        let mut app = self.app.clone();
+        let mut output = self.output.clone();
+        let report = |outcome, version, app: &Path| UpdateReport {
+            outcome,
+            version,
+            app: app.to_path_buf(),
+            release_notes: None,
+            elapsed: started.elapsed(),
+            audit_digest: None,
+        };
+
+        logger.log("Updater started");
+        logger.log(&format!("App path: {}", app.display()));
+        logger.log(&format!("Process name(s): {:?}", self.processes));
+        logger.log(&format!("Update source: {}", self.source.describe()));
+        for overlay in &self.overlays {
+            logger.log(&format!("Overlay: {}", overlay.describe()));
+        }
+        logger.log(&format!("Output dir: {}", output.display()));
+
+        // 沙箱安装的文件不可覆盖，直接拒绝或交给商店/包管理器更新
+        if let Some(sandbox) = sandbox::detect(&app) {
+            logger.warn(&format!("App is a sandboxed install: {:?}", sandbox));
+            if !self.sandbox_handoff {
+                logger.error(
+                    "Refusing to overwrite a sandboxed install, rerun with --sandbox-handoff",
+                );
+                return Ok(report(Outcome::SandboxRefused, None, &app));
+            }
+            if self.simulate {
+                logger.log("Simulation: would hand off the update, stopping here");
+                return Ok(report(Outcome::Simulated, None, &app));
+            }
+            sandbox::hand_off(&sandbox, logger).map_err(UpdateError::HandOff)?;
+            logger.log("Updater finished");
+            return Ok(report(Outcome::HandedOff, None, &app));
+        }
+
+        if !self.ignores.is_empty() {
+            logger.log(&format!("Ignore list: {:?}", self.ignores));
+        }
+        // 持有到本次更新结束，期间其他更新器不能操作同一 output，也不会把本次的日志当作中断
+        let mut _lock = None;
+        if !self.simulate {
+            if self.layout == Layout::Resources {
+                _lock = Some(journal::lock(&output).map_err(UpdateError::Apply)?);
+                journal::recover(&RealFs::default(), &output, logger).map_err(|e| {
+                    UpdateError::Apply(annotate(e, "Failed to recover the interrupted update"))
+                })?;
+            }
+            self.prune_backups();
+        }
+        if !self.ignore_existing.is_empty() {
+            logger.log(&format!(
+                "Existing output ignore list: {:?}",
+                self.ignore_existing
+            ));
+        }
+
+        if let Some(required) = &self.min_installed_version {
+            let installed = self.installed_version(&output);
+            let satisfied = installed
+                .as_deref()
+                .and_then(|v| version::compare(v, required))
+                .is_some_and(|o| o != Ordering::Less);
+            if !satisfied {
+                return Err(UpdateError::InstalledTooOld {
+                    installed,
+                    required: required.clone(),
+                });
+            }
+            logger.log(&format!("Installed version satisfies minimum {}", required));
+        }
+
+        // 更新源/远程模式：先下载并解压更新包，再退出应用
+        let Some(prepared) = self
+            .prepare()
+            .map_err(|e| self.classify(e, UpdateError::Prepare))?
+        else {
+            logger.log("This machine is not in the current rollout, skipping update");
+            logger.log("Updater finished");
+            return Ok(report(Outcome::NotInRollout, None, &app));
+        };
+        let (input_path, layers) = self
+            .prepare_overlays(prepared.path)
+            .map_err(|e| self.classify(e, UpdateError::Prepare))?;
+        for path in std::iter::once(&input_path).chain(&layers) {
+            policy::check_overlap(path, &output, &app, self.layout == Layout::Appimage)
+                .map_err(UpdateError::InvalidConfig)?;
+        }
+        // 单文件目标与 input 不匹配时在退出应用之前拒绝
+        if self.layout == Layout::Resources {
+            let single = single_file_input(&RealFs::default(), &output, &input_path)
+                .map_err(|e| UpdateError::InvalidConfig(e.to_string()))?;
+            if single.is_some() && !layers.is_empty() {
+                return Err(UpdateError::InvalidConfig(
+                    "Layered inputs are not supported for single-file outputs".to_string(),
+                ));
+            }
+        }
+        let version = self.app_version.clone().or(prepared.version);
+        let release_notes = self.release_notes.clone().or(prepared.release_notes);
+
+        if !self.is_newer(&output, &input_path, version.as_deref()) {
+            logger.log("Updater finished");
+            return Ok(report(Outcome::UpToDate, version, &app));
+        }
+        self.check_arch(&app, &input_path)?;
+        self.check_publisher(&app, &input_path)?;
+
+        // macOS：被转移运行或位于只读位置时，原地替换只会更新错误的路径
+        if cfg!(target_os = "macos") {
+            let location = macos::check_location(&output);
+            if location != macos::Location::Writable {
+                logger.warn(&format!(
+                    "Output {} is not updatable in place ({:?})",
+                    output.display(),
+                    location
+                ));
+                if !self.move_to_applications {
+                    return Err(UpdateError::NotRelocatable);
+                }
+                if self.simulate {
+                    logger.log("Simulation: would move the app to /Applications first");
+                } else {
+                    let (new_app, new_output) = macos::move_to_applications(&app, &output, logger)
+                        .map_err(UpdateError::Relocate)?;
+                    app = new_app;
+                    output = new_output;
+                    logger.log(&format!("Updating relocated app: {}", app.display()));
+                }
+            }
+        }
+
+        // 模拟模式：在载入了 output 与更新文件的内存文件系统上执行，磁盘不做任何改动
+        let memory = MemoryFs::new();
+        let real = RealFs::with_copy_engine(self.copy_engine())
+            .with_copy_jobs(self.copy_jobs, self.copy_granularity);
+        let vfs: &dyn FileSystem = if self.simulate {
+            logger.log("Simulation mode: no files will be changed and no processes killed");
+            for path in [&output, &input_path].into_iter().chain(&layers) {
+                if path.exists() {
+                    memory.load(path).map_err(UpdateError::Prepare)?;
+                }
+            }
+            &memory
+        } else {
+            &real
+        };
+
+        let ctx = HookContext {
+            output: &output,
+            app: &app,
+            staging: None,
+            version: version.as_deref(),
+            old_version,
+            user: None,
+            logger,
+        };
+        // 到 swap 及紧随其后的 verify 为止的步骤失败时中止并恢复，之后的步骤在更新完成后执行
+        let swap_at = self
+            .pipeline
+            .iter()
+            .position(|s| *s == Step::Swap)
+            .expect("checked by build");
+        let finishing = self.pipeline[swap_at + 1..]
+            .iter()
+            .position(|s| *s != Step::Verify)
+            .map_or(self.pipeline.len(), |i| swap_at + 1 + i);
+
+        // 移动后 input 中不再有更新文件，版本戳的摘要在暂存之前计算
+        let package_sha256 = (self.move_input && !self.simulate)
+            .then(|| stamp::tree_sha256(&input_path, self.hash_jobs));
+        let mut app_exited = false;
+        // resources 布局已暂存、尚未替换的 (<output>_new, <output>_old)
+        let mut staged = None;
+        let mut swapped = false;
+        let mut output_old = None;
+        let mut rolled_back = false;
+        // 回滚失败时保留替换日志，下次运行时再恢复
+        let mut rollback_failed = false;
+        let applied = (|| -> io::Result<()> {
+            for step in &self.pipeline[..finishing] {
+                match step {
+                    Step::Kill => {
+                        self.run_hooks(Hook::PreKill, &ctx)?;
+                        if self.simulate {
+                            logger.log(&format!("Simulation: would kill {:?}", self.processes));
+                        } else {
+                            kill_processes_by_names(&self.processes, logger)?;
+                        }
+                        app_exited = true;
+                    }
+                    Step::Stage if self.layout == Layout::Resources => {
+                        staged = Some(self.stage(vfs, &output, &input_path, &layers, &ctx)?);
+                    }
+                    // 其他布局在 swap 中暂存
+                    Step::Stage => {}
+                    Step::Verify if !swapped => {
+                        if let Some((output_new, _)) = &staged {
+                            self.verify_version(output_new)?;
+                        }
+                    }
+                    // 替换后核对版本，不一致时恢复备份（squirrel 已在替换前核对暂存目录）
+                    Step::Verify => {
+                        if self.layout == Layout::Resources
+                            && let Err(e) = self.verify_version(&output)
+                        {
+                            logger.warn("Installed version is not the expected one, rolling back");
+                            crate::rollback_in(vfs, &output, logger)
+                                .inspect_err(|_| rollback_failed = true)?;
+                            rolled_back = true;
+                            return Err(e);
+                        }
+                    }
+                    Step::Swap => {
+                        if self.layout == Layout::Resources && staged.is_none() {
+                            staged = Some(self.stage(vfs, &output, &input_path, &layers, &ctx)?);
+                        }
+                        output_old = self.apply(vfs, &input_path, &ctx, staged.as_ref())?;
+                        staged = None;
+                        swapped = true;
+                    }
+                    Step::Cleanup | Step::Restart => unreachable!("checked by build"),
+                }
+            }
+            // post-swap 钩子失败时按失败处理方式恢复备份或保留新版本中止
+            if let Err(e) = self.run_hooks(Hook::PostSwap, &ctx) {
+                // 取消时总是恢复
+                let failed = e.get_ref().is_some_and(|inner| inner.is::<HookFailed>());
+                if failed && self.on_failure(Hook::PostSwap) == OnFailure::Abort {
+                    return Err(e);
+                }
+                if self.layout != Layout::Resources {
+                    logger.warn(&format!("{}, the update cannot be rolled back", e));
+                    return Ok(());
+                }
+                logger.warn(&format!("{}, rolling back", e));
+                crate::rollback_in(vfs, &output, logger).inspect_err(|_| rollback_failed = true)?;
+                rolled_back = true;
+                return Err(e);
+            }
+            // 启动前最后一次检查，此时备份与更新文件都还在，损坏时恢复旧版本而不是启动它
+            if swapped
+                && !self.simulate
+                && self.pipeline.contains(&Step::Restart)
+                && let Err(e) = self.check_launchable(&output, &app, &input_path, &layers)
+            {
+                if self.layout != Layout::Resources {
+                    logger.error(&format!("{}, the update cannot be rolled back", e));
+                    return Err(e);
+                }
+                logger.warn(&format!("{}, rolling back", e));
+                crate::rollback_in(vfs, &output, logger).inspect_err(|_| rollback_failed = true)?;
+                rolled_back = true;
+                return Err(e);
+            }
+            Ok(())
+        })();
+        // 结果已在本次运行中处理（完成或恢复），不再需要日志
+        if rollback_failed {
+            logger.warn("Rollback failed, keeping the update journal to recover on the next run");
+        } else if !self.simulate
+            && self.layout == Layout::Resources
+            && let Err(e) = journal::remove(&output)
+        {
+            logger.warn(&format!("Failed to remove update journal: {}", e));
+        }
+        if let Err(e) = applied {
+            // 已暂存、尚未替换时删除暂存目录
+            if let Some((output_new, _)) = staged.filter(|(p, _)| vfs.exists(p)) {
+                logger.warn(&format!(
+                    "Removing incomplete temporary directory: {}",
+                    output_new.display()
+                ));
+                if let Err(e) = remove_path(vfs, &output_new) {
+                    logger.warn(&format!("Failed to remove temporary directory: {}", e));
+                }
+            }
+            let error = self.classify(e, UpdateError::Apply);
+            if (matches!(
+                error,
+                UpdateError::Cancelled
+                    | UpdateError::Hook(_)
+                    | UpdateError::UnexpectedVersion { .. }
+            ) || rolled_back)
+                && app_exited
+            {
+                // 替换后未恢复时（如 post-swap 钩子以 abort 失败）已安装的是新版本
+                if swapped && !rolled_back {
+                    logger.warn(&format!(
+                        "{}, keeping and restarting the new version",
+                        error
+                    ));
+                } else {
+                    logger.warn(&format!("{}, restarting current version", error));
+                }
+                self.restart(&app);
+            }
+            if let (UpdateError::Apply(_), Some(command)) = (&error, &self.retry_at_logon)
+                && !self.simulate
+            {
+                match registry::register_run_once(&self.output, command, logger) {
+                    Ok(()) => {
+                        logger.warn(&format!(
+                            "{}, the update will be retried at next logon",
+                            error
+                        ));
+                        if app_exited {
+                            self.restart(&app);
+                        }
+                        logger.log("Updater finished");
+                        return Ok(report(Outcome::RetryAtLogon, version, &app));
+                    }
+                    Err(e) => logger.warn(&format!("Failed to schedule retry at logon: {}", e)),
+                }
+            }
+            return Err(error);
+        }
+
+        logger.log("Update applied successfully");
+
+        if !self.simulate && output.is_dir() {
+            let version = self.installed_version(&output).or(version.clone());
+            let sha256 =
+                package_sha256.unwrap_or_else(|| stamp::tree_sha256(&input_path, self.hash_jobs));
+            write_stamp(&output, sha256, version, logger);
+        }
+
+        if self.refresh_shortcuts
+            && !self.simulate
+            && let Err(e) = shortcuts::refresh(&app, version.as_deref(), logger)
+        {
+            logger.warn(&format!("Failed to refresh shortcuts: {}", e));
+        }
+
+        if let Some(key) = self.uninstall_key.as_ref().filter(|_| !self.simulate) {
+            let install_dir = app.parent().unwrap_or_else(|| Path::new("."));
+            if let Err(e) =
+                registry::update_uninstall_entry(key, install_dir, version.as_deref(), logger)
+            {
+                logger.warn(&format!("Failed to update uninstall registry entry: {}", e));
+            }
+        }
+
+        // 重新启动应用之后清理时，清理的失败与 post-restart 钩子的失败互不影响
+        let mut restarted = Ok(());
+        // 之后的清理仍需要当前权限时，推迟到清理之后降权并执行 post-restart 钩子
+        let mut post_restart_deferred = false;
+        for (i, step) in self.pipeline[finishing..].iter().enumerate() {
+            match step {
+                Step::Cleanup => self.cleanup(vfs, &input_path, &layers, output_old.as_deref()),
+                Step::Restart if self.simulate => logger.log("Simulation: would restart the app"),
+                Step::Restart => {
+                    if let Some(path) = &self.restart_handoff {
+                        let handoff = Handoff {
+                            output: output.clone(),
+                            app: app.clone(),
+                            version: version.clone(),
+                            old_version: old_version.map(str::to_string),
+                            post_restart: true,
+                        };
+                        self.hand_off(path, &handoff);
+                    } else if self.drop_privileges
+                        && self.pipeline[finishing + i + 1..].contains(&Step::Cleanup)
+                    {
+                        // 应用单独以调用者身份启动，本进程保留权限完成清理
+                        restart_app_as(&app, privilege::caller().as_ref(), logger);
+                        post_restart_deferred = true;
+                    } else {
+                        let user = self.restart(&app);
+                        let ctx = HookContext {
+                            user: user.as_ref(),
+                            ..ctx
+                        };
+                        // 更新已完成，失败处理为 abort/rollback 时只以钩子失败结束
+                        restarted = self.run_hooks(Hook::PostRestart, &ctx);
+                    }
+                }
+                Step::Kill | Step::Stage | Step::Swap | Step::Verify => {
+                    unreachable!("checked by build")
+                }
+            }
+        }
+        if post_restart_deferred {
+            let user = self.drop_privileges();
+            let ctx = HookContext {
+                user: user.as_ref(),
+                ..ctx
+            };
+            restarted = self.run_hooks(Hook::PostRestart, &ctx);
+        }
+
+        if self.simulate {
+            logger.log(&format!(
+                "Simulation finished: {} would contain {} files",
+                output.display(),
+                memory.files(&output).len()
+            ));
+            return Ok(UpdateReport {
+                release_notes,
+                ..report(Outcome::Simulated, version, &app)
+            });
+        }
+        restarted.map_err(|e| self.classify(e, UpdateError::Apply))?;
+
+        logger.log("Updater finished");
+        Ok(UpdateReport {
+            release_notes,
+            ..report(Outcome::Applied, version, &app)
+        })
+    }
+
+    /// 被取消导致的中断归为 Cancelled，钩子失败归为 Hook，包装在 io::Error 中的
+    /// UpdateError 原样取出，其余错误按 kind 包装
+    fn classify(&self, e: io::Error, kind: fn(io::Error) -> UpdateError) -> UpdateError {
+        if e.kind() == io::ErrorKind::Interrupted && self.logger.is_cancelled() {
+            return UpdateError::Cancelled;
+        }
+        if is_typed(&e) {
+            let inner = e.into_inner().expect("typed error has an inner error");
+            return match inner.downcast::<HookFailed>() {
+                Ok(failed) => UpdateError::Hook(*failed),
+                Err(inner) => *inner
+                    .downcast::<UpdateError>()
+                    .expect("checked by is_typed"),
+            };
+        }
+        kind(e)
+    }
+
+    /// 核对 dir 中应用的版本号与 expected_version，不一致时返回包装了
+    /// [`UpdateError::UnexpectedVersion`] 的错误；未设置 expected_version 或模拟模式下不检查
+    fn verify_version(&self, dir: &Path) -> io::Result<()> {
+        let Some(expected) = &self.expected_version else {
+            return Ok(());
+        };
+        if self.simulate {
+            self.logger
+                .log("Simulation: skipping expected version check");
+            return Ok(());
+        }
+        let found = self.read_version(dir);
+        let matches = found.as_deref().is_some_and(|found| {
+            found == expected || version::compare(found, expected) == Some(Ordering::Equal)
+        });
+        if matches {
+            self.logger
+                .log(&format!("Verified installed version {}", expected));
+            return Ok(());
+        }
+        Err(io::Error::other(UpdateError::UnexpectedVersion {
+            expected: expected.clone(),
+            found,
+        }))
+    }
+
+    /// 启动前检查：主程序与 output 中的 app.asar 存在且非空，更新文件中有 app.asar 时内容一致
+    /// （多层时以最后提供它的一层为准；已移动或被忽略时只检查大小）
+    fn check_launchable(
+        &self,
+        output: &Path,
+        app: &Path,
+        input: &Path,
+        layers: &[PathBuf],
+    ) -> io::Result<()> {
+        let broken = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let non_empty = |path: &Path| {
+            let meta = std::fs::metadata(path)
+                .map_err(|e| broken(format!("Pre-launch check: {}: {}", path.display(), e)))?;
+            if meta.is_file() && meta.len() == 0 {
+                return Err(broken(format!(
+                    "Pre-launch check: {} is empty",
+                    path.display()
+                )));
+            }
+            Ok(())
+        };
+        non_empty(app)?;
+        if output.is_file() {
+            return non_empty(output);
+        }
+        let asar = output.join("app.asar");
+        let source = std::iter::once(input)
+            .chain(layers.iter().map(PathBuf::as_path))
+            .rev()
+            .map(|dir| dir.join("app.asar"))
+            .find(|p| p.is_file())
+            .filter(|_| !crate::is_ignored(Path::new("app.asar"), &self.ignores));
+        if !output.is_dir() || source.is_none() && !asar.exists() {
+            return Ok(());
+        }
+        non_empty(&asar)?;
+        if let Some(source) = source {
+            let (_, expected) = manifest::hash_file(&source)?;
+            let (_, found) = manifest::hash_file(&asar)?;
+            if expected != found {
+                return Err(broken(format!(
+                    "Pre-launch check: {} does not match the update files",
+                    asar.display()
+                )));
+            }
+        }
+        self.logger.log("Pre-launch check passed");
+        Ok(())
+    }
+
+    /// hook 位置的钩子失败时的处理方式
+    fn on_failure(&self, hook: Hook) -> OnFailure {
+        self.on_hook_failure
+            .iter()
+            .find(|(h, _)| *h == hook)
+            .map_or(hook.default_on_failure(), |(_, policy)| *policy)
+    }
+
+    /// 依次执行 hook 位置的生命周期钩子；失败处理为 continue 时失败只记录警告
+    fn run_hooks(&self, hook: Hook, ctx: &HookContext) -> io::Result<()> {
+        if self.simulate && !self.hooks.is_empty() {
+            self.logger
+                .log(&format!("Simulation: skipping {} hook", hook.name()));
+            return Ok(());
+        }
+        for h in &self.hooks {
+            self.logger.log(&format!("Running {} hook", hook.name()));
+            let Err(source) = h.run(hook, ctx) else {
+                continue;
+            };
+            // 钩子因取消而中止时按取消处理
+            if source.kind() == io::ErrorKind::Interrupted && self.logger.is_cancelled() {
+                return Err(source);
+            }
+            let failed = HookFailed { hook, source };
+            if self.on_failure(hook) == OnFailure::Continue {
+                self.logger.warn(&format!("{}, continuing", failed));
+                continue;
+            }
+            return Err(io::Error::other(failed));
+        }
+        Ok(())
+    }
+
+    /// 按安装方式执行文件替换，返回需要清理的备份目录；resources 布局替换 staged 中已暂存的目录
+    fn apply(
+        &self,
+        vfs: &dyn FileSystem,
+        input_path: &Path,
+        ctx: &HookContext,
+        staged: Option<&(PathBuf, PathBuf)>,
+    ) -> io::Result<Option<PathBuf>> {
+        let logger = &self.logger;
+        let format = self.format();
+        let output = ctx.output;
+        // 暂存完成后、替换之前执行的钩子
+        let on_staged = |staging: &Path| {
+            let ctx = HookContext {
+                staging: Some(staging),
+                ..*ctx
+            };
+            self.run_hooks(Hook::PostStage, &ctx)?;
+            if self.layout == Layout::Squirrel {
+                self.verify_version(staging)?;
+            }
+            logger.check_cancelled()?;
+            self.run_hooks(Hook::PreSwap, &ctx)
+        };
+        match self.layout {
+            Layout::Resources => {
+                let (output_new, output_old) = staged.expect("staged before swap");
+                logger.check_cancelled()?;
+                let ctx = HookContext {
+                    staging: Some(output_new),
+                    ..*ctx
+                };
+                self.run_hooks(Hook::PreSwap, &ctx)?;
+                logger.progress_start(Phase::Swap, None);
+                let result = swap(
+                    vfs,
+                    output,
+                    output_new,
+                    output_old,
+                    self.rename_retry,
+                    logger,
+                );
+                logger.progress_finish();
+                result?;
+                self.journal(output, journal::State::Swapped, ctx.version);
+                Ok(Some(output_old.clone()))
+            }
+            Layout::Squirrel => {
+                let version = ctx.version.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "--layout squirrel requires --app-version for this input",
+                    )
+                })?;
+                squirrel::apply(
+                    output,
+                    input_path,
+                    version,
+                    &self.ignores,
+                    &self.ignore_existing,
+                    &on_staged,
+                    logger,
+                )
+                .map_err(|e| annotate(e, &format!("Failed to install version {}", version)))?;
+                Ok(None)
+            }
+            Layout::Appimage => {
+                format
+                    .find(input_path)
+                    .and_then(|image| appimage::apply(output, &image, &on_staged, logger))
+                    .map_err(|e| annotate(e, "Failed to replace AppImage"))?;
+                Ok(None)
+            }
+            Layout::Deb | Layout::Rpm => {
+                format
+                    .find(input_path)
+                    .and_then(|package| {
+                        on_staged(&package)?;
+                        syspkg::install(format, &package, logger)
+                    })
+                    .map_err(|e| annotate(e, "Failed to install package"))?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// 实际使用的复制方式：io_uring 不可用或不是 Linux 时记录警告并逐个复制
+    fn copy_engine(&self) -> CopyEngine {
+        if self.copy_engine == CopyEngine::Std {
+            return CopyEngine::Std;
+        }
+        #[cfg(target_os = "linux")]
+        match (self.copy_engine, crate::uring::available()) {
+            (CopyEngine::Uring, Err(e)) => {
+                self.logger.warn(&format!(
+                    "io_uring is not available ({}), copying files one by one",
+                    e
+                ));
+                CopyEngine::Std
+            }
+            (engine, _) => engine,
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            self.logger.warn(&format!(
+                "Copy engine {:?} is only supported on Linux, copying files one by one",
+                self.copy_engine
+            ));
+            CopyEngine::Std
+        }
+    }
+
+    /// resources 布局：把旧 output、更新文件与各层合并到 <output>_new 并执行 post-stage 钩子，
+    /// 返回 (<output>_new, <output>_old)
+    fn stage(
+        &self,
+        vfs: &dyn FileSystem,
+        output: &Path,
+        input_path: &Path,
+        layers: &[PathBuf],
+        ctx: &HookContext,
+    ) -> io::Result<(PathBuf, PathBuf)> {
+        let on_staged = |staging: &Path| {
+            let ctx = HookContext {
+                staging: Some(staging),
+                ..*ctx
+            };
+            self.run_hooks(Hook::PostStage, &ctx)
+        };
+        self.journal(output, journal::State::Staging, ctx.version);
+        let staged = stage_output(
+            vfs,
+            output,
+            input_path,
+            layers,
+            &self.ignores,
+            &self.ignore_existing,
+            self.move_input,
+            &on_staged,
+            &self.logger,
+        )?;
+        self.journal(output, journal::State::Staged, ctx.version);
+        Ok(staged)
+    }
+
+    /// 记录 resources 布局替换进行到的阶段（见 [`journal`]），模拟运行时不记录；失败只记录警告
+    fn journal(&self, output: &Path, state: journal::State, version: Option<&str>) {
+        if self.simulate || self.layout != Layout::Resources {
+            return;
+        }
+        if let Err(e) = journal::write(output, state, version) {
+            self.logger
+                .warn(&format!("Failed to write update journal: {}", e));
+        }
+    }
+
+    /// 删除 input、各层与旧版本备份，失败只记录警告
+    fn cleanup(
+        &self,
+        vfs: &dyn FileSystem,
+        input_path: &Path,
+        layers: &[PathBuf],
+        output_old: Option<&Path>,
+    ) {
+        let logger = &self.logger;
+        logger.log("Cleaning up old files...");
+        logger.progress_start(Phase::Cleanup, None);
+        let inputs = std::iter::once(input_path).chain(layers.iter().map(PathBuf::as_path));
+        for input_path in inputs.filter(|p| vfs.exists(p)) {
+            if self.keep_input {
+                logger.log(&format!("Keeping input: {}", input_path.display()));
+                continue;
+            }
+            if !may_remove(vfs, input_path, false, self.confirm_delete, logger) {
+                continue;
+            }
+            self.shred(input_path);
+            if let Err(e) = remove_path(vfs, input_path) {
+                logger.warn(&format!("Failed to remove input: {}", e));
+            } else {
+                logger.log(&format!("Removed input: {}", input_path.display()));
+            }
+        }
+
+        let output_old = output_old.filter(|p| vfs.exists(p));
+        if let (Some(output_old), Backup::Keep) = (output_old, self.backup) {
+            logger.log(&format!(
+                "Keeping backup directory: {}",
+                output_old.display()
+            ));
+        }
+        if let (Some(output_old), Backup::Archive) = (output_old, self.backup) {
+            let dir = self.backup_dir.as_deref().expect("checked by build");
+            match crate::archive_backup(vfs, output_old, dir, logger) {
+                Ok(dest) => logger.log(&format!("Archived backup directory to {}", dest.display())),
+                Err(e) => logger.warn(&format!("Failed to archive backup directory: {}", e)),
+            }
+        }
+        if let Some(output_old) = output_old.filter(|_| self.backup == Backup::Delete)
+            && may_remove(vfs, output_old, true, self.confirm_delete, logger)
+        {
+            self.shred(output_old);
+            if let Err(e) = remove_path(vfs, output_old) {
+                logger.warn(&format!("Failed to remove output_old directory: {}", e));
+            } else {
+                logger.log(&format!(
+                    "Removed backup directory: {}",
+                    output_old.display()
+                ));
+            }
+        }
+
+        logger.progress_finish();
+    }
+
+    /// 删除超过保留天数的备份：保留的 `<output>_old`（resources 布局）与备份目录中的归档
+    fn prune_backups(&self) {
+        let Some(days) = self.backup_days else {
+            return;
+        };
+        let logger = &self.logger;
+        let mut backups = Vec::new();
+        if self.layout == Layout::Resources
+            && let Ok(output_old) = crate::sibling(&self.output, "", "_old")
+        {
+            backups.push(output_old);
+        }
+        if let Some(dir) = &self.backup_dir
+            && let Ok(entries) = std::fs::read_dir(dir)
+        {
+            backups.extend(entries.filter_map(|e| e.ok()).map(|e| e.path()));
+        }
+        for backup in backups {
+            if !crate::backup_expired(&backup, days)
+                || !may_remove(
+                    &RealFs::default(),
+                    &backup,
+                    true,
+                    self.confirm_delete,
+                    logger,
+                )
+            {
+                continue;
+            }
+            match remove_path(&RealFs::default(), &backup) {
+                Ok(()) => logger.log(&format!(
+                    "Removed backup older than {} days: {}",
+                    days,
+                    backup.display()
+                )),
+                Err(e) => logger.warn(&format!(
+                    "Failed to remove expired backup {}: {}",
+                    backup.display(),
+                    e
+                )),
+            }
+        }
+    }
+
+    /// 启动主程序；文件操作到此已全部完成，按设置先降权或交给未提权的进程。返回降权后的用户
+    fn restart(&self, app: &Path) -> Option<privilege::Caller> {
+        if let Some(path) = &self.restart_handoff {
+            let handoff = Handoff {
+                output: self.output.clone(),
+                app: app.to_path_buf(),
+                version: None,
+                old_version: None,
+                post_restart: false,
+            };
+            self.hand_off(path, &handoff);
+            return None;
+        }
+        let user = self.drop_privileges();
+        restart_app_as(app, user.as_ref(), &self.logger);
+        user
+    }
+
+    /// 按设置放弃权限，返回切换到的用户；之后不能再执行需要权限的步骤
+    fn drop_privileges(&self) -> Option<privilege::Caller> {
+        if !self.drop_privileges || self.simulate {
+            return None;
+        }
+        // 降权后可能无权打开历史文件，先打开备用
+        *self.history.lock().unwrap() = history::open(&self.output).ok();
+        privilege::drop_privileges(&self.logger).unwrap_or_else(|e| {
+            self.logger
+                .warn(&format!("Failed to drop privileges: {}", e));
+            None
+        })
+    }
+
+    /// 写入交接文件，由未提权的进程重新启动应用
+    fn hand_off(&self, path: &Path, handoff: &Handoff) {
+        match handoff.write(path) {
+            Ok(()) => self
+                .logger
+                .log("Restart handed off to the unelevated updater"),
+            Err(e) => {
+                self.logger
+                    .warn(&format!("Failed to write restart handoff: {}", e));
+                restart_app(&handoff.app, &self.logger);
+            }
+        }
+    }
+
+    /// 安装方式对应的更新包格式
+    fn format(&self) -> Format {
+        match self.layout {
+            Layout::Appimage => Format::AppImage,
+            Layout::Deb => Format::Deb,
+            Layout::Rpm => Format::Rpm,
+            Layout::Resources | Layout::Squirrel => Format::Zip,
+        }
+    }
+
+    /// 比较更新文件与已安装应用 package.json 中的版本号（更新文件中没有时使用 version），
+    /// 不高于已安装版本且未指定 force 时返回 false；无法确定任一版本时照常更新
+    fn is_newer(&self, output: &Path, input_path: &Path, version: Option<&str>) -> bool {
+        let logger = &self.logger;
+        let Some(installed) = self.installed_version(output) else {
+            return true;
+        };
+        let Some(candidate) = Some(input_path)
+            .filter(|p| p.exists())
+            .and_then(|dir| self.read_version(dir))
+            .or_else(|| version.map(str::to_string))
+        else {
+            return true;
+        };
+        let ordering = match version::compare(&candidate, &installed) {
+            Some(ordering) => ordering,
+            None => {
+                logger.warn(&format!(
+                    "Cannot compare versions {} and {}, continuing",
+                    candidate, installed
+                ));
+                return true;
+            }
+        };
+        logger.log(&format!(
+            "Installed version: {}, update version: {}",
+            installed, candidate
+        ));
+        if ordering == Ordering::Greater {
+            return true;
+        }
+        if self.force {
+            logger.warn("Update is not newer than the installed version, continuing with --force");
+            return true;
+        }
+        if ordering == Ordering::Less {
+            logger.warn("Update is older than the installed version, refusing to downgrade");
+        }
+        logger.log("Already up to date, rerun with --force to reinstall");
+        false
+    }
+
+    /// 比较更新文件与已安装主程序的 CPU 架构，不一致且未指定 force 时返回错误；无法识别任一架构时照常更新
+    fn check_arch(&self, app: &Path, input_path: &Path) -> Result<(), UpdateError> {
+        let logger = &self.logger;
+        let update_binary = match self.layout {
+            Layout::Resources | Layout::Squirrel if !input_path.is_dir() => return Ok(()),
+            Layout::Resources | Layout::Squirrel => {
+                let name = app.file_name().unwrap_or_default();
+                arch::find_binary(input_path, name)
+            }
+            Layout::Appimage => self.format().find(input_path).map(Some),
+            // 包管理器会自行检查架构
+            Layout::Deb | Layout::Rpm => return Ok(()),
+        };
+        let detected = app
+            .exists()
+            .then(|| arch::of_app(app))
+            .transpose()
+            .map(Option::flatten)
+            .and_then(|installed| {
+                let update = match update_binary? {
+                    Some(binary) => arch::detect(&binary)?,
+                    None => None,
+                };
+                Ok(installed.zip(update))
+            });
+        let (installed, update) = match detected {
+            Ok(Some(archs)) => archs,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                logger.warn(&format!("Failed to detect architecture: {}", e));
+                return Ok(());
+            }
+        };
+        if installed.compatible(update) {
+            logger.log(&format!("Architecture: {}", update));
+            return Ok(());
+        }
+        if self.force {
+            logger.warn(&format!(
+                "Update is built for {} but the installed app is {}, continuing with --force",
+                update, installed
+            ));
+            return Ok(());
+        }
+        Err(UpdateError::ArchMismatch { installed, update })
+    }
+
+    /// 删除前覆盖 path 中匹配 --secure-delete 的文件；模拟运行时不覆盖
+    fn shred(&self, path: &Path) {
+        if self.secure_delete.is_empty() || self.simulate {
+            return;
+        }
+        match shred::overwrite_matching(path, &self.secure_delete, &self.logger) {
+            Ok(0) => {}
+            Ok(count) => self.logger.log(&format!(
+                "Securely overwrote {} files in {}",
+                count,
+                path.display()
+            )),
+            Err(e) => self.logger.warn(&format!(
+                "Failed to securely overwrite files in {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    /// 比较更新文件与已安装主程序的签名者：已安装的主程序已签名，而新主程序没有同一签名者的有效签名
+    /// （未签名、签名无效、签名者不同或无法校验）时返回错误；
+    /// 更新文件中没有主程序、已安装的主程序未签名或无法读取其签名时照常更新
+    fn check_publisher(&self, app: &Path, input_path: &Path) -> Result<(), UpdateError> {
+        let logger = &self.logger;
+        if !matches!(self.layout, Layout::Resources | Layout::Squirrel)
+            || !input_path.is_dir()
+            || !app.exists()
+        {
+            return Ok(());
+        }
+        let name = app.file_name().unwrap_or_default();
+        let detect = || -> io::Result<Option<(PathBuf, String)>> {
+            let Some(binary) = signer::find_app(input_path, name)? else {
+                return Ok(None);
+            };
+            Ok(signer::publisher(app)?.map(|installed| (binary, installed)))
+        };
+        let (binary, installed) = match detect() {
+            Ok(Some(found)) => found,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                logger.warn(&format!(
+                    "Failed to read code signature of the installed app: {}",
+                    e
+                ));
+                return Ok(());
+            }
+        };
+        // 已安装的主程序已签名：新主程序的签名无法校验时按签名者不同处理
+        match signer::verify(&binary, &installed) {
+            Ok(true) => {
+                logger.log(&format!("Publisher: {}", installed));
+                return Ok(());
+            }
+            Ok(false) => {}
+            Err(e) => logger.warn(&format!(
+                "Failed to verify code signature of {}: {}",
+                binary.display(),
+                e
+            )),
+        }
+        // 签名者相同但校验未通过（签名无效）时不显示签名者
+        let update = signer::publisher(&binary)
+            .unwrap_or_default()
+            .filter(|update| *update != installed);
+        if self.allow_publisher_change {
+            logger.warn(&format!(
+                "Update {} but the installed app is signed by {}, continuing with --allow-publisher-change",
+                update.as_ref().map_or("has no valid signature".to_string(), |u| format!(
+                    "is signed by {}",
+                    u
+                )),
+                installed
+            ));
+            return Ok(());
+        }
+        Err(UpdateError::PublisherChanged { installed, update })
+    }
+
+    /// 已安装应用 package.json 中的版本号（appimage、deb、rpm 布局不支持）
+    fn installed_version(&self, output: &Path) -> Option<String> {
+        let dir = match self.layout {
+            Layout::Resources => Some(output.to_path_buf()),
+            Layout::Squirrel => squirrel::current_dir(output).ok().flatten(),
+            Layout::Appimage | Layout::Deb | Layout::Rpm => None,
+        };
+        dir.filter(|d| d.exists())
+            .and_then(|d| self.read_version(&d))
+    }
+
+    /// 读取 dir 中应用的版本号，失败时记录警告并返回 None
+    fn read_version(&self, dir: &Path) -> Option<String> {
+        version::package_version(dir).unwrap_or_else(|e| {
+            self.logger.warn(&format!(
+                "Failed to read app version from {}: {}",
+                dir.display(),
+                e
+            ));
+            None
+        })
+    }
+
+    /// 依次准备各层（不受灰度影响，总是解包），返回 (input, 各层)。有多层时 input 与各层
+    /// 移动到缓存中各自的目录（见 [`download::isolate`]），避免解包到同一位置时互相覆盖
+    fn prepare_overlays(&self, input_path: PathBuf) -> io::Result<(PathBuf, Vec<PathBuf>)> {
+        if self.overlays.is_empty() {
+            return Ok((input_path, Vec::new()));
+        }
+        let input_path = download::isolate(input_path, "layers", 0)?;
+        let mut layers = Vec::new();
+        for (i, overlay) in self.overlays.iter().enumerate() {
+            let ctx = SourceContext {
+                format: Format::Zip,
+                extract: true,
+                channel: self.channel.as_deref(),
+                rollout: None,
+                download: &self.download,
+                logger: &self.logger,
+            };
+            let Some(prepared) = overlay.prepare(&ctx)? else {
+                self.logger.log(&format!(
+                    "Overlay {} is not in the current rollout, skipping",
+                    overlay.describe()
+                ));
+                continue;
+            };
+            layers.push(download::isolate(prepared.path, "layers", i + 1)?);
+        }
+        Ok((input_path, layers))
+    }
+
+    /// 准备更新文件（下载、校验、解包），本机未命中灰度时返回 None
+    fn prepare(&self) -> io::Result<Option<Prepared>> {
+        self.source.prepare(&SourceContext {
+            format: self.format(),
+            // 单文件目标需要从解压后的目录中取出同名文件
+            extract: !self.stream_extract
+                || self.simulate
+                || self.layout != Layout::Resources
+                || self.output.is_file(),
+            channel: self.channel.as_deref(),
+            rollout: self.rollout,
+            download: &self.download,
+            logger: &self.logger,
+        })
+    }
+}
+
+/// 是否为包装在 io::Error 中、需要保留类型的错误（钩子失败或 UpdateError）
+fn is_typed(e: &io::Error) -> bool {
+    e.get_ref()
+        .is_some_and(|inner| inner.is::<HookFailed>() || inner.is::<UpdateError>())
+}
+
+/// 在 output 中写入版本戳，package_sha256 为更新文件的摘要（见 [`stamp::tree_sha256`]），失败只记录警告
+pub(crate) fn write_stamp(
+    output: &Path,
+    package_sha256: io::Result<String>,
+    version: Option<String>,
+    logger: &Logger,
+) {
+    let result =
+        package_sha256.and_then(|sha256| stamp::write(output, &stamp::Stamp::new(version, sha256)));
+    match result {
+        Ok(()) => logger.log(&format!(
+            "Wrote {}",
+            output.join(stamp::FILE_NAME).display()
+        )),
+        Err(e) => logger.warn(&format!("Failed to write version stamp: {}", e)),
+    }
+}
+
+/// 为错误附加说明；钩子失败等带类型的错误原样返回，保留其类型
+fn annotate(e: io::Error, msg: &str) -> io::Error {
+    if is_typed(&e) {
+        e
+    } else {
+        io::Error::new(e.kind(), format!("{}: {}", msg, e))
+    }
+}
+
+/// 规范化每一项（见 [`crate::normalize_ignore`]）并去除空项
+fn normalize_ignores<I, S>(ignores: I) -> Vec<PathBuf>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    ignores
+        .into_iter()
+        .filter_map(|s| crate::normalize_ignore(s.as_ref()))
+        .collect()
+}
+
+/// 本地的 input 按 [`resolve_path`] 规范化，URL 原样返回
+fn resolve_input(input: String) -> io::Result<String> {
+    if download::is_url(&input) {
+        return Ok(input);
+    }
+    let resolved = resolve_path(Path::new(&input))?;
+    Ok(resolved.into_os_string().into_string().unwrap_or(input))
+}
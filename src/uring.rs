@@ -0,0 +1,185 @@
+//! Linux 上基于 io_uring 的批量复制：同时提交多个小文件的读写请求，由内核统一调度磁盘访问，
+//! 在机械硬盘上复制成千上万个小文件时明显减少寻道等待。大文件仍逐个以 std::fs::copy 复制
+
+use io_uring::{IoUring, opcode, squeue, types};
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// 同时进行中的文件数
+const DEPTH: usize = 64;
+
+/// 超过此大小的文件不经过 io_uring
+const SMALL: u64 = 1024 * 1024;
+
+/// 当前内核与运行环境是否可以使用 io_uring（容器的 seccomp 配置可能禁止）
+pub fn available() -> io::Result<()> {
+    IoUring::new(2).map(drop)
+}
+
+/// 正在复制的文件：先把整个文件读入 buffer，再写出
+struct Job {
+    to: PathBuf,
+    source: File,
+    dest: File,
+    permissions: Permissions,
+    buffer: Vec<u8>,
+    /// 读取阶段为已读的字节数，写入阶段为已写的字节数
+    offset: usize,
+    writing: bool,
+}
+
+impl Job {
+    /// 从 offset 处继续读或写的请求
+    fn entry(&mut self, slot: usize) -> squeue::Entry {
+        let rest = &mut self.buffer[self.offset..];
+        let (ptr, len) = (rest.as_mut_ptr(), rest.len() as u32);
+        let entry = if self.writing {
+            opcode::Write::new(types::Fd(self.dest.as_raw_fd()), ptr, len)
+                .offset(self.offset as u64)
+                .build()
+        } else {
+            opcode::Read::new(types::Fd(self.source.as_raw_fd()), ptr, len)
+                .offset(self.offset as u64)
+                .build()
+        };
+        entry.user_data(slot as u64)
+    }
+}
+
+/// 复制 files，语义同 [`crate::vfs::FileSystem::copy_many`]。出错或 done 返回错误后不再提交新的请求，
+/// 等已提交的请求全部完成后返回第一个错误
+pub fn copy_files(
+    files: &[(PathBuf, PathBuf)],
+    done: &mut dyn FnMut(&Path, u64) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut ring = IoUring::new(DEPTH as u32)?;
+    let mut slots: Vec<Option<Job>> = (0..DEPTH).map(|_| None).collect();
+    let mut next = 0;
+    let mut in_flight = 0;
+    let mut error = None;
+    loop {
+        while error.is_none() && next < files.len() {
+            let Some(slot) = slots.iter().position(Option::is_none) else {
+                break;
+            };
+            let (from, to) = &files[next];
+            next += 1;
+            match start(from, to) {
+                Ok(Some(mut job)) => {
+                    let entry = job.entry(slot);
+                    slots[slot] = Some(job);
+                    // 每个位置最多一个请求，队列不会满
+                    // SAFETY: 请求引用的缓冲区与文件属于 slots[slot] 中的 job，在该请求完成前不会释放；
+                    // buffer 在堆上，job 移动不改变其地址，其间也不会扩容
+                    unsafe { ring.submission().push(&entry) }.expect("ring has a free entry");
+                    in_flight += 1;
+                }
+                // 空文件或大文件已直接复制
+                Ok(None) => {
+                    if let Err(e) = fs::metadata(to).and_then(|m| done(to, m.len())) {
+                        error = Some(e);
+                    }
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        if in_flight == 0 {
+            break;
+        }
+        match ring.submit_and_wait(1) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                // 无法确认内核是否仍在使用缓冲区，不释放
+                std::mem::forget(slots);
+                return Err(e);
+            }
+        }
+        let completions: Vec<_> = ring
+            .completion()
+            .map(|c| (c.user_data() as usize, c.result()))
+            .collect();
+        for (slot, result) in completions {
+            in_flight -= 1;
+            let job = slots[slot].as_mut().expect("completion for a running job");
+            let failed = if result < 0 {
+                Some(io::Error::from_raw_os_error(-result))
+            } else if result == 0 && job.writing {
+                Some(io::Error::from(io::ErrorKind::WriteZero))
+            } else {
+                None
+            };
+            if let Some(e) = failed {
+                let e = io::Error::new(e.kind(), format!("{}: {}", job.to.display(), e));
+                error.get_or_insert(e);
+            }
+            if error.is_some() {
+                slots[slot] = None;
+                continue;
+            }
+            if result == 0 {
+                // 读取时文件变短：只写出已读的部分
+                job.buffer.truncate(job.offset);
+            }
+            job.offset += result as usize;
+            if job.offset == job.buffer.len() {
+                if job.writing {
+                    let job = slots[slot].take().expect("checked above");
+                    if let Err(e) = finish(job, done) {
+                        error = Some(e);
+                    }
+                    continue;
+                }
+                job.writing = true;
+                job.offset = 0;
+                if job.buffer.is_empty() {
+                    let job = slots[slot].take().expect("checked above");
+                    if let Err(e) = finish(job, done) {
+                        error = Some(e);
+                    }
+                    continue;
+                }
+            }
+            let entry = job.entry(slot);
+            // SAFETY: 同上，job 仍在 slots[slot] 中，上一个请求已完成
+            unsafe { ring.submission().push(&entry) }.expect("ring has a free entry");
+            in_flight += 1;
+        }
+    }
+    match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// 打开源文件与目标文件；空文件与大文件直接复制并返回 None
+fn start(from: &Path, to: &Path) -> io::Result<Option<Job>> {
+    let source = File::open(from)?;
+    let meta = source.metadata()?;
+    if meta.len() == 0 || meta.len() > SMALL {
+        fs::copy(from, to)?;
+        return Ok(None);
+    }
+    let dest = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(to)?;
+    Ok(Some(Job {
+        to: to.to_path_buf(),
+        source,
+        dest,
+        permissions: meta.permissions(),
+        buffer: vec![0; meta.len() as usize],
+        offset: 0,
+        writing: false,
+    }))
+}
+
+/// 写入完成：设置权限并通知 done
+fn finish(job: Job, done: &mut dyn FnMut(&Path, u64) -> io::Result<()>) -> io::Result<()> {
+    job.dest.set_permissions(job.permissions)?;
+    done(&job.to, job.buffer.len() as u64)
+}
@@ -0,0 +1,178 @@
+//! 读取 Electron 应用 package.json 中的版本号，用于判断更新是否比已安装版本新；
+//! 以及检查当前系统版本是否满足更新源声明的最低要求
+
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// dir 中应用的版本号：依次查找 app.asar、app/package.json，以及 resources/ 下的同名文件（dir 为 asar 文件时读取其中的 package.json）；
+/// 都不存在时返回 None
+pub fn package_version(dir: &Path) -> io::Result<Option<String>> {
+    // 单文件目标：asar 文件本身
+    if dir.is_file() {
+        if dir.extension().is_none_or(|ext| ext != "asar") {
+            return Ok(None);
+        }
+        return read_asar_file(dir, "package.json")?
+            .map(|bytes| parse_version(&bytes))
+            .transpose()
+            .map(Option::flatten);
+    }
+    for base in [dir.to_path_buf(), dir.join("resources")] {
+        let asar = base.join("app.asar");
+        let manifest = if asar.is_file() {
+            read_asar_file(&asar, "package.json")?
+        } else {
+            match fs::read(base.join("app").join("package.json")) {
+                Ok(bytes) => Some(bytes),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            }
+        };
+        if let Some(bytes) = manifest {
+            return parse_version(&bytes);
+        }
+    }
+    Ok(None)
+}
+
+/// package.json 中的 version
+fn parse_version(bytes: &[u8]) -> io::Result<Option<String>> {
+    let value: serde_json::Value = serde_json::from_slice(bytes).map_err(io::Error::other)?;
+    Ok(value["version"].as_str().map(str::to_string))
+}
+
+/// 按 semver 比较 candidate 与 installed，任一无法解析时返回 None
+pub fn compare(candidate: &str, installed: &str) -> Option<Ordering> {
+    let parse = |v: &str| semver::Version::parse(v.trim().trim_start_matches('v')).ok();
+    Some(parse(candidate)?.cmp(&parse(installed)?))
+}
+
+/// 当前系统版本：Windows 为 NT 版本号（如 10.0.17763），macOS 为系统版本（如 13.6），其他平台返回 None
+pub fn system_version() -> Option<String> {
+    if cfg!(windows) {
+        let build: u64 = sysinfo::System::kernel_version()?.trim().parse().ok()?;
+        // Windows 10/11 的 NT 版本号均为 10.0，只有 build 不同；更早的系统只需比 10.0 小即可
+        let major = if build >= 10240 { "10.0" } else { "6.3" };
+        Some(format!("{}.{}", major, build))
+    } else if cfg!(target_os = "macos") {
+        sysinfo::System::os_version()
+    } else {
+        None
+    }
+}
+
+/// 检查当前系统是否满足更新源声明的最低系统版本（minimumSystemVersion），满足时返回当前系统版本，
+/// 不满足时返回 Unsupported 错误；无法取得系统版本的平台不检查，返回 None
+pub fn check_system(minimum: &str) -> io::Result<Option<String>> {
+    let Some(running) = system_version() else {
+        return Ok(None);
+    };
+    if compare_numeric(&running, minimum) != Ordering::Less {
+        return Ok(Some(running));
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "This update requires {} or later, this machine runs {}",
+            system_name(minimum),
+            system_name(&running)
+        ),
+    ))
+}
+
+/// 逐段比较以 . 分隔的数字版本号，缺少的段视为 0
+fn compare_numeric(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<u64> {
+        v.trim()
+            .split('.')
+            .map(|p| p.trim().parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+    (0..a.len().max(b.len()))
+        .map(|i| {
+            let x = a.get(i).copied().unwrap_or(0);
+            let y = b.get(i).copied().unwrap_or(0);
+            x.cmp(&y)
+        })
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// 便于阅读的系统名称，例如 10.0.17763 → Windows 10 1809 (10.0.17763)
+fn system_name(version: &str) -> String {
+    if cfg!(target_os = "macos") {
+        return format!("macOS {}", version);
+    }
+    let build = version
+        .rsplit('.')
+        .next()
+        .and_then(|b| b.parse::<u64>().ok());
+    let release = match build {
+        Some(10240) => "Windows 10 1507",
+        Some(10586) => "Windows 10 1511",
+        Some(14393) => "Windows 10 1607",
+        Some(15063) => "Windows 10 1703",
+        Some(16299) => "Windows 10 1709",
+        Some(17134) => "Windows 10 1803",
+        Some(17763) => "Windows 10 1809",
+        Some(18362) => "Windows 10 1903",
+        Some(18363) => "Windows 10 1909",
+        Some(19041) => "Windows 10 2004",
+        Some(19042) => "Windows 10 20H2",
+        Some(19043) => "Windows 10 21H1",
+        Some(19044) => "Windows 10 21H2",
+        Some(19045) => "Windows 10 22H2",
+        Some(22000) => "Windows 11 21H2",
+        Some(22621) => "Windows 11 22H2",
+        Some(22631) => "Windows 11 23H2",
+        Some(26100) => "Windows 11 24H2",
+        _ => "Windows",
+    };
+    format!("{} ({})", release, version)
+}
+
+/// 读取 asar 归档根目录下的文件，不存在时返回 None。
+/// 格式：8 字节 pickle 头（第二个 u32 为头部长度），随后是 JSON 头部，文件数据紧随头部之后；
+/// 标记为 unpacked 的文件位于同级的 app.asar.unpacked 目录
+fn read_asar_file(asar: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a valid asar archive", asar.display()),
+        )
+    };
+    let mut file = File::open(asar)?;
+    let mut prefix = [0u8; 16];
+    file.read_exact(&mut prefix)?;
+    let u32_at = |i: usize| u32::from_le_bytes(prefix[i..i + 4].try_into().unwrap()) as u64;
+    let header_size = u32_at(4);
+    let json_len = u32_at(12);
+    if json_len + 8 > header_size {
+        return Err(invalid());
+    }
+    let mut json = vec![0u8; json_len as usize];
+    file.read_exact(&mut json)?;
+    let header: serde_json::Value = serde_json::from_slice(&json).map_err(|_| invalid())?;
+
+    let entry = &header["files"][name];
+    if entry.is_null() {
+        return Ok(None);
+    }
+    if entry["unpacked"].as_bool() == Some(true) {
+        let mut unpacked = asar.as_os_str().to_owned();
+        unpacked.push(".unpacked");
+        return fs::read(Path::new(&unpacked).join(name)).map(Some);
+    }
+    let offset: u64 = entry["offset"]
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(invalid)?;
+    let size = entry["size"].as_u64().ok_or_else(invalid)?;
+    file.seek(SeekFrom::Start(8 + header_size + offset))?;
+    let mut bytes = vec![0u8; size as usize];
+    file.read_exact(&mut bytes)?;
+    Ok(Some(bytes))
+}
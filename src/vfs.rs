@@ -0,0 +1,799 @@
+//! 文件系统抽象：暂存、替换、回滚只通过 [`FileSystem`] 操作文件，
+//! 既可以作用于真实磁盘（[`RealFs`]），也可以在内存中模拟（[`MemoryFs`]），便于确定性地验证各种失败路径
+
+use crate::Logger;
+use std::collections::BTreeMap;
+#[cfg(target_os = "linux")]
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::thread;
+
+/// 更新流程用到的文件操作，语义与 std::fs 中的同名函数一致
+pub trait FileSystem: Send + Sync {
+    fn exists(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    /// 目录下的直接子项
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// 创建只有当前用户可以访问的目录（暂存目录）
+    fn create_private_dir(&self, path: &Path) -> io::Result<()> {
+        self.create_dir_all(path)
+    }
+    /// 替换前把私有目录的权限恢复为与 like 相同
+    fn release_private_dir(&self, _path: &Path, _like: &Path) -> io::Result<()> {
+        Ok(())
+    }
+    /// 复制单个文件，返回字节数
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64>;
+    /// 复制多个文件（目标所在目录已存在），每复制完一个以目标路径与字节数调用 done，
+    /// done 返回错误时停止；系统接口提供时，以（目标路径，已复制，总大小）调用 progress 报告大文件的进度。
+    /// 默认依次调用 copy
+    fn copy_many(
+        &self,
+        files: &[(PathBuf, PathBuf)],
+        _progress: &dyn Fn(&Path, u64, u64),
+        done: &mut dyn FnMut(&Path, u64) -> io::Result<()>,
+    ) -> io::Result<()> {
+        for (from, to) in files {
+            let bytes = self.copy(from, to)?;
+            done(to, bytes)?;
+        }
+        Ok(())
+    }
+    /// 在 to 处创建 from 的硬链接，两者共享内容而不必复制；默认复制
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.copy(from, to).map(drop)
+    }
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    /// 写入文件（所在目录已存在），已存在时覆盖
+    fn write_file(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    /// 把 zip 包中的文件解压到已有的目录 dest，跳过 ignores（见 [`crate::download::extract_zip_into`]）
+    fn extract_zip(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        ignores: &[PathBuf],
+        logger: &Logger,
+    ) -> io::Result<()>;
+    /// 文件是否被其他进程占用而暂时无法重命名所在目录（Windows）；默认 false
+    fn is_locked(&self, _path: &Path) -> bool {
+        false
+    }
+    /// 暂存完成后移除 path 下从网络下载带来的标记（macOS quarantine），默认不做处理
+    fn strip_quarantine(&self, _path: &Path, _logger: &Logger) {}
+    /// a 与 b 是否为内容相同的普通文件（符号链接不跟随）；默认无法比较，返回 false
+    fn same_content(&self, _a: &Path, _b: &Path) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    /// path 本身是否为符号链接（不跟随链接）
+    fn is_symlink(&self, _path: &Path) -> bool {
+        false
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Symlinks are not supported: {}", path.display()),
+        ))
+    }
+
+    /// 在 link 处创建指向 target 的符号链接，is_dir 表示目标是否为目录（Windows 上需要区分）
+    fn symlink(&self, _target: &Path, link: &Path, _is_dir: bool) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Symlinks are not supported: {}", link.display()),
+        ))
+    }
+}
+
+/// 暂存时复制文件的方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CopyEngine {
+    /// 逐个复制（std::fs::copy）
+    #[default]
+    Std,
+    /// Linux：逐个复制，同时提前打开后续文件并通知内核预读，减少机械硬盘上的寻道等待
+    Readahead,
+    /// Linux：通过 io_uring 同时提交多个小文件的读写，适合成千上万个小文件；内核不支持时回退为 std
+    Uring,
+}
+
+/// 并行复制（std 方式）时分配给各线程的单位
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Granularity {
+    /// 按文件分配，相邻的小文件合为一批以减少线程间的调度，适合随机访问快的本地磁盘（NVMe、SSD）
+    #[default]
+    File,
+    /// 按目录分配，同一目录中的文件由同一个线程复制，适合网络共享等目录操作开销大的存储
+    Dir,
+}
+
+/// 按文件分配时每批的文件数
+const BATCH_FILES: usize = 64;
+
+/// 真实磁盘
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RealFs {
+    copy_engine: CopyEngine,
+    copy_jobs: usize,
+    granularity: Granularity,
+}
+
+impl RealFs {
+    /// 以指定的方式复制文件；非 Linux 平台上总是逐个复制
+    pub fn with_copy_engine(copy_engine: CopyEngine) -> Self {
+        Self {
+            copy_engine,
+            ..Self::default()
+        }
+    }
+
+    /// 以 jobs 个线程并行复制文件（仅 std 方式，0 或 1 时依次复制），按 granularity 分配
+    pub fn with_copy_jobs(self, jobs: usize, granularity: Granularity) -> Self {
+        Self {
+            copy_jobs: jobs,
+            granularity,
+            ..self
+        }
+    }
+}
+
+impl FileSystem for RealFs {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect()
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn create_private_dir(&self, path: &Path) -> io::Result<()> {
+        crate::secure::create_private_dir(path)
+    }
+
+    fn release_private_dir(&self, path: &Path, like: &Path) -> io::Result<()> {
+        crate::secure::release(path, like)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        crate::copier::copy(from, to, &mut |_, _| {})
+    }
+
+    fn copy_many(
+        &self,
+        files: &[(PathBuf, PathBuf)],
+        progress: &dyn Fn(&Path, u64, u64),
+        done: &mut dyn FnMut(&Path, u64) -> io::Result<()>,
+    ) -> io::Result<()> {
+        match self.copy_engine {
+            #[cfg(target_os = "linux")]
+            CopyEngine::Readahead => copy_readahead(files, done),
+            #[cfg(target_os = "linux")]
+            CopyEngine::Uring => crate::uring::copy_files(files, done),
+            _ if self.copy_jobs > 1 => {
+                copy_parallel(files, self.copy_jobs, self.granularity, progress, done)
+            }
+            _ => {
+                for (from, to) in files {
+                    let bytes = crate::copier::copy(from, to, &mut |copied, total| {
+                        progress(to, copied, total)
+                    })?;
+                    done(to, bytes)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::hard_link(from, to)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    /// path 为符号链接时只删除链接本身，目录中的链接同样不跟随
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        if self.is_symlink(path) {
+            return remove_link(path);
+        }
+        fs::remove_dir_all(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn write_file(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn extract_zip(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        ignores: &[PathBuf],
+        logger: &Logger,
+    ) -> io::Result<()> {
+        crate::download::extract_zip_into(archive, dest, ignores, logger)
+    }
+
+    /// 以不共享的方式打开，共享冲突或锁冲突时视为占用；其他平台上打开的文件不妨碍重命名
+    fn is_locked(&self, path: &Path) -> bool {
+        is_locked(path)
+    }
+
+    fn strip_quarantine(&self, path: &Path, logger: &Logger) {
+        crate::macos::strip_quarantine(path, logger);
+    }
+
+    /// 先比较大小，再逐块比较内容
+    fn same_content(&self, a: &Path, b: &Path) -> io::Result<bool> {
+        let (meta_a, meta_b) = (fs::symlink_metadata(a)?, fs::symlink_metadata(b)?);
+        if !meta_a.is_file() || !meta_b.is_file() || meta_a.len() != meta_b.len() {
+            return Ok(false);
+        }
+        let (mut file_a, mut file_b) = (fs::File::open(a)?, fs::File::open(b)?);
+        let (mut buf_a, mut buf_b) = (vec![0; COMPARE_CHUNK], vec![0; COMPARE_CHUNK]);
+        loop {
+            let n = read_full(&mut file_a, &mut buf_a)?;
+            if n != read_full(&mut file_b, &mut buf_b)? || buf_a[..n] != buf_b[..n] {
+                return Ok(false);
+            }
+            if n == 0 {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        path.is_symlink()
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    #[cfg(unix)]
+    fn symlink(&self, target: &Path, link: &Path, _is_dir: bool) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    #[cfg(windows)]
+    fn symlink(&self, target: &Path, link: &Path, is_dir: bool) -> io::Result<()> {
+        if is_dir {
+            std::os::windows::fs::symlink_dir(target, link)
+        } else {
+            std::os::windows::fs::symlink_file(target, link)
+        }
+    }
+}
+
+#[cfg(windows)]
+fn is_locked(path: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt;
+    /// ERROR_SHARING_VIOLATION、ERROR_LOCK_VIOLATION
+    const LOCKED: [i32; 2] = [32, 33];
+    match fs::OpenOptions::new().read(true).share_mode(0).open(path) {
+        Ok(_) => false,
+        Err(e) => e.raw_os_error().is_some_and(|code| LOCKED.contains(&code)),
+    }
+}
+
+#[cfg(not(windows))]
+fn is_locked(_path: &Path) -> bool {
+    false
+}
+
+/// 比较文件内容时每次读取的字节数
+const COMPARE_CHUNK: usize = 256 * 1024;
+
+/// 读满 buf 或读到文件末尾，返回读取的字节数
+fn read_full(file: &mut fs::File, buf: &mut [u8]) -> io::Result<usize> {
+    use std::io::Read;
+    let mut n = 0;
+    while n < buf.len() {
+        match file.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(read) => n += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+/// 预读的文件数
+#[cfg(target_os = "linux")]
+const READAHEAD: usize = 32;
+
+/// 依次复制，复制当前文件时已打开其后的 READAHEAD 个文件并以 POSIX_FADV_WILLNEED 通知内核预读
+#[cfg(target_os = "linux")]
+fn copy_readahead(
+    files: &[(PathBuf, PathBuf)],
+    done: &mut dyn FnMut(&Path, u64) -> io::Result<()>,
+) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let open = |path: &Path| {
+        fs::File::open(path).inspect(|file| {
+            // 只是建议，失败不影响复制
+            // SAFETY: fd 属于刚打开的 file，调用期间有效
+            unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_WILLNEED) };
+        })
+    };
+    let mut opened = VecDeque::new();
+    for (index, (_, to)) in files.iter().enumerate() {
+        while opened.len() < READAHEAD && index + opened.len() < files.len() {
+            opened.push_back(open(&files[index + opened.len()].0));
+        }
+        let mut source = opened.pop_front().expect("opened ahead")?;
+        let permissions = source.metadata()?.permissions();
+        let mut dest = fs::File::create(to)?;
+        let bytes = io::copy(&mut source, &mut dest)?;
+        dest.set_permissions(permissions)?;
+        done(to, bytes)?;
+    }
+    Ok(())
+}
+
+/// 并行复制中工作线程发给调用线程的消息；progress 与 done 只在调用线程中执行
+enum CopyEvent {
+    Progress(PathBuf, u64, u64),
+    Done(PathBuf, u64),
+    Failed(io::Error),
+}
+
+/// 以 jobs 个线程复制：files 按 granularity 分成若干单位，空闲的线程依次领取下一个单位。
+/// 出错或 done 返回错误后不再开始复制新的文件，等各线程结束后返回第一个错误
+fn copy_parallel(
+    files: &[(PathBuf, PathBuf)],
+    jobs: usize,
+    granularity: Granularity,
+    progress: &dyn Fn(&Path, u64, u64),
+    done: &mut dyn FnMut(&Path, u64) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut units = Vec::new();
+    let mut start = 0;
+    for i in 1..=files.len() {
+        let split = i == files.len()
+            || match granularity {
+                Granularity::File => i - start == BATCH_FILES,
+                Granularity::Dir => files[i].1.parent() != files[start].1.parent(),
+            };
+        if split {
+            units.push(start..i);
+            start = i;
+        }
+    }
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (tx, rx) = mpsc::sync_channel(jobs * 4);
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(units.len()) {
+            let (tx, units, next, stop) = (tx.clone(), &units, &next, &stop);
+            scope.spawn(move || {
+                while let Some(unit) = units.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    for (from, to) in &files[unit.clone()] {
+                        if stop.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        let result = crate::copier::copy(from, to, &mut |copied, total| {
+                            let _ = tx.send(CopyEvent::Progress(to.clone(), copied, total));
+                        });
+                        let event = match result {
+                            Ok(bytes) => CopyEvent::Done(to.clone(), bytes),
+                            Err(e) => CopyEvent::Failed(e),
+                        };
+                        if tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+        drop(tx);
+        let mut error = None;
+        for event in rx {
+            let result = match event {
+                CopyEvent::Progress(to, copied, total) => {
+                    progress(&to, copied, total);
+                    continue;
+                }
+                CopyEvent::Done(_, _) if error.is_some() => continue,
+                CopyEvent::Done(to, bytes) => done(&to, bytes),
+                CopyEvent::Failed(e) => Err(e),
+            };
+            if let Err(e) = result {
+                error.get_or_insert(e);
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+        error.map_or(Ok(()), Err)
+    })
+}
+
+/// 删除符号链接本身；Windows 上指向目录的链接需要按目录删除
+fn remove_link(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if cfg!(windows) => fs::remove_dir(path).map_err(|_| e),
+        result => result,
+    }
+}
+
+/// 内存中的文件内容
+#[derive(Clone, Debug)]
+enum Node {
+    Dir,
+    File(Content),
+    /// 符号链接及其目标
+    Link(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+enum Content {
+    Bytes(Vec<u8>),
+    /// 从磁盘载入的文件只记录大小，不读取内容
+    Disk {
+        len: u64,
+    },
+}
+
+impl Content {
+    fn len(&self) -> u64 {
+        match self {
+            Content::Bytes(bytes) => bytes.len() as u64,
+            Content::Disk { len } => *len,
+        }
+    }
+}
+
+/// 可注入失败的操作，见 [`MemoryFs::fail_on`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Copy,
+    HardLink,
+    Rename,
+    /// remove_file 与 remove_dir_all
+    Remove,
+    Write,
+}
+
+/// 内存文件系统：所有修改只发生在内存中。
+/// 可通过 [`MemoryFs::load`] 载入磁盘上的目录结构，用于模拟一次真实的更新；
+/// 通过 [`MemoryFs::fail_on`] 让指定的操作失败，确定性地验证中途失败时的恢复
+#[derive(Default)]
+pub struct MemoryFs {
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+    /// 尚未触发的失败：(操作, 再执行几次该操作时失败)
+    faults: Mutex<Vec<(Op, usize)>>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 载入磁盘上 path（文件或目录）的结构，文件内容不读入内存，符号链接不跟随
+    pub fn load(&self, path: &Path) -> io::Result<()> {
+        let meta = fs::symlink_metadata(path)?;
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent)?;
+        }
+        if meta.is_symlink() {
+            self.nodes
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), Node::Link(fs::read_link(path)?));
+        } else if meta.is_dir() {
+            self.nodes
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), Node::Dir);
+            for entry in fs::read_dir(path)? {
+                self.load(&entry?.path())?;
+            }
+        } else {
+            self.nodes.lock().unwrap().insert(
+                path.to_path_buf(),
+                Node::File(Content::Disk { len: meta.len() }),
+            );
+        }
+        Ok(())
+    }
+
+    /// 写入文件内容，父目录不存在时自动创建
+    pub fn write(&self, path: &Path, bytes: impl Into<Vec<u8>>) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent)?;
+        }
+        self.nodes
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), Node::File(Content::Bytes(bytes.into())));
+        Ok(())
+    }
+
+    /// 读取通过 write 写入的文件内容；从磁盘载入的文件返回 None
+    pub fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        match self.nodes.lock().unwrap().get(path) {
+            Some(Node::File(Content::Bytes(bytes))) => Some(bytes.clone()),
+            _ => None,
+        }
+    }
+
+    /// 之后第 nth 次（从 1 开始）执行 op 时返回错误且不做任何修改，只触发一次
+    pub fn fail_on(&self, op: Op, nth: usize) {
+        self.faults.lock().unwrap().push((op, nth.max(1)));
+    }
+
+    /// 执行 op 前调用：到达注入的次数时返回错误
+    fn inject(&self, op: Op, path: &Path) -> io::Result<()> {
+        let mut faults = self.faults.lock().unwrap();
+        let mut failed = false;
+        for fault in faults.iter_mut().filter(|fault| fault.0 == op) {
+            fault.1 -= 1;
+            failed |= fault.1 == 0;
+        }
+        faults.retain(|fault| fault.1 > 0);
+        if failed {
+            return Err(io::Error::other(format!(
+                "Injected {:?} failure: {}",
+                op,
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// 复制文件节点，内容共享与否对内存文件系统没有区别
+    fn copy_node(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let Some(Node::File(content)) = nodes.get(from).cloned() else {
+            return Err(not_found(from));
+        };
+        if let Some(parent) = to.parent().filter(|p| !p.as_os_str().is_empty())
+            && !matches!(nodes.get(parent), Some(Node::Dir))
+        {
+            return Err(not_found(parent));
+        }
+        let len = content.len();
+        nodes.insert(to.to_path_buf(), Node::File(content));
+        Ok(len)
+    }
+
+    /// path 下（含自身）的所有文件路径，按字典序排列
+    pub fn files(&self, path: &Path) -> Vec<PathBuf> {
+        self.nodes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(p, node)| p.starts_with(path) && matches!(node, Node::File(_)))
+            .map(|(p, _)| p.clone())
+            .collect()
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} not found", path.display()),
+    )
+}
+
+/// path 本身及其下所有子项的路径
+fn subtree(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> Vec<PathBuf> {
+    nodes
+        .range(path.to_path_buf()..)
+        .take_while(|(p, _)| p.starts_with(path))
+        .map(|(p, _)| p.clone())
+        .collect()
+}
+
+impl FileSystem for MemoryFs {
+    fn exists(&self, path: &Path) -> bool {
+        self.nodes.lock().unwrap().contains_key(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        matches!(self.nodes.lock().unwrap().get(path), Some(Node::Dir))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let nodes = self.nodes.lock().unwrap();
+        match nodes.get(path) {
+            Some(Node::Dir) => Ok(subtree(&nodes, path)
+                .into_iter()
+                .filter(|p| p.parent() == Some(path))
+                .collect()),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{} is not a directory", path.display()),
+            )),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        for dir in path.ancestors().filter(|p| !p.as_os_str().is_empty()) {
+            match nodes.get(dir) {
+                Some(Node::Dir) => break,
+                Some(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} is a file", dir.display()),
+                    ));
+                }
+                None => {
+                    nodes.insert(dir.to_path_buf(), Node::Dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        self.inject(Op::Copy, to)?;
+        self.copy_node(from, to)
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.inject(Op::HardLink, to)?;
+        self.copy_node(from, to).map(drop)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.inject(Op::Rename, from)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        if !nodes.contains_key(from) {
+            return Err(not_found(from));
+        }
+        if matches!(nodes.get(to), Some(Node::Dir)) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", to.display()),
+            ));
+        }
+        for old in subtree(&nodes, from) {
+            let node = nodes.remove(&old).unwrap();
+            let new = if old == from {
+                to.to_path_buf()
+            } else {
+                to.join(old.strip_prefix(from).unwrap())
+            };
+            nodes.insert(new, node);
+        }
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.inject(Op::Remove, path)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        if !matches!(nodes.get(path), Some(Node::Dir)) {
+            return Err(not_found(path));
+        }
+        for p in subtree(&nodes, path) {
+            nodes.remove(&p);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.inject(Op::Remove, path)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        match nodes.get(path) {
+            Some(Node::File(_) | Node::Link(_)) => {
+                nodes.remove(path);
+                Ok(())
+            }
+            _ => Err(not_found(path)),
+        }
+    }
+
+    fn write_file(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.inject(Op::Write, path)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty())
+            && !matches!(nodes.get(parent), Some(Node::Dir))
+        {
+            return Err(not_found(parent));
+        }
+        nodes.insert(
+            path.to_path_buf(),
+            Node::File(Content::Bytes(contents.to_vec())),
+        );
+        Ok(())
+    }
+
+    /// 只读取磁盘上 archive 的目录，条目以只有大小的文件放入 dest
+    fn extract_zip(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        ignores: &[PathBuf],
+        _logger: &Logger,
+    ) -> io::Result<()> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let mut zip =
+            zip::ZipArchive::new(fs::File::open(archive)?).map_err(|e| invalid(e.to_string()))?;
+        for i in 0..zip.len() {
+            let entry = zip.by_index_raw(i).map_err(|e| invalid(e.to_string()))?;
+            let Some(relative) = entry.enclosed_name() else {
+                return Err(invalid(format!(
+                    "Refusing to extract {}: unsafe path",
+                    entry.name().map(|n| n.into_owned()).unwrap_or_default()
+                )));
+            };
+            if crate::is_ignored(&relative, ignores) {
+                continue;
+            }
+            let path = dest.join(&relative);
+            if entry.is_dir() {
+                self.create_dir_all(&path)?;
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                self.create_dir_all(parent)?;
+            }
+            self.nodes
+                .lock()
+                .unwrap()
+                .insert(path, Node::File(Content::Disk { len: entry.size() }));
+        }
+        Ok(())
+    }
+
+    /// 从磁盘载入的文件没有内容，只有写入的内容可以比较
+    fn same_content(&self, a: &Path, b: &Path) -> io::Result<bool> {
+        let nodes = self.nodes.lock().unwrap();
+        match (nodes.get(a), nodes.get(b)) {
+            (Some(Node::File(Content::Bytes(a))), Some(Node::File(Content::Bytes(b)))) => {
+                Ok(a == b)
+            }
+            (Some(_), Some(_)) => Ok(false),
+            (None, _) => Err(not_found(a)),
+            (_, None) => Err(not_found(b)),
+        }
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        matches!(self.nodes.lock().unwrap().get(path), Some(Node::Link(_)))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.nodes.lock().unwrap().get(path) {
+            Some(Node::Link(target)) => Ok(target.clone()),
+            _ => Err(not_found(path)),
+        }
+    }
+
+    fn symlink(&self, target: &Path, link: &Path, _is_dir: bool) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        if nodes.contains_key(link) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", link.display()),
+            ));
+        }
+        nodes.insert(link.to_path_buf(), Node::Link(target.to_path_buf()));
+        Ok(())
+    }
+}
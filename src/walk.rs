@@ -0,0 +1,164 @@
+//! 流式遍历目录：文件逐个产出，列表按内存上限分批处理，包含数十万个文件的目录树
+//! 在暂存与计算清单时也不需要一次性保存完整的文件列表，低内存的终端设备上占用可控
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// 默认的内存上限
+pub const DEFAULT_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// 列表中每个路径除内容以外的估计开销（PathBuf 本身与分配器的额外占用）
+const PATH_OVERHEAD: usize = 64;
+
+static MEMORY_LIMIT: AtomicUsize = AtomicUsize::new(DEFAULT_MEMORY_LIMIT);
+
+/// 设置进程内文件列表可占用的内存上限（字节），超过时先处理已收集的部分
+pub fn set_memory_limit(bytes: usize) {
+    MEMORY_LIMIT.store(bytes.max(1), Ordering::Relaxed);
+}
+
+pub fn memory_limit() -> usize {
+    MEMORY_LIMIT.load(Ordering::Relaxed)
+}
+
+/// 路径在列表中的估计占用
+pub fn path_cost(path: &Path) -> usize {
+    path.as_os_str().len() + PATH_OVERHEAD
+}
+
+/// 按内存上限分批处理的列表
+pub struct Batch<T> {
+    items: Vec<T>,
+    bytes: usize,
+}
+
+impl<T> Default for Batch<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            bytes: 0,
+        }
+    }
+}
+
+impl<T> Batch<T> {
+    /// 加入一项，cost 为其估计占用；达到上限时返回 true，调用方应取出（[`Batch::take`]）并处理
+    pub fn push(&mut self, item: T, cost: usize) -> bool {
+        self.items.push(item);
+        self.bytes += cost;
+        self.bytes >= memory_limit()
+    }
+
+    /// 取出已收集的项
+    pub fn take(&mut self) -> Vec<T> {
+        self.bytes = 0;
+        std::mem::take(&mut self.items)
+    }
+}
+
+/// 按相对路径（以 / 分隔）的字典序深度优先遍历 root 下的文件，跟随符号链接；
+/// 产出的顺序与收集全部相对路径后排序的结果一致，同时只在内存中保存当前路径上各层目录的子项。
+/// 名称按原始字节比较与拼接，非 UTF-8 的文件名同样可以访问
+pub struct Walk {
+    root: PathBuf,
+    /// 每层目录中尚未访问的子项，逆序存放，末尾为下一项
+    pending: Vec<Vec<(OsString, bool)>>,
+    /// pending 各层对应的目录（相对 root）
+    dirs: Vec<PathBuf>,
+}
+
+impl Walk {
+    pub fn new(root: &Path) -> io::Result<Self> {
+        Ok(Self {
+            root: root.to_path_buf(),
+            pending: vec![children(root)?],
+            dirs: vec![PathBuf::new()],
+        })
+    }
+}
+
+/// 目录中的子项（名称，是否为目录），按排序键逆序排列。目录的键为名称加 /，
+/// 与完整相对路径排序时目录内容的位置一致（如 `a.txt` 在 `a/b` 之前）
+fn children(dir: &Path) -> io::Result<Vec<(OsString, bool)>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_dir = entry.path().is_dir();
+        entries.push((entry.file_name(), is_dir));
+    }
+    let key = |(name, is_dir): &(OsString, bool)| {
+        let mut key = name.as_encoded_bytes().to_vec();
+        if *is_dir {
+            key.push(b'/');
+        }
+        key
+    };
+    entries.sort_by_cached_key(|entry| std::cmp::Reverse(key(entry)));
+    Ok(entries)
+}
+
+/// 相对路径以 / 分隔的形式，用于清单与日志；非 UTF-8 的部分以替换字符表示
+pub fn slash_path(relative: &Path) -> String {
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    parts.join("/")
+}
+
+impl Iterator for Walk {
+    /// 文件相对 root 的路径
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let level = self.pending.last_mut()?;
+            let Some((name, is_dir)) = level.pop() else {
+                self.pending.pop();
+                self.dirs.pop();
+                continue;
+            };
+            let parent = self.dirs.last().expect("one directory per level");
+            let relative = parent.join(name);
+            if !is_dir {
+                return Some(Ok(relative));
+            }
+            match children(&self.root.join(&relative)) {
+                Ok(entries) => {
+                    self.pending.push(entries);
+                    self.dirs.push(relative);
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walk_matches_sorted_paths() {
+        let root = std::env::temp_dir().join(format!("eqi-walk-{}", std::process::id()));
+        let files = ["B", "a-b", "a.txt", "a/b", "a/c/d", "a0", "c/d/e", "c/é"];
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+        fs::create_dir_all(root.join("empty/dir")).unwrap();
+        let walked: Vec<_> = Walk::new(&root)
+            .unwrap()
+            .map(|path| slash_path(&path.unwrap()))
+            .collect();
+        fs::remove_dir_all(&root).unwrap();
+
+        let mut sorted: Vec<_> = files.iter().map(|f| f.to_string()).collect();
+        sorted.sort();
+        assert_eq!(walked, sorted);
+    }
+}
@@ -0,0 +1,55 @@
+//! 监视模式：常驻等待更新文件被完整放入输入目录（以标记文件为准），适合通过文件同步更新的自助终端
+
+use crate::CancelToken;
+use crate::control::Control;
+use notify::{RecursiveMode, Watcher};
+use std::io;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// 默认的标记文件名：文件同步工具在更新文件全部写入后放置此文件
+pub const DEFAULT_SENTINEL: &str = ".eqi-ready";
+
+/// 检查取消的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 等待 dir 中出现标记文件 sentinel（dir 不存在时先创建）；出现时返回 true，被取消时返回 false。
+/// 传入 control 时还会响应其 apply（立即返回）与 defer（推迟到期前不返回）命令
+pub fn wait_for_sentinel(
+    dir: &Path,
+    sentinel: &str,
+    cancel: &CancelToken,
+    control: Option<&Control>,
+) -> io::Result<bool> {
+    std::fs::create_dir_all(dir)?;
+    let marker = dir.join(sentinel);
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })
+    .map_err(io::Error::other)?;
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(io::Error::other)?;
+
+    // 开始监视前可能已经放好
+    let ready = || {
+        let ready = marker.is_file();
+        control.map_or(ready, |c| c.should_apply(ready))
+    };
+    while !ready() {
+        if cancel.is_cancelled() {
+            return Ok(false);
+        }
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(_)) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Ok(Err(e)) => return Err(io::Error::other(e)),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::other("File watcher stopped unexpectedly"));
+            }
+        }
+    }
+    Ok(true)
+}